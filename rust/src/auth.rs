@@ -0,0 +1,101 @@
+//! Credential loading for the Agent CLI
+//!
+//! Reads the Claude OAuth credentials that the Claude Code CLI stores in
+//! `~/.claude/.credentials.json`, matching the JavaScript implementation's
+//! auth/claude-oauth.ts. Obtaining or refreshing tokens is not handled here.
+
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::error::{AgentError, Result};
+
+/// Environment variable that supplies a Claude OAuth token directly
+pub const CLAUDE_CODE_OAUTH_TOKEN_ENV: &str = "CLAUDE_CODE_OAUTH_TOKEN";
+
+/// OAuth credentials from the `claudeAiOauth` entry of the credentials file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaudeCredentials {
+    pub access_token: String,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    /// Expiry as a Unix timestamp in milliseconds
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub subscription_type: Option<String>,
+}
+
+impl ClaudeCredentials {
+    /// Whether the access token has expired at `now` (milliseconds)
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires| expires < now)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CredentialsFile {
+    claude_ai_oauth: Option<ClaudeCredentials>,
+}
+
+/// Location of the Claude Code credentials file for the given home directory
+pub fn claude_credentials_path(home: &Path) -> PathBuf {
+    home.join(".claude").join(".credentials.json")
+}
+
+/// Home directory from `HOME` (or `USERPROFILE` on Windows)
+pub fn home_dir_from_env(getenv: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    getenv("HOME")
+        .or_else(|| getenv("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Read Claude OAuth credentials from a credentials file.
+///
+/// Returns `Ok(None)` when the file or its `claudeAiOauth` entry is missing
+/// and `AgentError::Authentication` when the file cannot be parsed or its
+/// access token has expired.
+pub fn read_claude_credentials(path: &Path) -> Result<Option<ClaudeCredentials>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let file: CredentialsFile =
+        serde_json::from_str(&content).map_err(|e| AgentError::Authentication {
+            message: format!("Invalid credentials file {}: {}", path.display(), e),
+        })?;
+    let Some(creds) = file
+        .claude_ai_oauth
+        .filter(|creds| !creds.access_token.is_empty())
+    else {
+        return Ok(None);
+    };
+    if creds.is_expired(crate::session::now()) {
+        return Err(AgentError::Authentication {
+            message: format!(
+                "Claude OAuth token in {} has expired. Run the Claude Code CLI to refresh it",
+                path.display()
+            ),
+        });
+    }
+    Ok(Some(creds))
+}
+
+/// Resolve a Claude OAuth access token from `CLAUDE_CODE_OAUTH_TOKEN` or the
+/// credentials file under the home directory
+pub fn claude_oauth_token_from_env(
+    getenv: impl Fn(&str) -> Option<String>,
+) -> Result<Option<String>> {
+    if let Some(token) = getenv(CLAUDE_CODE_OAUTH_TOKEN_ENV).filter(|t| !t.is_empty()) {
+        return Ok(Some(token));
+    }
+    let Some(home) = home_dir_from_env(getenv) else {
+        return Ok(None);
+    };
+    Ok(read_claude_credentials(&claude_credentials_path(&home))?.map(|c| c.access_token))
+}
@@ -3,19 +3,38 @@
 //! Handles command-line argument parsing and the main execution flow,
 //! matching the JavaScript implementation's CLI interface.
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
 use std::io::{self, BufRead};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use crate::auth;
 pub use crate::defaults::{
     default_compaction_model, default_compaction_models, default_compaction_safety_margin_percent,
     default_model, DEFAULT_COMPACTION_MODEL, DEFAULT_COMPACTION_MODELS,
     DEFAULT_COMPACTION_SAFETY_MARGIN_PERCENT, DEFAULT_MODEL,
 };
+use crate::defaults::{model_parts, ModelParts};
 use crate::error::{AgentError, Result};
-use crate::id::{ascending, Prefix};
-use crate::tool::{ToolContext, ToolRegistry};
+use crate::global;
+use crate::provider::anthropic::{AnthropicAuth, AnthropicProvider};
+use crate::provider::echo::{self, EchoProvider};
+use crate::provider::models;
+use crate::provider::retry_fetch::RetryPolicy;
+use crate::provider::timeout::Timeouts;
+use crate::provider::{self, HttpOptions, Provider};
+use crate::session::compaction::{self, Compaction, CompactionModel, ContextDiagnostics, Target};
+use crate::session::message::{ModelInfo, Tokens};
+use crate::session::prompt::{PromptInput, SessionPrompt};
+use crate::session::revert::{self, Revert};
+use crate::session::stats::{self, Stats};
+use crate::session::summary::{self, FileDiff, SessionSummary};
+use crate::session::title;
+use crate::session::{self, Part, Session, SessionEvent};
+use crate::snapshot::{self, Snapshot};
+use crate::storage::{self, Storage};
+use crate::tool::ToolRegistry;
 
 /// Agent CLI - A minimal AI CLI agent compatible with OpenCode's JSON interface
 #[derive(Parser, Debug)]
@@ -26,6 +45,11 @@ pub struct Args {
     #[arg(long, default_value_t = default_model())]
     pub model: String,
 
+    /// Model to switch to, in format providerID/modelID, when --model is
+    /// reported as not supported by its provider. Disabled by default.
+    #[arg(long)]
+    pub fallback_model: Option<String>,
+
     /// JSON output format standard: "opencode" (default) or "claude" (experimental)
     #[arg(long, default_value = "opencode", value_parser = ["opencode", "claude"])]
     pub json_standard: String,
@@ -139,6 +163,30 @@ pub struct Args {
     #[arg(long = "no-retry-on-rate-limits", hide = true)]
     no_retry_on_rate_limits: bool,
 
+    /// Maximum single backoff delay in seconds between retries (default: 1200 = 20 minutes)
+    #[arg(long)]
+    pub max_retry_delay: Option<u64>,
+
+    /// Minimum delay in seconds between rate-limited retries (default: 30)
+    #[arg(long)]
+    pub min_retry_interval: Option<u64>,
+
+    /// Timeout for establishing a connection to the provider, in milliseconds (default: 30000)
+    #[arg(long)]
+    pub connect_timeout_ms: Option<u64>,
+
+    /// Timeout for the provider's response headers, in milliseconds (default: 300000)
+    #[arg(long)]
+    pub first_byte_timeout_ms: Option<u64>,
+
+    /// Timeout between stream chunks, in milliseconds (default: 120000)
+    #[arg(long)]
+    pub stream_chunk_timeout_ms: Option<u64>,
+
+    /// Timeout for each model step, in milliseconds (default: 600000)
+    #[arg(long)]
+    pub stream_step_timeout_ms: Option<u64>,
+
     /// Include model info in step_finish output (default: true).
     /// Use --no-output-response-model to disable.
     #[arg(long)]
@@ -190,6 +238,72 @@ pub struct Args {
     /// Env: LINK_ASSISTANT_AGENT_PERMISSION. See docs/permissions.md.
     #[arg(long, default_value_t = crate::permission::default_permission())]
     pub permission: String,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Subcommands; without one the agent runs a session
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Export a stored session as JSON, a JSONL transcript or Markdown
+    Export(ExportArgs),
+    /// Show token usage and cost statistics of stored sessions
+    Stats(StatsArgs),
+    /// Roll a stored session and its files back to before a message
+    Revert(RevertArgs),
+    /// Undo the revert of a stored session
+    Unrevert(UnrevertArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ExportArgs {
+    /// Session to export; defaults to the most recently updated session of
+    /// the project in the working directory
+    pub session_id: Option<String>,
+
+    /// "opencode" (default, the JavaScript `export` document), "claude"
+    /// (Claude Code JSONL transcript) or "markdown"
+    #[arg(long, default_value = "opencode", value_parser = ["opencode", "claude", "markdown"])]
+    pub format: String,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct StatsArgs {
+    /// Only count sessions updated in the last N days (default: all time)
+    #[arg(long)]
+    pub days: Option<u64>,
+
+    /// Number of tools to list (default: all)
+    #[arg(long)]
+    pub tools: Option<usize>,
+
+    /// Only count sessions of this project; without a value, the project of
+    /// the working directory (default: all projects)
+    #[arg(long, num_args = 0..=1, default_missing_value = "")]
+    pub project: Option<String>,
+
+    /// "table" (default) or "json"
+    #[arg(long, default_value = "table", value_parser = ["table", "json"])]
+    pub format: String,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RevertArgs {
+    pub session_id: String,
+
+    /// First message to revert; reverting an assistant message reverts the
+    /// turn from the user message that prompted it
+    pub message_id: String,
+
+    /// Only revert the message from this part on
+    #[arg(long)]
+    pub part: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct UnrevertArgs {
+    pub session_id: String,
 }
 
 impl Args {
@@ -222,19 +336,61 @@ impl Args {
         !self.no_always_accept_stdin
     }
 
+    /// Effective generate-title: --generate-title or LINK_ASSISTANT_AGENT_GENERATE_TITLE
+    pub fn generate_title(&self) -> bool {
+        self.generate_title || title::enabled()
+    }
+
     /// Effective retry-on-rate-limits: defaults to true, --no-retry-on-rate-limits sets to false
     pub fn retry_on_rate_limits(&self) -> bool {
         !self.no_retry_on_rate_limits
     }
 
+    /// Retry policy for provider requests: `LINK_ASSISTANT_AGENT_*`
+    /// environment overrides, then the retry flags
+    pub fn retry_policy(&self) -> RetryPolicy {
+        let mut policy = RetryPolicy::from_env(global::getenv)
+            .with_retry_on_rate_limits(self.retry_on_rate_limits());
+        if let Some(seconds) = self.retry_timeout {
+            policy = policy.with_retry_timeout(Duration::from_secs(seconds));
+        }
+        if let Some(seconds) = self.max_retry_delay {
+            policy = policy.with_max_retry_delay(Duration::from_secs(seconds));
+        }
+        if let Some(seconds) = self.min_retry_interval {
+            policy = policy.with_min_retry_interval(Duration::from_secs(seconds));
+        }
+        policy
+    }
+
+    /// Request timeouts: `LINK_ASSISTANT_AGENT_*_MS` environment overrides,
+    /// then the timeout flags
+    pub fn timeouts(&self) -> Timeouts {
+        let mut timeouts = Timeouts::from_env(global::getenv);
+        if let Some(ms) = self.connect_timeout_ms {
+            timeouts = timeouts.with_connect(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.first_byte_timeout_ms {
+            timeouts = timeouts.with_first_byte(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.stream_chunk_timeout_ms {
+            timeouts = timeouts.with_chunk(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.stream_step_timeout_ms {
+            timeouts = timeouts.with_step(Duration::from_millis(ms));
+        }
+        timeouts
+    }
+
     /// Effective output-response-model: defaults to true, --no-output-response-model sets to false
     pub fn output_response_model(&self) -> bool {
         !self.no_output_response_model
     }
 
-    /// Effective summarize-session: defaults to true, --no-summarize-session sets to false
+    /// Effective summarize-session: defaults to true, --no-summarize-session or
+    /// LINK_ASSISTANT_AGENT_SUMMARIZE_SESSION=false sets to false
     pub fn summarize_session(&self) -> bool {
-        !self.no_summarize_session
+        self.summarize_session || (!self.no_summarize_session && !summary::disabled())
     }
 
     /// Resolve the active permission policy from --permission-mode and
@@ -259,6 +415,20 @@ pub struct InputMessage {
     pub tools: Option<Vec<ToolCall>>,
 }
 
+/// Session command given as JSON input instead of a message, e.g.
+/// `{"command": "revert", "messageID": "msg_..."}`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum InputCommand {
+    Revert {
+        #[serde(rename = "messageID")]
+        message_id: String,
+        #[serde(default, rename = "partID")]
+        part_id: Option<String>,
+    },
+    Unrevert,
+}
+
 /// Tool call from input
 #[derive(Debug, Deserialize)]
 pub struct ToolCall {
@@ -276,7 +446,17 @@ pub enum OutputEvent {
         message: String,
         #[serde(skip_serializing_if = "Option::is_none")]
         hint: Option<String>,
+        #[serde(rename = "originalSessionID", skip_serializing_if = "Option::is_none")]
+        original_session_id: Option<String>,
+        #[serde(rename = "sessionID", skip_serializing_if = "Option::is_none")]
+        session_id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        forked: Option<bool>,
     },
+    #[serde(rename = "warning")]
+    Warning { message: String },
     #[serde(rename = "step_start")]
     StepStart {
         timestamp: u64,
@@ -304,6 +484,71 @@ pub enum OutputEvent {
         #[serde(rename = "sessionID")]
         session_id: String,
         reason: String,
+        tokens: Tokens,
+        /// USD, priced from the models catalog
+        cost: f64,
+        /// Omitted with --no-output-response-model
+        #[serde(skip_serializing_if = "Option::is_none")]
+        model: Option<ModelInfo>,
+        /// Context window usage, with --verbose
+        #[serde(skip_serializing_if = "Option::is_none")]
+        context: Option<ContextDiagnostics>,
+    },
+    #[serde(rename = "retry")]
+    Retry {
+        timestamp: u64,
+        #[serde(rename = "sessionID")]
+        session_id: String,
+        provider: String,
+        attempt: u32,
+        /// Wait before the next attempt, in milliseconds
+        delay: u64,
+        #[serde(rename = "statusCode", skip_serializing_if = "Option::is_none")]
+        status_code: Option<u16>,
+        message: String,
+    },
+    #[serde(rename = "compaction")]
+    Compaction {
+        timestamp: u64,
+        #[serde(rename = "sessionID")]
+        session_id: String,
+        #[serde(rename = "providerID")]
+        provider_id: String,
+        #[serde(rename = "modelID")]
+        model_id: String,
+        /// Tokens in use before compacting
+        tokens: u64,
+    },
+    #[serde(rename = "title")]
+    Title {
+        timestamp: u64,
+        #[serde(rename = "sessionID")]
+        session_id: String,
+        title: String,
+    },
+    #[serde(rename = "summary")]
+    Summary {
+        timestamp: u64,
+        #[serde(rename = "sessionID")]
+        session_id: String,
+        #[serde(flatten)]
+        summary: SessionSummary,
+        /// Changed files with their line counts; contents stay in storage
+        changes: Vec<FileChange>,
+    },
+    #[serde(rename = "revert")]
+    Revert {
+        timestamp: u64,
+        #[serde(rename = "sessionID")]
+        session_id: String,
+        #[serde(flatten)]
+        revert: Revert,
+    },
+    #[serde(rename = "unrevert")]
+    Unrevert {
+        timestamp: u64,
+        #[serde(rename = "sessionID")]
+        session_id: String,
     },
     #[serde(rename = "error")]
     Error {
@@ -314,6 +559,24 @@ pub enum OutputEvent {
     },
 }
 
+/// A changed file in a `summary` event
+#[derive(Debug, Serialize)]
+pub struct FileChange {
+    pub file: String,
+    pub additions: u64,
+    pub deletions: u64,
+}
+
+impl From<&FileDiff> for FileChange {
+    fn from(diff: &FileDiff) -> Self {
+        Self {
+            file: diff.file.clone(),
+            additions: diff.additions,
+            deletions: diff.deletions,
+        }
+    }
+}
+
 /// Output an event to stdout
 fn output_event(event: &OutputEvent, compact: bool) {
     let json = if compact {
@@ -327,6 +590,134 @@ fn output_event(event: &OutputEvent, compact: bool) {
     }
 }
 
+/// How session events are written for a run
+#[derive(Debug, Clone)]
+struct EventOutput {
+    compact: bool,
+    /// Claude stream-json frames instead of OpenCode events
+    claude: bool,
+    /// Include the requested and responding model in step_finish
+    response_model: bool,
+    /// Include context window usage in step_finish
+    verbose: bool,
+    /// `providerID/modelID`, reported on the Claude `init` frame
+    model: String,
+    /// When the run started, for the Claude `duration_ms`
+    started: u64,
+}
+
+impl EventOutput {
+    fn new(args: &Args, model: &ModelParts) -> Self {
+        Self {
+            compact: args.compact_json,
+            claude: args.effective_json_standard() == "claude",
+            response_model: args.output_response_model(),
+            verbose: args.verbose,
+            model: format!("{}/{}", model.provider_id, model.model_id),
+            started: timestamp_ms(),
+        }
+    }
+
+    fn write(&self, event: &OutputEvent) {
+        if !self.claude {
+            return output_event(event, self.compact);
+        }
+        // Claude stream-json is always one frame per line
+        if let Some(frame) = self.claude_frame(event) {
+            println!("{}", frame);
+        }
+    }
+
+    /// The Claude stream-json frame for `event`, as converted by the
+    /// JavaScript json-standard module; events without one are dropped
+    fn claude_frame(&self, event: &OutputEvent) -> Option<serde_json::Value> {
+        let iso = |millis: u64| {
+            chrono::DateTime::<chrono::Utc>::from_timestamp_millis(millis as i64)
+                .unwrap_or_default()
+                .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+        };
+        let frame = match event {
+            OutputEvent::StepStart {
+                timestamp,
+                session_id,
+            } => serde_json::json!({
+                "type": "init",
+                "timestamp": iso(*timestamp),
+                "session_id": session_id,
+                "model": self.model,
+            }),
+            OutputEvent::Text {
+                timestamp,
+                session_id,
+                text,
+            } => serde_json::json!({
+                "type": "message",
+                "timestamp": iso(*timestamp),
+                "session_id": session_id,
+                "role": "assistant",
+                "content": [{ "type": "text", "text": text }],
+            }),
+            OutputEvent::ToolUse {
+                timestamp,
+                session_id,
+                tool,
+                result,
+            } => serde_json::json!({
+                "type": "tool_use",
+                "timestamp": iso(*timestamp),
+                "session_id": session_id,
+                "name": tool,
+                "input": result.get("input").cloned().unwrap_or_else(|| serde_json::json!({})),
+            }),
+            OutputEvent::StepFinish {
+                timestamp,
+                session_id,
+                tokens,
+                cost,
+                model,
+                ..
+            } => {
+                let mut frame = serde_json::json!({
+                    "type": "result",
+                    "timestamp": iso(*timestamp),
+                    "session_id": session_id,
+                    "status": "success",
+                    "duration_ms": timestamp.saturating_sub(self.started),
+                    "total_cost_usd": cost,
+                    "usage": {
+                        "input_tokens": tokens.input,
+                        "output_tokens": tokens.output,
+                        "reasoning_tokens": tokens.reasoning,
+                        "cache_read_input_tokens": tokens.cache.read,
+                        "cache_creation_input_tokens": tokens.cache.write,
+                    },
+                });
+                if let Some(model) = model {
+                    let id = model
+                        .responded_model_id
+                        .as_ref()
+                        .unwrap_or(&model.requested_model_id);
+                    frame["model"] = serde_json::json!(id);
+                }
+                frame
+            }
+            OutputEvent::Error {
+                timestamp,
+                session_id,
+                error,
+            } => serde_json::json!({
+                "type": "result",
+                "timestamp": iso(*timestamp),
+                "session_id": session_id,
+                "status": "error",
+                "output": error.to_string(),
+            }),
+            _ => return None,
+        };
+        Some(frame)
+    }
+}
+
 /// Get current timestamp in milliseconds
 fn timestamp_ms() -> u64 {
     std::time::SystemTime::now()
@@ -366,13 +757,68 @@ fn resolve_system_messages(args: &Args) -> Result<(Option<String>, Option<String
     Ok((system_message, append_system_message))
 }
 
+/// The `--working-directory` among raw command line arguments, found before
+/// clap parses them (and computes defaults from the settings in `.lenv`)
+pub fn working_directory_arg(
+    args: impl IntoIterator<Item = std::ffi::OsString>,
+) -> Option<PathBuf> {
+    let mut args = args.into_iter();
+    let mut found = None;
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy().to_string();
+        if arg == "--" {
+            break;
+        }
+        if arg == "--working-directory" {
+            found = args.next().map(PathBuf::from);
+        } else if let Some(dir) = arg.strip_prefix("--working-directory=") {
+            found = Some(PathBuf::from(dir));
+        }
+    }
+    found
+}
+
 /// Run the CLI with parsed arguments
 pub async fn run(args: Args) -> Result<()> {
+    // Settings already fell back to .lenv while parsing; a malformed file
+    // must not be silently ignored
+    global::check_lenv()?;
+
     let working_dir = args
         .working_directory
         .clone()
         .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
 
+    match &args.command {
+        Some(Command::Export(export)) => {
+            storage::init(&working_dir);
+            print!("{}", export_session(export, &working_dir)?);
+            return Ok(());
+        }
+        Some(Command::Stats(stats)) => {
+            storage::init(&working_dir);
+            print!("{}", session_stats(stats, &working_dir)?);
+            return Ok(());
+        }
+        Some(Command::Revert(revert)) => {
+            storage::init(&working_dir);
+            let project_id = storage::project_id(&working_dir);
+            let mut session = load_session(&Storage::open(), &project_id, &revert.session_id)?;
+            let command = InputCommand::Revert {
+                message_id: revert.message_id.clone(),
+                part_id: revert.part.clone(),
+            };
+            return run_command(&args, &working_dir, &mut session, command);
+        }
+        Some(Command::Unrevert(unrevert)) => {
+            storage::init(&working_dir);
+            let project_id = storage::project_id(&working_dir);
+            let mut session = load_session(&Storage::open(), &project_id, &unrevert.session_id)?;
+            return run_command(&args, &working_dir, &mut session, InputCommand::Unrevert);
+        }
+        None => {}
+    }
+
     // Resolve system messages from file args if needed
     let (system_message, append_system_message) = resolve_system_messages(&args)?;
 
@@ -390,11 +836,19 @@ pub async fn run(args: Args) -> Result<()> {
         ));
     }
 
+    // Sessions written by older versions, or by the JavaScript agent, must
+    // be readable before one is resumed
+    storage::init(&working_dir);
+
+    // All input in this process belongs to one session
+    let mut session = resolve_session(&args, &working_dir)?;
+
     // Handle direct prompt mode
     if let Some(ref prompt) = args.prompt {
         return run_with_input(
             &args,
             &working_dir,
+            &mut session,
             prompt,
             system_message.as_deref(),
             append_system_message.as_deref(),
@@ -413,6 +867,10 @@ pub async fn run(args: Args) -> Result<()> {
             mode: mode.to_string(),
             message: "Agent CLI (Rust) ready. Accepts JSON and plain text input.".to_string(),
             hint: Some("Press CTRL+C to exit.".to_string()),
+            original_session_id: None,
+            session_id: None,
+            title: None,
+            forked: None,
         },
         args.compact_json,
     );
@@ -427,6 +885,21 @@ pub async fn run(args: Args) -> Result<()> {
                     continue;
                 }
 
+                // Session commands are not messages and never end the run
+                if let Ok(command) = serde_json::from_str::<InputCommand>(trimmed) {
+                    if let Err(e) = run_command(&args, &working_dir, &mut session, command) {
+                        output_event(
+                            &OutputEvent::Error {
+                                timestamp: timestamp_ms(),
+                                session_id: Some(session.id().to_string()),
+                                error: e.to_json(),
+                            },
+                            args.compact_json,
+                        );
+                    }
+                    continue;
+                }
+
                 // Try to parse as JSON if not in interactive mode, otherwise treat as plain text
                 let message = if args.interactive() {
                     match serde_json::from_str::<InputMessage>(trimmed) {
@@ -457,6 +930,7 @@ pub async fn run(args: Args) -> Result<()> {
                 if let Err(e) = run_with_input(
                     &args,
                     &working_dir,
+                    &mut session,
                     &message,
                     system_message.as_deref(),
                     append_system_message.as_deref(),
@@ -466,7 +940,7 @@ pub async fn run(args: Args) -> Result<()> {
                     output_event(
                         &OutputEvent::Error {
                             timestamp: timestamp_ms(),
-                            session_id: None,
+                            session_id: Some(session.id().to_string()),
                             error: e.to_json(),
                         },
                         args.compact_json,
@@ -497,245 +971,687 @@ pub async fn run(args: Args) -> Result<()> {
     Ok(())
 }
 
+/// The session for this run: a new one, or a stored one picked by
+/// `--resume`/`--continue`. Resumed sessions are forked to a new ID unless
+/// `--no-fork` is set.
+fn resolve_session(args: &Args, working_dir: &Path) -> Result<Session> {
+    let project_id = storage::project_id(working_dir);
+    if args.resume.is_none() && !args.continue_session {
+        let mut session = Session::new(working_dir);
+        session.info.project_id = project_id;
+        return Ok(session);
+    }
+
+    let storage = Storage::open();
+    let session_id = match &args.resume {
+        Some(id) => id.clone(),
+        None => storage
+            .sessions(&project_id)?
+            .into_iter()
+            .filter(|info| info.parent_id.is_none())
+            .max_by_key(|info| info.time.updated)
+            .map(|info| info.id)
+            .ok_or_else(|| AgentError::Session {
+                session_id: None,
+                message: "No existing sessions found to continue. Start a new session first."
+                    .to_string(),
+            })?,
+    };
+    let original = load_session(&storage, &project_id, &session_id)?;
+
+    if args.no_fork {
+        output_event(
+            &OutputEvent::Status {
+                mode: "resume".to_string(),
+                message: format!("Continuing session without forking: {}", session_id),
+                hint: None,
+                original_session_id: None,
+                session_id: Some(session_id),
+                title: Some(original.info.title.clone()),
+                forked: Some(false),
+            },
+            args.compact_json,
+        );
+        return Ok(original);
+    }
+
+    let mut forked = original.fork(working_dir);
+    storage.write_session(&mut forked)?;
+    output_event(
+        &OutputEvent::Status {
+            mode: "resume".to_string(),
+            message: format!(
+                "Forked session {} to new session: {}",
+                session_id,
+                forked.id()
+            ),
+            hint: None,
+            original_session_id: Some(session_id),
+            session_id: Some(forked.id().to_string()),
+            title: Some(forked.info.title.clone()),
+            forked: Some(true),
+        },
+        args.compact_json,
+    );
+    Ok(forked)
+}
+
+/// A stored session of `project_id`; a missing one is a session error
+fn load_session(storage: &Storage, project_id: &str, session_id: &str) -> Result<Session> {
+    match storage.read_session(project_id, session_id) {
+        Err(AgentError::NotFound { .. }) => Err(AgentError::Session {
+            session_id: Some(session_id.to_string()),
+            message: format!("Session not found: {}", session_id),
+        }),
+        result => result,
+    }
+}
+
+/// Revert or unrevert `session`, then save it and report the outcome
+fn run_command(
+    args: &Args,
+    working_dir: &Path,
+    session: &mut Session,
+    command: InputCommand,
+) -> Result<()> {
+    let snapshot = session_snapshot(working_dir);
+    let event = match command {
+        InputCommand::Revert {
+            message_id,
+            part_id,
+        } => {
+            let revert =
+                revert::revert(session, snapshot.as_ref(), &message_id, part_id.as_deref())?;
+            OutputEvent::Revert {
+                timestamp: timestamp_ms(),
+                session_id: session.id().to_string(),
+                revert,
+            }
+        }
+        InputCommand::Unrevert if revert::unrevert(session, snapshot.as_ref()) => {
+            OutputEvent::Unrevert {
+                timestamp: timestamp_ms(),
+                session_id: session.id().to_string(),
+            }
+        }
+        InputCommand::Unrevert => OutputEvent::Warning {
+            message: format!("Session {} is not reverted", session.id()),
+        },
+    };
+    save_session(session);
+    output_event(&event, args.compact_json);
+    Ok(())
+}
+
+/// Snapshots of the working tree, unless disabled or outside a git
+/// repository
+fn session_snapshot(working_dir: &Path) -> Option<Snapshot> {
+    if snapshot::disabled() {
+        return None;
+    }
+    Snapshot::for_directory(&global::data_dir(), working_dir)
+}
+
+/// Render a stored session of the working directory's project for `agent export`
+fn export_session(export: &ExportArgs, working_dir: &Path) -> Result<String> {
+    let format: session::export::Format = export.format.parse()?;
+    let project_id = storage::project_id(working_dir);
+    let storage = Storage::open();
+    let session_id = match &export.session_id {
+        Some(id) => id.clone(),
+        None => storage
+            .sessions(&project_id)?
+            .into_iter()
+            .max_by_key(|info| info.time.updated)
+            .map(|info| info.id)
+            .ok_or_else(|| AgentError::Session {
+                session_id: None,
+                message: "No stored sessions found to export".to_string(),
+            })?,
+    };
+    let session = load_session(&storage, &project_id, &session_id)?;
+    let diffs = match storage.read::<Vec<serde_json::Value>>(&["session_diff", &session_id]) {
+        Ok(diffs) => diffs,
+        Err(AgentError::NotFound { .. }) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    session::export::export(&session, &diffs, format)
+}
+
+/// Aggregate stored sessions for `agent stats`
+fn session_stats(args: &StatsArgs, working_dir: &Path) -> Result<String> {
+    let filter = stats::Filter {
+        days: args.days,
+        project: args.project.as_ref().map(|project| match project.as_str() {
+            "" => storage::project_id(working_dir),
+            id => id.to_string(),
+        }),
+        now: timestamp_ms(),
+    };
+    // Pricing only needs the cached or bundled catalog
+    let catalog = models::ModelsCache::new(&global::data_dir()).load();
+    let stats = Stats::collect(&Storage::open(), &catalog, &filter)?;
+    if args.format == "json" {
+        Ok(format!("{}\n", serde_json::to_string_pretty(&stats)?))
+    } else {
+        Ok(stats.table(args.tools))
+    }
+}
+
 /// Run with a specific input message
 async fn run_with_input(
     args: &Args,
     working_dir: &PathBuf,
+    session: &mut Session,
     message: &str,
     system_message: Option<&str>,
     append_system_message: Option<&str>,
 ) -> Result<()> {
-    let session_id = ascending(Prefix::Session, None);
-    let message_id = ascending(Prefix::Message, None);
+    output_verbose_config(args, session.id(), system_message, append_system_message);
 
-    // Output step start
-    output_event(
-        &OutputEvent::StepStart {
-            timestamp: timestamp_ms(),
-            session_id: session_id.clone(),
-        },
-        args.compact_json,
-    );
+    let model = resolve_model(args);
+    let fallback_model = args.fallback_model.as_deref().map(model_parts);
 
-    // Log configuration when verbose mode is on
-    if args.verbose {
-        let temp_display = match args.temperature {
-            Some(t) => format!("{}", t),
-            None => "default".to_string(),
+    // Dry runs and local models stay offline: use the cached or bundled
+    // catalog as is
+    let data_dir = global::data_dir();
+    let catalog = if args.dry_run || model.provider_id == echo::PROVIDER_ID {
+        models::ModelsCache::new(&data_dir).load()
+    } else {
+        models::get(&data_dir).await
+    };
+    provider::validate(&catalog, &model)?;
+    if let Some(fallback_model) = &fallback_model {
+        provider::validate(&catalog, fallback_model)?;
+    }
+
+    let session_id = session.id().to_string();
+    let events = EventOutput::new(args, &model);
+    let retry_events = events.clone();
+    let retry_session_id = session_id.clone();
+    let retry = args.retry_policy().with_listener(move |event| {
+        retry_events.write(&OutputEvent::Retry {
+            timestamp: timestamp_ms(),
+            session_id: retry_session_id.clone(),
+            provider: event.provider.clone(),
+            attempt: event.attempt,
+            delay: event.delay.as_millis() as u64,
+            status_code: event.status,
+            message: event.message.clone(),
+        })
+    });
+    let http = HttpOptions {
+        retry,
+        timeouts: args.timeouts(),
+    };
+    let provider = resolve_provider(args, &model, &http)?;
+    let fallback = match (&fallback_model, args.dry_run) {
+        (Some(fallback_model), false) => Some(resolve_provider(args, fallback_model, &http)?),
+        _ => None,
+    };
+    let compaction_models = compaction_models(args, &catalog)?;
+    let compaction_providers: Vec<Option<Box<dyn Provider>>> = compaction_models
+        .iter()
+        .map(|entry| match entry {
+            CompactionModel::Model(model) if !args.dry_run => {
+                provider::create_with_options(model, &http)
+                    .map_err(
+                        |e| tracing::warn!(model = %entry, error = %e, "skipping compaction model"),
+                    )
+                    .ok()
+            }
+            _ => None,
+        })
+        .collect();
+    let mut cascade: Vec<Target> = compaction_models
+        .iter()
+        .zip(&compaction_providers)
+        .filter_map(|(entry, provider)| match (entry, provider) {
+            (CompactionModel::Same, _) => Some(Target::Same),
+            (CompactionModel::Model(model), Some(provider)) => Some(Target::Model {
+                model: model.clone(),
+                provider: provider.as_ref(),
+                context_limit: catalog.context_limit(model),
+            }),
+            _ => None,
+        })
+        .collect();
+    if cascade.is_empty() {
+        cascade.push(Target::Same);
+    }
+    let compaction = if compaction::disabled() {
+        Compaction::disabled()
+    } else {
+        Compaction::new(cascade, args.compaction_safety_margin)
+    }
+    .with_prune(!compaction::prune_disabled());
+
+    // Titles and recaps come from the head of the compaction cascade, a
+    // small model by default, asked without rate-limit retries so they never
+    // hold up a run
+    let small_model = match compaction_models.first() {
+        Some(CompactionModel::Model(head)) if !args.dry_run => head.clone(),
+        _ => model.clone(),
+    };
+    let wants_small_model = args.generate_title() || args.summarize_session();
+    let small_provider = if wants_small_model && !args.dry_run {
+        let http = HttpOptions {
+            retry: RetryPolicy::none(),
+            timeouts: http.timeouts,
         };
-        output_event(
-            &OutputEvent::Text {
-                timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!("Temperature: {}", temp_display),
-            },
-            args.compact_json,
-        );
+        provider::create_with_options(&small_model, &http)
+            .map_err(|e| tracing::warn!(error = %e, "skipping titles and summaries"))
+            .ok()
+    } else {
+        None
+    };
 
-        output_event(
-            &OutputEvent::Text {
-                timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!("Model: {}", args.model),
-            },
-            args.compact_json,
-        );
+    let registry = ToolRegistry::new();
+    let policy = args
+        .resolve_policy()
+        .map_err(|e| AgentError::invalid_arguments("permission", e))?;
+
+    let mut prompt = SessionPrompt::new(provider.as_ref(), &registry, working_dir)
+        .with_catalog(&catalog)
+        .with_policy(policy)
+        .with_timeouts(http.timeouts)
+        .with_compaction(compaction);
+    if let Some(info) = catalog.lookup(&model) {
+        prompt = prompt.with_model_info(info.clone());
+    }
+    if let Some(snapshot) = session_snapshot(working_dir) {
+        prompt = prompt.with_snapshot(snapshot);
+    }
+    if let (Some(fallback), Some(fallback_model)) = (&fallback, fallback_model) {
+        prompt = prompt.with_fallback(fallback.as_ref(), fallback_model);
+    }
+    // Dry runs are titled and recapped by the echo provider
+    let small_provider = small_provider
+        .as_deref()
+        .or(args.dry_run.then_some(provider.as_ref()));
+    if let Some(small_provider) = small_provider {
+        if args.generate_title() {
+            prompt = prompt.with_title(small_provider, small_model.clone());
+        }
+        if args.summarize_session() {
+            prompt = prompt.with_summary(small_provider, small_model);
+        }
+    }
+    let input = PromptInput {
+        text: message.to_string(),
+        model,
+        system: system_message.map(str::to_string),
+        append_system: append_system_message.map(str::to_string),
+        temperature: args.temperature,
+    };
 
-        output_event(
-            &OutputEvent::Text {
-                timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!("JSON standard: {}", args.effective_json_standard()),
-            },
-            args.compact_json,
-        );
+    let mut diffs = None;
+    let result = prompt
+        .prompt(session, input, &mut |event| {
+            if let SessionEvent::Summary { diffs: changed, .. } = &event {
+                diffs = Some(changed.clone());
+            }
+            output_session_event(&event, &session_id, &events)
+        })
+        .await;
+    save_session(session);
+    if let Some(diffs) = diffs {
+        if let Err(e) = Storage::open().write(&["session_diff", &session_id], &diffs) {
+            tracing::warn!(session_id = session_id.as_str(), error = %e, "failed to save session diff");
+        }
+    }
+    result
+}
 
-        output_event(
-            &OutputEvent::Text {
-                timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!("Compaction model: {}", args.compaction_model),
-            },
-            args.compact_json,
-        );
+/// Persist the session so it can be resumed; a storage failure only costs
+/// the ability to resume, so it does not fail the run
+fn save_session(session: &mut Session) {
+    session.info.time.updated = timestamp_ms();
+    if let Err(e) = Storage::open().write_session(session) {
+        tracing::warn!(session_id = session.id(), error = %e, "failed to save session");
+    }
+}
 
-        output_event(
-            &OutputEvent::Text {
-                timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!("Compaction models: {}", args.compaction_models),
-            },
-            args.compact_json,
-        );
+/// The compaction cascade for a run.
+///
+/// `--compaction-models` takes precedence; a `--compaction-model` given
+/// while the cascade is left at its default replaces the cascade.
+fn compaction_models(args: &Args, catalog: &models::Catalog) -> Result<Vec<CompactionModel>> {
+    let notation = if args.compaction_models == default_compaction_models()
+        && args.compaction_model != default_compaction_model()
+    {
+        &args.compaction_model
+    } else {
+        &args.compaction_models
+    };
+    compaction::cascade(notation, catalog)
+}
 
-        output_event(
-            &OutputEvent::Text {
-                timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!(
-                    "Compaction safety margin: {}%",
-                    args.compaction_safety_margin
-                ),
-            },
-            args.compact_json,
-        );
+/// Model used by `--use-existing-claude-oauth` when `--model` is left at the default
+const CLAUDE_OAUTH_DEFAULT_MODEL: &str = "claude-oauth/claude-sonnet-4-5";
 
-        if let Some(sys_msg) = system_message {
-            output_event(
-                &OutputEvent::Text {
-                    timestamp: timestamp_ms(),
-                    session_id: session_id.clone(),
-                    text: format!("System message: {}", sys_msg),
-                },
-                args.compact_json,
-            );
-        }
+/// Resolve the model for a run.
+///
+/// `--dry-run` always uses the echo model; with `--use-existing-claude-oauth`
+/// the default model switches to Claude.
+fn resolve_model(args: &Args) -> ModelParts {
+    if args.dry_run {
+        model_parts(&format!("{}/{}", echo::PROVIDER_ID, echo::MODEL_ID))
+    } else if args.use_existing_claude_oauth && args.model == default_model() {
+        model_parts(CLAUDE_OAUTH_DEFAULT_MODEL)
+    } else {
+        model_parts(&args.model)
+    }
+}
 
-        if let Some(append_msg) = append_system_message {
-            output_event(
-                &OutputEvent::Text {
-                    timestamp: timestamp_ms(),
-                    session_id: session_id.clone(),
-                    text: format!("Append system message: {}", append_msg),
-                },
-                args.compact_json,
-            );
-        }
+/// Resolve the provider for a model of a run (the main or fallback model).
+///
+/// Dry runs go through the same loop, answered by the echo provider. With
+/// `--use-existing-claude-oauth` the Claude Code CLI credentials are required
+/// and Anthropic models are authenticated with the OAuth token. An explicitly
+/// chosen non-Anthropic model is respected with a warning, as in the
+/// JavaScript implementation.
+fn resolve_provider(
+    args: &Args,
+    model: &ModelParts,
+    http: &HttpOptions,
+) -> Result<Box<dyn Provider>> {
+    if args.dry_run {
+        return Ok(Box::new(EchoProvider::dry_run()));
+    }
+    if !args.use_existing_claude_oauth {
+        return provider::create_with_options(model, http);
+    }
 
-        output_event(
-            &OutputEvent::Text {
-                timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!("Permission mode: {}", args.permission_mode),
-            },
-            args.compact_json,
-        );
+    let credentials = match auth::home_dir_from_env(global::getenv) {
+        Some(home) => auth::read_claude_credentials(&auth::claude_credentials_path(&home))?,
+        None => None,
+    };
+    let Some(credentials) = credentials else {
+        return Err(AgentError::Authentication {
+            message: "No Claude OAuth credentials found in ~/.claude/.credentials.json. Authenticate with the Claude Code CLI first".to_string(),
+        });
+    };
 
-        if !args.permission.trim().is_empty() {
+    let provider: Box<dyn Provider> = match model.provider_id.as_str() {
+        "claude-oauth" | "anthropic" => {
+            let mut provider = AnthropicProvider::new(
+                model.provider_id.as_str(),
+                AnthropicAuth::OAuth(credentials.access_token),
+            )
+            .with_retry(http.retry.clone())
+            .with_timeouts(http.timeouts);
+            if let Some(base_url) = provider::base_url_override(&model.provider_id) {
+                provider = provider.with_base_url(base_url);
+            }
+            Box::new(provider)
+        }
+        other => {
             output_event(
-                &OutputEvent::Text {
-                    timestamp: timestamp_ms(),
-                    session_id: session_id.clone(),
-                    text: format!("Permission override: {}", args.permission),
+                &OutputEvent::Warning {
+                    message: format!(
+                        "--use-existing-claude-oauth is set but model uses provider \"{}\". Using specified provider.",
+                        other
+                    ),
                 },
                 args.compact_json,
             );
+            provider::create_with_options(model, http)?
         }
+    };
+    Ok(provider)
+}
 
-        output_event(
-            &OutputEvent::Text {
-                timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!("Server mode: {}", args.server()),
-            },
-            args.compact_json,
-        );
-
-        output_event(
-            &OutputEvent::Text {
+/// Translate a session event into the JSON output stream
+fn output_session_event(event: &SessionEvent, session_id: &str, output: &EventOutput) {
+    let part = match event {
+        SessionEvent::Part(part) => part,
+        SessionEvent::Retry {
+            provider,
+            attempt,
+            delay,
+            error,
+        } => {
+            return output.write(&OutputEvent::Retry {
                 timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!("Interactive: {}", args.interactive()),
-            },
-            args.compact_json,
-        );
-
-        output_event(
-            &OutputEvent::Text {
+                session_id: session_id.to_string(),
+                provider: provider.clone(),
+                attempt: *attempt,
+                delay: delay.as_millis() as u64,
+                status_code: None,
+                message: error.clone(),
+            });
+        }
+        SessionEvent::Compaction { model, tokens } => {
+            return output.write(&OutputEvent::Compaction {
                 timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!("Generate title: {}", args.generate_title),
-            },
-            args.compact_json,
-        );
-
-        output_event(
-            &OutputEvent::Text {
+                session_id: session_id.to_string(),
+                provider_id: model.provider_id.clone(),
+                model_id: model.model_id.clone(),
+                tokens: *tokens,
+            });
+        }
+        SessionEvent::Title { title } => {
+            return output.write(&OutputEvent::Title {
                 timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!("Summarize session: {}", args.summarize_session()),
-            },
-            args.compact_json,
-        );
-
-        output_event(
-            &OutputEvent::Text {
+                session_id: session_id.to_string(),
+                title: title.clone(),
+            });
+        }
+        SessionEvent::Summary { summary, diffs } => {
+            return output.write(&OutputEvent::Summary {
                 timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!("Retry on rate limits: {}", args.retry_on_rate_limits()),
-            },
-            args.compact_json,
-        );
-
-        if let Some(timeout) = args.retry_timeout {
-            output_event(
-                &OutputEvent::Text {
-                    timestamp: timestamp_ms(),
-                    session_id: session_id.clone(),
-                    text: format!("Retry timeout: {}s", timeout),
-                },
-                args.compact_json,
-            );
+                session_id: session_id.to_string(),
+                summary: summary.clone(),
+                changes: diffs.iter().map(FileChange::from).collect(),
+            });
         }
+        SessionEvent::ModelFallback { from, to, message } => {
+            return output.write(&OutputEvent::Warning {
+                message: format!(
+                    "Model {}/{} is not supported ({}). Falling back to {}/{}",
+                    from.provider_id, from.model_id, message, to.provider_id, to.model_id
+                ),
+            });
+        }
+    };
+    let event = match part {
+        Part::StepStart(p) => OutputEvent::StepStart {
+            timestamp: timestamp_ms(),
+            session_id: p.session_id.clone(),
+        },
+        Part::Text(p) => OutputEvent::Text {
+            timestamp: timestamp_ms(),
+            session_id: p.session_id.clone(),
+            text: p.text.clone(),
+        },
+        Part::Tool(p) => OutputEvent::ToolUse {
+            timestamp: timestamp_ms(),
+            session_id: p.session_id.clone(),
+            tool: p.tool.clone(),
+            result: serde_json::to_value(&p.state).unwrap_or_default(),
+        },
+        Part::StepFinish(p) => OutputEvent::StepFinish {
+            timestamp: timestamp_ms(),
+            session_id: p.session_id.clone(),
+            reason: p.reason.clone(),
+            tokens: p.tokens.clone(),
+            cost: p.cost,
+            model: p.model.clone().filter(|_| output.response_model),
+            context: p.context.filter(|_| output.verbose),
+        },
+        Part::Reasoning(_) | Part::Compaction(_) | Part::Patch(_) => return,
+    };
+    output.write(&event);
+}
+
+/// Log configuration when verbose mode is on
+fn output_verbose_config(
+    args: &Args,
+    session_id: &str,
+    system_message: Option<&str>,
+    append_system_message: Option<&str>,
+) {
+    if !args.verbose {
+        return;
     }
 
-    if args.dry_run {
-        // In dry run mode, echo the message and show configuration
-        let temp_info = match args.temperature {
-            Some(t) => format!(", temperature: {}", t),
-            None => String::new(),
-        };
+    let temp_display = match args.temperature {
+        Some(t) => format!("{}", t),
+        None => "default".to_string(),
+    };
+    output_event(
+        &OutputEvent::Text {
+            timestamp: timestamp_ms(),
+            session_id: session_id.to_string(),
+            text: format!("Temperature: {}", temp_display),
+        },
+        args.compact_json,
+    );
+
+    output_event(
+        &OutputEvent::Text {
+            timestamp: timestamp_ms(),
+            session_id: session_id.to_string(),
+            text: format!("Model: {}", args.model),
+        },
+        args.compact_json,
+    );
+
+    output_event(
+        &OutputEvent::Text {
+            timestamp: timestamp_ms(),
+            session_id: session_id.to_string(),
+            text: format!("JSON standard: {}", args.effective_json_standard()),
+        },
+        args.compact_json,
+    );
+
+    output_event(
+        &OutputEvent::Text {
+            timestamp: timestamp_ms(),
+            session_id: session_id.to_string(),
+            text: format!("Compaction model: {}", args.compaction_model),
+        },
+        args.compact_json,
+    );
+
+    output_event(
+        &OutputEvent::Text {
+            timestamp: timestamp_ms(),
+            session_id: session_id.to_string(),
+            text: format!("Compaction models: {}", args.compaction_models),
+        },
+        args.compact_json,
+    );
+
+    output_event(
+        &OutputEvent::Text {
+            timestamp: timestamp_ms(),
+            session_id: session_id.to_string(),
+            text: format!(
+                "Compaction safety margin: {}%",
+                args.compaction_safety_margin
+            ),
+        },
+        args.compact_json,
+    );
+
+    if let Some(sys_msg) = system_message {
         output_event(
             &OutputEvent::Text {
                 timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!("[DRY RUN] Received message: {}{}", message, temp_info),
+                session_id: session_id.to_string(),
+                text: format!("System message: {}", sys_msg),
             },
             args.compact_json,
         );
-    } else {
-        // Create tool context
-        let _ctx = ToolContext::new(&session_id, &message_id, working_dir);
-
-        // Initialize tool registry
-        let registry = ToolRegistry::new();
+    }
 
-        // For now, just output a simple response
-        // In a full implementation, this would call the LLM API
+    if let Some(append_msg) = append_system_message {
         output_event(
             &OutputEvent::Text {
                 timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!(
-                    "Agent (Rust) ready. {} tools available. Message: {}",
-                    registry.all().len(),
-                    message
-                ),
+                session_id: session_id.to_string(),
+                text: format!("Append system message: {}", append_msg),
             },
             args.compact_json,
         );
+    }
 
-        // List available tools
-        let tools: Vec<&str> = registry.all().iter().map(|t| t.id()).collect();
+    output_event(
+        &OutputEvent::Text {
+            timestamp: timestamp_ms(),
+            session_id: session_id.to_string(),
+            text: format!("Permission mode: {}", args.permission_mode),
+        },
+        args.compact_json,
+    );
+
+    if !args.permission.trim().is_empty() {
         output_event(
             &OutputEvent::Text {
                 timestamp: timestamp_ms(),
-                session_id: session_id.clone(),
-                text: format!("Available tools: {}", tools.join(", ")),
+                session_id: session_id.to_string(),
+                text: format!("Permission override: {}", args.permission),
             },
             args.compact_json,
         );
     }
 
-    // Output step finish
     output_event(
-        &OutputEvent::StepFinish {
+        &OutputEvent::Text {
             timestamp: timestamp_ms(),
-            session_id,
-            reason: "stop".to_string(),
+            session_id: session_id.to_string(),
+            text: format!("Server mode: {}", args.server()),
         },
         args.compact_json,
     );
 
-    Ok(())
+    output_event(
+        &OutputEvent::Text {
+            timestamp: timestamp_ms(),
+            session_id: session_id.to_string(),
+            text: format!("Interactive: {}", args.interactive()),
+        },
+        args.compact_json,
+    );
+
+    output_event(
+        &OutputEvent::Text {
+            timestamp: timestamp_ms(),
+            session_id: session_id.to_string(),
+            text: format!("Generate title: {}", args.generate_title()),
+        },
+        args.compact_json,
+    );
+
+    output_event(
+        &OutputEvent::Text {
+            timestamp: timestamp_ms(),
+            session_id: session_id.to_string(),
+            text: format!("Summarize session: {}", args.summarize_session()),
+        },
+        args.compact_json,
+    );
+
+    output_event(
+        &OutputEvent::Text {
+            timestamp: timestamp_ms(),
+            session_id: session_id.to_string(),
+            text: format!("Retry on rate limits: {}", args.retry_on_rate_limits()),
+        },
+        args.compact_json,
+    );
+
+    if let Some(timeout) = args.retry_timeout {
+        output_event(
+            &OutputEvent::Text {
+                timestamp: timestamp_ms(),
+                session_id: session_id.to_string(),
+                text: format!("Retry timeout: {}s", timeout),
+            },
+            args.compact_json,
+        );
+    }
 }
@@ -50,7 +50,7 @@ pub fn default_model_from_env(getenv: impl Fn(&str) -> Option<String>) -> String
 }
 
 pub fn default_model() -> String {
-    default_model_from_env(|key| std::env::var(key).ok())
+    default_model_from_env(crate::global::getenv)
 }
 
 pub fn default_compaction_model_from_env(getenv: impl Fn(&str) -> Option<String>) -> String {
@@ -59,7 +59,7 @@ pub fn default_compaction_model_from_env(getenv: impl Fn(&str) -> Option<String>
 }
 
 pub fn default_compaction_model() -> String {
-    default_compaction_model_from_env(|key| std::env::var(key).ok())
+    default_compaction_model_from_env(crate::global::getenv)
 }
 
 pub fn default_compaction_models_from_env(getenv: impl Fn(&str) -> Option<String>) -> String {
@@ -68,7 +68,7 @@ pub fn default_compaction_models_from_env(getenv: impl Fn(&str) -> Option<String
 }
 
 pub fn default_compaction_models() -> String {
-    default_compaction_models_from_env(|key| std::env::var(key).ok())
+    default_compaction_models_from_env(crate::global::getenv)
 }
 
 pub fn default_compaction_safety_margin_percent_from_env(
@@ -80,7 +80,7 @@ pub fn default_compaction_safety_margin_percent_from_env(
 }
 
 pub fn default_compaction_safety_margin_percent() -> u32 {
-    default_compaction_safety_margin_percent_from_env(|key| std::env::var(key).ok())
+    default_compaction_safety_margin_percent_from_env(crate::global::getenv)
 }
 
 pub fn model_parts(model: &str) -> ModelParts {
@@ -32,6 +32,36 @@ pub enum AgentError {
     #[error("Authentication error: {message}")]
     Authentication { message: String },
 
+    #[error("Model not found: {provider_id}/{model_id}")]
+    ModelNotFound {
+        provider_id: String,
+        model_id: String,
+        suggestions: Vec<String>,
+    },
+
+    #[error("Model not supported by {provider}: {message}")]
+    ModelNotSupported { provider: String, message: String },
+
+    #[error("API error from {provider}: {message}")]
+    Api {
+        provider: String,
+        status: Option<u16>,
+        message: String,
+        retryable: bool,
+    },
+
+    #[error("Connection to {provider} timed out after {timeout_ms}ms")]
+    ConnectTimeout { provider: String, timeout_ms: u64 },
+
+    #[error("No response from {provider} within {timeout_ms}ms")]
+    FirstByteTimeout { provider: String, timeout_ms: u64 },
+
+    #[error("Stream from {provider} stalled: no data for {timeout_ms}ms")]
+    StreamIdleTimeout { provider: String, timeout_ms: u64 },
+
+    #[error("Step with {provider} did not finish within {timeout_ms}ms")]
+    StepTimeout { provider: String, timeout_ms: u64 },
+
     #[error("Session error: {message}")]
     Session {
         session_id: Option<String>,
@@ -41,6 +71,9 @@ pub enum AgentError {
     #[error("Configuration error: {message}")]
     Config { message: String },
 
+    #[error("{message}")]
+    NotFound { message: String },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -79,6 +112,36 @@ impl AgentError {
         }
     }
 
+    /// Create a new Api error, deriving retryability from the status code
+    pub fn api(
+        provider: impl Into<String>,
+        status: Option<u16>,
+        message: impl Into<String>,
+    ) -> Self {
+        let retryable = match status {
+            Some(code) => code == 408 || code == 429 || code >= 500,
+            None => true,
+        };
+        Self::Api {
+            provider: provider.into(),
+            status,
+            message: message.into(),
+            retryable,
+        }
+    }
+
+    /// Whether this is one of the timeout hierarchy errors, which the
+    /// agent loop retries
+    pub fn is_timeout(&self) -> bool {
+        matches!(
+            self,
+            Self::ConnectTimeout { .. }
+                | Self::FirstByteTimeout { .. }
+                | Self::StreamIdleTimeout { .. }
+                | Self::StepTimeout { .. }
+        )
+    }
+
     /// Convert to JSON-serializable error object
     pub fn to_json(&self) -> serde_json::Value {
         match self {
@@ -131,6 +194,79 @@ impl AgentError {
                     "message": message,
                 }
             }),
+            Self::ModelNotFound {
+                provider_id,
+                model_id,
+                suggestions,
+            } => {
+                let mut msg = format!("Model not found: {provider_id}/{model_id}");
+                if !suggestions.is_empty() {
+                    msg.push_str("\n\nDid you mean one of these?\n");
+                    msg.push_str(&suggestions.join("\n"));
+                }
+                serde_json::json!({
+                    "name": "ProviderModelNotFoundError",
+                    "data": {
+                        "providerID": provider_id,
+                        "modelID": model_id,
+                        "suggestions": suggestions,
+                        "message": msg,
+                    }
+                })
+            }
+            Self::ModelNotSupported { provider, message } => serde_json::json!({
+                "name": "ModelNotSupportedError",
+                "data": {
+                    "providerID": provider,
+                    "message": message,
+                }
+            }),
+            Self::Api {
+                provider,
+                status,
+                message,
+                retryable,
+            } => serde_json::json!({
+                "name": "APIError",
+                "data": {
+                    "providerID": provider,
+                    "statusCode": status,
+                    "isRetryable": retryable,
+                    "message": message,
+                }
+            }),
+            Self::ConnectTimeout {
+                provider,
+                timeout_ms,
+            }
+            | Self::FirstByteTimeout {
+                provider,
+                timeout_ms,
+            }
+            | Self::StreamIdleTimeout {
+                provider,
+                timeout_ms,
+            }
+            | Self::StepTimeout {
+                provider,
+                timeout_ms,
+            } => {
+                let name = match self {
+                    Self::ConnectTimeout { .. } => "ConnectTimeoutError",
+                    Self::FirstByteTimeout { .. } => "FirstByteTimeoutError",
+                    Self::StreamIdleTimeout { .. } => "StreamIdleTimeoutError",
+                    _ => "StepTimeoutError",
+                };
+                serde_json::json!({
+                    "name": name,
+                    "data": {
+                        "providerID": provider,
+                        "timeoutMs": timeout_ms,
+                        "isRetryable": true,
+                        "message": self.to_string(),
+                    }
+                })
+            }
             Self::Session {
                 session_id,
                 message,
@@ -147,6 +283,12 @@ impl AgentError {
                     "message": message,
                 }
             }),
+            Self::NotFound { message } => serde_json::json!({
+                "name": "NotFoundError",
+                "data": {
+                    "message": message,
+                }
+            }),
             Self::Io(e) => serde_json::json!({
                 "name": "IOError",
                 "data": {
@@ -0,0 +1,112 @@
+//! Global paths and settings for the Agent CLI
+//!
+//! Resolves the per-user data directory the same way as the JavaScript
+//! implementation's global/index.ts (`$XDG_DATA_HOME/link-assistant-agent`),
+//! with an explicit override for tests and automation.
+//!
+//! Settings are looked up in the environment first and then in a `.lenv`
+//! file (`KEY: value` lines in Links Notation) in the working directory,
+//! matching the lino-arguments precedence of the JavaScript implementation.
+//! That is the current directory until `--working-directory` names another
+//! one (see `set_lenv_dir`).
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::{AgentError, Result};
+use crate::util::lino;
+
+/// Application directory name under the XDG base directories
+pub const APP: &str = "link-assistant-agent";
+
+/// Env var overriding the data directory
+pub const DATA_DIR_ENV: &str = "LINK_ASSISTANT_AGENT_DATA_DIR";
+
+/// Settings file read from the working directory
+pub const LENV_FILE: &str = ".lenv";
+
+/// Resolve the data directory through `getenv`.
+///
+/// Order: `LINK_ASSISTANT_AGENT_DATA_DIR`, `$XDG_DATA_HOME/link-assistant-agent`,
+/// `~/.local/share/link-assistant-agent`, then the system temp directory.
+pub fn data_dir_from_env(getenv: impl Fn(&str) -> Option<String>) -> PathBuf {
+    let getenv = |key: &str| getenv(key).filter(|value| !value.trim().is_empty());
+    if let Some(dir) = getenv(DATA_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+    if let Some(xdg) = getenv("XDG_DATA_HOME") {
+        return PathBuf::from(xdg).join(APP);
+    }
+    match crate::auth::home_dir_from_env(getenv) {
+        Some(home) => home.join(".local").join("share").join(APP),
+        None => std::env::temp_dir().join(APP),
+    }
+}
+
+pub fn data_dir() -> PathBuf {
+    data_dir_from_env(getenv)
+}
+
+/// Settings from the `.lenv` file in `dir`; a missing file holds none
+pub fn read_lenv(dir: &Path) -> Result<BTreeMap<String, String>> {
+    let path = dir.join(LENV_FILE);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+    match lino::parse_lenv(&content) {
+        Ok(entries) => Ok(entries.into_iter().collect()),
+        Err(AgentError::Config { message }) => Err(AgentError::Config {
+            message: format!("{}: {}", path.display(), message),
+        }),
+        Err(e) => Err(e),
+    }
+}
+
+type Lenv = std::result::Result<BTreeMap<String, String>, String>;
+
+/// The working directory's `.lenv`, read once per directory
+static LENV: Mutex<Option<Lenv>> = Mutex::new(None);
+
+fn load_lenv(dir: &Path) -> Lenv {
+    read_lenv(dir).map_err(|e| match e {
+        AgentError::Config { message } => message,
+        other => other.to_string(),
+    })
+}
+
+/// Run `f` on the `.lenv` settings, reading the current directory's file on
+/// first use
+fn with_lenv<T>(f: impl FnOnce(&Lenv) -> T) -> T {
+    let mut lenv = LENV.lock().unwrap_or_else(|e| e.into_inner());
+    f(lenv.get_or_insert_with(|| {
+        let dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        load_lenv(&dir)
+    }))
+}
+
+/// Read settings from the `.lenv` file in `dir` from now on, for runs given
+/// a `--working-directory`
+pub fn set_lenv_dir(dir: &Path) {
+    *LENV.lock().unwrap_or_else(|e| e.into_inner()) = Some(load_lenv(dir));
+}
+
+/// Look up a setting: the environment first, then the `.lenv` file
+pub fn getenv(key: &str) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .or_else(|| with_lenv(|lenv| lenv.as_ref().ok()?.get(key).cloned()))
+}
+
+/// Fail with a config error when the `.lenv` file cannot be read
+pub fn check_lenv() -> Result<()> {
+    with_lenv(|lenv| {
+        lenv.as_ref()
+            .map(|_| ())
+            .map_err(|message| AgentError::Config {
+                message: message.clone(),
+            })
+    })
+}
@@ -3,10 +3,16 @@
 //! This is the Rust implementation of the @link-assistant/agent CLI tool.
 //! It provides the same functionality as the JavaScript/Bun version but runs as a native binary.
 
+pub mod auth;
 pub mod cli;
 pub mod defaults;
 pub mod error;
+pub mod global;
 pub mod id;
 pub mod permission;
+pub mod provider;
+pub mod session;
+pub mod snapshot;
+pub mod storage;
 pub mod tool;
 pub mod util;
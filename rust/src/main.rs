@@ -5,8 +5,8 @@
 
 use clap::Parser;
 use cli::Args;
-use link_assistant_agent::cli;
 use link_assistant_agent::error::Result;
+use link_assistant_agent::{cli, global};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
 #[tokio::main]
@@ -19,6 +19,12 @@ async fn main() -> Result<()> {
         .with(filter)
         .init();
 
+    // Argument defaults fall back to .lenv, so a run in another directory
+    // must point there before parsing
+    if let Some(dir) = cli::working_directory_arg(std::env::args_os()) {
+        global::set_lenv_dir(&dir);
+    }
+
     // Parse command line arguments
     let args = Args::parse();
 
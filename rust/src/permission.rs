@@ -18,13 +18,12 @@
 //!                               top of the mode, e.g.
 //!                               '{"bash":{"git push*":"ask","*":"allow"},"edit":"ask"}'
 //!
-//! The Rust binary does not yet drive a model agent loop (tools are invoked
-//! directly, not through a model), so this module provides the policy core,
-//! override parsing/merging, bash evaluation, and the JSON request/response
-//! schemas — establishing full parity with the JavaScript policy semantics and
-//! the documented JSON protocol. The enforcement wiring lives in the JS agent
-//! loop; once the Rust binary grows a model loop it can call `evaluate_bash` /
-//! `Policy::action_for` at the same tool boundaries.
+//! This module provides the policy core, override parsing/merging, bash
+//! evaluation, and the JSON request/response schemas — establishing full parity
+//! with the JavaScript policy semantics and the documented JSON protocol. The
+//! Rust agent loop (`session::prompt`) calls `evaluate_bash` /
+//! `Policy::action_for` at the tool boundary; `ask` decisions are rejected
+//! there until the interactive approval round-trip is wired up.
 
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -48,7 +47,7 @@ pub fn default_permission_mode_from_env(getenv: impl Fn(&str) -> Option<String>)
 
 /// Default permission mode, reading the process environment.
 pub fn default_permission_mode() -> String {
-    default_permission_mode_from_env(|key| std::env::var(key).ok())
+    default_permission_mode_from_env(crate::global::getenv)
 }
 
 /// Default `--permission` JSON override, honoring the env var (clap default).
@@ -61,7 +60,7 @@ pub fn default_permission_from_env(getenv: impl Fn(&str) -> Option<String>) -> S
 
 /// Default `--permission` JSON override, reading the process environment.
 pub fn default_permission() -> String {
-    default_permission_from_env(|key| std::env::var(key).ok())
+    default_permission_from_env(crate::global::getenv)
 }
 
 // ─── Policy types ───────────────────────────────────────────────────────────
@@ -106,7 +105,7 @@ impl Action {
         }
     }
 
-    fn more_restrictive(self, other: Action) -> Action {
+    pub(crate) fn more_restrictive(self, other: Action) -> Action {
         if other.restrictiveness() > self.restrictiveness() {
             other
         } else {
@@ -0,0 +1,426 @@
+//! Anthropic Messages API provider
+//!
+//! Streams `/v1/messages` responses and assembles text, thinking and
+//! `tool_use` content blocks. Authenticates with an API key (`x-api-key`) or
+//! with a Claude OAuth token (`Authorization: Bearer`), sending the same
+//! `anthropic-beta` flags as the JavaScript implementation's anthropic and
+//! claude-oauth loaders in provider/provider.ts.
+
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+use super::retry_fetch::RetryPolicy;
+use super::timeout::Timeouts;
+use super::{
+    error_message, http_error, ChatMessage, ChatRequest, ChatResponse, ContentPart, Provider, Usage,
+};
+use crate::error::{AgentError, Result};
+use crate::util::sse;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.anthropic.com/v1";
+pub const API_VERSION: &str = "2023-06-01";
+const BETA: &str =
+    "claude-code-20250219,interleaved-thinking-2025-05-14,fine-grained-tool-streaming-2025-05-14";
+const OAUTH_BETA: &str = "oauth-2025-04-20";
+/// `max_tokens` is required by the API; used when the request sets no limit
+pub const DEFAULT_MAX_TOKENS: u64 = 32_000;
+/// First system block of every request, from
+/// `js/src/session/prompt/anthropic_spoof.txt`
+pub const SYSTEM_HEADER: &str = "You are Claude Code, Anthropic's official CLI for Claude.";
+/// System blocks that receive a cache breakpoint, matching `applyCaching`
+const CACHED_SYSTEM_BLOCKS: usize = 2;
+
+/// How requests are authenticated
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnthropicAuth {
+    ApiKey(String),
+    OAuth(String),
+}
+
+/// Provider for the Anthropic Messages API
+pub struct AnthropicProvider {
+    id: String,
+    base_url: String,
+    auth: AnthropicAuth,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    timeouts: Timeouts,
+}
+
+impl AnthropicProvider {
+    pub fn new(id: impl Into<String>, auth: AnthropicAuth) -> Self {
+        Self {
+            id: id.into(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            auth,
+            client: Timeouts::default().client(),
+            retry: RetryPolicy::none(),
+            timeouts: Timeouts::default(),
+        }
+    }
+
+    /// Send requests to a different endpoint (proxies, tests)
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Retry rate limits, server errors and dropped connections per `policy`
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Apply the connect, first-byte and chunk timeouts in `timeouts`
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.client = timeouts.client();
+        self.timeouts = timeouts;
+        self
+    }
+
+    pub fn auth(&self) -> &AnthropicAuth {
+        &self.auth
+    }
+
+    /// Build the JSON body for a streaming messages request
+    pub fn request_body(&self, request: &ChatRequest) -> Value {
+        // The header comes first, as `SystemPrompt.header` adds it for
+        // anthropic providers
+        let system: Vec<Value> = std::iter::once(SYSTEM_HEADER)
+            .chain(request.system.iter().map(String::as_str))
+            .filter(|text| !text.is_empty())
+            .enumerate()
+            .map(|(index, text)| {
+                let mut block = json!({ "type": "text", "text": text });
+                if index < CACHED_SYSTEM_BLOCKS {
+                    block["cache_control"] = ephemeral();
+                }
+                block
+            })
+            .collect();
+
+        let mut messages = messages(&request.messages);
+        if let Some(block) = messages
+            .last_mut()
+            .and_then(|m| m["content"].as_array_mut())
+            .and_then(|content| content.last_mut())
+        {
+            block["cache_control"] = ephemeral();
+        }
+
+        let mut body = json!({
+            "model": request.model,
+            // Thinking counts towards max_tokens
+            "max_tokens": request.max_output_tokens.unwrap_or(DEFAULT_MAX_TOKENS)
+                + request.thinking_budget.unwrap_or(0),
+            "messages": messages,
+            "stream": true,
+        });
+        body["system"] = Value::Array(system);
+        if !request.tools.is_empty() {
+            let mut tools: Vec<Value> = request
+                .tools
+                .iter()
+                .map(|tool| {
+                    json!({
+                        "name": tool.name,
+                        "description": tool.description,
+                        "input_schema": tool.parameters,
+                    })
+                })
+                .collect();
+            // A breakpoint on the last tool caches every definition before it
+            if let Some(last) = tools.last_mut() {
+                last["cache_control"] = ephemeral();
+            }
+            body["tools"] = Value::Array(tools);
+        }
+        if let Some(budget) = request.thinking_budget {
+            body["thinking"] = json!({ "type": "enabled", "budget_tokens": budget });
+        } else if let Some(temperature) = request.temperature {
+            // Thinking only runs at the default temperature
+            body["temperature"] = json!(temperature);
+        }
+        body
+    }
+}
+
+fn ephemeral() -> Value {
+    json!({ "type": "ephemeral" })
+}
+
+/// Convert the conversation into Anthropic messages.
+///
+/// Tool results become `tool_result` blocks of a user message, and
+/// consecutive messages with the same role are merged so roles alternate.
+fn messages(history: &[ChatMessage]) -> Vec<Value> {
+    let mut messages: Vec<Value> = Vec::new();
+    for message in history {
+        let (role, blocks) = match message {
+            ChatMessage::User { text } => ("user", vec![json!({ "type": "text", "text": text })]),
+            ChatMessage::Assistant { content } => (
+                "assistant",
+                content.iter().filter_map(assistant_block).collect(),
+            ),
+            ChatMessage::Tool {
+                call_id,
+                output,
+                is_error,
+                ..
+            } => {
+                let mut block = json!({
+                    "type": "tool_result",
+                    "tool_use_id": call_id,
+                    "content": output,
+                });
+                if *is_error {
+                    block["is_error"] = json!(true);
+                }
+                ("user", vec![block])
+            }
+        };
+        if blocks.is_empty() {
+            continue;
+        }
+        match messages.last_mut() {
+            Some(last) if last["role"] == role => {
+                if let Some(content) = last["content"].as_array_mut() {
+                    content.extend(blocks);
+                }
+            }
+            _ => messages.push(json!({ "role": role, "content": blocks })),
+        }
+    }
+    messages
+}
+
+fn assistant_block(part: &ContentPart) -> Option<Value> {
+    match part {
+        ContentPart::Text { text } if text.is_empty() => None,
+        ContentPart::Text { text } => Some(json!({ "type": "text", "text": text })),
+        ContentPart::Reasoning { text, metadata } => {
+            let metadata = metadata.as_ref();
+            if let Some(data) = metadata.and_then(|m| m["redactedData"].as_str()) {
+                return Some(json!({ "type": "redacted_thinking", "data": data }));
+            }
+            // Thinking without a signature cannot be sent back to the API
+            let signature = metadata.and_then(|m| m["signature"].as_str())?;
+            Some(json!({ "type": "thinking", "thinking": text, "signature": signature }))
+        }
+        ContentPart::ToolCall {
+            call_id,
+            tool,
+            input,
+        } => Some(json!({
+            "type": "tool_use",
+            "id": call_id,
+            "name": tool,
+            "input": if input.is_object() { input.clone() } else { json!({}) },
+        })),
+    }
+}
+
+#[async_trait]
+impl Provider for AnthropicProvider {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        let body = self.request_body(request);
+        let build = || {
+            let builder = self
+                .client
+                .post(format!("{}/messages", self.base_url))
+                .header("anthropic-version", API_VERSION)
+                .header("Accept", "text/event-stream")
+                .json(&body);
+            match &self.auth {
+                AnthropicAuth::ApiKey(key) => builder
+                    .header("x-api-key", key)
+                    .header("anthropic-beta", BETA),
+                AnthropicAuth::OAuth(token) => builder
+                    .bearer_auth(token)
+                    .header("anthropic-beta", format!("{},{}", OAUTH_BETA, BETA)),
+            }
+        };
+
+        let mut response = self.retry.send(&self.id, &self.timeouts, build).await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(http_error(&self.id, status.as_u16(), &body));
+        }
+
+        let mut decoder = sse::Decoder::new();
+        let mut state = StreamState::default();
+        while let Some(chunk) = self.timeouts.chunk(&self.id, response.chunk()).await? {
+            for event in decoder.push(&chunk) {
+                state.apply(&self.id, &event.data)?;
+            }
+        }
+        if let Some(event) = decoder.finish() {
+            state.apply(&self.id, &event.data)?;
+        }
+        Ok(state.into_response())
+    }
+}
+
+/// A content block being streamed
+enum Block {
+    Text(String),
+    Thinking {
+        text: String,
+        signature: String,
+    },
+    RedactedThinking(String),
+    ToolUse {
+        id: String,
+        name: String,
+        json: String,
+    },
+}
+
+/// Accumulates stream events into a complete response
+#[derive(Default)]
+struct StreamState {
+    blocks: BTreeMap<u64, Block>,
+    stop_reason: Option<String>,
+    usage: Usage,
+    model: Option<String>,
+}
+
+impl StreamState {
+    fn apply(&mut self, provider: &str, data: &str) -> Result<()> {
+        let event: Value = serde_json::from_str(data)?;
+        let index = event["index"].as_u64().unwrap_or(0);
+        match event["type"].as_str().unwrap_or_default() {
+            "message_start" => {
+                let message = &event["message"];
+                self.model = message["model"].as_str().map(str::to_string);
+                self.apply_usage(&message["usage"]);
+            }
+            "content_block_start" => {
+                let block = &event["content_block"];
+                let text = |key: &str| block[key].as_str().unwrap_or_default().to_string();
+                let block = match block["type"].as_str().unwrap_or_default() {
+                    "thinking" => Block::Thinking {
+                        text: text("thinking"),
+                        signature: text("signature"),
+                    },
+                    "redacted_thinking" => Block::RedactedThinking(text("data")),
+                    "tool_use" => Block::ToolUse {
+                        id: text("id"),
+                        name: text("name"),
+                        json: String::new(),
+                    },
+                    _ => Block::Text(text("text")),
+                };
+                self.blocks.insert(index, block);
+            }
+            "content_block_delta" => {
+                let delta = &event["delta"];
+                let value = |key: &str| delta[key].as_str().unwrap_or_default();
+                match (self.blocks.get_mut(&index), delta["type"].as_str()) {
+                    (Some(Block::Text(text)), Some("text_delta")) => text.push_str(value("text")),
+                    (Some(Block::Thinking { text, .. }), Some("thinking_delta")) => {
+                        text.push_str(value("thinking"))
+                    }
+                    (Some(Block::Thinking { signature, .. }), Some("signature_delta")) => {
+                        signature.push_str(value("signature"))
+                    }
+                    (Some(Block::ToolUse { json, .. }), Some("input_json_delta")) => {
+                        json.push_str(value("partial_json"))
+                    }
+                    _ => {}
+                }
+            }
+            "message_delta" => {
+                if let Some(reason) = event["delta"]["stop_reason"].as_str() {
+                    self.stop_reason = Some(reason.to_string());
+                }
+                self.apply_usage(&event["usage"]);
+            }
+            "error" => {
+                let message = error_message(&event).unwrap_or_else(|| data.to_string());
+                // Overload and API errors mid-stream are transient
+                let retryable = matches!(
+                    event["error"]["type"].as_str(),
+                    Some("overloaded_error") | Some("api_error")
+                );
+                return Err(AgentError::Api {
+                    provider: provider.to_string(),
+                    status: None,
+                    message,
+                    retryable,
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn apply_usage(&mut self, usage: &Value) {
+        let field = |key: &str| usage[key].as_u64();
+        if let Some(input) = field("input_tokens") {
+            self.usage.input = input;
+        }
+        if let Some(output) = field("output_tokens") {
+            self.usage.output = output;
+        }
+        if let Some(read) = field("cache_read_input_tokens") {
+            self.usage.cache_read = read;
+        }
+        if let Some(write) = field("cache_creation_input_tokens") {
+            self.usage.cache_write = write;
+        }
+    }
+
+    fn into_response(self) -> ChatResponse {
+        let content = self
+            .blocks
+            .into_values()
+            .filter_map(|block| match block {
+                Block::Text(text) if text.is_empty() => None,
+                Block::Text(text) => Some(ContentPart::Text { text }),
+                Block::Thinking { text, signature } => Some(ContentPart::Reasoning {
+                    text,
+                    metadata: (!signature.is_empty()).then(|| json!({ "signature": signature })),
+                }),
+                Block::RedactedThinking(data) => Some(ContentPart::Reasoning {
+                    text: String::new(),
+                    metadata: Some(json!({ "redactedData": data })),
+                }),
+                Block::ToolUse { id, name, json } => Some(ContentPart::ToolCall {
+                    call_id: id,
+                    tool: name,
+                    input: if json.trim().is_empty() {
+                        Value::Object(Map::new())
+                    } else {
+                        serde_json::from_str(&json).unwrap_or(Value::String(json))
+                    },
+                }),
+            })
+            .collect();
+
+        ChatResponse {
+            content,
+            finish_reason: stop_reason(self.stop_reason.as_deref()).to_string(),
+            usage: self.usage,
+            model: self.model,
+        }
+    }
+}
+
+/// Translate an Anthropic stop reason to the AI SDK vocabulary
+fn stop_reason(reason: Option<&str>) -> &'static str {
+    match reason {
+        Some("end_turn") | Some("stop_sequence") => "stop",
+        Some("tool_use") => "tool-calls",
+        Some("max_tokens") => "length",
+        Some("refusal") => "content-filter",
+        Some(_) => "other",
+        None => "unknown",
+    }
+}
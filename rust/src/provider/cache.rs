@@ -0,0 +1,134 @@
+//! Cache provider implementation
+//!
+//! Records upstream responses as Links Notation files and replays them,
+//! matching the JavaScript implementation's provider/cache.ts. Used with
+//! `--model link-assistant/cache/<provider>/<model>`; recordings live under
+//! `data/api-cache/{provider}/{model}/{key}.lino`.
+//!
+//! Unlike the JavaScript version, a miss calls the real upstream provider
+//! rather than falling back to echo, so recordings hold real transcripts.
+
+use async_trait::async_trait;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+use super::{ChatRequest, ChatResponse, Provider};
+use crate::defaults::ModelParts;
+use crate::error::{AgentError, Result};
+use crate::util::lino;
+
+/// Model ID prefix selecting the cache provider under `link-assistant/`
+pub const MODEL_PREFIX: &str = "cache/";
+
+/// Default cache root, relative to the working directory
+pub const DEFAULT_CACHE_DIR: &str = "data/api-cache";
+
+/// Env var overriding the cache root
+pub const CACHE_DIR_ENV: &str = "LINK_ASSISTANT_AGENT_API_CACHE_DIR";
+
+/// Provider that replays recorded responses and records misses
+pub struct CacheProvider {
+    upstream_model: ModelParts,
+    /// The upstream provider, or why it could not be created. Replays work
+    /// without it, so missing credentials only matter on a cache miss.
+    upstream: std::result::Result<Box<dyn Provider>, String>,
+    dir: PathBuf,
+}
+
+impl CacheProvider {
+    /// Cache `upstream` for `upstream_model` under `root/{provider}/{model}`
+    pub fn new(
+        upstream_model: ModelParts,
+        upstream: Result<Box<dyn Provider>>,
+        root: &Path,
+    ) -> Self {
+        let dir = root
+            .join(&upstream_model.provider_id)
+            .join(&upstream_model.model_id);
+        Self {
+            upstream_model,
+            upstream: upstream.map_err(|e| e.to_string()),
+            dir,
+        }
+    }
+
+    /// Directory holding this model's recordings
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Recording file for a request
+    pub fn path(&self, request: &ChatRequest) -> PathBuf {
+        self.dir.join(format!("{}.lino", cache_key(request)))
+    }
+}
+
+/// Stable key for a request.
+///
+/// Covers the conversation and sampling settings but not the system prompt
+/// or tool definitions, which embed the working directory and date and
+/// would otherwise make every run a miss.
+pub fn cache_key(request: &ChatRequest) -> String {
+    let content = json!({
+        "model": request.model,
+        "messages": request.messages,
+        "temperature": request.temperature,
+        "maxOutputTokens": request.max_output_tokens,
+    })
+    .to_string();
+    // FNV-1a, so keys stay the same across builds and platforms
+    let hash = content.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Read a recording, treating unreadable files as misses
+fn load(path: &Path) -> Option<ChatResponse> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let value = lino::decode_json(&content).ok()?;
+    serde_json::from_value(value).ok()
+}
+
+/// Write a recording atomically so concurrent runs never see partial files
+fn save(path: &Path, response: &ChatResponse) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let encoded = lino::encode_json(&serde_json::to_value(response)?);
+    let tmp = path.with_extension(format!("lino.{}.tmp", std::process::id()));
+    std::fs::write(&tmp, encoded)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[async_trait]
+impl Provider for CacheProvider {
+    fn id(&self) -> &str {
+        super::echo::PROVIDER_ID
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        let path = self.path(request);
+        if let Some(response) = load(&path) {
+            return Ok(response);
+        }
+
+        let upstream = self
+            .upstream
+            .as_ref()
+            .map_err(|reason| AgentError::ProviderInit {
+                provider: self.upstream_model.provider_id.clone(),
+                message: format!(
+                    "No recording at {} and the upstream provider is unavailable: {}",
+                    path.display(),
+                    reason
+                ),
+            })?;
+        let mut upstream_request = request.clone();
+        upstream_request.model = self.upstream_model.model_id.clone();
+        let response = upstream.complete(&upstream_request).await?;
+        save(&path, &response)?;
+        Ok(response)
+    }
+}
@@ -0,0 +1,73 @@
+//! Echo provider implementation
+//!
+//! A synthetic provider that answers every step with the latest user message,
+//! without network access, matching the JavaScript implementation's
+//! provider/echo.ts. Used explicitly with `--model link-assistant/echo` and
+//! automatically by `--dry-run`.
+
+use async_trait::async_trait;
+
+use super::{ChatMessage, ChatRequest, ChatResponse, ContentPart, Provider, Usage};
+use crate::error::Result;
+
+pub const PROVIDER_ID: &str = "link-assistant";
+pub const MODEL_ID: &str = "echo";
+
+/// Provider that echoes the latest user message back
+#[derive(Debug, Default)]
+pub struct EchoProvider {
+    dry_run: bool,
+}
+
+impl EchoProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Label replies as dry-run output (`[DRY RUN] Received message: ...`)
+    pub fn dry_run() -> Self {
+        Self { dry_run: true }
+    }
+}
+
+#[async_trait]
+impl Provider for EchoProvider {
+    fn id(&self) -> &str {
+        PROVIDER_ID
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        let message = request
+            .messages
+            .iter()
+            .rev()
+            .find_map(|message| match message {
+                ChatMessage::User { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .unwrap_or("Echo: No user message found");
+
+        let text = if self.dry_run {
+            let temperature = request
+                .temperature
+                .map(|t| format!(", temperature: {}", t))
+                .unwrap_or_default();
+            format!("[DRY RUN] Received message: {}{}", message, temperature)
+        } else {
+            message.to_string()
+        };
+
+        // Simulated usage, as in the JavaScript implementation
+        let tokens = message.chars().count().div_ceil(4) as u64;
+        Ok(ChatResponse {
+            content: vec![ContentPart::Text { text }],
+            finish_reason: "stop".to_string(),
+            usage: Usage {
+                input: tokens,
+                output: tokens,
+                ..Default::default()
+            },
+            model: Some(request.model.clone()),
+        })
+    }
+}
@@ -0,0 +1,475 @@
+//! Model providers for the Agent CLI
+//!
+//! Defines the provider-neutral request/response types exchanged between the
+//! session loop and a language model, and the `Provider` trait every backend
+//! implements. Mirrors the role of the JavaScript implementation's provider/
+//! directory, without the AI SDK layer in between.
+
+pub mod anthropic;
+pub mod cache;
+pub mod echo;
+pub mod models;
+pub mod openai;
+pub mod retry_fetch;
+pub mod timeout;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::auth;
+use crate::defaults::ModelParts;
+use crate::error::{AgentError, Result};
+use retry_fetch::RetryPolicy;
+use timeout::Timeouts;
+
+/// A tool definition advertised to the model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,
+}
+
+/// A single piece of assistant content
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ContentPart {
+    Text {
+        text: String,
+    },
+    Reasoning {
+        text: String,
+        /// Provider-specific data needed to send the block back (e.g. signatures)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metadata: Option<Value>,
+    },
+    ToolCall {
+        #[serde(rename = "callID")]
+        call_id: String,
+        tool: String,
+        input: Value,
+    },
+}
+
+/// A message in the provider-neutral conversation format
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "role", rename_all = "lowercase")]
+pub enum ChatMessage {
+    User {
+        text: String,
+    },
+    Assistant {
+        content: Vec<ContentPart>,
+    },
+    Tool {
+        #[serde(rename = "callID")]
+        call_id: String,
+        tool: String,
+        output: String,
+        #[serde(default, rename = "isError")]
+        is_error: bool,
+    },
+}
+
+/// A request for one model generation step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatRequest {
+    /// Model ID within the provider (the part after `providerID/`)
+    pub model: String,
+    pub system: Vec<String>,
+    pub messages: Vec<ChatMessage>,
+    pub tools: Vec<ToolSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u64>,
+    /// Tokens the model may spend thinking, on top of `max_output_tokens`.
+    /// Providers without a thinking budget ignore it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_budget: Option<u64>,
+}
+
+/// Token usage reported for a generation step
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub input: u64,
+    pub output: u64,
+    pub reasoning: u64,
+    pub cache_read: u64,
+    pub cache_write: u64,
+}
+
+/// The assembled result of one model generation step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatResponse {
+    pub content: Vec<ContentPart>,
+    /// Finish reason using the AI SDK vocabulary ("stop", "tool-calls", "length", ...)
+    pub finish_reason: String,
+    #[serde(default)]
+    pub usage: Usage,
+    /// Model ID reported by the provider, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+impl ChatResponse {
+    /// Tool calls requested by the model in this step
+    pub fn tool_calls(&self) -> impl Iterator<Item = (&str, &str, &Value)> {
+        self.content.iter().filter_map(|part| match part {
+            ContentPart::ToolCall {
+                call_id,
+                tool,
+                input,
+            } => Some((call_id.as_str(), tool.as_str(), input)),
+            _ => None,
+        })
+    }
+}
+
+/// Trait that all model providers must implement
+#[async_trait]
+pub trait Provider: Send + Sync {
+    /// Get the provider's identifier (the `providerID` part of `--model`)
+    fn id(&self) -> &str;
+
+    /// Run one generation step and return the assembled response
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse>;
+}
+
+/// An OpenAI-compatible endpoint known by provider ID
+struct KnownEndpoint {
+    id: &'static str,
+    base_url: &'static str,
+    api_key_env: Option<&'static str>,
+    /// Key used when the environment variable is unset (free/anonymous tiers)
+    default_api_key: Option<&'static str>,
+    headers: &'static [(&'static str, &'static str)],
+}
+
+const OPENAI_COMPATIBLE: &[KnownEndpoint] = &[
+    KnownEndpoint {
+        id: "opencode",
+        base_url: "https://opencode.ai/zen/v1",
+        api_key_env: Some("OPENCODE_API_KEY"),
+        default_api_key: Some("public"),
+        headers: &[],
+    },
+    KnownEndpoint {
+        id: "openrouter",
+        base_url: "https://openrouter.ai/api/v1",
+        api_key_env: Some("OPENROUTER_API_KEY"),
+        default_api_key: None,
+        headers: &[
+            ("HTTP-Referer", "https://opencode.ai/"),
+            ("X-Title", "opencode"),
+        ],
+    },
+    KnownEndpoint {
+        id: "groq",
+        base_url: "https://api.groq.com/openai/v1",
+        api_key_env: Some("GROQ_API_KEY"),
+        default_api_key: None,
+        headers: &[],
+    },
+    KnownEndpoint {
+        id: "kilo",
+        base_url: "https://api.kilo.ai/api/gateway",
+        api_key_env: Some("KILO_API_KEY"),
+        default_api_key: Some("anonymous"),
+        headers: &[
+            ("User-Agent", "opencode-kilo-provider"),
+            ("X-KILOCODE-EDITORNAME", "link-assistant-agent"),
+        ],
+    },
+    KnownEndpoint {
+        id: "openai",
+        base_url: "https://api.openai.com/v1",
+        api_key_env: Some("OPENAI_API_KEY"),
+        default_api_key: None,
+        headers: &[],
+    },
+    KnownEndpoint {
+        id: "ollama",
+        base_url: "http://localhost:11434/v1",
+        api_key_env: None,
+        default_api_key: None,
+        headers: &[],
+    },
+    KnownEndpoint {
+        id: "lmstudio",
+        base_url: "http://localhost:1234/v1",
+        api_key_env: None,
+        default_api_key: None,
+        headers: &[],
+    },
+];
+
+/// Retry and timeout behaviour of the HTTP providers
+#[derive(Debug, Clone, Default)]
+pub struct HttpOptions {
+    pub retry: RetryPolicy,
+    pub timeouts: Timeouts,
+}
+
+impl HttpOptions {
+    /// Defaults with the `LINK_ASSISTANT_AGENT_*` retry and timeout overrides
+    pub fn from_env(getenv: impl Fn(&str) -> Option<String>) -> Self {
+        Self {
+            retry: RetryPolicy::from_env(&getenv),
+            timeouts: Timeouts::from_env(&getenv),
+        }
+    }
+}
+
+/// Prefix for per-provider environment variables (`OPENROUTER`, `MY_PROXY`, ...)
+fn env_prefix(provider_id: &str) -> String {
+    provider_id.to_uppercase().replace(['-', '.'], "_")
+}
+
+/// The `{PROVIDER}_BASE_URL` endpoint set for a provider, if any
+pub fn base_url_override(provider_id: &str) -> Option<String> {
+    crate::global::getenv(&format!("{}_BASE_URL", env_prefix(provider_id)))
+        .filter(|value| !value.trim().is_empty())
+}
+
+/// Create the provider for a model, reading credentials through `getenv`.
+///
+/// Known OpenAI-compatible providers use their public endpoint unless
+/// `{PROVIDER}_BASE_URL` overrides it; any other provider ID becomes usable by
+/// setting `{PROVIDER}_BASE_URL` (and optionally `{PROVIDER}_API_KEY`), which
+/// covers local servers and self-hosted gateways.
+///
+/// `link-assistant/cache/<provider>/<model>` wraps the upstream provider in
+/// a record/replay cache rooted at `LINK_ASSISTANT_AGENT_API_CACHE_DIR`
+/// (default `data/api-cache`).
+///
+/// HTTP requests are retried and timed out per [`HttpOptions::from_env`].
+pub fn create_from_env(
+    model: &ModelParts,
+    getenv: impl Fn(&str) -> Option<String>,
+) -> Result<Box<dyn Provider>> {
+    let options = HttpOptions::from_env(&getenv);
+    create_with_options_from_env(model, &options, getenv)
+}
+
+/// [`create_from_env`] with explicit HTTP options
+pub fn create_with_options_from_env(
+    model: &ModelParts,
+    options: &HttpOptions,
+    getenv: impl Fn(&str) -> Option<String>,
+) -> Result<Box<dyn Provider>> {
+    create_with(model, options, &|key: &str| {
+        getenv(key).filter(|value| !value.trim().is_empty())
+    })
+}
+
+fn create_with(
+    model: &ModelParts,
+    options: &HttpOptions,
+    getenv: &dyn Fn(&str) -> Option<String>,
+) -> Result<Box<dyn Provider>> {
+    let provider_id = model.provider_id.as_str();
+    let prefix = env_prefix(provider_id);
+    let base_url_override = getenv(&format!("{}_BASE_URL", prefix));
+
+    if provider_id == echo::PROVIDER_ID {
+        if model.model_id == echo::MODEL_ID {
+            return Ok(Box::new(echo::EchoProvider::new()));
+        }
+        if let Some(upstream) = model.model_id.strip_prefix(cache::MODEL_PREFIX) {
+            if !upstream.contains('/') {
+                return Err(AgentError::ProviderInit {
+                    provider: provider_id.to_string(),
+                    message: format!(
+                        "Invalid cache model ID: {}. Expected format: {}/{}<provider>/<model>",
+                        model.model_id,
+                        provider_id,
+                        cache::MODEL_PREFIX
+                    ),
+                });
+            }
+            let upstream_model = crate::defaults::model_parts(upstream);
+            let root = getenv(cache::CACHE_DIR_ENV)
+                .unwrap_or_else(|| cache::DEFAULT_CACHE_DIR.to_string());
+            let upstream_provider = create_with(&upstream_model, options, getenv);
+            return Ok(Box::new(cache::CacheProvider::new(
+                upstream_model,
+                upstream_provider,
+                std::path::Path::new(&root),
+            )));
+        }
+    }
+    if let Some(auth) = anthropic_auth(model, &getenv)? {
+        let mut provider = anthropic::AnthropicProvider::new(provider_id, auth)
+            .with_retry(options.retry.clone())
+            .with_timeouts(options.timeouts);
+        if let Some(base_url) = base_url_override {
+            provider = provider.with_base_url(base_url);
+        }
+        return Ok(Box::new(provider));
+    }
+    let known = OPENAI_COMPATIBLE.iter().find(|e| e.id == provider_id);
+
+    let Some(base_url) = base_url_override
+        .clone()
+        .or_else(|| known.map(|e| e.base_url.to_string()))
+    else {
+        return Err(AgentError::ProviderInit {
+            provider: provider_id.to_string(),
+            message: format!(
+                "No provider is available for model {}/{}. Set {}_BASE_URL to use an OpenAI-compatible endpoint",
+                provider_id, model.model_id, prefix
+            ),
+        });
+    };
+
+    let mut provider = openai::OpenAiCompatibleProvider::new(provider_id, base_url)
+        .with_retry(options.retry.clone())
+        .with_timeouts(options.timeouts);
+    let key_env = known
+        .and_then(|e| e.api_key_env.map(str::to_string))
+        .unwrap_or_else(|| format!("{}_API_KEY", prefix));
+    match getenv(&key_env).or_else(|| known.and_then(|e| e.default_api_key.map(str::to_string))) {
+        Some(key) => provider = provider.with_api_key(key),
+        // Local servers and overridden endpoints may not need a key
+        None if known.is_some_and(|e| e.api_key_env.is_some()) && base_url_override.is_none() => {
+            return Err(AgentError::Authentication {
+                message: format!(
+                    "No API key found for provider {}. Set {} to use model {}/{}",
+                    provider_id, key_env, provider_id, model.model_id
+                ),
+            });
+        }
+        None => {}
+    }
+    for (name, value) in known.map(|e| e.headers).unwrap_or_default() {
+        provider = provider.with_header(*name, *value);
+    }
+    if provider_id == "kilo" {
+        if let Some(org) = getenv("KILO_ORG_ID") {
+            provider = provider.with_header("X-KILOCODE-ORGANIZATIONID", org);
+        }
+    }
+    Ok(Box::new(provider))
+}
+
+/// Credentials for the Anthropic-backed provider IDs.
+///
+/// `anthropic` uses `ANTHROPIC_API_KEY`, falling back to Claude OAuth
+/// credentials; `claude-oauth` uses `CLAUDE_CODE_OAUTH_TOKEN` or
+/// `~/.claude/.credentials.json`. Other providers yield `None`.
+fn anthropic_auth(
+    model: &ModelParts,
+    getenv: &dyn Fn(&str) -> Option<String>,
+) -> Result<Option<anthropic::AnthropicAuth>> {
+    let api_key = match model.provider_id.as_str() {
+        "anthropic" => getenv("ANTHROPIC_API_KEY"),
+        "claude-oauth" => None,
+        _ => return Ok(None),
+    };
+    if let Some(key) = api_key {
+        return Ok(Some(anthropic::AnthropicAuth::ApiKey(key)));
+    }
+    match auth::claude_oauth_token_from_env(getenv)? {
+        Some(token) => Ok(Some(anthropic::AnthropicAuth::OAuth(token))),
+        None => Err(AgentError::Authentication {
+            message: format!(
+                "No credentials found for provider {}. Set ANTHROPIC_API_KEY or {}, or sign in with the Claude Code CLI",
+                model.provider_id,
+                auth::CLAUDE_CODE_OAUTH_TOKEN_ENV
+            ),
+        }),
+    }
+}
+
+/// Create the provider responsible for a `providerID/modelID` pair
+pub fn create(model: &ModelParts) -> Result<Box<dyn Provider>> {
+    create_from_env(model, crate::global::getenv)
+}
+
+/// [`create`] with explicit HTTP options
+pub fn create_with_options(model: &ModelParts, options: &HttpOptions) -> Result<Box<dyn Provider>> {
+    create_with_options_from_env(model, options, crate::global::getenv)
+}
+
+/// Check `--model` against the catalog before any request is made.
+///
+/// Catalog providers must list the model. Cache recordings may name any
+/// upstream model, and providers the catalog does not cover (local servers,
+/// `{PREFIX}_BASE_URL` endpoints) accept whatever model they serve.
+pub fn validate_from_env(
+    catalog: &models::Catalog,
+    model: &ModelParts,
+    getenv: impl Fn(&str) -> Option<String>,
+) -> Result<()> {
+    let provider_id = model.provider_id.as_str();
+    if provider_id == echo::PROVIDER_ID && model.model_id.starts_with(cache::MODEL_PREFIX) {
+        return Ok(());
+    }
+    if catalog.provider(provider_id).is_none()
+        && (OPENAI_COMPATIBLE.iter().any(|e| e.id == provider_id)
+            || getenv(&format!("{}_BASE_URL", env_prefix(provider_id)))
+                .is_some_and(|value| !value.trim().is_empty()))
+    {
+        return Ok(());
+    }
+    catalog.check(model)
+}
+
+pub fn validate(catalog: &models::Catalog, model: &ModelParts) -> Result<()> {
+    validate_from_env(catalog, model, crate::global::getenv)
+}
+
+/// Whether an error body reports an unsupported or unknown model.
+///
+/// Some providers (OpenCode, OpenRouter) answer with HTTP 401 and
+/// `{"type":"error","error":{"type":"ModelError",...}}`, which would
+/// otherwise read as an authentication failure. Mirrors the JavaScript
+/// implementation's `SessionProcessor.isModelNotSupportedError`.
+pub fn is_model_not_supported(body: &str) -> bool {
+    match serde_json::from_str::<Value>(body) {
+        Ok(value) => {
+            value.pointer("/error/type").and_then(Value::as_str) == Some("ModelError")
+                || value.get("type").and_then(Value::as_str) == Some("ModelError")
+        }
+        Err(_) => {
+            let lower = body.to_lowercase();
+            body.contains("ModelError")
+                || lower.contains("model not supported")
+                || lower.contains("model not found")
+        }
+    }
+}
+
+/// Map a non-2xx response to an agent error
+pub(crate) fn http_error(provider: &str, status: u16, body: &str) -> AgentError {
+    let message = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|value| error_message(&value))
+        .unwrap_or_else(|| body.trim().to_string());
+    if is_model_not_supported(body) {
+        return AgentError::ModelNotSupported {
+            provider: provider.to_string(),
+            message,
+        };
+    }
+    match status {
+        401 | 403 => AgentError::Authentication {
+            message: format!("{} rejected the credentials: {}", provider, message),
+        },
+        _ => AgentError::api(provider, Some(status), message),
+    }
+}
+
+/// Extract the message from `{"error": {"message": ...}}` style payloads
+pub(crate) fn error_message(value: &Value) -> Option<String> {
+    let error = value.get("error")?;
+    Some(match error.get("message").and_then(|m| m.as_str()) {
+        Some(message) => message.to_string(),
+        None => error
+            .as_str()
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| error.to_string()),
+    })
+}
@@ -0,0 +1,377 @@
+//! Models catalog
+//!
+//! Provider and model metadata in the models.dev `api.json` format: context
+//! and output limits, costs and modalities. Mirrors the JavaScript
+//! implementation's provider/models.ts: the catalog is cached in the data
+//! directory, refreshed from models.dev when missing or stale, and falls back
+//! to a bundled snapshot so lookups keep working offline.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::defaults::ModelParts;
+use crate::error::{AgentError, Result};
+
+/// Where the catalog is fetched from
+pub const MODELS_URL: &str = "https://models.dev/api.json";
+
+/// Cache file name inside the data directory
+pub const CACHE_FILE: &str = "models.json";
+
+/// Caches older than this are refreshed before use (1 hour)
+pub const CACHE_STALE_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// Env var that disables fetching from models.dev
+pub const DISABLE_FETCH_ENV: &str = "LINK_ASSISTANT_AGENT_DISABLE_MODELS_FETCH";
+
+/// Close matches listed when a model is not found
+const MAX_SUGGESTIONS: usize = 3;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Trimmed models.dev snapshot compiled into the binary
+const SNAPSHOT: &str = include_str!("models-snapshot.json");
+
+/// Price per million tokens, in USD
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostTier {
+    #[serde(default)]
+    pub input: f64,
+    #[serde(default)]
+    pub output: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_write: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cost {
+    #[serde(flatten)]
+    pub base: CostTier,
+    /// Prices applied when the prompt exceeds 200K tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_over_200k: Option<CostTier>,
+}
+
+/// Token limits; zero means unknown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Limit {
+    #[serde(default)]
+    pub context: u64,
+    #[serde(default)]
+    pub output: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Modalities {
+    #[serde(default)]
+    pub input: Vec<String>,
+    #[serde(default)]
+    pub output: Vec<String>,
+}
+
+/// A model entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Model {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<String>,
+    #[serde(default)]
+    pub attachment: bool,
+    #[serde(default)]
+    pub reasoning: bool,
+    #[serde(default)]
+    pub temperature: bool,
+    #[serde(default)]
+    pub tool_call: bool,
+    #[serde(default)]
+    pub cost: Cost,
+    #[serde(default)]
+    pub limit: Limit,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modalities: Option<Modalities>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub experimental: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub options: BTreeMap<String, Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<BTreeMap<String, String>>,
+}
+
+impl Model {
+    /// Whether the model accepts the given input modality (e.g. "image")
+    pub fn accepts(&self, modality: &str) -> bool {
+        self.modalities
+            .as_ref()
+            .is_some_and(|m| m.input.iter().any(|i| i == modality))
+    }
+}
+
+/// A provider entry with its models
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderInfo {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub env: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub npm: Option<String>,
+    #[serde(default)]
+    pub models: BTreeMap<String, Model>,
+}
+
+/// The provider/model catalog
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Catalog {
+    providers: BTreeMap<String, ProviderInfo>,
+}
+
+impl Catalog {
+    /// Parse a models.dev `api.json` document
+    pub fn from_json(json: &str) -> Result<Self> {
+        let providers: BTreeMap<String, ProviderInfo> =
+            serde_json::from_str(json).map_err(|e| AgentError::Config {
+                message: format!("Invalid models catalog: {}", e),
+            })?;
+        Ok(Self { providers })
+    }
+
+    /// The snapshot bundled with this build
+    pub fn bundled() -> Self {
+        Self::from_json(SNAPSHOT).expect("bundled models snapshot is valid")
+    }
+
+    pub fn providers(&self) -> impl Iterator<Item = &ProviderInfo> {
+        self.providers.values()
+    }
+
+    pub fn provider(&self, provider_id: &str) -> Option<&ProviderInfo> {
+        self.providers.get(catalog_provider_id(provider_id))
+    }
+
+    /// Look up a model by provider and model ID
+    pub fn model(&self, provider_id: &str, model_id: &str) -> Option<&Model> {
+        self.provider(provider_id)?.models.get(model_id)
+    }
+
+    /// Look up the model for a `providerID/modelID` pair
+    pub fn lookup(&self, model: &ModelParts) -> Option<&Model> {
+        self.model(&model.provider_id, &model.model_id)
+    }
+
+    /// Context window of a model, if known
+    pub fn context_limit(&self, model: &ModelParts) -> Option<u64> {
+        self.lookup(model)
+            .map(|m| m.limit.context)
+            .filter(|limit| *limit > 0)
+    }
+
+    /// Fail with `AgentError::ModelNotFound` unless the catalog lists `model`.
+    ///
+    /// Suggestions name the same model under other providers first (e.g.
+    /// `openrouter/z-ai/glm-4.7` for `z-ai/glm-4.7`), then the closest
+    /// `providerID/modelID` spellings.
+    pub fn check(&self, model: &ModelParts) -> Result<()> {
+        if self.lookup(model).is_some() {
+            return Ok(());
+        }
+        Err(AgentError::ModelNotFound {
+            provider_id: model.provider_id.clone(),
+            model_id: model.model_id.clone(),
+            suggestions: self.close_matches(model),
+        })
+    }
+
+    /// Resolve a bare model ID (e.g. `gpt-5-nano`) to the provider serving
+    /// it: the only provider listing it, else `opencode` as the primary free
+    /// provider, else the first provider in ID order
+    pub fn resolve_short_name(&self, model_id: &str) -> Option<ModelParts> {
+        let matching: Vec<&str> = self
+            .providers
+            .iter()
+            .filter(|(_, provider)| provider.models.contains_key(model_id))
+            .map(|(provider_id, _)| provider_id.as_str())
+            .collect();
+        let provider_id = match matching.as_slice() {
+            [] => return None,
+            [only] => *only,
+            _ if matching.contains(&"opencode") => "opencode",
+            [first, ..] => *first,
+        };
+        Some(ModelParts {
+            provider_id: provider_id.to_string(),
+            model_id: model_id.to_string(),
+        })
+    }
+
+    /// Known models resembling `model`, best first
+    pub fn close_matches(&self, model: &ModelParts) -> Vec<String> {
+        let wanted = if model.model_id.is_empty() {
+            model.provider_id.clone()
+        } else {
+            format!("{}/{}", model.provider_id, model.model_id)
+        };
+        let mut matches: Vec<String> = self
+            .providers
+            .iter()
+            .filter(|(_, provider)| provider.models.contains_key(&wanted))
+            .map(|(provider_id, _)| format!("{}/{}", provider_id, wanted))
+            .collect();
+
+        // Within a known provider only its own models are candidates
+        let candidates: Vec<String> = match self.provider(&model.provider_id) {
+            Some(provider) => provider
+                .models
+                .keys()
+                .map(|model_id| format!("{}/{}", model.provider_id, model_id))
+                .collect(),
+            None => self
+                .providers
+                .iter()
+                .flat_map(|(provider_id, provider)| {
+                    provider
+                        .models
+                        .keys()
+                        .map(move |model_id| format!("{}/{}", provider_id, model_id))
+                })
+                .collect(),
+        };
+        let candidates: Vec<&str> = candidates.iter().map(String::as_str).collect();
+        for close in similar::get_close_matches(wanted.as_str(), &candidates, MAX_SUGGESTIONS, 0.6)
+        {
+            if !matches.iter().any(|m| m == close) {
+                matches.push(close.to_string());
+            }
+        }
+        matches.truncate(MAX_SUGGESTIONS);
+        matches
+    }
+}
+
+/// Provider IDs that share another provider's catalog entries
+fn catalog_provider_id(provider_id: &str) -> &str {
+    match provider_id {
+        "claude-oauth" => "anthropic",
+        other => other,
+    }
+}
+
+/// The on-disk catalog cache in a data directory
+pub struct ModelsCache {
+    path: PathBuf,
+    url: String,
+    fetch: bool,
+}
+
+impl ModelsCache {
+    /// Cache at `{data_dir}/models.json`, refreshed from models.dev
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join(CACHE_FILE),
+            url: MODELS_URL.to_string(),
+            fetch: true,
+        }
+    }
+
+    /// Fetch from a different URL (mirrors, tests)
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    /// Enable or disable network refreshes
+    pub fn with_fetch(mut self, fetch: bool) -> Self {
+        self.fetch = fetch;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the cache file is missing or older than `CACHE_STALE_AFTER`
+    pub fn is_stale(&self) -> bool {
+        std::fs::metadata(&self.path)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_none_or(|age| age > CACHE_STALE_AFTER)
+    }
+
+    /// Load the catalog, refreshing a missing or stale cache first.
+    ///
+    /// Never fails: refresh errors are ignored and an unreadable cache falls
+    /// back to the bundled snapshot.
+    pub async fn get(&self) -> Catalog {
+        if self.fetch && self.is_stale() {
+            if let Err(e) = self.refresh().await {
+                tracing::info!(error = %e, "models catalog refresh failed, using cached data");
+            }
+        }
+        self.load()
+    }
+
+    /// Read the cached catalog without touching the network
+    pub fn load(&self) -> Catalog {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|json| Catalog::from_json(&json).ok())
+            .unwrap_or_else(Catalog::bundled)
+    }
+
+    /// Download the catalog and replace the cache if the response is valid
+    pub async fn refresh(&self) -> Result<()> {
+        let response = reqwest::Client::new()
+            .get(&self.url)
+            .header(
+                "User-Agent",
+                concat!("agent-cli/", env!("CARGO_PKG_VERSION")),
+            )
+            .timeout(FETCH_TIMEOUT)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(AgentError::api(
+                "models.dev",
+                Some(status.as_u16()),
+                format!("Failed to fetch {}", self.url),
+            ));
+        }
+        let body = response.text().await?;
+        Catalog::from_json(&body)?;
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self
+            .path
+            .with_extension(format!("json.{}.tmp", std::process::id()));
+        std::fs::write(&tmp, body)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Load the catalog from the data directory, honouring
+/// `LINK_ASSISTANT_AGENT_DISABLE_MODELS_FETCH`
+pub async fn get_from_env(data_dir: &Path, getenv: impl Fn(&str) -> Option<String>) -> Catalog {
+    let disabled = getenv(DISABLE_FETCH_ENV)
+        .is_some_and(|value| matches!(value.trim(), "1" | "true" | "yes" | "on"));
+    ModelsCache::new(data_dir).with_fetch(!disabled).get().await
+}
+
+pub async fn get(data_dir: &Path) -> Catalog {
+    get_from_env(data_dir, crate::global::getenv).await
+}
@@ -0,0 +1,340 @@
+//! OpenAI-compatible chat-completions provider
+//!
+//! Speaks the streaming `/chat/completions` protocol shared by OpenAI,
+//! OpenRouter, Groq, OpenCode Zen, Kilo Gateway and local servers such as
+//! Ollama or LM Studio. Mirrors the role the `@ai-sdk/openai-compatible`
+//! package plays in the JavaScript implementation.
+
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+use super::retry_fetch::RetryPolicy;
+use super::timeout::Timeouts;
+use super::{
+    error_message, http_error, ChatMessage, ChatRequest, ChatResponse, ContentPart, Provider, Usage,
+};
+use crate::error::{AgentError, Result};
+use crate::util::sse;
+
+/// Provider for any endpoint implementing the OpenAI chat-completions API
+pub struct OpenAiCompatibleProvider {
+    id: String,
+    base_url: String,
+    api_key: Option<String>,
+    headers: Vec<(String, String)>,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    timeouts: Timeouts,
+}
+
+impl OpenAiCompatibleProvider {
+    /// Create a provider for `base_url` (e.g. `https://openrouter.ai/api/v1`)
+    pub fn new(id: impl Into<String>, base_url: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key: None,
+            headers: Vec::new(),
+            client: Timeouts::default().client(),
+            retry: RetryPolicy::none(),
+            timeouts: Timeouts::default(),
+        }
+    }
+
+    /// Retry rate limits, server errors and dropped connections per `policy`
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Apply the connect, first-byte and chunk timeouts in `timeouts`
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.client = timeouts.client();
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Send `Authorization: Bearer <key>` with every request
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Send an extra header with every request
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn api_key(&self) -> Option<&str> {
+        self.api_key.as_deref()
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Build the JSON body for a streaming chat-completions request
+    pub fn request_body(&self, request: &ChatRequest) -> Value {
+        let mut messages: Vec<Value> = request
+            .system
+            .iter()
+            .map(|text| json!({ "role": "system", "content": text }))
+            .collect();
+        for message in &request.messages {
+            messages.push(match message {
+                ChatMessage::User { text } => json!({ "role": "user", "content": text }),
+                ChatMessage::Assistant { content } => assistant_message(content),
+                ChatMessage::Tool {
+                    call_id, output, ..
+                } => json!({ "role": "tool", "tool_call_id": call_id, "content": output }),
+            });
+        }
+
+        let mut body = json!({
+            "model": request.model,
+            "messages": messages,
+            "stream": true,
+            "stream_options": { "include_usage": true },
+        });
+        if !request.tools.is_empty() {
+            body["tools"] = request
+                .tools
+                .iter()
+                .map(|tool| {
+                    json!({
+                        "type": "function",
+                        "function": {
+                            "name": tool.name,
+                            "description": tool.description,
+                            "parameters": tool.parameters,
+                        }
+                    })
+                })
+                .collect();
+        }
+        if let Some(temperature) = request.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(max_tokens) = request.max_output_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        body
+    }
+}
+
+fn assistant_message(content: &[ContentPart]) -> Value {
+    let text: String = content
+        .iter()
+        .filter_map(|part| match part {
+            ContentPart::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    let tool_calls: Vec<Value> = content
+        .iter()
+        .filter_map(|part| match part {
+            ContentPart::ToolCall {
+                call_id,
+                tool,
+                input,
+            } => Some(json!({
+                "id": call_id,
+                "type": "function",
+                "function": { "name": tool, "arguments": input.to_string() },
+            })),
+            _ => None,
+        })
+        .collect();
+
+    let mut message = json!({ "role": "assistant", "content": text });
+    if !tool_calls.is_empty() {
+        message["tool_calls"] = Value::Array(tool_calls);
+    }
+    message
+}
+
+#[async_trait]
+impl Provider for OpenAiCompatibleProvider {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        let body = self.request_body(request);
+        let build = || {
+            let mut builder = self
+                .client
+                .post(format!("{}/chat/completions", self.base_url))
+                .header("Accept", "text/event-stream")
+                .json(&body);
+            if let Some(key) = &self.api_key {
+                builder = builder.bearer_auth(key);
+            }
+            for (name, value) in &self.headers {
+                builder = builder.header(name, value);
+            }
+            builder
+        };
+
+        let mut response = self.retry.send(&self.id, &self.timeouts, build).await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(http_error(&self.id, status.as_u16(), &body));
+        }
+
+        let mut decoder = sse::Decoder::new();
+        let mut state = StreamState::default();
+        while let Some(chunk) = self.timeouts.chunk(&self.id, response.chunk()).await? {
+            for event in decoder.push(&chunk) {
+                state.apply(&self.id, &event.data)?;
+            }
+        }
+        if let Some(event) = decoder.finish() {
+            state.apply(&self.id, &event.data)?;
+        }
+        Ok(state.into_response())
+    }
+}
+
+#[derive(Default)]
+struct PendingToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+/// Accumulates streamed deltas into a complete response
+#[derive(Default)]
+struct StreamState {
+    text: String,
+    reasoning: String,
+    tool_calls: BTreeMap<u64, PendingToolCall>,
+    finish_reason: Option<String>,
+    usage: Usage,
+    model: Option<String>,
+}
+
+impl StreamState {
+    fn apply(&mut self, provider: &str, data: &str) -> Result<()> {
+        if data.trim() == sse::DONE {
+            return Ok(());
+        }
+        let chunk: Value = serde_json::from_str(data)?;
+        if let Some(message) = error_message(&chunk) {
+            let status = chunk["error"]["code"].as_u64().map(|code| code as u16);
+            return Err(AgentError::api(provider, status, message));
+        }
+        if let Some(model) = chunk.get("model").and_then(|m| m.as_str()) {
+            self.model = Some(model.to_string());
+        }
+        if let Some(usage) = sse::usage_from_value(&chunk) {
+            self.usage = Usage {
+                input: usage
+                    .prompt_tokens
+                    .saturating_sub(usage.cached_tokens.unwrap_or(0)),
+                output: usage.completion_tokens,
+                reasoning: usage.reasoning_tokens.unwrap_or(0),
+                cache_read: usage.cached_tokens.unwrap_or(0),
+                cache_write: 0,
+            };
+        }
+
+        for choice in chunk
+            .get("choices")
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let Some(reason) = choice.get("finish_reason").and_then(|r| r.as_str()) {
+                self.finish_reason = Some(reason.to_string());
+            }
+            let Some(delta) = choice.get("delta") else {
+                continue;
+            };
+            if let Some(text) = delta.get("content").and_then(|c| c.as_str()) {
+                self.text.push_str(text);
+            }
+            for key in ["reasoning_content", "reasoning"] {
+                if let Some(text) = delta.get(key).and_then(|c| c.as_str()) {
+                    self.reasoning.push_str(text);
+                }
+            }
+            for call in delta
+                .get("tool_calls")
+                .and_then(|c| c.as_array())
+                .into_iter()
+                .flatten()
+            {
+                let index = call["index"]
+                    .as_u64()
+                    .unwrap_or(self.tool_calls.len() as u64);
+                let pending = self.tool_calls.entry(index).or_default();
+                if let Some(id) = call["id"].as_str() {
+                    pending.id = id.to_string();
+                }
+                if let Some(name) = call["function"]["name"].as_str() {
+                    pending.name.push_str(name);
+                }
+                if let Some(arguments) = call["function"]["arguments"].as_str() {
+                    pending.arguments.push_str(arguments);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn into_response(self) -> ChatResponse {
+        let mut content = Vec::new();
+        if !self.reasoning.is_empty() {
+            content.push(ContentPart::Reasoning {
+                text: self.reasoning,
+                metadata: None,
+            });
+        }
+        if !self.text.is_empty() {
+            content.push(ContentPart::Text { text: self.text });
+        }
+        for (index, call) in self.tool_calls {
+            let input = if call.arguments.trim().is_empty() {
+                Value::Object(Map::new())
+            } else {
+                serde_json::from_str(&call.arguments).unwrap_or(Value::String(call.arguments))
+            };
+            content.push(ContentPart::ToolCall {
+                call_id: if call.id.is_empty() {
+                    format!("call_{}", index)
+                } else {
+                    call.id
+                },
+                tool: call.name,
+                input,
+            });
+        }
+
+        ChatResponse {
+            content,
+            finish_reason: finish_reason(self.finish_reason.as_deref()).to_string(),
+            usage: self.usage,
+            model: self.model,
+        }
+    }
+}
+
+/// Translate an OpenAI finish reason to the AI SDK vocabulary
+fn finish_reason(reason: Option<&str>) -> &'static str {
+    match reason {
+        Some("stop") => "stop",
+        Some("tool_calls") | Some("function_call") => "tool-calls",
+        Some("length") => "length",
+        Some("content_filter") => "content-filter",
+        Some(_) => "other",
+        None => "unknown",
+    }
+}
@@ -0,0 +1,302 @@
+//! Retrying HTTP layer for provider requests
+//!
+//! Mirrors the JavaScript implementation's provider/retry-fetch.ts. Rate
+//! limits (HTTP 429) are retried until the global retry budget runs out,
+//! honouring `retry-after-ms` and `retry-after` (seconds or HTTP date).
+//! Server errors (500, 502, 503) are retried a fixed number of times with
+//! exponential backoff, and dropped connections are retried like rate
+//! limits. Refused connections and unknown hosts fail immediately: retrying
+//! does not make anything start listening.
+//!
+//! When retries are exhausted the last response is returned unchanged, so
+//! the provider maps it to an error as usual.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::header::HeaderMap;
+
+use super::timeout::Timeouts;
+use crate::error::{AgentError, Result};
+
+/// Environment variable for the total retry budget, in seconds
+pub const RETRY_TIMEOUT_ENV: &str = "LINK_ASSISTANT_AGENT_RETRY_TIMEOUT";
+/// Environment variable for the longest single backoff delay, in seconds
+pub const MAX_RETRY_DELAY_ENV: &str = "LINK_ASSISTANT_AGENT_MAX_RETRY_DELAY";
+/// Environment variable for the shortest delay between attempts, in seconds
+pub const MIN_RETRY_INTERVAL_ENV: &str = "LINK_ASSISTANT_AGENT_MIN_RETRY_INTERVAL";
+
+/// Default total retry budget (7 days)
+pub const DEFAULT_RETRY_TIMEOUT: Duration = Duration::from_secs(604_800);
+/// Default longest single backoff delay (20 minutes)
+pub const DEFAULT_MAX_RETRY_DELAY: Duration = Duration::from_secs(1_200);
+/// Default shortest delay between rate-limited attempts
+pub const DEFAULT_MIN_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// First backoff delay; doubled on every attempt
+pub const DEFAULT_INITIAL_DELAY: Duration = Duration::from_secs(2);
+
+/// Retries for server errors, unlike rate limits which retry until the budget
+/// runs out, so permanently broken endpoints are not retried for days
+pub const SERVER_ERROR_MAX_RETRIES: u32 = 3;
+
+/// Backoff cap for server errors, which carry no `retry-after`
+const MAX_DELAY_NO_HEADERS: Duration = Duration::from_secs(30);
+
+/// A retry about to happen, reported before waiting
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryEvent {
+    pub provider: String,
+    /// The attempt that failed, starting at 1
+    pub attempt: u32,
+    pub delay: Duration,
+    /// HTTP status, or `None` for network errors
+    pub status: Option<u16>,
+    pub message: String,
+}
+
+type Listener = Arc<dyn Fn(&RetryEvent) + Send + Sync>;
+
+/// When and how long to retry failed provider requests
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// Total time spent retrying one request
+    pub retry_timeout: Duration,
+    /// Cap for exponential backoff delays
+    pub max_retry_delay: Duration,
+    /// Floor for rate limit delays, so attempts are never fired in bursts
+    pub min_retry_interval: Duration,
+    pub initial_delay: Duration,
+    /// Retry HTTP 429 responses (`--no-retry-on-rate-limits` turns this off)
+    pub retry_on_rate_limits: bool,
+    listener: Option<Listener>,
+}
+
+impl std::fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("retry_timeout", &self.retry_timeout)
+            .field("max_retry_delay", &self.max_retry_delay)
+            .field("min_retry_interval", &self.min_retry_interval)
+            .field("initial_delay", &self.initial_delay)
+            .field("retry_on_rate_limits", &self.retry_on_rate_limits)
+            .field("listener", &self.listener.is_some())
+            .finish()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retry_timeout: DEFAULT_RETRY_TIMEOUT,
+            max_retry_delay: DEFAULT_MAX_RETRY_DELAY,
+            min_retry_interval: DEFAULT_MIN_RETRY_INTERVAL,
+            initial_delay: DEFAULT_INITIAL_DELAY,
+            retry_on_rate_limits: true,
+            listener: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries; every response is returned as is
+    pub fn none() -> Self {
+        Self {
+            retry_timeout: Duration::ZERO,
+            ..Self::default()
+        }
+    }
+
+    /// The default policy with the `LINK_ASSISTANT_AGENT_*` overrides applied
+    pub fn from_env(getenv: impl Fn(&str) -> Option<String>) -> Self {
+        let seconds = |key: &str| {
+            getenv(key)
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .map(Duration::from_secs)
+        };
+        let defaults = Self::default();
+        Self {
+            retry_timeout: seconds(RETRY_TIMEOUT_ENV).unwrap_or(defaults.retry_timeout),
+            max_retry_delay: seconds(MAX_RETRY_DELAY_ENV).unwrap_or(defaults.max_retry_delay),
+            min_retry_interval: seconds(MIN_RETRY_INTERVAL_ENV)
+                .unwrap_or(defaults.min_retry_interval),
+            ..defaults
+        }
+    }
+
+    pub fn with_retry_timeout(mut self, timeout: Duration) -> Self {
+        self.retry_timeout = timeout;
+        self
+    }
+
+    pub fn with_max_retry_delay(mut self, delay: Duration) -> Self {
+        self.max_retry_delay = delay;
+        self
+    }
+
+    pub fn with_min_retry_interval(mut self, interval: Duration) -> Self {
+        self.min_retry_interval = interval;
+        self
+    }
+
+    pub fn with_initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    pub fn with_retry_on_rate_limits(mut self, enabled: bool) -> Self {
+        self.retry_on_rate_limits = enabled;
+        self
+    }
+
+    /// Call `listener` before every retry wait
+    pub fn with_listener(mut self, listener: impl Fn(&RetryEvent) + Send + Sync + 'static) -> Self {
+        self.listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// Exponential backoff for `attempt` (starting at 1), capped at `cap`
+    fn backoff(&self, attempt: u32, cap: Duration) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_delay.saturating_mul(factor).min(cap)
+    }
+
+    /// Delay before retrying a rate-limited attempt.
+    ///
+    /// A `retry-after` longer than `remaining` yields `None`: the caller
+    /// cannot wait that long, so higher-level handling takes over.
+    pub fn rate_limit_delay(
+        &self,
+        headers: &HeaderMap,
+        attempt: u32,
+        remaining: Duration,
+    ) -> Option<Duration> {
+        let delay = match parse_retry_after(headers, Utc::now()) {
+            Some(retry_after) if retry_after > remaining => return None,
+            Some(retry_after) => retry_after,
+            None => self.backoff(attempt, self.max_retry_delay),
+        };
+        Some(jitter(delay.max(self.min_retry_interval)))
+    }
+
+    /// Send a request, retrying per this policy.
+    ///
+    /// `request` builds a fresh request for every attempt, each of which
+    /// must produce response headers within the first-byte timeout; waits
+    /// between attempts are not bound by it. Non-retryable responses, and
+    /// the last response once retries are exhausted, are returned for the
+    /// caller to inspect.
+    pub async fn send(
+        &self,
+        provider: &str,
+        timeouts: &Timeouts,
+        request: impl Fn() -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let start = Instant::now();
+        let mut attempt = 0u32;
+        loop {
+            attempt += 1;
+            let response = match timeouts.first_byte(provider, request().send()).await {
+                Ok(response) => response,
+                Err(AgentError::Http(e))
+                    if is_retryable(&e) && start.elapsed() < self.retry_timeout =>
+                {
+                    let delay = self.backoff(attempt, self.max_retry_delay);
+                    self.wait(provider, attempt, delay, None, e.to_string())
+                        .await;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            let status = response.status().as_u16();
+            let elapsed = start.elapsed();
+            if matches!(status, 500 | 502 | 503) {
+                if attempt > SERVER_ERROR_MAX_RETRIES || elapsed >= self.retry_timeout {
+                    return Ok(response);
+                }
+                let cap = self.max_retry_delay.min(MAX_DELAY_NO_HEADERS);
+                let delay = jitter(self.backoff(attempt, cap));
+                let message = format!("Server error (HTTP {})", status);
+                self.wait(provider, attempt, delay, Some(status), message)
+                    .await;
+                continue;
+            }
+
+            if status != 429 || !self.retry_on_rate_limits || elapsed >= self.retry_timeout {
+                return Ok(response);
+            }
+            let remaining = self.retry_timeout - elapsed;
+            let delay = match self.rate_limit_delay(response.headers(), attempt, remaining) {
+                Some(delay) if delay < remaining => delay,
+                _ => return Ok(response),
+            };
+            let message = "Rate limited (HTTP 429)".to_string();
+            self.wait(provider, attempt, delay, Some(status), message)
+                .await;
+        }
+    }
+
+    async fn wait(
+        &self,
+        provider: &str,
+        attempt: u32,
+        delay: Duration,
+        status: Option<u16>,
+        message: String,
+    ) {
+        tracing::info!(
+            provider,
+            attempt,
+            delay_ms = delay.as_millis() as u64,
+            status,
+            message = message.as_str(),
+            "retrying provider request"
+        );
+        if let Some(listener) = &self.listener {
+            listener(&RetryEvent {
+                provider: provider.to_string(),
+                attempt,
+                delay,
+                status,
+                message,
+            });
+        }
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Network errors worth retrying: dropped connections and timeouts, but not
+/// refused connections or unknown hosts
+fn is_retryable(error: &reqwest::Error) -> bool {
+    !error.is_connect() && (error.is_request() || error.is_timeout() || error.is_body())
+}
+
+/// Add 0-10% random jitter so concurrent clients do not retry in lockstep
+fn jitter(delay: Duration) -> Duration {
+    delay.mul_f64(1.0 + rand::thread_rng().gen_range(0.0..0.1))
+}
+
+/// Delay requested by `retry-after-ms` or `retry-after` (seconds or an HTTP
+/// date relative to `now`). Zero, negative and past values are ignored.
+pub fn parse_retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok().map(str::trim);
+
+    if let Some(ms) = header("retry-after-ms").and_then(|v| v.parse::<f64>().ok()) {
+        if ms.is_finite() && ms > 0.0 {
+            return Some(Duration::from_secs_f64(ms / 1000.0));
+        }
+    }
+
+    let value = header("retry-after")?;
+    if let Ok(seconds) = value.parse::<f64>() {
+        return (seconds.is_finite() && seconds > 0.0)
+            .then(|| Duration::from_millis((seconds * 1000.0).ceil() as u64));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&Utc) - now)
+        .to_std()
+        .ok()
+        .filter(|delay| !delay.is_zero())
+}
@@ -0,0 +1,163 @@
+//! Timeout hierarchy for provider requests
+//!
+//! Each layer catches a different way a request can hang (see
+//! docs/timeout-hierarchy.md):
+//!
+//! - connect: establishing the TCP/TLS connection
+//! - first byte: waiting for the response headers of one attempt
+//! - chunk: silence between two chunks of a streaming body
+//! - step: one whole model step, including the full stream
+//!
+//! Rate limit waits in [`super::retry_fetch`] sit outside the connect and
+//! first-byte layers, so a long `retry-after` is not cut short by them.
+//! Every layer fails with its own [`AgentError`] variant, all of which are
+//! retried by the agent loop.
+
+use std::future::Future;
+use std::time::Duration;
+
+use crate::error::{AgentError, Result};
+
+/// Environment variable for the connect timeout, in milliseconds
+pub const CONNECT_TIMEOUT_ENV: &str = "LINK_ASSISTANT_AGENT_CONNECT_TIMEOUT_MS";
+/// Environment variable for the first-byte timeout, in milliseconds
+pub const FIRST_BYTE_TIMEOUT_ENV: &str = "LINK_ASSISTANT_AGENT_FIRST_BYTE_TIMEOUT_MS";
+/// Environment variable for the inter-chunk idle timeout, in milliseconds
+pub const STREAM_CHUNK_TIMEOUT_ENV: &str = "LINK_ASSISTANT_AGENT_STREAM_CHUNK_TIMEOUT_MS";
+/// Environment variable for the step timeout, in milliseconds
+pub const STREAM_STEP_TIMEOUT_ENV: &str = "LINK_ASSISTANT_AGENT_STREAM_STEP_TIMEOUT_MS";
+
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Matches the JavaScript implementation's provider timeout (5 minutes)
+pub const DEFAULT_FIRST_BYTE_TIMEOUT: Duration = Duration::from_secs(300);
+pub const DEFAULT_STREAM_CHUNK_TIMEOUT: Duration = Duration::from_millis(120_000);
+pub const DEFAULT_STREAM_STEP_TIMEOUT: Duration = Duration::from_millis(600_000);
+
+/// Waits before retrying a timed-out step, as in the JavaScript
+/// implementation's `SessionRetry.TIMEOUT_DELAYS`; one retry per entry
+pub const TIMEOUT_RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(30),
+    Duration::from_secs(60),
+    Duration::from_secs(120),
+];
+
+/// Timeouts for each layer of a provider request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    pub connect: Duration,
+    pub first_byte: Duration,
+    pub chunk: Duration,
+    pub step: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: DEFAULT_CONNECT_TIMEOUT,
+            first_byte: DEFAULT_FIRST_BYTE_TIMEOUT,
+            chunk: DEFAULT_STREAM_CHUNK_TIMEOUT,
+            step: DEFAULT_STREAM_STEP_TIMEOUT,
+        }
+    }
+}
+
+impl Timeouts {
+    /// The defaults with the `LINK_ASSISTANT_AGENT_*_MS` overrides applied
+    pub fn from_env(getenv: impl Fn(&str) -> Option<String>) -> Self {
+        let millis = |key: &str, default: Duration| {
+            getenv(key)
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(default)
+        };
+        let defaults = Self::default();
+        Self {
+            connect: millis(CONNECT_TIMEOUT_ENV, defaults.connect),
+            first_byte: millis(FIRST_BYTE_TIMEOUT_ENV, defaults.first_byte),
+            chunk: millis(STREAM_CHUNK_TIMEOUT_ENV, defaults.chunk),
+            step: millis(STREAM_STEP_TIMEOUT_ENV, defaults.step),
+        }
+    }
+
+    pub fn with_connect(mut self, timeout: Duration) -> Self {
+        self.connect = timeout;
+        self
+    }
+
+    pub fn with_first_byte(mut self, timeout: Duration) -> Self {
+        self.first_byte = timeout;
+        self
+    }
+
+    pub fn with_chunk(mut self, timeout: Duration) -> Self {
+        self.chunk = timeout;
+        self
+    }
+
+    pub fn with_step(mut self, timeout: Duration) -> Self {
+        self.step = timeout;
+        self
+    }
+
+    /// An HTTP client enforcing the connect timeout
+    pub fn client(&self) -> reqwest::Client {
+        reqwest::Client::builder()
+            .connect_timeout(self.connect)
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new())
+    }
+
+    /// Wait for the response headers of one attempt
+    pub async fn first_byte<T>(
+        &self,
+        provider: &str,
+        response: impl Future<Output = reqwest::Result<T>>,
+    ) -> Result<T> {
+        match tokio::time::timeout(self.first_byte, response).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(e)) if e.is_connect() && e.is_timeout() => Err(AgentError::ConnectTimeout {
+                provider: provider.to_string(),
+                timeout_ms: millis(self.connect),
+            }),
+            Ok(Err(e)) => Err(e.into()),
+            Err(_) => Err(AgentError::FirstByteTimeout {
+                provider: provider.to_string(),
+                timeout_ms: millis(self.first_byte),
+            }),
+        }
+    }
+
+    /// Wait for the next chunk of a streaming body
+    pub async fn chunk<T>(
+        &self,
+        provider: &str,
+        chunk: impl Future<Output = reqwest::Result<T>>,
+    ) -> Result<T> {
+        match tokio::time::timeout(self.chunk, chunk).await {
+            Ok(chunk) => Ok(chunk?),
+            Err(_) => Err(AgentError::StreamIdleTimeout {
+                provider: provider.to_string(),
+                timeout_ms: millis(self.chunk),
+            }),
+        }
+    }
+
+    /// Run one model step
+    pub async fn step<T>(
+        &self,
+        provider: &str,
+        step: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        match tokio::time::timeout(self.step, step).await {
+            Ok(result) => result,
+            Err(_) => Err(AgentError::StepTimeout {
+                provider: provider.to_string(),
+                timeout_ms: millis(self.step),
+            }),
+        }
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
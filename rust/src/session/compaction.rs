@@ -0,0 +1,430 @@
+//! Context compaction
+//!
+//! Rust counterpart of `js/src/session/compaction.ts`. When the conversation
+//! plus the output reserved for the next step would no longer fit the
+//! model's context window, the history is summarized by a compaction model
+//! and only the summary and what follows it are sent from then on.
+//!
+//! Compaction models form an ordered cascade (`--compaction-models`): a
+//! model whose context window cannot hold the conversation, or whose request
+//! fails, is skipped in favour of the next one. The special entry `same`
+//! stands for the session's own model.
+
+use serde::{Deserialize, Serialize};
+
+use crate::defaults::ModelParts;
+use crate::error::{AgentError, Result};
+use crate::provider::models::{Catalog, Model};
+use crate::provider::Provider;
+use crate::util::lino;
+use crate::util::token::{count_tokens, estimate};
+
+use super::message::{MessageInfo, MessageWithParts, Part, ToolState};
+use super::now;
+use super::prompt::OUTPUT_TOKEN_MAX;
+
+/// Share of the usable context window filled before compacting when no
+/// safety margin is configured
+pub const OVERFLOW_SAFETY_MARGIN: f64 = 0.75;
+
+/// Environment variable that turns automatic compaction off
+pub const DISABLE_AUTOCOMPACT_ENV: &str = "LINK_ASSISTANT_AGENT_DISABLE_AUTOCOMPACT";
+
+/// Environment variable that turns tool-output pruning off
+pub const DISABLE_PRUNE_ENV: &str = "LINK_ASSISTANT_AGENT_DISABLE_PRUNE";
+
+/// Pruning only happens when it frees at least this many tokens
+pub const PRUNE_MINIMUM: u64 = 20_000;
+
+/// Tokens of the most recent tool output that are never pruned
+pub const PRUNE_PROTECT: u64 = 40_000;
+
+/// Cascade entry standing for the session's own model
+pub const SAME_MODEL: &str = "same";
+
+/// Request appended to the conversation sent to the compaction model
+pub const SUMMARY_REQUEST: &str = "Provide a detailed but concise summary of our conversation above. Focus on information that would be helpful for continuing the conversation, including what we did, what we're doing, which files we're working on, and what we're going to do next.";
+
+/// Synthetic user message that lets the agent resume after compacting
+pub const CONTINUE_TEXT: &str = "Continue if you have next steps";
+
+/// One entry of the compaction cascade
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompactionModel {
+    /// The session's own model
+    Same,
+    Model(ModelParts),
+}
+
+impl CompactionModel {
+    /// Resolve a cascade entry: `same`, `providerID/modelID`, or a bare
+    /// model ID looked up in the catalog
+    pub fn resolve(name: &str, catalog: &Catalog) -> Option<Self> {
+        if name.eq_ignore_ascii_case(SAME_MODEL) {
+            return Some(Self::Same);
+        }
+        match name.split_once('/') {
+            Some((provider_id, model_id)) => Some(Self::Model(ModelParts {
+                provider_id: provider_id.to_string(),
+                model_id: model_id.to_string(),
+            })),
+            None => catalog.resolve_short_name(name).map(Self::Model),
+        }
+    }
+}
+
+impl std::fmt::Display for CompactionModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Same => f.write_str(SAME_MODEL),
+            Self::Model(model) => write!(f, "{}/{}", model.provider_id, model.model_id),
+        }
+    }
+}
+
+/// Model names of a links notation sequence such as
+/// `(gpt-5-nano opencode/big-pickle same)`; the parentheses are optional
+pub fn parse_models(notation: &str) -> Result<Vec<String>> {
+    lino::parse_sequence(notation).map_err(|e| match e {
+        AgentError::Config { message } => AgentError::Config {
+            message: format!("Invalid compaction models \"{}\": {}", notation, message),
+        },
+        other => other,
+    })
+}
+
+/// Parse and resolve a compaction cascade. Entries that cannot be resolved
+/// are skipped, as the default cascade names models that come and go.
+pub fn cascade(notation: &str, catalog: &Catalog) -> Result<Vec<CompactionModel>> {
+    Ok(parse_models(notation)?
+        .iter()
+        .filter_map(|name| {
+            let resolved = CompactionModel::resolve(name, catalog);
+            if resolved.is_none() {
+                tracing::debug!(
+                    model = name.as_str(),
+                    "skipping unresolvable compaction model"
+                );
+            }
+            resolved
+        })
+        .collect())
+}
+
+/// Whether `LINK_ASSISTANT_AGENT_DISABLE_AUTOCOMPACT` turns compaction off
+pub fn disabled_from_env(getenv: impl Fn(&str) -> Option<String>) -> bool {
+    getenv(DISABLE_AUTOCOMPACT_ENV)
+        .is_some_and(|value| matches!(value.trim(), "1" | "true" | "yes" | "on"))
+}
+
+/// Whether automatic compaction is turned off in the process environment
+pub fn disabled() -> bool {
+    disabled_from_env(crate::global::getenv)
+}
+
+/// Whether `LINK_ASSISTANT_AGENT_DISABLE_PRUNE` turns pruning off
+pub fn prune_disabled_from_env(getenv: impl Fn(&str) -> Option<String>) -> bool {
+    getenv(DISABLE_PRUNE_ENV)
+        .is_some_and(|value| matches!(value.trim(), "1" | "true" | "yes" | "on"))
+}
+
+/// Whether pruning is turned off in the process environment
+pub fn prune_disabled() -> bool {
+    prune_disabled_from_env(crate::global::getenv)
+}
+
+/// Share of the usable context window to fill before compacting.
+///
+/// `compaction_context_limit` is the context window of the first compaction
+/// model, or `None` when it is the session's own model. A compaction model
+/// with a larger window than the base model can ingest everything the base
+/// model holds, so no margin is needed; otherwise the configured margin
+/// applies.
+pub fn safety_margin_ratio(
+    base_context_limit: u64,
+    safety_margin_percent: u32,
+    compaction_context_limit: Option<u64>,
+) -> f64 {
+    match compaction_context_limit {
+        Some(limit) if limit > 0 && limit > base_context_limit => 1.0,
+        _ => 1.0 - f64::from(safety_margin_percent.min(100)) / 100.0,
+    }
+}
+
+/// Context usage of a model, as reported in verbose diagnostics
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextDiagnostics {
+    pub context_limit: u64,
+    /// Output tokens reserved for the next step
+    pub output_limit: u64,
+    pub usable_context: u64,
+    /// Tokens that may be used before compacting
+    pub safe_limit: u64,
+    pub safety_margin: f64,
+    pub current_tokens: u64,
+    pub headroom: i64,
+    pub overflow: bool,
+}
+
+/// Context usage of `model` holding `current_tokens`, or `None` when the
+/// model's context window is unknown
+pub fn context_diagnostics(
+    model: &Model,
+    current_tokens: u64,
+    safety_margin: f64,
+) -> Option<ContextDiagnostics> {
+    let context_limit = model.limit.context;
+    if context_limit == 0 {
+        return None;
+    }
+    let output_limit = match model.limit.output {
+        0 => OUTPUT_TOKEN_MAX,
+        limit => limit.min(OUTPUT_TOKEN_MAX),
+    };
+    let usable_context = context_limit.saturating_sub(output_limit);
+    let safe_limit = (usable_context as f64 * safety_margin).floor() as u64;
+    Some(ContextDiagnostics {
+        context_limit,
+        output_limit,
+        usable_context,
+        safe_limit,
+        safety_margin,
+        current_tokens,
+        headroom: safe_limit as i64 - current_tokens as i64,
+        overflow: current_tokens > safe_limit,
+    })
+}
+
+/// Whether `current_tokens` exceed the safe share of `model`'s context
+pub fn is_overflow(model: &Model, current_tokens: u64, safety_margin: f64) -> bool {
+    let Some(diagnostics) = context_diagnostics(model, current_tokens, safety_margin) else {
+        return false;
+    };
+    tracing::info!(
+        context_limit = diagnostics.context_limit,
+        safe_limit = diagnostics.safe_limit,
+        safety_margin,
+        current_tokens,
+        overflow = diagnostics.overflow,
+        "overflow check"
+    );
+    diagnostics.overflow
+}
+
+/// Tokens in use after the last finished step of `messages`, or `None`
+/// when there is no such step or it was itself a summary.
+///
+/// Providers that report no usage at all are covered by counting the text
+/// and tool output of the conversation instead.
+pub fn current_tokens(messages: &[MessageWithParts]) -> Option<u64> {
+    let last = messages.iter().rev().find_map(|m| match &m.info {
+        MessageInfo::Assistant(a) if a.finish.is_some() => Some(a),
+        _ => None,
+    })?;
+    if last.summary == Some(true) {
+        return None;
+    }
+    let tokens = &last.tokens;
+    let reported = tokens.input + tokens.cache.read + tokens.output;
+    if reported > 0 {
+        return Some(reported);
+    }
+    let content: String = messages
+        .iter()
+        .flat_map(|m| &m.parts)
+        .filter_map(|part| match part {
+            Part::Text(t) => Some(t.text.as_str()),
+            Part::Tool(t) => match &t.state {
+                ToolState::Completed { output, time, .. } if time.compacted.is_none() => {
+                    Some(output.as_str())
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    Some(count_tokens(&content).count)
+}
+
+/// The messages still sent to the model: everything from the most recent
+/// completed compaction on, or the whole history when there is none.
+/// Mirrors the JavaScript `MessageV2.filterCompacted`.
+pub fn active_messages(messages: &[MessageWithParts]) -> &[MessageWithParts] {
+    &messages[active_start(messages)..]
+}
+
+/// Index of the first message returned by [`active_messages`]
+pub fn active_start(messages: &[MessageWithParts]) -> usize {
+    let mut summarized = Vec::new();
+    for (index, message) in messages.iter().enumerate().rev() {
+        match &message.info {
+            MessageInfo::Assistant(a) if a.summary == Some(true) && a.finish.is_some() => {
+                summarized.push(a.parent_id.as_str());
+            }
+            MessageInfo::User(u)
+                if summarized.contains(&u.id.as_str())
+                    && message
+                        .parts
+                        .iter()
+                        .any(|part| matches!(part, Part::Compaction(_))) =>
+            {
+                return index;
+            }
+            _ => {}
+        }
+    }
+    0
+}
+
+/// A tool part whose output was pruned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrunedPart {
+    pub message_id: String,
+    pub part_id: String,
+}
+
+/// What a [`prune`] pass removed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pruned {
+    pub parts: Vec<PrunedPart>,
+    /// Estimated tokens freed
+    pub tokens: u64,
+}
+
+/// Prune the output of old tool calls, a cheap pass before summarizing.
+///
+/// Walks back from the newest message, skipping the latest two user turns,
+/// and keeps the most recent `PRUNE_PROTECT` tokens of tool output. Older
+/// completed outputs are marked with `time.compacted` (and replaced by
+/// [`super::message::PRUNED_OUTPUT`] when sent to the model), but only when
+/// that frees more than `PRUNE_MINIMUM` tokens. Stops at a summary or at an
+/// output pruned before. Mirrors the JavaScript `SessionCompaction.prune`.
+pub fn prune(messages: &mut [MessageWithParts]) -> Pruned {
+    let mut total = 0;
+    let mut pruned = Pruned::default();
+    let mut turns = 0;
+    'messages: for message in messages.iter().rev() {
+        match &message.info {
+            MessageInfo::User(_) => turns += 1,
+            MessageInfo::Assistant(a) if turns >= 2 && a.summary == Some(true) => break,
+            MessageInfo::Assistant(_) => {}
+        }
+        if turns < 2 {
+            continue;
+        }
+        for part in message.parts.iter().rev() {
+            let Part::Tool(tool) = part else { continue };
+            let ToolState::Completed { output, time, .. } = &tool.state else {
+                continue;
+            };
+            if time.compacted.is_some() {
+                break 'messages;
+            }
+            let tokens = estimate(output);
+            total += tokens;
+            if total > PRUNE_PROTECT {
+                pruned.tokens += tokens;
+                pruned.parts.push(PrunedPart {
+                    message_id: tool.message_id.clone(),
+                    part_id: tool.id.clone(),
+                });
+            }
+        }
+    }
+    tracing::info!(pruned = pruned.tokens, total, "prune scan");
+    if pruned.tokens <= PRUNE_MINIMUM {
+        return Pruned::default();
+    }
+
+    let compacted = now();
+    for message in messages.iter_mut() {
+        for part in &mut message.parts {
+            let Part::Tool(tool) = part else { continue };
+            if !pruned.parts.iter().any(|p| p.part_id == tool.id) {
+                continue;
+            }
+            if let ToolState::Completed { time, .. } = &mut tool.state {
+                time.compacted = Some(compacted);
+            }
+        }
+    }
+    tracing::info!(count = pruned.parts.len(), tokens = pruned.tokens, "pruned");
+    pruned
+}
+
+/// A compaction model ready to be called
+pub enum Target<'a> {
+    /// The session's own model and provider
+    Same,
+    Model {
+        model: ModelParts,
+        provider: &'a dyn Provider,
+        /// Context window from the catalog, if known
+        context_limit: Option<u64>,
+    },
+}
+
+/// Compaction settings of an agent loop
+pub struct Compaction<'a> {
+    /// Models tried in order; empty turns compaction off
+    pub cascade: Vec<Target<'a>>,
+    pub safety_margin_percent: u32,
+    /// Prune old tool output before summarizing and after every turn
+    pub prune: bool,
+}
+
+impl Default for Compaction<'_> {
+    /// Compact with the session's own model at the default margin
+    fn default() -> Self {
+        Self {
+            cascade: vec![Target::Same],
+            safety_margin_percent: ((1.0 - OVERFLOW_SAFETY_MARGIN) * 100.0).round() as u32,
+            prune: true,
+        }
+    }
+}
+
+impl<'a> Compaction<'a> {
+    /// Never summarize (pruning stays on)
+    pub fn disabled() -> Self {
+        Self {
+            cascade: Vec::new(),
+            ..Self::default()
+        }
+    }
+
+    /// Compact with `cascade`, keeping `safety_margin_percent` of the usable
+    /// context free
+    pub fn new(cascade: Vec<Target<'a>>, safety_margin_percent: u32) -> Self {
+        Self {
+            cascade,
+            safety_margin_percent,
+            prune: true,
+        }
+    }
+
+    /// Turn tool-output pruning on or off
+    pub fn with_prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.cascade.is_empty()
+    }
+
+    /// Safety margin ratio for a base model with `base_context_limit`,
+    /// judged by the first model of the cascade
+    pub fn safety_margin_ratio(&self, base_context_limit: u64) -> f64 {
+        let compaction_context_limit = match self.cascade.first() {
+            Some(Target::Model { context_limit, .. }) => *context_limit,
+            _ => None,
+        };
+        safety_margin_ratio(
+            base_context_limit,
+            self.safety_margin_percent,
+            compaction_context_limit,
+        )
+    }
+}
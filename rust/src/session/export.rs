@@ -0,0 +1,310 @@
+//! Session export formats for `agent export`
+//!
+//! Renders a stored session three ways: the OpenCode JSON document the
+//! JavaScript `export` command writes (`{ info, messages }`), a Claude Code
+//! style JSONL transcript (one `user`/`assistant` entry per line) and a
+//! Markdown transcript meant for code reviews and bug reports.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use super::message::{MessageInfo, MessageWithParts, Part, ToolPart, ToolState};
+use super::Session;
+use crate::error::{AgentError, Result};
+
+/// Output format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `{ info, messages, diffs }`, as written by the JavaScript agent
+    OpenCode,
+    /// Claude Code JSONL transcript
+    Claude,
+    /// Readable Markdown transcript
+    Markdown,
+}
+
+impl FromStr for Format {
+    type Err = AgentError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "opencode" | "json" => Ok(Self::OpenCode),
+            "claude" | "jsonl" => Ok(Self::Claude),
+            "markdown" | "md" => Ok(Self::Markdown),
+            other => Err(AgentError::invalid_arguments(
+                "export",
+                format!(
+                    "Unknown export format \"{}\" (expected opencode, claude or markdown)",
+                    other
+                ),
+            )),
+        }
+    }
+}
+
+/// Render `session` with the file changes recorded for it (`diffs`, as
+/// stored under `session_diff`)
+pub fn export(session: &Session, diffs: &[Value], format: Format) -> Result<String> {
+    match format {
+        Format::OpenCode => opencode(session, diffs),
+        Format::Claude => claude(session),
+        Format::Markdown => Ok(markdown(session, diffs)),
+    }
+}
+
+fn opencode(session: &Session, diffs: &[Value]) -> Result<String> {
+    let mut document = json!({
+        "info": session.info,
+        "messages": session.messages,
+    });
+    if !diffs.is_empty() {
+        document["diffs"] = json!(diffs);
+    }
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+/// One JSON line per transcript entry: user messages, assistant messages
+/// (text and `tool_use` blocks) and the `tool_result` entries answering them
+fn claude(session: &Session) -> Result<String> {
+    let session_id = session.id();
+    let cwd = &session.info.directory;
+    let mut lines = Vec::new();
+    let mut parent: Option<String> = None;
+    let mut push = |entry_type: &str, uuid: String, created: u64, message: Value| {
+        let entry = json!({
+            "type": entry_type,
+            "uuid": uuid,
+            "parentUuid": parent,
+            "sessionId": session_id,
+            "timestamp": iso(created),
+            "cwd": cwd,
+            "version": session.info.version,
+            "message": message,
+        });
+        parent = Some(uuid);
+        entry
+    };
+
+    for message in &session.messages {
+        match &message.info {
+            MessageInfo::User(user) => {
+                let text = user_text(message);
+                lines.push(push(
+                    "user",
+                    user.id.clone(),
+                    user.time.created,
+                    json!({ "role": "user", "content": text }),
+                ));
+            }
+            MessageInfo::Assistant(assistant) => {
+                let mut content = Vec::new();
+                let mut results = Vec::new();
+                for part in &message.parts {
+                    match part {
+                        Part::Text(text) => {
+                            content.push(json!({ "type": "text", "text": text.text }))
+                        }
+                        Part::Reasoning(reasoning) => content.push(json!({
+                            "type": "thinking",
+                            "thinking": reasoning.text,
+                        })),
+                        Part::Tool(tool) => {
+                            content.push(json!({
+                                "type": "tool_use",
+                                "id": tool.call_id,
+                                "name": tool.tool,
+                                "input": tool_input(tool),
+                            }));
+                            if let Some((output, is_error)) = tool_output(tool) {
+                                results.push(json!({
+                                    "type": "tool_result",
+                                    "tool_use_id": tool.call_id,
+                                    "content": output,
+                                    "is_error": is_error,
+                                }));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                let tokens = &assistant.tokens;
+                let stop_reason = match assistant.finish.as_deref() {
+                    Some("tool-calls") => "tool_use",
+                    Some("length") => "max_tokens",
+                    _ => "end_turn",
+                };
+                lines.push(push(
+                    "assistant",
+                    assistant.id.clone(),
+                    assistant.time.created,
+                    json!({
+                        "id": assistant.id,
+                        "type": "message",
+                        "role": "assistant",
+                        "model": assistant.model_id,
+                        "content": content,
+                        "stop_reason": stop_reason,
+                        "usage": {
+                            "input_tokens": tokens.input,
+                            "output_tokens": tokens.output,
+                            "cache_read_input_tokens": tokens.cache.read,
+                            "cache_creation_input_tokens": tokens.cache.write,
+                        },
+                    }),
+                ));
+                if !results.is_empty() {
+                    let created = assistant.time.completed.unwrap_or(assistant.time.created);
+                    lines.push(push(
+                        "user",
+                        format!("{}-tool-results", assistant.id),
+                        created,
+                        json!({ "role": "user", "content": results }),
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for line in lines {
+        out.push_str(&serde_json::to_string(&line)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn markdown(session: &Session, diffs: &[Value]) -> String {
+    let info = &session.info;
+    let mut out = String::new();
+    let _ = writeln!(out, "# {}\n", info.title);
+    let _ = writeln!(out, "- Session: `{}`", info.id);
+    let _ = writeln!(out, "- Directory: `{}`", info.directory);
+    let _ = writeln!(out, "- Created: {}", iso(info.time.created));
+    let _ = writeln!(out, "- Updated: {}", iso(info.time.updated));
+
+    let (mut input, mut output, mut cost) = (0, 0, 0.0);
+    for message in &session.messages {
+        match &message.info {
+            MessageInfo::User(_) => {
+                let _ = writeln!(out, "\n## User\n\n{}", user_text(message));
+            }
+            MessageInfo::Assistant(assistant) => {
+                let _ = writeln!(
+                    out,
+                    "\n## Assistant ({}/{})",
+                    assistant.provider_id, assistant.model_id
+                );
+                for part in &message.parts {
+                    match part {
+                        Part::Text(text) => {
+                            let _ = writeln!(out, "\n{}", text.text);
+                        }
+                        Part::Reasoning(reasoning) => {
+                            let quoted: Vec<String> =
+                                reasoning.text.lines().map(|l| format!("> {}", l)).collect();
+                            let _ = writeln!(out, "\n{}", quoted.join("\n"));
+                        }
+                        Part::Tool(tool) => markdown_tool(&mut out, tool),
+                        _ => {}
+                    }
+                }
+                let tokens = &assistant.tokens;
+                let _ = writeln!(
+                    out,
+                    "\n_Tokens: {} in, {} out · Cost: ${:.4}_",
+                    tokens.input, tokens.output, assistant.cost
+                );
+                input += tokens.input;
+                output += tokens.output;
+                cost += assistant.cost;
+            }
+        }
+    }
+
+    if !diffs.is_empty() {
+        let _ = writeln!(out, "\n## Changes\n\n| File | Additions | Deletions |");
+        let _ = writeln!(out, "| --- | ---: | ---: |");
+        for diff in diffs {
+            let _ = writeln!(
+                out,
+                "| `{}` | {} | {} |",
+                diff["file"].as_str().unwrap_or_default(),
+                diff["additions"].as_u64().unwrap_or(0),
+                diff["deletions"].as_u64().unwrap_or(0)
+            );
+        }
+    }
+
+    let _ = writeln!(
+        out,
+        "\n## Usage\n\n- Input tokens: {}\n- Output tokens: {}\n- Cost: ${:.4}",
+        input, output, cost
+    );
+    out
+}
+
+fn markdown_tool(out: &mut String, tool: &ToolPart) {
+    let status = match &tool.state {
+        ToolState::Pending { .. } => "pending",
+        ToolState::Running { .. } => "running",
+        ToolState::Completed { .. } => "completed",
+        ToolState::Error { .. } => "error",
+    };
+    let input = serde_json::to_string_pretty(tool_input(tool)).unwrap_or_default();
+    let _ = writeln!(
+        out,
+        "\n**Tool: {}** ({})\n\n```json\n{}\n```",
+        tool.tool, status, input
+    );
+    if let Some((output, _)) = tool_output(tool) {
+        let fence = fence_for(output);
+        let _ = writeln!(out, "\n{fence}\n{}\n{fence}", output.trim_end());
+    }
+}
+
+/// A code fence longer than any backtick run inside `text`
+fn fence_for(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn user_text(message: &MessageWithParts) -> String {
+    message
+        .parts
+        .iter()
+        .filter_map(|part| match part {
+            Part::Text(text) => Some(text.text.as_str()),
+            Part::Compaction(_) => Some("(compaction requested)"),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn tool_input(tool: &ToolPart) -> &Value {
+    match &tool.state {
+        ToolState::Pending { input, .. }
+        | ToolState::Running { input, .. }
+        | ToolState::Completed { input, .. }
+        | ToolState::Error { input, .. } => input,
+    }
+}
+
+/// The output of a finished tool call and whether it failed
+fn tool_output(tool: &ToolPart) -> Option<(&str, bool)> {
+    match &tool.state {
+        ToolState::Completed { output, .. } => Some((output, false)),
+        ToolState::Error { error, .. } => Some((error, true)),
+        _ => None,
+    }
+}
+
+fn iso(millis: u64) -> String {
+    DateTime::<Utc>::from_timestamp_millis(millis as i64)
+        .unwrap_or_default()
+        .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+        .to_string()
+}
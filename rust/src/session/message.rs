@@ -0,0 +1,503 @@
+//! Session messages and parts
+//!
+//! Rust counterpart of `js/src/session/message-v2.ts`. Messages carry the
+//! per-turn metadata (role, model, tokens) and parts carry the content
+//! (text, reasoning, tool calls, step boundaries). The JSON shape matches
+//! the JavaScript implementation so both binaries read the same records.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::compaction::ContextDiagnostics;
+use crate::provider::models::Cost;
+use crate::provider::{ChatMessage, ContentPart};
+
+/// Provider/model pair stored on user messages
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelRef {
+    #[serde(rename = "providerID")]
+    pub provider_id: String,
+    #[serde(rename = "modelID")]
+    pub model_id: String,
+}
+
+/// Message timestamps in milliseconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageTime {
+    pub created: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<u64>,
+}
+
+/// Cache token counters
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CacheTokens {
+    pub read: u64,
+    pub write: u64,
+}
+
+/// Token counters for an assistant message or step
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Tokens {
+    pub input: u64,
+    pub output: u64,
+    pub reasoning: u64,
+    pub cache: CacheTokens,
+}
+
+impl Tokens {
+    /// Add `other` to these counters
+    pub fn add(&mut self, other: &Tokens) {
+        self.input += other.input;
+        self.output += other.output;
+        self.reasoning += other.reasoning;
+        self.cache.read += other.cache.read;
+        self.cache.write += other.cache.write;
+    }
+
+    /// Price of these tokens in USD under `cost`, as the JavaScript
+    /// `Session.getUsage` computes it: the over-200K tier applies when the
+    /// prompt (input plus cache reads) exceeds 200K tokens, and reasoning
+    /// tokens are charged as output
+    pub fn cost(&self, cost: &Cost) -> f64 {
+        let tier = match &cost.context_over_200k {
+            Some(tier) if self.input + self.cache.read > 200_000 => tier,
+            _ => &cost.base,
+        };
+        let price = |tokens: u64, per_million: f64| tokens as f64 * per_million / 1_000_000.0;
+        let total = price(self.input, tier.input)
+            + price(self.output + self.reasoning, tier.output)
+            + price(self.cache.read, tier.cache_read.unwrap_or(0.0))
+            + price(self.cache.write, tier.cache_write.unwrap_or(0.0));
+        if total.is_finite() {
+            total
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Working directory information for an assistant message
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessagePath {
+    pub cwd: String,
+    pub root: String,
+}
+
+/// A user message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserMessage {
+    pub id: String,
+    #[serde(rename = "sessionID")]
+    pub session_id: String,
+    pub time: MessageTime,
+    pub agent: String,
+    pub model: ModelRef,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(
+        default,
+        rename = "appendSystem",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub append_system: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Fields this implementation does not model (written by the JavaScript
+    /// agent), kept so that saving the message again does not drop them
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// An assistant message (one per model step)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssistantMessage {
+    pub id: String,
+    #[serde(rename = "sessionID")]
+    pub session_id: String,
+    pub time: MessageTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<Value>,
+    #[serde(rename = "parentID")]
+    pub parent_id: String,
+    #[serde(rename = "modelID")]
+    pub model_id: String,
+    #[serde(rename = "providerID")]
+    pub provider_id: String,
+    pub mode: String,
+    pub path: MessagePath,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<bool>,
+    pub cost: f64,
+    pub tokens: Tokens,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish: Option<String>,
+    /// Fields this implementation does not model (written by the JavaScript
+    /// agent), kept so that saving the message again does not drop them
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Message info, discriminated by role
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "role", rename_all = "lowercase")]
+pub enum MessageInfo {
+    User(UserMessage),
+    Assistant(AssistantMessage),
+}
+
+impl MessageInfo {
+    pub fn id(&self) -> &str {
+        match self {
+            MessageInfo::User(m) => &m.id,
+            MessageInfo::Assistant(m) => &m.id,
+        }
+    }
+
+    pub fn session_id(&self) -> &str {
+        match self {
+            MessageInfo::User(m) => &m.session_id,
+            MessageInfo::Assistant(m) => &m.session_id,
+        }
+    }
+
+    /// Give the message a new ID in another session
+    pub fn reassign(&mut self, id: String, session_id: &str) {
+        let (own_id, own_session_id) = match self {
+            MessageInfo::User(m) => (&mut m.id, &mut m.session_id),
+            MessageInfo::Assistant(m) => (&mut m.id, &mut m.session_id),
+        };
+        *own_id = id;
+        *own_session_id = session_id.to_string();
+    }
+}
+
+/// Start/end timestamps for streamed parts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PartTime {
+    pub start: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<u64>,
+}
+
+/// Plain text content
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextPart {
+    pub id: String,
+    #[serde(rename = "sessionID")]
+    pub session_id: String,
+    #[serde(rename = "messageID")]
+    pub message_id: String,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synthetic: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<PartTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+}
+
+/// Model reasoning content
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReasoningPart {
+    pub id: String,
+    #[serde(rename = "sessionID")]
+    pub session_id: String,
+    #[serde(rename = "messageID")]
+    pub message_id: String,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+    pub time: PartTime,
+}
+
+/// Timestamps of a finished tool call
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolTime {
+    pub start: u64,
+    pub end: u64,
+    /// When the output was pruned from the conversation sent to the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compacted: Option<u64>,
+}
+
+/// Lifecycle state of a tool call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum ToolState {
+    Pending {
+        input: Value,
+        raw: String,
+    },
+    Running {
+        input: Value,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metadata: Option<Value>,
+        time: PartTime,
+    },
+    Completed {
+        input: Value,
+        output: String,
+        title: String,
+        metadata: Value,
+        time: ToolTime,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attachments: Option<Vec<crate::tool::FileAttachment>>,
+    },
+    Error {
+        input: Value,
+        error: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metadata: Option<Value>,
+        time: ToolTime,
+    },
+}
+
+/// A tool call and its result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolPart {
+    pub id: String,
+    #[serde(rename = "sessionID")]
+    pub session_id: String,
+    #[serde(rename = "messageID")]
+    pub message_id: String,
+    #[serde(rename = "callID")]
+    pub call_id: String,
+    pub tool: String,
+    pub state: ToolState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+}
+
+/// Marks the beginning of a model step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepStartPart {
+    pub id: String,
+    #[serde(rename = "sessionID")]
+    pub session_id: String,
+    #[serde(rename = "messageID")]
+    pub message_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+}
+
+/// Marks the end of a model step
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepFinishPart {
+    pub id: String,
+    #[serde(rename = "sessionID")]
+    pub session_id: String,
+    #[serde(rename = "messageID")]
+    pub message_id: String,
+    pub reason: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+    pub cost: f64,
+    pub tokens: Tokens,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<ModelInfo>,
+    /// Context window usage after the step, when the model's limits are known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<ContextDiagnostics>,
+}
+
+/// The model behind a step: the one requested and, when the provider
+/// reports it, the one that actually responded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    #[serde(rename = "providerID")]
+    pub provider_id: String,
+    #[serde(rename = "requestedModelID")]
+    pub requested_model_id: String,
+    #[serde(
+        rename = "respondedModelID",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub responded_model_id: Option<String>,
+}
+
+/// Marks a user message that asks for the conversation so far to be
+/// summarized; everything before it is replaced by the summary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompactionPart {
+    pub id: String,
+    #[serde(rename = "sessionID")]
+    pub session_id: String,
+    #[serde(rename = "messageID")]
+    pub message_id: String,
+}
+
+/// Files a step changed, recorded with the snapshot taken before it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatchPart {
+    pub id: String,
+    #[serde(rename = "sessionID")]
+    pub session_id: String,
+    #[serde(rename = "messageID")]
+    pub message_id: String,
+    pub hash: String,
+    pub files: Vec<String>,
+}
+
+/// A message part, discriminated by type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Part {
+    #[serde(rename = "text")]
+    Text(TextPart),
+    #[serde(rename = "reasoning")]
+    Reasoning(ReasoningPart),
+    #[serde(rename = "tool")]
+    Tool(ToolPart),
+    #[serde(rename = "step-start")]
+    StepStart(StepStartPart),
+    #[serde(rename = "step-finish")]
+    StepFinish(StepFinishPart),
+    #[serde(rename = "compaction")]
+    Compaction(CompactionPart),
+    #[serde(rename = "patch")]
+    Patch(PatchPart),
+}
+
+impl Part {
+    pub fn id(&self) -> &str {
+        match self {
+            Part::Text(p) => &p.id,
+            Part::Reasoning(p) => &p.id,
+            Part::Tool(p) => &p.id,
+            Part::StepStart(p) => &p.id,
+            Part::StepFinish(p) => &p.id,
+            Part::Compaction(p) => &p.id,
+            Part::Patch(p) => &p.id,
+        }
+    }
+
+    pub fn message_id(&self) -> &str {
+        match self {
+            Part::Text(p) => &p.message_id,
+            Part::Reasoning(p) => &p.message_id,
+            Part::Tool(p) => &p.message_id,
+            Part::StepStart(p) => &p.message_id,
+            Part::StepFinish(p) => &p.message_id,
+            Part::Compaction(p) => &p.message_id,
+            Part::Patch(p) => &p.message_id,
+        }
+    }
+
+    /// Give the part a new ID in another message
+    pub fn reassign(&mut self, id: String, session_id: &str, message_id: &str) {
+        let (own_id, own_session_id, own_message_id) = match self {
+            Part::Text(p) => (&mut p.id, &mut p.session_id, &mut p.message_id),
+            Part::Reasoning(p) => (&mut p.id, &mut p.session_id, &mut p.message_id),
+            Part::Tool(p) => (&mut p.id, &mut p.session_id, &mut p.message_id),
+            Part::StepStart(p) => (&mut p.id, &mut p.session_id, &mut p.message_id),
+            Part::StepFinish(p) => (&mut p.id, &mut p.session_id, &mut p.message_id),
+            Part::Compaction(p) => (&mut p.id, &mut p.session_id, &mut p.message_id),
+            Part::Patch(p) => (&mut p.id, &mut p.session_id, &mut p.message_id),
+        };
+        *own_id = id;
+        *own_session_id = session_id.to_string();
+        *own_message_id = message_id.to_string();
+    }
+}
+
+/// Replaces the output of pruned tool calls in the conversation
+pub const PRUNED_OUTPUT: &str = "[output pruned]";
+
+/// How a compaction request is phrased to the model
+const COMPACTION_QUESTION: &str = "What did we do so far?";
+
+/// A message together with its parts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageWithParts {
+    pub info: MessageInfo,
+    pub parts: Vec<Part>,
+}
+
+/// Convert stored messages into the provider-neutral conversation format.
+/// Mirrors the JavaScript `MessageV2.toModelMessage`.
+pub fn to_chat_messages(messages: &[MessageWithParts]) -> Vec<ChatMessage> {
+    let mut result = Vec::new();
+
+    for message in messages {
+        match &message.info {
+            MessageInfo::User(_) => {
+                let text: Vec<&str> = message
+                    .parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        Part::Text(t) => Some(t.text.as_str()),
+                        Part::Compaction(_) => Some(COMPACTION_QUESTION),
+                        _ => None,
+                    })
+                    .collect();
+                if !text.is_empty() {
+                    result.push(ChatMessage::User {
+                        text: text.join("\n"),
+                    });
+                }
+            }
+            MessageInfo::Assistant(_) => {
+                let mut content = Vec::new();
+                let mut tool_results = Vec::new();
+
+                for part in &message.parts {
+                    match part {
+                        Part::Text(t) if !t.text.is_empty() => {
+                            content.push(ContentPart::Text {
+                                text: t.text.clone(),
+                            });
+                        }
+                        Part::Reasoning(r) => content.push(ContentPart::Reasoning {
+                            text: r.text.clone(),
+                            metadata: r.metadata.clone(),
+                        }),
+                        Part::Tool(tool) => {
+                            let (input, output, is_error) = match &tool.state {
+                                ToolState::Completed {
+                                    input,
+                                    output,
+                                    time,
+                                    ..
+                                } => {
+                                    let output = match time.compacted {
+                                        Some(_) => PRUNED_OUTPUT.to_string(),
+                                        None => output.clone(),
+                                    };
+                                    (input, output, false)
+                                }
+                                ToolState::Error { input, error, .. } => {
+                                    (input, format!("Error: {}", error), true)
+                                }
+                                // Interrupted calls are not replayed to the model
+                                _ => continue,
+                            };
+                            content.push(ContentPart::ToolCall {
+                                call_id: tool.call_id.clone(),
+                                tool: tool.tool.clone(),
+                                input: input.clone(),
+                            });
+                            tool_results.push(ChatMessage::Tool {
+                                call_id: tool.call_id.clone(),
+                                tool: tool.tool.clone(),
+                                output,
+                                is_error,
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+
+                if !content.is_empty() {
+                    result.push(ChatMessage::Assistant { content });
+                }
+                result.extend(tool_results);
+            }
+        }
+    }
+
+    result
+}
@@ -0,0 +1,197 @@
+//! Session management for the Agent CLI
+//!
+//! A session is an ordered list of user and assistant messages, each made of
+//! parts. This module mirrors the JavaScript implementation's session/
+//! directory: message types, system prompt assembly and the agent loop.
+
+pub mod compaction;
+pub mod export;
+pub mod message;
+pub mod prompt;
+pub mod revert;
+pub mod stats;
+pub mod summary;
+pub mod system;
+pub mod title;
+pub mod usage;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::defaults::ModelParts;
+use crate::id::{ascending, Prefix};
+pub use message::{MessageInfo, MessageWithParts, Part};
+
+const PARENT_TITLE_PREFIX: &str = "New session - ";
+
+/// Session timestamps in milliseconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionTime {
+    pub created: u64,
+    pub updated: u64,
+}
+
+/// Session metadata, matching the JavaScript `Session.Info` shape
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub id: String,
+    #[serde(rename = "projectID")]
+    pub project_id: String,
+    pub directory: String,
+    #[serde(default, rename = "parentID", skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    /// Changes made so far and a recap, updated after each turn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<summary::SessionSummary>,
+    /// Set while the end of the conversation is reverted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert: Option<revert::Revert>,
+    pub title: String,
+    pub version: String,
+    pub time: SessionTime,
+    /// Fields this implementation does not model (written by the JavaScript
+    /// agent), kept so that saving the session again does not drop them
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl SessionInfo {
+    /// Create metadata for a brand new session in the given directory
+    pub fn new(directory: &Path) -> Self {
+        let now = now();
+        Self {
+            id: ascending(Prefix::Session, None),
+            project_id: "global".to_string(),
+            directory: directory.to_string_lossy().to_string(),
+            parent_id: None,
+            summary: None,
+            revert: None,
+            title: default_title(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            time: SessionTime {
+                created: now,
+                updated: now,
+            },
+            extra: Default::default(),
+        }
+    }
+}
+
+/// A session with its full message history
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub info: SessionInfo,
+    pub messages: Vec<MessageWithParts>,
+    /// What storage last held of the messages, for saving changes only
+    pub(crate) stored: crate::storage::Stored,
+}
+
+impl Session {
+    /// Create an empty session in the given directory
+    pub fn new(directory: &Path) -> Self {
+        Self {
+            info: SessionInfo::new(directory),
+            messages: Vec::new(),
+            stored: Default::default(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.info.id
+    }
+
+    /// Copy the history into a new session in `directory`, with fresh IDs
+    /// for the session, its messages and parts. Mirrors `Session.fork`;
+    /// reverted messages are left behind.
+    pub fn fork(&self, directory: &Path) -> Session {
+        let mut info = SessionInfo::new(directory);
+        info.project_id = self.info.project_id.clone();
+        let mut source = self.clone();
+        revert::cleanup(&mut source);
+        let mut ids = HashMap::new();
+        let messages = source
+            .messages
+            .iter()
+            .map(|message| {
+                let mut message = message.clone();
+                let id = ascending(Prefix::Message, None);
+                ids.insert(message.info.id().to_string(), id.clone());
+                message.info.reassign(id, &info.id);
+                if let MessageInfo::Assistant(assistant) = &mut message.info {
+                    if let Some(parent) = ids.get(&assistant.parent_id) {
+                        assistant.parent_id = parent.clone();
+                    }
+                }
+                for part in &mut message.parts {
+                    part.reassign(ascending(Prefix::Part, None), &info.id, message.info.id());
+                }
+                message
+            })
+            .collect();
+        Session {
+            info,
+            messages,
+            stored: Default::default(),
+        }
+    }
+}
+
+/// Events published while a session runs
+#[derive(Debug, Clone)]
+pub enum SessionEvent {
+    /// A part was created or reached its final state
+    Part(Part),
+    /// The selected model is not supported; continuing with the fallback
+    ModelFallback {
+        from: ModelParts,
+        to: ModelParts,
+        message: String,
+    },
+    /// A step timed out and will be retried after `delay`
+    Retry {
+        provider: String,
+        /// The attempt that timed out, starting at 1
+        attempt: u32,
+        delay: Duration,
+        error: String,
+    },
+    /// The conversation was summarized to fit the context window
+    Compaction {
+        /// The model that wrote the summary
+        model: ModelParts,
+        /// Tokens in use before compacting
+        tokens: u64,
+    },
+    /// The session was named after its first user message
+    Title { title: String },
+    /// A turn completed and the session summary was updated
+    Summary {
+        summary: summary::SessionSummary,
+        /// Files changed over the whole session
+        diffs: Vec<summary::FileDiff>,
+    },
+}
+
+/// Default title assigned to new sessions
+pub fn default_title() -> String {
+    format!(
+        "{}{}",
+        PARENT_TITLE_PREFIX,
+        chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ")
+    )
+}
+
+/// Whether a title is still the default one
+pub fn is_default_title(title: &str) -> bool {
+    title.starts_with(PARENT_TITLE_PREFIX)
+}
+
+/// Current timestamp in milliseconds
+pub(crate) fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
@@ -0,0 +1,1045 @@
+//! Agent loop
+//!
+//! Rust counterpart of `js/src/session/prompt.ts`. A prompt appends the user
+//! message to the session, then repeatedly sends the conversation and the
+//! tool schemas to the model, executes the tool calls it returns and feeds
+//! the results back until the model finishes without calling tools.
+
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::compaction::{self, Compaction, Target, CONTINUE_TEXT, SUMMARY_REQUEST};
+use super::message::{to_chat_messages, CompactionPart, PartTime, PatchPart};
+use super::message::{
+    AssistantMessage, MessageInfo, MessagePath, MessageTime, MessageWithParts, ModelInfo, ModelRef,
+    Part, ReasoningPart, StepFinishPart, StepStartPart, TextPart, Tokens, ToolPart, ToolState,
+    ToolTime, UserMessage,
+};
+use super::summary::{self, SessionSummary};
+use super::usage::StepUsage;
+use super::{now, revert, system, title, Session, SessionEvent};
+use crate::defaults::ModelParts;
+use crate::error::{AgentError, Result};
+use crate::id::{ascending, Prefix};
+use crate::permission::{evaluate_bash, Action, Policy};
+use crate::provider::models::{Catalog, Model};
+use crate::provider::timeout::{Timeouts, TIMEOUT_RETRY_DELAYS};
+use crate::provider::{ChatMessage, ChatRequest, ChatResponse, ContentPart, Provider, ToolSpec};
+use crate::snapshot::Snapshot;
+use crate::tool::{ToolContext, ToolRegistry};
+use crate::util::token;
+
+/// Upper bound on output tokens requested per step, as in the JavaScript
+/// implementation's `SessionPrompt.OUTPUT_TOKEN_MAX`
+pub const OUTPUT_TOKEN_MAX: u64 = 32_000;
+
+/// Thinking budget of Anthropic models that reason, unless their catalog
+/// `thinking` option sets one
+pub const THINKING_BUDGET_TOKENS: u64 = 16_000;
+
+/// Catalog `npm` package of providers speaking the Anthropic Messages API
+const ANTHROPIC_NPM: &str = "@ai-sdk/anthropic";
+
+/// Tools that modify files and are governed by the `edit` permission
+const EDIT_TOOLS: &[&str] = &["edit", "write", "multiedit", "patch"];
+
+/// Tools that can change the working tree; steps calling them are
+/// snapshotted
+const MUTATING_TOOLS: &[&str] = &["edit", "write", "multiedit", "patch", "bash", "batch"];
+
+/// Input for a single user turn
+#[derive(Debug, Clone)]
+pub struct PromptInput {
+    pub text: String,
+    pub model: ModelParts,
+    pub system: Option<String>,
+    pub append_system: Option<String>,
+    pub temperature: Option<f64>,
+}
+
+/// Runs user turns against a provider and the tool registry
+pub struct SessionPrompt<'a> {
+    provider: &'a dyn Provider,
+    registry: &'a ToolRegistry,
+    working_directory: PathBuf,
+    policy: Option<Policy>,
+    model_info: Option<Model>,
+    catalog: Option<&'a Catalog>,
+    fallback: Option<(&'a dyn Provider, ModelParts)>,
+    timeouts: Timeouts,
+    timeout_retry_delays: Vec<Duration>,
+    compaction: Compaction<'a>,
+    /// Model that names new sessions; `None` leaves titles alone
+    title: Option<(&'a dyn Provider, ModelParts)>,
+    /// Model that recaps the session after each turn; `None` skips summaries
+    summary: Option<(&'a dyn Provider, ModelParts)>,
+    /// Records the working tree around steps that run mutating tools
+    snapshot: Option<Snapshot>,
+}
+
+impl<'a> SessionPrompt<'a> {
+    /// Create a new agent loop
+    pub fn new(
+        provider: &'a dyn Provider,
+        registry: &'a ToolRegistry,
+        working_directory: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            provider,
+            registry,
+            working_directory: working_directory.into(),
+            policy: None,
+            model_info: None,
+            catalog: None,
+            fallback: None,
+            timeouts: Timeouts::default(),
+            timeout_retry_delays: TIMEOUT_RETRY_DELAYS.to_vec(),
+            compaction: Compaction::default(),
+            title: None,
+            summary: None,
+            snapshot: None,
+        }
+    }
+
+    /// Enforce a permission policy at the tool boundary
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Use catalog metadata (limits, capabilities) for the selected model
+    pub fn with_model_info(mut self, model: Model) -> Self {
+        self.model_info = Some(model);
+        self
+    }
+
+    /// Price steps from `catalog`, including steps served by the fallback
+    /// model; otherwise only the model given to [`Self::with_model_info`]
+    /// is priced
+    pub fn with_catalog(mut self, catalog: &'a Catalog) -> Self {
+        self.catalog = Some(catalog);
+        self
+    }
+
+    /// Switch to `model` served by `provider` when the selected model
+    /// reports that it is not supported
+    pub fn with_fallback(mut self, provider: &'a dyn Provider, model: ModelParts) -> Self {
+        self.fallback = Some((provider, model));
+        self
+    }
+
+    /// Enforce the step layer of `timeouts`; the other layers are applied
+    /// by the providers themselves
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Waits before retrying a timed-out step, one retry per entry
+    pub fn with_timeout_retry_delays(mut self, delays: Vec<Duration>) -> Self {
+        self.timeout_retry_delays = delays;
+        self
+    }
+
+    /// Summarize the conversation with `compaction` when it outgrows the
+    /// model's context window (known from [`Self::with_model_info`])
+    pub fn with_compaction(mut self, compaction: Compaction<'a>) -> Self {
+        self.compaction = compaction;
+        self
+    }
+
+    /// Name new sessions after their first user message with `model`
+    pub fn with_title(mut self, provider: &'a dyn Provider, model: ModelParts) -> Self {
+        self.title = Some((provider, model));
+        self
+    }
+
+    /// Summarize the session after each completed turn, recapping it with
+    /// `model`
+    pub fn with_summary(mut self, provider: &'a dyn Provider, model: ModelParts) -> Self {
+        self.summary = Some((provider, model));
+        self
+    }
+
+    /// Snapshot the working tree before and after steps that run mutating
+    /// tools
+    pub fn with_snapshot(mut self, snapshot: Snapshot) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Catalog entry of `model`, used to price its steps and report their
+    /// context usage
+    fn catalog_entry(&self, model: &ModelParts) -> Option<&Model> {
+        self.catalog
+            .and_then(|catalog| catalog.lookup(model))
+            .or_else(|| {
+                self.model_info
+                    .as_ref()
+                    .filter(|info| info.id == model.model_id)
+            })
+    }
+
+    /// Output token limit for each step: the model's limit capped at
+    /// `OUTPUT_TOKEN_MAX`, or `OUTPUT_TOKEN_MAX` when the model is unknown
+    pub fn max_output_tokens(&self) -> u64 {
+        match self.model_info.as_ref().map(|m| m.limit.output) {
+            Some(limit) if limit > 0 => limit.min(OUTPUT_TOKEN_MAX),
+            _ => OUTPUT_TOKEN_MAX,
+        }
+    }
+
+    /// Output limits for a step of `model`: tokens for the answer and, for
+    /// Anthropic models that reason, the thinking budget on top of it.
+    ///
+    /// As in the JavaScript implementation, answer and thinking together
+    /// stay within the model's output limit, keeping the usual answer
+    /// limit when they fit.
+    fn output_limits(&self, model: &ModelParts) -> (u64, Option<u64>) {
+        let standard = self.max_output_tokens();
+        let Some(catalog) = self.catalog else {
+            return (standard, None);
+        };
+        let anthropic = catalog
+            .provider(&model.provider_id)
+            .is_some_and(|p| p.npm.as_deref() == Some(ANTHROPIC_NPM));
+        let Some(info) = catalog.lookup(model).filter(|_| anthropic) else {
+            return (standard, None);
+        };
+        let budget = match info.options.get("thinking") {
+            Some(thinking) if thinking["type"] == "enabled" => thinking["budgetTokens"].as_u64(),
+            Some(_) => None,
+            None => info.reasoning.then_some(THINKING_BUDGET_TOKENS),
+        };
+        let cap = match info.limit.output {
+            0 => OUTPUT_TOKEN_MAX,
+            limit => limit,
+        };
+        match budget.filter(|budget| *budget > 0 && *budget < cap) {
+            Some(budget) => (OUTPUT_TOKEN_MAX.min(cap - budget), Some(budget)),
+            None => (standard, None),
+        }
+    }
+
+    /// Run one user turn to completion
+    pub async fn prompt(
+        &self,
+        session: &mut Session,
+        input: PromptInput,
+        emit: &mut (dyn FnMut(SessionEvent) + Send),
+    ) -> Result<()> {
+        // Prompting after a revert makes it final
+        revert::cleanup(session);
+        self.compact_if_needed(session, self.provider, &input.model, false, emit)
+            .await?;
+        let user = self.create_user_message(session, &input);
+        let user_id = user.info.id().to_string();
+        session.messages.push(user);
+
+        // The title request runs alongside the turn and cannot fail it. Once
+        // the turn is over, a title still pending is given up, as the
+        // JavaScript implementation never waits for it either.
+        let title = self.title.as_ref().filter(|_| title::is_needed(session));
+        let text = input.text.clone();
+        let (result, title) = {
+            let run = self.run(session, input, &user_id, emit);
+            let title = async {
+                let (provider, model) = title?;
+                self.generate_title(*provider, model, &text).await
+            };
+            tokio::pin!(run, title);
+            tokio::select! {
+                biased;
+                title = &mut title => (run.await, title),
+                result = &mut run => (result, None),
+            }
+        };
+        if let Some(title) = title {
+            session.info.title = title.clone();
+            emit(SessionEvent::Title { title });
+        }
+        if let (Ok(()), Some((provider, model))) = (&result, &self.summary) {
+            self.summarize(session, *provider, model, emit).await;
+        }
+        result
+    }
+
+    /// Update the session summary: the files changed so far and a recap.
+    /// A failed recap request is only logged.
+    async fn summarize(
+        &self,
+        session: &mut Session,
+        provider: &dyn Provider,
+        model: &ModelParts,
+        emit: &mut (dyn FnMut(SessionEvent) + Send),
+    ) {
+        // Snapshots also see changes made through bash
+        let diffs = match (&self.snapshot, summary::snapshot_range(&session.messages)) {
+            (Some(snapshot), Some((from, to))) => snapshot.diff_full(&from, &to),
+            _ => summary::diffs(&session.messages, &self.working_directory),
+        };
+        let reasoning = self.catalog_entry(model).is_some_and(|info| info.reasoning);
+        // The turn is over: a slow recap must not keep the caller waiting
+        let timeouts = self
+            .timeouts
+            .with_step(self.timeouts.step.min(summary::RECAP_TIMEOUT));
+        let recap = summary::recap(provider, model, reasoning, &session.messages);
+        let body = match timeouts.step(provider.id(), recap).await {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::warn!(
+                    provider = model.provider_id.as_str(),
+                    model = model.model_id.as_str(),
+                    error = %e,
+                    "failed to summarize session"
+                );
+                None
+            }
+        };
+        let summary = SessionSummary::new(&diffs, body);
+        session.info.summary = Some(summary.clone());
+        emit(SessionEvent::Summary { summary, diffs });
+    }
+
+    /// Name the session opened with `text`; failures are only logged
+    async fn generate_title(
+        &self,
+        provider: &dyn Provider,
+        model: &ModelParts,
+        text: &str,
+    ) -> Option<String> {
+        let reasoning = self.catalog_entry(model).is_some_and(|info| info.reasoning);
+        let request = title::generate(provider, model, reasoning, text);
+        match self.timeouts.step(provider.id(), request).await {
+            Ok(title) => title,
+            Err(e) => {
+                tracing::warn!(
+                    provider = model.provider_id.as_str(),
+                    model = model.model_id.as_str(),
+                    error = %e,
+                    "failed to generate title"
+                );
+                None
+            }
+        }
+    }
+
+    /// Steps of a turn whose user message is already in the session
+    async fn run(
+        &self,
+        session: &mut Session,
+        input: PromptInput,
+        user_id: &str,
+        emit: &mut (dyn FnMut(SessionEvent) + Send),
+    ) -> Result<()> {
+        let system = system::resolve(
+            input.system.as_deref(),
+            input.append_system.as_deref(),
+            &self.working_directory,
+        )
+        .await;
+        let tools = self.tool_specs();
+        let mut provider = self.provider;
+        let mut model = input.model.clone();
+        let mut fallback = self.fallback.as_ref();
+
+        let mut first = true;
+        loop {
+            if !first {
+                self.compact_if_needed(session, provider, &model, true, emit)
+                    .await?;
+            }
+            first = false;
+            let (max_output_tokens, thinking_budget) = self.output_limits(&model);
+            let request = ChatRequest {
+                model: model.model_id.clone(),
+                system: system.clone(),
+                messages: to_chat_messages(compaction::active_messages(&session.messages)),
+                tools: tools.clone(),
+                temperature: input.temperature,
+                max_output_tokens: Some(max_output_tokens),
+                thinking_budget,
+            };
+
+            let result = self
+                .step(provider, session.id(), user_id, &model, &request, emit)
+                .await;
+            let (message, has_tool_calls) = match (result, fallback) {
+                (Err(AgentError::ModelNotSupported { message, .. }), Some((next, next_model))) => {
+                    emit(SessionEvent::ModelFallback {
+                        from: model,
+                        to: next_model.clone(),
+                        message,
+                    });
+                    provider = *next;
+                    model = next_model.clone();
+                    fallback = None;
+                    continue;
+                }
+                (result, _) => result?,
+            };
+            let finish = match &message.info {
+                MessageInfo::Assistant(a) => a.finish.clone().unwrap_or_default(),
+                MessageInfo::User(_) => String::new(),
+            };
+            session.messages.push(message);
+            session.info.time.updated = now();
+
+            if !has_tool_calls {
+                // A zero-token response with an unknown finish reason means the
+                // provider failed silently (JS issue #196); surface it instead of
+                // ending the turn as if the model had answered.
+                if finish == "unknown" && zero_tokens(session.messages.last()) {
+                    return Err(AgentError::Session {
+                        session_id: Some(session.id().to_string()),
+                        message: format!(
+                            "Provider returned zero tokens with unknown finish reason. Requested model: {} (provider: {}). Check provider status, model availability, and API keys.",
+                            model.model_id, model.provider_id
+                        ),
+                    });
+                }
+                break;
+            }
+        }
+
+        if self.compaction.prune {
+            let start = compaction::active_start(&session.messages);
+            compaction::prune(&mut session.messages[start..]);
+        }
+        Ok(())
+    }
+
+    /// Compact the session when its last step overflowed the context window:
+    /// prune old tool output first, and summarize when that is not enough.
+    ///
+    /// With `resume` a synthetic user message follows the summary so the
+    /// interrupted turn carries on.
+    async fn compact_if_needed(
+        &self,
+        session: &mut Session,
+        provider: &dyn Provider,
+        model: &ModelParts,
+        resume: bool,
+        emit: &mut (dyn FnMut(SessionEvent) + Send),
+    ) -> Result<()> {
+        let Some(info) = &self.model_info else {
+            return Ok(());
+        };
+        if !self.compaction.is_enabled() && !self.compaction.prune {
+            return Ok(());
+        }
+        let start = compaction::active_start(&session.messages);
+        let Some(mut tokens) = compaction::current_tokens(&session.messages[start..]) else {
+            return Ok(());
+        };
+        let ratio = self.compaction.safety_margin_ratio(info.limit.context);
+        if !compaction::is_overflow(info, tokens, ratio) {
+            return Ok(());
+        }
+        if self.compaction.prune {
+            // Dropping old tool output is free; summarize only if that is
+            // not enough
+            let pruned = compaction::prune(&mut session.messages[start..]);
+            tokens = tokens.saturating_sub(pruned.tokens);
+            if pruned.tokens > 0 && !compaction::is_overflow(info, tokens, ratio) {
+                return Ok(());
+            }
+        }
+        if !self.compaction.is_enabled() {
+            return Ok(());
+        }
+        self.compact(session, provider, model, tokens, resume, emit)
+            .await
+    }
+
+    /// Summarize the active conversation with the first cascade model that
+    /// can hold it and succeeds, recording the request and the summary as a
+    /// user/assistant pair.
+    async fn compact(
+        &self,
+        session: &mut Session,
+        provider: &dyn Provider,
+        model: &ModelParts,
+        tokens: u64,
+        resume: bool,
+        emit: &mut (dyn FnMut(SessionEvent) + Send),
+    ) -> Result<()> {
+        let request_message = self.create_compaction_message(session, model);
+        let parent_id = request_message.info.id().to_string();
+        session.messages.push(request_message);
+
+        let mut messages = to_chat_messages(compaction::active_messages(&session.messages));
+        messages.push(ChatMessage::User {
+            text: SUMMARY_REQUEST.to_string(),
+        });
+
+        let mut last_error = None;
+        for target in &self.compaction.cascade {
+            let (target_provider, target_model, max_output_tokens) = match target {
+                Target::Same => (provider, model.clone(), Some(self.max_output_tokens())),
+                Target::Model {
+                    model,
+                    provider,
+                    context_limit,
+                } => {
+                    if context_limit.is_some_and(|limit| limit > 0 && tokens > limit) {
+                        tracing::info!(
+                            provider = model.provider_id.as_str(),
+                            model = model.model_id.as_str(),
+                            tokens,
+                            "skipping compaction model, context too small"
+                        );
+                        continue;
+                    }
+                    (*provider, model.clone(), None)
+                }
+            };
+            let request = ChatRequest {
+                model: target_model.model_id.clone(),
+                system: system::summarize(),
+                messages: messages.clone(),
+                tools: Vec::new(),
+                temperature: None,
+                max_output_tokens,
+                thinking_budget: None,
+            };
+            match self.complete(target_provider, &request, emit).await {
+                Ok(response) => {
+                    let summary = self.create_summary_message(
+                        session.id(),
+                        &parent_id,
+                        &target_model,
+                        response,
+                    );
+                    session.messages.push(summary);
+                    if resume {
+                        let text = self.create_user_message(
+                            session,
+                            &PromptInput {
+                                text: CONTINUE_TEXT.to_string(),
+                                model: model.clone(),
+                                system: None,
+                                append_system: None,
+                                temperature: None,
+                            },
+                        );
+                        session.messages.push(mark_synthetic(text));
+                    }
+                    session.info.time.updated = now();
+                    emit(SessionEvent::Compaction {
+                        model: target_model,
+                        tokens,
+                    });
+                    return Ok(());
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        provider = target_model.provider_id.as_str(),
+                        model = target_model.model_id.as_str(),
+                        error = %e,
+                        "compaction model failed, trying next in cascade"
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        // Without a summary the request would only confuse later steps
+        session.messages.pop();
+        Err(last_error.unwrap_or_else(|| AgentError::Session {
+            session_id: Some(session.id().to_string()),
+            message: format!(
+                "No compaction model can hold the conversation ({} tokens)",
+                tokens
+            ),
+        }))
+    }
+
+    fn create_compaction_message(&self, session: &Session, model: &ModelParts) -> MessageWithParts {
+        let message_id = ascending(Prefix::Message, None);
+        let info = MessageInfo::User(UserMessage {
+            id: message_id.clone(),
+            session_id: session.id().to_string(),
+            time: MessageTime {
+                created: now(),
+                completed: None,
+            },
+            agent: "build".to_string(),
+            model: ModelRef {
+                provider_id: model.provider_id.clone(),
+                model_id: model.model_id.clone(),
+            },
+            system: None,
+            append_system: None,
+            temperature: None,
+            extra: Default::default(),
+        });
+        let part = Part::Compaction(CompactionPart {
+            id: ascending(Prefix::Part, None),
+            session_id: session.id().to_string(),
+            message_id,
+        });
+        MessageWithParts {
+            info,
+            parts: vec![part],
+        }
+    }
+
+    fn create_summary_message(
+        &self,
+        session_id: &str,
+        parent_id: &str,
+        model: &ModelParts,
+        response: ChatResponse,
+    ) -> MessageWithParts {
+        let message_id = ascending(Prefix::Message, None);
+        let text: String = response
+            .content
+            .iter()
+            .filter_map(|content| match content {
+                ContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        let usage = StepUsage::new(&response.usage, self.catalog_entry(model));
+        let info = MessageInfo::Assistant(AssistantMessage {
+            id: message_id.clone(),
+            session_id: session_id.to_string(),
+            time: MessageTime {
+                created: now(),
+                completed: Some(now()),
+            },
+            error: None,
+            parent_id: parent_id.to_string(),
+            model_id: model.model_id.clone(),
+            provider_id: model.provider_id.clone(),
+            mode: "build".to_string(),
+            path: MessagePath {
+                cwd: self.working_directory.to_string_lossy().to_string(),
+                root: self.working_directory.to_string_lossy().to_string(),
+            },
+            summary: Some(true),
+            cost: usage.cost,
+            tokens: usage.tokens,
+            finish: Some(response.finish_reason),
+            extra: Default::default(),
+        });
+        let part = Part::Text(TextPart {
+            id: ascending(Prefix::Part, None),
+            session_id: session_id.to_string(),
+            message_id,
+            text,
+            synthetic: None,
+            time: None,
+            metadata: None,
+        });
+        MessageWithParts {
+            info,
+            parts: vec![part],
+        }
+    }
+
+    fn create_user_message(&self, session: &Session, input: &PromptInput) -> MessageWithParts {
+        let message_id = ascending(Prefix::Message, None);
+        let info = MessageInfo::User(UserMessage {
+            id: message_id.clone(),
+            session_id: session.id().to_string(),
+            time: MessageTime {
+                created: now(),
+                completed: None,
+            },
+            agent: "build".to_string(),
+            model: ModelRef {
+                provider_id: input.model.provider_id.clone(),
+                model_id: input.model.model_id.clone(),
+            },
+            system: input.system.clone(),
+            append_system: input.append_system.clone(),
+            temperature: input.temperature,
+            extra: Default::default(),
+        });
+        let text = Part::Text(TextPart {
+            id: ascending(Prefix::Part, None),
+            session_id: session.id().to_string(),
+            message_id,
+            text: input.text.clone(),
+            synthetic: None,
+            time: None,
+            metadata: None,
+        });
+        MessageWithParts {
+            info,
+            parts: vec![text],
+        }
+    }
+
+    /// Tool schemas advertised to the model
+    fn tool_specs(&self) -> Vec<ToolSpec> {
+        self.registry
+            .all()
+            .iter()
+            .filter(|t| t.id() != "invalid")
+            .map(|t| ToolSpec {
+                name: t.id().to_string(),
+                description: t.description().to_string(),
+                parameters: t.parameters_schema(),
+            })
+            .collect()
+    }
+
+    /// Call the provider within the step timeout. Any timeout in the
+    /// hierarchy (connect, first byte, stalled stream, step) is retried
+    /// after each of the configured delays before it is surfaced.
+    async fn complete(
+        &self,
+        provider: &dyn Provider,
+        request: &ChatRequest,
+        emit: &mut (dyn FnMut(SessionEvent) + Send),
+    ) -> Result<ChatResponse> {
+        let mut delays = self.timeout_retry_delays.iter();
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let error = match self
+                .timeouts
+                .step(provider.id(), provider.complete(request))
+                .await
+            {
+                Err(e) if e.is_timeout() => e,
+                result => return result,
+            };
+            let Some(delay) = delays.next() else {
+                return Err(error);
+            };
+            tracing::info!(attempt, error = %error, "step timed out, retrying");
+            emit(SessionEvent::Retry {
+                provider: provider.id().to_string(),
+                attempt,
+                delay: *delay,
+                error: error.to_string(),
+            });
+            tokio::time::sleep(*delay).await;
+        }
+    }
+
+    /// Run one model step: call the provider, execute tool calls and record
+    /// everything as a new assistant message.
+    async fn step(
+        &self,
+        provider: &dyn Provider,
+        session_id: &str,
+        parent_id: &str,
+        model: &ModelParts,
+        request: &ChatRequest,
+        emit: &mut (dyn FnMut(SessionEvent) + Send),
+    ) -> Result<(MessageWithParts, bool)> {
+        let message_id = ascending(Prefix::Message, None);
+        let mut assistant = AssistantMessage {
+            id: message_id.clone(),
+            session_id: session_id.to_string(),
+            time: MessageTime {
+                created: now(),
+                completed: None,
+            },
+            error: None,
+            parent_id: parent_id.to_string(),
+            model_id: model.model_id.clone(),
+            provider_id: model.provider_id.clone(),
+            mode: "build".to_string(),
+            path: MessagePath {
+                cwd: self.working_directory.to_string_lossy().to_string(),
+                root: self.working_directory.to_string_lossy().to_string(),
+            },
+            summary: None,
+            cost: 0.0,
+            tokens: Tokens::default(),
+            finish: None,
+            extra: Default::default(),
+        };
+        let mut parts = Vec::new();
+
+        let start = Part::StepStart(StepStartPart {
+            id: ascending(Prefix::Part, None),
+            session_id: session_id.to_string(),
+            message_id: message_id.clone(),
+            snapshot: None,
+        });
+        emit(SessionEvent::Part(start.clone()));
+        parts.push(start);
+
+        let response = self.complete(provider, request, emit).await?;
+
+        let mutates = response.content.iter().any(|content| {
+            matches!(content, ContentPart::ToolCall { tool, .. } if MUTATING_TOOLS.contains(&tool.as_str()))
+        });
+        // Tools run only once the response is complete, so the snapshot
+        // before them lands on the stored step-start part
+        let snapshot = self.snapshot.as_ref().filter(|_| mutates);
+        let before = snapshot.and_then(Snapshot::track);
+        if let (Some(hash), Part::StepStart(start)) = (&before, &mut parts[0]) {
+            start.snapshot = Some(hash.clone());
+        }
+
+        let mut has_tool_calls = false;
+        for content in response.content {
+            let part = match content {
+                ContentPart::Text { text } => {
+                    if text.is_empty() {
+                        continue;
+                    }
+                    Part::Text(TextPart {
+                        id: ascending(Prefix::Part, None),
+                        session_id: session_id.to_string(),
+                        message_id: message_id.clone(),
+                        text,
+                        synthetic: None,
+                        time: None,
+                        metadata: None,
+                    })
+                }
+                ContentPart::Reasoning { text, metadata } => Part::Reasoning(ReasoningPart {
+                    id: ascending(Prefix::Part, None),
+                    session_id: session_id.to_string(),
+                    message_id: message_id.clone(),
+                    text,
+                    metadata,
+                    time: PartTime {
+                        start: now(),
+                        end: Some(now()),
+                    },
+                }),
+                ContentPart::ToolCall {
+                    call_id,
+                    tool,
+                    input,
+                } => {
+                    has_tool_calls = true;
+                    let state = self
+                        .execute_tool(session_id, &message_id, &call_id, &tool, input, model)
+                        .await;
+                    Part::Tool(ToolPart {
+                        id: ascending(Prefix::Part, None),
+                        session_id: session_id.to_string(),
+                        message_id: message_id.clone(),
+                        call_id,
+                        tool,
+                        state,
+                        metadata: None,
+                    })
+                }
+            };
+            emit(SessionEvent::Part(part.clone()));
+            parts.push(part);
+        }
+
+        let after = match (snapshot, &before) {
+            (Some(snapshot), Some(_)) => snapshot.track(),
+            _ => None,
+        };
+
+        let usage = StepUsage::new(&response.usage, self.catalog_entry(model));
+        assistant.tokens = usage.tokens;
+        assistant.cost = usage.cost;
+        let context = self.catalog_entry(model).and_then(|info| {
+            let tokens = &assistant.tokens;
+            let current = match tokens.input + tokens.cache.read + tokens.output {
+                // Without reported usage, estimate what the request held
+                0 => token::request_tokens(token::default_tokenizer(), request),
+                reported => reported,
+            };
+            let ratio = self.compaction.safety_margin_ratio(info.limit.context);
+            compaction::context_diagnostics(info, current, ratio)
+        });
+        let reason = if has_tool_calls && response.finish_reason == "unknown" {
+            "tool-calls".to_string()
+        } else {
+            response.finish_reason
+        };
+
+        let finish = Part::StepFinish(StepFinishPart {
+            id: ascending(Prefix::Part, None),
+            session_id: session_id.to_string(),
+            message_id: message_id.clone(),
+            reason: reason.clone(),
+            snapshot: after,
+            cost: assistant.cost,
+            tokens: assistant.tokens.clone(),
+            model: Some(ModelInfo {
+                provider_id: model.provider_id.clone(),
+                requested_model_id: model.model_id.clone(),
+                responded_model_id: response.model,
+            }),
+            context,
+        });
+        emit(SessionEvent::Part(finish.clone()));
+        parts.push(finish);
+
+        if let (Some(snapshot), Some(hash)) = (snapshot, before) {
+            let patch = snapshot.patch(&hash);
+            if !patch.files.is_empty() {
+                let part = Part::Patch(PatchPart {
+                    id: ascending(Prefix::Part, None),
+                    session_id: session_id.to_string(),
+                    message_id: message_id.clone(),
+                    hash: patch.hash,
+                    files: patch.files,
+                });
+                emit(SessionEvent::Part(part.clone()));
+                parts.push(part);
+            }
+        }
+
+        assistant.finish = Some(reason);
+        assistant.time.completed = Some(now());
+
+        Ok((
+            MessageWithParts {
+                info: MessageInfo::Assistant(assistant),
+                parts,
+            },
+            has_tool_calls,
+        ))
+    }
+
+    /// Execute one tool call and return its final state
+    async fn execute_tool(
+        &self,
+        session_id: &str,
+        message_id: &str,
+        call_id: &str,
+        tool_id: &str,
+        input: Value,
+        model: &ModelParts,
+    ) -> ToolState {
+        let start = now();
+
+        // Unknown tools are routed through the invalid tool, like the JS
+        // experimental_repairToolCall hook, so the model can correct itself.
+        let (tool, params) = match self.registry.get(tool_id) {
+            Some(tool) => (tool, input.clone()),
+            None => {
+                let available: Vec<&str> = self
+                    .registry
+                    .all()
+                    .iter()
+                    .map(|t| t.id())
+                    .filter(|id| *id != "invalid")
+                    .collect();
+                let params = json!({
+                    "tool": tool_id,
+                    "error": format!(
+                        "Model tried to call unavailable tool '{}'. Available tools: {}.",
+                        tool_id,
+                        available.join(", ")
+                    ),
+                });
+                (self.registry.get("invalid").expect("invalid tool"), params)
+            }
+        };
+
+        if let Some(policy) = &self.policy {
+            let action = permission_action(policy, tool.id(), &params);
+            if action != Action::Allow {
+                let error = match action {
+                    Action::Deny => format!(
+                        "Permission denied: the active permission policy does not allow the {} tool for this call",
+                        tool.id()
+                    ),
+                    _ => format!(
+                        "Permission required: the {} tool needs approval, which is not available in this session",
+                        tool.id()
+                    ),
+                };
+                return ToolState::Error {
+                    input,
+                    error,
+                    metadata: None,
+                    time: ToolTime {
+                        start,
+                        end: now(),
+                        compacted: None,
+                    },
+                };
+            }
+        }
+
+        let ctx = ToolContext::new(session_id, message_id, &self.working_directory)
+            .with_call_id(call_id)
+            .with_model(&model.provider_id, &model.model_id);
+
+        match tool.execute(params, &ctx).await {
+            Ok(result) => ToolState::Completed {
+                input,
+                output: result.output,
+                title: result.title,
+                metadata: result.metadata,
+                time: ToolTime {
+                    start,
+                    end: now(),
+                    compacted: None,
+                },
+                attachments: result.attachments,
+            },
+            Err(e) => ToolState::Error {
+                input,
+                error: e.to_string(),
+                metadata: None,
+                time: ToolTime {
+                    start,
+                    end: now(),
+                    compacted: None,
+                },
+            },
+        }
+    }
+}
+
+/// Permission action for a tool call under a policy.
+/// Batch calls take the most restrictive action of their inner calls.
+pub fn permission_action(policy: &Policy, tool: &str, params: &Value) -> Action {
+    match tool {
+        "bash" => {
+            if !policy.bash_enforced() {
+                return Action::Allow;
+            }
+            let command = params.get("command").and_then(Value::as_str).unwrap_or("");
+            evaluate_bash(command, &policy.bash).action
+        }
+        "webfetch" => policy.action_for("webfetch"),
+        "batch" => params
+            .get("tool_calls")
+            .and_then(Value::as_array)
+            .map(|calls| {
+                calls
+                    .iter()
+                    .map(|call| {
+                        let tool = call.get("tool").and_then(Value::as_str).unwrap_or("");
+                        let params = call.get("parameters").cloned().unwrap_or(Value::Null);
+                        permission_action(policy, tool, &params)
+                    })
+                    .fold(Action::Allow, Action::more_restrictive)
+            })
+            .unwrap_or(Action::Allow),
+        tool if EDIT_TOOLS.contains(&tool) => policy.action_for("edit"),
+        _ => Action::Allow,
+    }
+}
+
+fn mark_synthetic(mut message: MessageWithParts) -> MessageWithParts {
+    for part in &mut message.parts {
+        if let Part::Text(text) = part {
+            text.synthetic = Some(true);
+        }
+    }
+    message
+}
+
+fn zero_tokens(message: Option<&MessageWithParts>) -> bool {
+    match message.map(|m| &m.info) {
+        Some(MessageInfo::Assistant(a)) => {
+            a.tokens.input == 0 && a.tokens.output == 0 && a.tokens.reasoning == 0
+        }
+        _ => false,
+    }
+}
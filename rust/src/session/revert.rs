@@ -0,0 +1,182 @@
+//! Session revert
+//!
+//! Rust counterpart of `js/src/session/revert.ts`. Reverting to a message
+//! or part rolls the working tree back to the state before it, using the
+//! patch parts recorded after each snapshotted step, and marks the session
+//! with where the conversation now ends. Nothing is deleted yet: `unrevert`
+//! restores the files from the snapshot taken before reverting, and the
+//! next prompt drops the reverted messages for good.
+
+use serde::{Deserialize, Serialize};
+
+use super::message::{MessageInfo, Part};
+use super::Session;
+use crate::error::{AgentError, Result};
+use crate::snapshot::{Patch, Snapshot};
+
+/// Where a reverted session ends, matching the JavaScript
+/// `Session.Info.revert` shape
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Revert {
+    /// First message that is reverted
+    #[serde(rename = "messageID")]
+    pub message_id: String,
+    /// First reverted part of that message; `None` reverts all of it
+    #[serde(default, rename = "partID", skip_serializing_if = "Option::is_none")]
+    pub part_id: Option<String>,
+    /// Working tree before reverting, restored by `unrevert`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+    /// Unified diff of the working tree against `snapshot`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+/// Roll `session` back to the state before the message `message_id`, or
+/// before the part `part_id` when given.
+///
+/// As in the JavaScript implementation, reverting an assistant message
+/// reverts the whole turn from the user message that prompted it, and so
+/// does reverting a part with no text or tool call before it. Files are
+/// only rolled back with a `snapshot`. Reverting an already reverted
+/// session first restores its files, so the new target alone decides the
+/// working tree.
+pub fn revert(
+    session: &mut Session,
+    snapshot: Option<&Snapshot>,
+    message_id: &str,
+    part_id: Option<&str>,
+) -> Result<Revert> {
+    let (message, part) = target(session, message_id, part_id).ok_or_else(|| {
+        let (kind, id) = match part_id {
+            Some(part_id) => ("Part", part_id),
+            None => ("Message", message_id),
+        };
+        AgentError::Session {
+            session_id: Some(session.id().to_string()),
+            message: format!("{} not found: {}", kind, id),
+        }
+    })?;
+    let patches: Vec<Patch> = session.messages[message..]
+        .iter()
+        .enumerate()
+        .flat_map(|(index, reverted)| {
+            let from = if index == 0 { part.unwrap_or(0) } else { 0 };
+            &reverted.parts[from..]
+        })
+        .filter_map(|reverted| match reverted {
+            Part::Patch(patch) => Some(Patch {
+                hash: patch.hash.clone(),
+                files: patch.files.clone(),
+            }),
+            _ => None,
+        })
+        .collect();
+
+    let mut revert = Revert {
+        message_id: session.messages[message].info.id().to_string(),
+        part_id: part.map(|part| session.messages[message].parts[part].id().to_string()),
+        snapshot: session
+            .info
+            .revert
+            .take()
+            .and_then(|revert| revert.snapshot),
+        diff: None,
+    };
+    if let Some(snapshot) = snapshot {
+        match &revert.snapshot {
+            Some(previous) => snapshot.restore(previous),
+            None => revert.snapshot = snapshot.track(),
+        }
+        snapshot.revert(&patches);
+        revert.diff = revert
+            .snapshot
+            .as_deref()
+            .map(|hash| snapshot.diff(hash))
+            .filter(|diff| !diff.is_empty());
+    }
+    tracing::info!(
+        session_id = session.id(),
+        message_id = revert.message_id.as_str(),
+        patches = patches.len(),
+        "session reverted"
+    );
+    session.info.revert = Some(revert.clone());
+    Ok(revert)
+}
+
+/// Undo a revert: restore the files from the snapshot taken before it and
+/// keep the whole conversation. Returns whether the session was reverted.
+pub fn unrevert(session: &mut Session, snapshot: Option<&Snapshot>) -> bool {
+    let Some(revert) = session.info.revert.take() else {
+        return false;
+    };
+    if let (Some(snapshot), Some(hash)) = (snapshot, &revert.snapshot) {
+        snapshot.restore(hash);
+    }
+    tracing::info!(session_id = session.id(), "session unreverted");
+    true
+}
+
+/// Drop the reverted messages and parts of `session` and clear its revert
+/// marker; called before the next prompt
+pub fn cleanup(session: &mut Session) {
+    let Some(revert) = session.info.revert.take() else {
+        return;
+    };
+    let Some(message) = session
+        .messages
+        .iter()
+        .position(|message| message.info.id() == revert.message_id)
+    else {
+        return;
+    };
+    let part = revert.part_id.as_deref().and_then(|part_id| {
+        session.messages[message]
+            .parts
+            .iter()
+            .position(|part| part.id() == part_id)
+    });
+    match part {
+        Some(part) => {
+            session.messages.truncate(message + 1);
+            session.messages[message].parts.truncate(part);
+        }
+        None => session.messages.truncate(message),
+    }
+}
+
+/// Index of the first reverted message, and of its first reverted part
+/// when only part of it is reverted
+fn target(
+    session: &Session,
+    message_id: &str,
+    part_id: Option<&str>,
+) -> Option<(usize, Option<usize>)> {
+    let mut last_user = None;
+    for (index, message) in session.messages.iter().enumerate() {
+        if let MessageInfo::User(_) = message.info {
+            last_user = Some(index);
+        }
+        let part = match part_id {
+            Some(part_id) => match message.parts.iter().position(|part| part.id() == part_id) {
+                Some(part) => Some(part),
+                None => continue,
+            },
+            None if message.info.id() == message_id => None,
+            None => continue,
+        };
+        // With nothing useful left in the message, revert all of it
+        let useful = part.is_some_and(|part| {
+            message.parts[..part]
+                .iter()
+                .any(|part| matches!(part, Part::Text(_) | Part::Tool(_)))
+        });
+        return Some(if useful {
+            (index, part)
+        } else {
+            (last_user.unwrap_or(index), None)
+        });
+    }
+    None
+}
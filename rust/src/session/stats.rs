@@ -0,0 +1,343 @@
+//! Token and cost ledger for `agent stats`
+//!
+//! Port of the JavaScript `stats` command: stored sessions are aggregated
+//! into totals and grouped by model, provider, day and project, each with
+//! session, message and per-tool call counts, token counters and cost.
+//!
+//! Costs recorded on assistant messages are used as is; messages recorded
+//! without one are priced from the models catalog. Models are also sorted
+//! into free and paid tiers by their catalog prices, so the work done by
+//! free models shows next to what the paid ones cost.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::message::{MessageInfo, Part, Tokens};
+use super::{Session, SessionInfo};
+use crate::error::{AgentError, Result};
+use crate::provider::models::Catalog;
+use crate::storage::Storage;
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Tier of models priced at zero in the catalog (or named `*-free`)
+pub const FREE_TIER: &str = "free";
+/// Tier of models with a catalog price
+pub const PAID_TIER: &str = "paid";
+/// Tier of models missing from the catalog
+pub const UNKNOWN_TIER: &str = "unknown";
+
+/// Which sessions are counted
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Only sessions updated in the last N days
+    pub days: Option<u64>,
+    /// Only sessions of this project
+    pub project: Option<String>,
+    /// Current time in milliseconds, the reference for `days`
+    pub now: u64,
+}
+
+/// Counters for one group of messages
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    pub sessions: u64,
+    pub messages: u64,
+    /// Calls per tool ID
+    pub tool_calls: BTreeMap<String, u64>,
+    pub tokens: Tokens,
+    /// Recorded or estimated cost in USD
+    pub cost: f64,
+    #[serde(skip)]
+    session_ids: BTreeSet<String>,
+}
+
+impl Usage {
+    /// Total tool calls across tools
+    pub fn total_tool_calls(&self) -> u64 {
+        self.tool_calls.values().sum()
+    }
+
+    fn count_message(&mut self, session_id: &str) {
+        self.messages += 1;
+        if self.session_ids.insert(session_id.to_string()) {
+            self.sessions += 1;
+        }
+    }
+}
+
+/// Aggregated usage of the stored sessions
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+    pub total: Usage,
+    /// Days between the first and the last session, at least 1
+    pub days: u64,
+    pub cost_per_day: f64,
+    /// Keyed by `providerID/modelID`
+    pub by_model: BTreeMap<String, Usage>,
+    pub by_provider: BTreeMap<String, Usage>,
+    /// Keyed by UTC date (`YYYY-MM-DD`) of the message
+    pub by_day: BTreeMap<String, Usage>,
+    pub by_project: BTreeMap<String, Usage>,
+    /// Keyed by [`FREE_TIER`], [`PAID_TIER`] and [`UNKNOWN_TIER`]
+    pub by_tier: BTreeMap<String, Usage>,
+}
+
+impl Stats {
+    /// Aggregate the sessions in `storage` that pass `filter`, pricing
+    /// messages without a recorded cost from `catalog`
+    pub fn collect(storage: &Storage, catalog: &Catalog, filter: &Filter) -> Result<Self> {
+        let cutoff = filter
+            .days
+            .map(|days| filter.now.saturating_sub(days * DAY_MS));
+        let mut stats = Stats::default();
+        let (mut earliest, mut latest) = (u64::MAX, 0);
+        for key in storage.list(&["session"])? {
+            let [_, project_id, session_id] = key.as_slice() else {
+                continue;
+            };
+            if filter.project.as_ref().is_some_and(|p| p != project_id) {
+                continue;
+            }
+            let info: SessionInfo = match storage.read(&["session", project_id, session_id]) {
+                Ok(info) => info,
+                Err(AgentError::Json(e)) => {
+                    tracing::debug!(session = %session_id, error = %e, "skipping unreadable session");
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if cutoff.is_some_and(|cutoff| info.time.updated < cutoff) {
+                continue;
+            }
+            let session = storage.read_session(project_id, session_id)?;
+            earliest = earliest.min(info.time.created);
+            latest = latest.max(info.time.updated);
+            stats.add_session(&session, catalog);
+        }
+        if stats.total.sessions > 0 {
+            stats.days = latest.saturating_sub(earliest).div_ceil(DAY_MS).max(1);
+            stats.cost_per_day = stats.total.cost / stats.days as f64;
+        }
+        Ok(stats)
+    }
+
+    fn add_session(&mut self, session: &Session, catalog: &Catalog) {
+        let session_id = session.id();
+        // Sessions without messages still count towards the total
+        if self.total.session_ids.insert(session_id.to_string()) {
+            self.total.sessions += 1;
+        }
+        for message in &session.messages {
+            let (provider_id, model_id, created) = match &message.info {
+                MessageInfo::User(user) => (
+                    &user.model.provider_id,
+                    &user.model.model_id,
+                    user.time.created,
+                ),
+                MessageInfo::Assistant(assistant) => (
+                    &assistant.provider_id,
+                    &assistant.model_id,
+                    assistant.time.created,
+                ),
+            };
+            let model = catalog.model(provider_id, model_id);
+            let (tokens, cost) = match &message.info {
+                MessageInfo::Assistant(assistant) => {
+                    let cost = match model {
+                        Some(model) if assistant.cost == 0.0 => assistant.tokens.cost(&model.cost),
+                        _ => assistant.cost,
+                    };
+                    (assistant.tokens.clone(), cost)
+                }
+                MessageInfo::User(_) => (Tokens::default(), 0.0),
+            };
+            let tier = match model {
+                _ if model_id.ends_with("-free") => FREE_TIER,
+                Some(model) if model.cost.base.input == 0.0 && model.cost.base.output == 0.0 => {
+                    FREE_TIER
+                }
+                Some(_) => PAID_TIER,
+                None => UNKNOWN_TIER,
+            };
+            let tools: Vec<&str> = message
+                .parts
+                .iter()
+                .filter_map(|part| match part {
+                    Part::Tool(tool) => Some(tool.tool.as_str()),
+                    _ => None,
+                })
+                .collect();
+
+            let groups = [
+                &mut self.total,
+                entry(&mut self.by_model, format!("{}/{}", provider_id, model_id)),
+                entry(&mut self.by_provider, provider_id.clone()),
+                entry(&mut self.by_day, day(created)),
+                entry(&mut self.by_project, session.info.project_id.clone()),
+                entry(&mut self.by_tier, tier.to_string()),
+            ];
+            for usage in groups {
+                usage.count_message(session_id);
+                usage.tokens.add(&tokens);
+                usage.cost += cost;
+                for tool in &tools {
+                    *usage.tool_calls.entry(tool.to_string()).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    /// Boxed summary in the layout of the JavaScript command, followed by
+    /// one table per grouping; `tool_limit` caps the tools listed
+    pub fn table(&self, tool_limit: Option<usize>) -> String {
+        let mut out = String::new();
+        let total = &self.total;
+        boxed(
+            &mut out,
+            "OVERVIEW",
+            &[
+                ("Sessions", total.sessions.to_string()),
+                ("Messages", total.messages.to_string()),
+                ("Tool Calls", total.total_tool_calls().to_string()),
+                ("Days", self.days.to_string()),
+            ],
+        );
+        boxed(
+            &mut out,
+            "COST & TOKENS",
+            &[
+                ("Total Cost", format!("${:.2}", total.cost)),
+                ("Cost/Day", format!("${:.2}", self.cost_per_day)),
+                ("Input", format_number(total.tokens.input)),
+                ("Output", format_number(total.tokens.output)),
+                ("Reasoning", format_number(total.tokens.reasoning)),
+                ("Cache Read", format_number(total.tokens.cache.read)),
+                ("Cache Write", format_number(total.tokens.cache.write)),
+            ],
+        );
+        if !total.tool_calls.is_empty() {
+            tool_usage(&mut out, &total.tool_calls, tool_limit);
+        }
+        for (title, label, groups) in [
+            ("BY TIER", "Tier", &self.by_tier),
+            ("BY MODEL", "Model", &self.by_model),
+            ("BY PROVIDER", "Provider", &self.by_provider),
+            ("BY PROJECT", "Project", &self.by_project),
+            ("BY DAY", "Day", &self.by_day),
+        ] {
+            if !groups.is_empty() {
+                grouped(&mut out, title, label, groups);
+            }
+        }
+        out
+    }
+}
+
+fn entry(groups: &mut BTreeMap<String, Usage>, key: String) -> &mut Usage {
+    groups.entry(key).or_default()
+}
+
+fn day(millis: u64) -> String {
+    DateTime::<Utc>::from_timestamp_millis(millis as i64)
+        .unwrap_or_default()
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+const WIDTH: usize = 56;
+
+fn boxed(out: &mut String, title: &str, rows: &[(&str, String)]) {
+    let _ = writeln!(out, "┌{}┐", "─".repeat(WIDTH));
+    let _ = writeln!(out, "│{:^width$}│", title, width = WIDTH);
+    let _ = writeln!(out, "├{}┤", "─".repeat(WIDTH));
+    for (label, value) in rows {
+        let padding = (WIDTH - 1).saturating_sub(label.chars().count() + value.chars().count());
+        let _ = writeln!(out, "│{}{}{} │", label, " ".repeat(padding), value);
+    }
+    let _ = writeln!(out, "└{}┘\n", "─".repeat(WIDTH));
+}
+
+fn tool_usage(out: &mut String, tools: &BTreeMap<String, u64>, limit: Option<usize>) {
+    let mut sorted: Vec<(&String, &u64)> = tools.iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    let total: u64 = tools.values().sum();
+    let shown = &sorted[..limit.unwrap_or(sorted.len()).min(sorted.len())];
+    let max = shown.iter().map(|(_, count)| **count).max().unwrap_or(1);
+
+    let _ = writeln!(out, "┌{}┐", "─".repeat(WIDTH));
+    let _ = writeln!(out, "│{:^width$}│", "TOOL USAGE", width = WIDTH);
+    let _ = writeln!(out, "├{}┤", "─".repeat(WIDTH));
+    for (tool, count) in shown {
+        let bar = "█".repeat(((**count * 20) / max).max(1) as usize);
+        let name = if tool.chars().count() > 18 {
+            format!("{}..", tool.chars().take(16).collect::<String>())
+        } else {
+            tool.to_string()
+        };
+        let percentage = **count as f64 * 100.0 / total as f64;
+        let content = format!(
+            " {:<18} {:<20} {:>3} ({:>4.1}%)",
+            name, bar, count, percentage
+        );
+        let padding = (WIDTH - 1).saturating_sub(content.chars().count());
+        let _ = writeln!(out, "│{}{} │", content, " ".repeat(padding));
+    }
+    let _ = writeln!(out, "└{}┘\n", "─".repeat(WIDTH));
+}
+
+fn grouped(out: &mut String, title: &str, label: &str, groups: &BTreeMap<String, Usage>) {
+    let header = [
+        label, "Sessions", "Messages", "Tools", "Input", "Output", "Cache", "Cost",
+    ];
+    let rows: Vec<[String; 8]> = groups
+        .iter()
+        .map(|(key, usage)| {
+            [
+                key.clone(),
+                usage.sessions.to_string(),
+                usage.messages.to_string(),
+                usage.total_tool_calls().to_string(),
+                format_number(usage.tokens.input),
+                format_number(usage.tokens.output),
+                format_number(usage.tokens.cache.read + usage.tokens.cache.write),
+                format!("${:.2}", usage.cost),
+            ]
+        })
+        .collect();
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: [&str; 8]| {
+        let mut line = format!("{:<width$}", cells[0], width = widths[0]);
+        for (cell, width) in cells.iter().zip(widths).skip(1) {
+            let _ = write!(line, "  {:>width$}", cell, width = width);
+        }
+        line
+    };
+    let _ = writeln!(out, "{}", title);
+    let _ = writeln!(out, "{}", line(header));
+    for row in &rows {
+        let _ = writeln!(out, "{}", line(row.each_ref().map(String::as_str)));
+    }
+    let _ = writeln!(out);
+}
+
+/// `1234` as `1.2K`, `1234567` as `1.2M`
+fn format_number(value: u64) -> String {
+    if value >= 1_000_000 {
+        format!("{:.1}M", value as f64 / 1_000_000.0)
+    } else if value >= 1_000 {
+        format!("{:.1}K", value as f64 / 1_000.0)
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,174 @@
+//! Session summaries
+//!
+//! Rust counterpart of `js/src/session/summary.ts`. After each completed
+//! turn the session gets a short recap of what the agent did and a rollup of
+//! the files it changed. With snapshots the changes are diffed between the
+//! first and last snapshot of the session, as in the JavaScript
+//! implementation; otherwise they come from the `filediff` metadata of the
+//! edit, multiedit, patch and write tools. The rollup is stored under
+//! `session_diff/<sessionID>` and its totals on the session, as in the
+//! JavaScript implementation.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+
+use super::message::{to_chat_messages, MessageWithParts, Part, ToolState};
+use super::{compaction, system};
+use crate::defaults::ModelParts;
+use crate::error::Result;
+use crate::provider::{ChatMessage, ChatRequest, ContentPart, Provider};
+pub use crate::snapshot::FileDiff;
+
+/// Environment variable that turns summaries off with `false` or `0`
+pub const SUMMARIZE_SESSION_ENV: &str = "LINK_ASSISTANT_AGENT_SUMMARIZE_SESSION";
+
+/// Closes the conversation sent for a recap
+const RECAP_REQUEST: &str = "Summarize what happened in this session.";
+
+/// Longest a recap request may hold up the end of a turn
+pub const RECAP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Output budget of a recap request; reasoning models need room to think
+const MAX_OUTPUT_TOKENS: u64 = 100;
+const REASONING_MAX_OUTPUT_TOKENS: u64 = 1500;
+
+/// Summary stored on the session, matching the JavaScript
+/// `Session.Info.summary` totals; older sessions may lack some of them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionSummary {
+    pub additions: u64,
+    pub deletions: u64,
+    /// Number of files changed
+    pub files: u64,
+    /// Natural-language recap of the session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+impl SessionSummary {
+    /// Totals of `diffs`, with an optional recap
+    pub fn new(diffs: &[FileDiff], body: Option<String>) -> Self {
+        Self {
+            additions: diffs.iter().map(|diff| diff.additions).sum(),
+            deletions: diffs.iter().map(|diff| diff.deletions).sum(),
+            files: diffs.len() as u64,
+            body,
+        }
+    }
+}
+
+/// Whether `LINK_ASSISTANT_AGENT_SUMMARIZE_SESSION` turns summaries off
+pub fn disabled_from_env(getenv: impl Fn(&str) -> Option<String>) -> bool {
+    getenv(SUMMARIZE_SESSION_ENV)
+        .is_some_and(|value| matches!(value.trim().to_lowercase().as_str(), "false" | "0"))
+}
+
+/// Whether summaries are turned off in the process environment
+pub fn disabled() -> bool {
+    disabled_from_env(crate::global::getenv)
+}
+
+/// Files changed by the completed tool calls in `messages`, one entry per
+/// file in the order they were first changed.
+///
+/// Repeated changes to a file keep its first `before` and last `after`, and
+/// add up their line counts.
+pub fn diffs(messages: &[MessageWithParts], working_directory: &Path) -> Vec<FileDiff> {
+    let mut rollup: Vec<FileDiff> = Vec::new();
+    let changes = messages
+        .iter()
+        .flat_map(|message| &message.parts)
+        .filter_map(|part| match part {
+            Part::Tool(tool) => match &tool.state {
+                ToolState::Completed { metadata, .. } => Some(metadata),
+                _ => None,
+            },
+            _ => None,
+        })
+        .flat_map(file_diffs);
+    for mut change in changes {
+        if let Ok(relative) = Path::new(&change.file).strip_prefix(working_directory) {
+            change.file = relative.to_string_lossy().to_string();
+        }
+        match rollup.iter_mut().find(|diff| diff.file == change.file) {
+            Some(diff) => {
+                diff.after = change.after;
+                diff.additions += change.additions;
+                diff.deletions += change.deletions;
+            }
+            None => rollup.push(change),
+        }
+    }
+    rollup
+}
+
+/// The first and last snapshots recorded around steps in `messages`, as in
+/// the JavaScript `computeDiff`
+pub fn snapshot_range(messages: &[MessageWithParts]) -> Option<(String, String)> {
+    let parts = || messages.iter().flat_map(|message| &message.parts);
+    let from = parts().find_map(|part| match part {
+        Part::StepStart(start) => start.snapshot.clone(),
+        _ => None,
+    })?;
+    let to = parts().rev().find_map(|part| match part {
+        Part::StepFinish(finish) => finish.snapshot.clone(),
+        _ => None,
+    })?;
+    Some((from, to))
+}
+
+/// `filediff` entries of a tool result; multiedit nests one per edit
+fn file_diffs(metadata: &Value) -> Vec<FileDiff> {
+    let nested = metadata["results"]
+        .as_array()
+        .map(|results| results.iter().map(|result| &result["filediff"]))
+        .into_iter()
+        .flatten();
+    std::iter::once(&metadata["filediff"])
+        .chain(nested)
+        .filter_map(|diff| serde_json::from_value(diff.clone()).ok())
+        .collect()
+}
+
+/// Ask `model` for a short recap of `messages`.
+///
+/// `reasoning` raises the output budget for models that think before
+/// answering.
+pub async fn recap(
+    provider: &dyn Provider,
+    model: &ModelParts,
+    reasoning: bool,
+    messages: &[MessageWithParts],
+) -> Result<Option<String>> {
+    let mut conversation = to_chat_messages(compaction::active_messages(messages));
+    conversation.push(ChatMessage::User {
+        text: RECAP_REQUEST.to_string(),
+    });
+    let request = ChatRequest {
+        model: model.model_id.clone(),
+        system: system::summarize_turn(),
+        messages: conversation,
+        tools: Vec::new(),
+        temperature: None,
+        max_output_tokens: Some(if reasoning {
+            REASONING_MAX_OUTPUT_TOKENS
+        } else {
+            MAX_OUTPUT_TOKENS
+        }),
+        thinking_budget: None,
+    };
+    let response = provider.complete(&request).await?;
+    let text: String = response
+        .content
+        .iter()
+        .filter_map(|content| match content {
+            ContentPart::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    let text = text.trim();
+    Ok((!text.is_empty()).then(|| text.to_string()))
+}
@@ -0,0 +1,156 @@
+//! System prompt assembly
+//!
+//! Rust counterpart of `js/src/session/system.ts` and the JavaScript
+//! `resolveSystemPrompt` helper: a base prompt, the environment block and
+//! any project instruction files (AGENTS.md / CLAUDE.md).
+
+use std::path::Path;
+
+use crate::util::Filesystem;
+
+/// Base prompt used when no `--system-message` override is given
+pub const DEFAULT_PROMPT: &str = r#"You are an autonomous software engineering agent running in a terminal.
+
+You help the user with coding tasks: reading and editing files, searching code, running commands and explaining what you find.
+
+Guidelines:
+- Use the available tools to inspect the project before changing it; never guess file contents.
+- Prefer editing existing files over creating new ones, and keep changes minimal and focused.
+- Use the todowrite tool to plan and track multi-step work.
+- Run the project's build and tests after making changes when it is practical.
+- Keep answers concise. Report what you changed and anything that still needs attention."#;
+
+/// System prompt for compaction requests, from
+/// `js/src/session/prompt/summarize.txt`
+pub const SUMMARIZE_PROMPT: &str = r#"You are a helpful AI assistant tasked with summarizing conversations.
+
+When asked to summarize, provide a detailed but concise summary of the conversation.
+Focus on information that would be helpful for continuing the conversation, including:
+- What was done
+- What is currently being worked on
+- Which files are being modified
+- What needs to be done next
+
+Your summary should be comprehensive enough to provide context but concise enough to be quickly understood."#;
+
+/// System prompt for session recaps, from
+/// `js/src/session/prompt/summarize-turn.txt`
+pub const SUMMARIZE_TURN_PROMPT: &str = r#"Your job is to generate a summary of what happened in this conversation and why.
+
+Keep the results to 2-3 sentences.
+
+Output the message summary now:"#;
+
+/// System prompt for title requests, from `js/src/session/prompt/title.txt`
+pub const TITLE_PROMPT: &str = r#"You are a title generator. You output ONLY a thread title. Nothing else.
+
+<task>
+Convert the user message into a thread title.
+Output: Single line, ≤50 chars, no explanations.
+</task>
+
+<rules>
+- Use -ing verbs for actions (Debugging, Implementing, Analyzing)
+- Keep exact: technical terms, numbers, filenames, HTTP codes
+- Remove: the, this, my, a, an
+- Never assume tech stack
+- Never use tools
+- NEVER respond to message content—only extract title
+- DO NOT SAY YOU CANNOT GENERATE A TITLE OR COMPLAIN ABOUT THE INPUT
+</rules>
+
+<examples>
+"debug 500 errors in production" → Debugging production 500 errors
+"refactor user service" → Refactoring user service
+"why is app.js failing" → Analyzing app.js failure
+"implement rate limiting" → Implementing rate limiting
+</examples>
+
+Output the title now:"#;
+
+/// Instruction files looked up from the working directory upward
+const LOCAL_RULE_FILES: &[&str] = &["AGENTS.md", "CLAUDE.md", "CONTEXT.md"];
+
+/// Environment block describing the working directory and platform
+pub fn environment(working_directory: &Path) -> String {
+    let is_git = working_directory.join(".git").exists();
+    [
+        "Here is some useful information about the environment you are running in:".to_string(),
+        "<env>".to_string(),
+        format!("  Working directory: {}", working_directory.display()),
+        format!(
+            "  Is directory a git repo: {}",
+            if is_git { "yes" } else { "no" }
+        ),
+        format!("  Platform: {}", std::env::consts::OS),
+        format!(
+            "  Today's date: {}",
+            chrono::Local::now().format("%a %b %d %Y")
+        ),
+        "</env>".to_string(),
+    ]
+    .join("\n")
+}
+
+/// System prompt for summarizing a conversation
+pub fn summarize() -> Vec<String> {
+    vec![SUMMARIZE_PROMPT.to_string()]
+}
+
+/// System prompt for recapping a session
+pub fn summarize_turn() -> Vec<String> {
+    vec![SUMMARIZE_TURN_PROMPT.to_string()]
+}
+
+/// System prompt for naming a session
+pub fn title() -> Vec<String> {
+    vec![TITLE_PROMPT.to_string()]
+}
+
+/// Contents of the nearest project instruction file, if any
+pub async fn custom(working_directory: &Path) -> Vec<String> {
+    for name in LOCAL_RULE_FILES {
+        let matches = Filesystem::find_up(name, working_directory, None).await;
+        if matches.is_empty() {
+            continue;
+        }
+        let mut result = Vec::new();
+        for path in matches {
+            if let Ok(text) = tokio::fs::read_to_string(&path).await {
+                result.push(format!("Instructions from: {}\n{}", path.display(), text));
+            }
+        }
+        return result;
+    }
+    Vec::new()
+}
+
+/// Resolve the system prompt for a request.
+///
+/// A full `--system-message` override is used exclusively, without the
+/// environment block or instruction files, so low-limit models stay within
+/// budget. Otherwise the default prompt (plus any appended text) is followed
+/// by the environment and instructions, joined into at most two messages so
+/// providers can cache the stable prefix.
+pub async fn resolve(
+    system: Option<&str>,
+    append_system: Option<&str>,
+    working_directory: &Path,
+) -> Vec<String> {
+    if let Some(system) = system {
+        if system.trim().is_empty() {
+            return Vec::new();
+        }
+        return vec![system.to_string()];
+    }
+
+    let base = match append_system {
+        Some(append) => format!("{}\n{}", DEFAULT_PROMPT, append),
+        None => DEFAULT_PROMPT.to_string(),
+    };
+
+    let mut rest = vec![environment(working_directory)];
+    rest.extend(custom(working_directory).await);
+
+    vec![base, rest.join("\n")]
+}
@@ -0,0 +1,120 @@
+//! Session titles
+//!
+//! Rust counterpart of the JavaScript `ensureTitle`. Title generation is off
+//! by default to save tokens; with `--generate-title` (or
+//! `LINK_ASSISTANT_AGENT_GENERATE_TITLE`) a small model names the session
+//! after its first user message. A title is a nicety, so a failed request is
+//! only logged and never fails the turn.
+
+use regex::Regex;
+
+use super::message::{MessageInfo, MessageWithParts, Part};
+use super::{is_default_title, system, Session};
+use crate::defaults::ModelParts;
+use crate::error::Result;
+use crate::provider::{ChatMessage, ChatRequest, ContentPart, Provider};
+
+/// Environment variable that turns title generation on
+pub const GENERATE_TITLE_ENV: &str = "LINK_ASSISTANT_AGENT_GENERATE_TITLE";
+
+/// Longest title kept; longer ones are cut with an ellipsis
+pub const MAX_TITLE_LENGTH: usize = 100;
+
+/// Output budget of a title request; reasoning models need room to think
+const MAX_OUTPUT_TOKENS: u64 = 20;
+const REASONING_MAX_OUTPUT_TOKENS: u64 = 1500;
+
+/// Leads the user message in a title request
+const TITLE_REQUEST: &str = "The following is the text to summarize:";
+
+/// Whether `LINK_ASSISTANT_AGENT_GENERATE_TITLE` turns title generation on
+pub fn enabled_from_env(getenv: impl Fn(&str) -> Option<String>) -> bool {
+    getenv(GENERATE_TITLE_ENV)
+        .is_some_and(|value| matches!(value.trim(), "1" | "true" | "yes" | "on"))
+}
+
+/// Whether title generation is turned on in the process environment
+pub fn enabled() -> bool {
+    enabled_from_env(crate::global::getenv)
+}
+
+/// Whether `session` should be named now: a top-level session that still
+/// has its default title and has just received its first user message
+pub fn is_needed(session: &Session) -> bool {
+    if session.info.parent_id.is_some() || !is_default_title(&session.info.title) {
+        return false;
+    }
+    session
+        .messages
+        .iter()
+        .filter(|message| is_user_written(message))
+        .count()
+        == 1
+}
+
+/// User messages made only of synthetic text or compaction requests were not
+/// written by the user
+fn is_user_written(message: &MessageWithParts) -> bool {
+    matches!(message.info, MessageInfo::User(_))
+        && message.parts.iter().any(|part| match part {
+            Part::Text(text) => text.synthetic != Some(true),
+            Part::Compaction(_) => false,
+            _ => true,
+        })
+}
+
+/// Turn a model response into a title: drop `<think>` blocks, keep the first
+/// non-empty line and cap its length
+pub fn clean(text: &str) -> Option<String> {
+    let think = Regex::new(r"(?s)<think>.*?</think>\s*").expect("valid regex");
+    let text = think.replace_all(text, "");
+    let line = text.lines().map(str::trim).find(|line| !line.is_empty())?;
+    if line.chars().count() > MAX_TITLE_LENGTH {
+        let cut: String = line.chars().take(MAX_TITLE_LENGTH - 3).collect();
+        Some(format!("{}...", cut))
+    } else {
+        Some(line.to_string())
+    }
+}
+
+/// Ask `model` for a title for a session opened with `text`.
+///
+/// `reasoning` raises the output budget for models that think before
+/// answering.
+pub async fn generate(
+    provider: &dyn Provider,
+    model: &ModelParts,
+    reasoning: bool,
+    text: &str,
+) -> Result<Option<String>> {
+    let request = ChatRequest {
+        model: model.model_id.clone(),
+        system: system::title(),
+        messages: vec![
+            ChatMessage::User {
+                text: TITLE_REQUEST.to_string(),
+            },
+            ChatMessage::User {
+                text: text.to_string(),
+            },
+        ],
+        tools: Vec::new(),
+        temperature: None,
+        max_output_tokens: Some(if reasoning {
+            REASONING_MAX_OUTPUT_TOKENS
+        } else {
+            MAX_OUTPUT_TOKENS
+        }),
+        thinking_budget: None,
+    };
+    let response = provider.complete(&request).await?;
+    let text: String = response
+        .content
+        .iter()
+        .filter_map(|content| match content {
+            ContentPart::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    Ok(clean(&text))
+}
@@ -0,0 +1,37 @@
+//! Token usage and cost of model steps
+//!
+//! Rust counterpart of the JavaScript `Session.getUsage`. Providers already
+//! report usage normalized (input excludes cache reads, cache writes are
+//! separate), so what remains is converting it to the session's token
+//! counters and pricing it from the models catalog.
+
+use serde::Serialize;
+
+use super::message::{CacheTokens, Tokens};
+use crate::provider::models::Model;
+use crate::provider::Usage;
+
+/// Tokens and cost of one model step
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StepUsage {
+    pub tokens: Tokens,
+    /// USD; zero when the model has no catalog price
+    pub cost: f64,
+}
+
+impl StepUsage {
+    /// Usage reported by a provider, priced for `model`
+    pub fn new(usage: &Usage, model: Option<&Model>) -> Self {
+        let tokens = Tokens {
+            input: usage.input,
+            output: usage.output,
+            reasoning: usage.reasoning,
+            cache: CacheTokens {
+                read: usage.cache_read,
+                write: usage.cache_write,
+            },
+        };
+        let cost = model.map_or(0.0, |model| tokens.cost(&model.cost));
+        Self { tokens, cost }
+    }
+}
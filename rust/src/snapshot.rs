@@ -0,0 +1,244 @@
+//! Working-tree snapshots
+//!
+//! Rust counterpart of `js/src/snapshot`. A shadow git repository under
+//! `<data dir>/snapshot/<projectID>`, separate from the project's own `.git`,
+//! records the working tree as tree objects. Hashes taken around a step
+//! capture every change it made, including changes made through `bash` that
+//! the edit tools' `filediff` metadata never sees, and any two of them can be
+//! diffed per file.
+//!
+//! Snapshots are best effort, as in the JavaScript implementation: a failing
+//! git command is logged and yields no snapshot or an empty diff.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::storage;
+
+/// Directory under the data directory holding the shadow repositories
+pub const SNAPSHOT_DIR: &str = "snapshot";
+
+/// Environment variable that turns snapshots off
+pub const DISABLE_SNAPSHOT_ENV: &str = "LINK_ASSISTANT_AGENT_DISABLE_SNAPSHOT";
+
+/// Changes to one file, matching the JavaScript `Snapshot.FileDiff` shape
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileDiff {
+    /// Relative to the working directory when inside it
+    pub file: String,
+    pub before: String,
+    pub after: String,
+    pub additions: u64,
+    pub deletions: u64,
+}
+
+/// Files changed since a snapshot
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Patch {
+    /// The snapshot the files changed from
+    pub hash: String,
+    /// Absolute paths
+    pub files: Vec<String>,
+}
+
+/// Whether `LINK_ASSISTANT_AGENT_DISABLE_SNAPSHOT` turns snapshots off
+pub fn disabled_from_env(getenv: impl Fn(&str) -> Option<String>) -> bool {
+    getenv(DISABLE_SNAPSHOT_ENV)
+        .is_some_and(|value| matches!(value.trim(), "1" | "true" | "yes" | "on"))
+}
+
+/// Whether snapshots are turned off in the process environment
+pub fn disabled() -> bool {
+    disabled_from_env(crate::global::getenv)
+}
+
+/// Shadow repository tracking one working tree
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    git_dir: PathBuf,
+    worktree: PathBuf,
+}
+
+impl Snapshot {
+    /// Track `worktree` in the shadow repository at `git_dir`
+    pub fn new(git_dir: impl Into<PathBuf>, worktree: impl Into<PathBuf>) -> Self {
+        Self {
+            git_dir: git_dir.into(),
+            worktree: worktree.into(),
+        }
+    }
+
+    /// Snapshots of the git repository containing `directory`, kept under
+    /// `data_dir`; `None` outside a git repository, as in the JavaScript
+    /// implementation
+    pub fn for_directory(data_dir: &Path, directory: &Path) -> Option<Self> {
+        let output = Command::new("git")
+            .args(["rev-parse", "--show-toplevel"])
+            .current_dir(directory)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        let worktree = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+        let project_id = storage::project_id(&worktree);
+        Some(Self::new(
+            data_dir.join(SNAPSHOT_DIR).join(project_id),
+            worktree,
+        ))
+    }
+
+    /// The tracked working tree
+    pub fn worktree(&self) -> &Path {
+        &self.worktree
+    }
+
+    /// Record the working tree, returning its tree hash
+    pub fn track(&self) -> Option<String> {
+        if !self.git_dir.exists() {
+            std::fs::create_dir_all(&self.git_dir)
+                .map_err(|e| tracing::warn!(error = %e, "failed to create snapshot repository"))
+                .ok()?;
+            self.git(&["init", "--quiet"])?;
+            self.git(&["config", "core.autocrlf", "false"])?;
+            tracing::info!(git_dir = %self.git_dir.display(), "snapshot repository initialized");
+        }
+        self.git(&["add", "--all", "."])?;
+        let hash = self.git(&["write-tree"])?.trim().to_string();
+        tracing::info!(hash = hash.as_str(), "snapshot tracked");
+        Some(hash)
+    }
+
+    /// Files that changed since `hash`
+    pub fn patch(&self, hash: &str) -> Patch {
+        let files = self
+            .git(&["add", "--all", "."])
+            .and_then(|_| self.git(&["diff", "--name-only", hash, "--", "."]))
+            .map(|output| {
+                output
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(|file| self.worktree.join(file).to_string_lossy().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Patch {
+            hash: hash.to_string(),
+            files,
+        }
+    }
+
+    /// Unified diff of the working tree against `hash`
+    pub fn diff(&self, hash: &str) -> String {
+        self.git(&["add", "--all", "."])
+            .and_then(|_| self.git(&["diff", hash, "--", "."]))
+            .map(|output| output.trim().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Per-file changes between two snapshots; binary files have no
+    /// contents and no line counts
+    pub fn diff_full(&self, from: &str, to: &str) -> Vec<FileDiff> {
+        let Some(numstat) = self.git(&["diff", "--no-renames", "--numstat", from, to, "--", "."])
+        else {
+            return Vec::new();
+        };
+        numstat
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let (additions, deletions, file) = (fields.next()?, fields.next()?, fields.next()?);
+                let binary = additions == "-" && deletions == "-";
+                let show = |hash: &str| {
+                    if binary {
+                        return String::new();
+                    }
+                    self.git(&["show", &format!("{}:{}", hash, file)])
+                        .unwrap_or_default()
+                };
+                Some(FileDiff {
+                    file: file.to_string(),
+                    before: show(from),
+                    after: show(to),
+                    additions: additions.parse().unwrap_or(0),
+                    deletions: deletions.parse().unwrap_or(0),
+                })
+            })
+            .collect()
+    }
+
+    /// Put the whole working tree back to `hash`; files created since are
+    /// left in place, as in the JavaScript implementation
+    pub fn restore(&self, hash: &str) {
+        tracing::info!(hash, "restoring snapshot");
+        if self.git(&["read-tree", hash]).is_some() {
+            self.git(&["checkout-index", "-a", "-f"]);
+        }
+    }
+
+    /// Put the files of `patches` back to the snapshot each changed from.
+    ///
+    /// A file is reverted once, to the first patch listing it, so pass the
+    /// patches oldest first. Files the snapshot does not have are deleted.
+    pub fn revert(&self, patches: &[Patch]) {
+        let mut reverted = std::collections::HashSet::new();
+        for patch in patches {
+            for file in &patch.files {
+                if !reverted.insert(file.as_str()) {
+                    continue;
+                }
+                tracing::info!(
+                    file = file.as_str(),
+                    hash = patch.hash.as_str(),
+                    "reverting"
+                );
+                if self.git(&["checkout", &patch.hash, "--", file]).is_some() {
+                    continue;
+                }
+                let relative = Path::new(file)
+                    .strip_prefix(&self.worktree)
+                    .unwrap_or(Path::new(file))
+                    .to_string_lossy();
+                let tracked = self
+                    .git(&["ls-tree", &patch.hash, "--", &relative])
+                    .is_some_and(|output| !output.trim().is_empty());
+                if tracked {
+                    tracing::info!(file = file.as_str(), "checkout failed, keeping file");
+                } else if let Err(e) = std::fs::remove_file(file) {
+                    tracing::debug!(file = file.as_str(), error = %e, "failed to delete file");
+                }
+            }
+        }
+    }
+
+    /// Run git against the shadow repository, returning its output on
+    /// success
+    fn git(&self, args: &[&str]) -> Option<String> {
+        let output = Command::new("git")
+            .args(["-c", "core.autocrlf=false", "-c", "core.quotepath=false"])
+            .arg("--git-dir")
+            .arg(&self.git_dir)
+            .arg("--work-tree")
+            .arg(&self.worktree)
+            .args(args)
+            .current_dir(&self.worktree)
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {
+                Some(String::from_utf8_lossy(&output.stdout).to_string())
+            }
+            Ok(output) => {
+                tracing::warn!(
+                    command = args.first().copied().unwrap_or_default(),
+                    stderr = %String::from_utf8_lossy(&output.stderr).trim(),
+                    "snapshot git command failed"
+                );
+                None
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to run git for snapshot");
+                None
+            }
+        }
+    }
+}
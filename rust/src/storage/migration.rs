@@ -0,0 +1,325 @@
+//! Storage layout versions and migrations
+//!
+//! The `migration` file in the storage directory holds the number of
+//! migrations already applied, as plain text. The list below mirrors the
+//! JavaScript implementation's `MIGRATIONS` one for one, so both agents
+//! agree on the version of a data directory they share and never migrate
+//! it twice.
+//!
+//! Before pending migrations run, the storage directory is copied to
+//! `storage-backup-<version>` next to it. Migrations only copy records into
+//! the current layout (through atomic writes) and are safe to run again
+//! after an interruption; a failed migration is logged and skipped, as in
+//! JavaScript, leaving the backup for recovery.
+//!
+//! Separately, session trees left in project-local `.link-assistant-agent`
+//! and `.opencode` directories are imported on startup. Rather than being
+//! rewritten in place, a legacy tree is copied into the shared storage so
+//! both agents find its sessions where they now look, and is itself left
+//! readable for older agents still using it. Its `storage` directory is
+//! backed up first, like the shared one, and the `migrated` file in it
+//! lists the storage directories it was imported into, so later runs skip
+//! it. Records already present are never overwritten, so an interrupted
+//! import can simply run again.
+
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use super::{lock, project_id, write_atomic, Storage, GLOBAL_PROJECT, STORAGE_DIR};
+use crate::error::Result;
+
+/// File in the storage directory holding the number of applied migrations
+pub const MIGRATION_FILE: &str = "migration";
+
+/// Project-local directories that may hold legacy session trees, newest first
+pub const LEGACY_DIRS: [&str; 2] = [".link-assistant-agent", ".opencode"];
+
+/// File in a legacy storage directory listing the storage directories it
+/// was imported into, one per line
+pub const LEGACY_MARKER: &str = "migrated";
+
+type Migration = fn(&Storage) -> Result<()>;
+
+/// Applied in order; append only, never reorder
+const MIGRATIONS: [Migration; 2] = [project_layout, session_diffs];
+
+/// The layout version a fresh storage directory is created with
+pub fn latest() -> usize {
+    MIGRATIONS.len()
+}
+
+/// The layout version of `dir`; a missing or unreadable marker is version 0
+pub fn version(dir: &Path) -> usize {
+    std::fs::read_to_string(dir.join(MIGRATION_FILE))
+        .ok()
+        .and_then(|text| text.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Bring `storage` to the latest layout, returning how many migrations ran
+pub fn run(storage: &Storage) -> Result<usize> {
+    let dir = storage.root();
+    if version(dir) >= latest() {
+        return Ok(0);
+    }
+    // Another agent may be migrating: wait for it, then look again
+    let _lock = lock(&dir.join(MIGRATION_FILE), true)?;
+    let start = version(dir);
+    if start >= latest() {
+        return Ok(0);
+    }
+    backup(dir, start)?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(start) {
+        tracing::info!(index, "running storage migration");
+        if let Err(e) = migration(storage) {
+            tracing::error!(index, error = %e, "failed to run storage migration");
+        }
+        write_atomic(&dir.join(MIGRATION_FILE), &(index + 1).to_string())?;
+    }
+    Ok(latest() - start)
+}
+
+/// Where the copy of a storage directory at `version` is kept
+pub fn backup_dir(dir: &Path, version: usize) -> PathBuf {
+    dir.with_file_name(format!("{}-backup-{}", STORAGE_DIR, version))
+}
+
+/// Copy `dir` aside before migrating it; nothing to keep for an empty one
+fn backup(dir: &Path, version: usize) -> Result<()> {
+    let target = backup_dir(dir, version);
+    let has_records = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .any(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name != MIGRATION_FILE && !name.ends_with(".lock")
+        });
+    if !has_records || target.exists() {
+        return Ok(());
+    }
+    // Copy under a temporary name so an interrupted backup is not mistaken
+    // for a complete one
+    let partial = target.with_extension("tmp");
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)?;
+    }
+    copy_dir(dir, &partial)?;
+    std::fs::rename(&partial, &target)?;
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    for entry in walkdir::WalkDir::new(from) {
+        let entry = entry.map_err(std::io::Error::other)?;
+        let Ok(relative) = entry.path().strip_prefix(from) else {
+            continue;
+        };
+        let dest = to.join(relative);
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&dest)?;
+        } else if !entry.file_name().to_string_lossy().ends_with(".lock") {
+            std::fs::copy(entry.path(), &dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Migration 1: move sessions out of the old per-project trees
+/// (`<data>/project/<name>/storage/session/...`) into the shared layout,
+/// keyed by the project's root commit
+fn project_layout(storage: &Storage) -> Result<()> {
+    let Some(data_dir) = storage.root().parent() else {
+        return Ok(());
+    };
+    let projects = data_dir.join("project");
+    if !projects.is_dir() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(&projects)?.filter_map(|entry| entry.ok()) {
+        if entry.file_name() == GLOBAL_PROJECT {
+            continue;
+        }
+        let tree = entry.path().join("storage").join("session");
+        let Some(worktree) = legacy_worktree(&tree) else {
+            continue;
+        };
+        let id = project_id(&worktree);
+        if id == GLOBAL_PROJECT {
+            continue;
+        }
+        tracing::info!(project = %id, tree = %tree.display(), "migrating project sessions");
+        let now = crate::session::now();
+        storage.write(
+            &["project", &id],
+            &json!({
+                "id": id,
+                "vcs": "git",
+                "worktree": worktree,
+                "time": { "created": now, "initialized": now },
+            }),
+        )?;
+        import_tree(storage, &tree, &id, true)?;
+    }
+    Ok(())
+}
+
+/// Migration 2: move file diffs out of session records into `session_diff`,
+/// keeping only the totals in the session summary
+fn session_diffs(storage: &Storage) -> Result<()> {
+    for key in storage.list(&["session"])? {
+        let key = super::as_key(&key);
+        let Ok(mut session) = storage.read::<Value>(&key) else {
+            continue;
+        };
+        let Some(diffs) = session["summary"]["diffs"].as_array().cloned() else {
+            continue;
+        };
+        let Some(id) = session["id"].as_str().map(str::to_string) else {
+            continue;
+        };
+        let total = |field: &str| -> u64 { diffs.iter().filter_map(|d| d[field].as_u64()).sum() };
+        storage.write(&["session_diff", &id], &diffs)?;
+        session["summary"] = json!({
+            "additions": total("additions"),
+            "deletions": total("deletions"),
+        });
+        storage.write(&key, &session)?;
+    }
+    Ok(())
+}
+
+/// Import session trees from the project-local legacy directories of
+/// `directory` and its parents up to the repository root, without
+/// replacing anything already stored. Each legacy storage directory is
+/// backed up before its first import and marked once imported; marked
+/// trees are skipped. Returns the sessions imported.
+pub fn import_legacy(storage: &Storage, directory: &Path) -> Result<usize> {
+    // Outside a repository only the directory itself is looked at
+    let root = directory
+        .ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .unwrap_or(directory);
+    let mut imported = 0;
+    for ancestor in directory.ancestors() {
+        for legacy in LEGACY_DIRS {
+            let dir = ancestor.join(legacy).join(STORAGE_DIR);
+            let tree = dir.join("session");
+            if !tree.join("info").is_dir() || is_imported(&dir, storage) {
+                continue;
+            }
+            // Another agent may be importing: wait for it, then look again
+            let _lock = lock(&dir.join(LEGACY_MARKER), true)?;
+            if is_imported(&dir, storage) {
+                continue;
+            }
+            backup(&dir, version(&dir))?;
+            tracing::info!(tree = %tree.display(), "importing legacy sessions");
+            imported += import_tree(storage, &tree, &project_id(ancestor), false)?;
+            let marker = dir.join(LEGACY_MARKER);
+            let mut imported_into = std::fs::read_to_string(&marker).unwrap_or_default();
+            imported_into.push_str(&format!("{}\n", storage.root().display()));
+            write_atomic(&marker, &imported_into)?;
+        }
+        if ancestor == root {
+            break;
+        }
+    }
+    Ok(imported)
+}
+
+/// Whether the legacy storage directory `dir` was imported into `storage`
+fn is_imported(dir: &Path, storage: &Storage) -> bool {
+    let root = storage.root().display().to_string();
+    std::fs::read_to_string(dir.join(LEGACY_MARKER))
+        .is_ok_and(|marker| marker.lines().any(|line| line == root))
+}
+
+/// Copy a legacy session tree (`info/<session>.json`,
+/// `message/<session>/<message>.json`, `part/<session>/<message>/<part>.json`)
+/// into the current layout. Returns the sessions copied.
+///
+/// Records are written through `storage`, so names taken from the legacy
+/// tree cannot escape the storage directory.
+fn import_tree(storage: &Storage, tree: &Path, project: &str, overwrite: bool) -> Result<usize> {
+    let copy = |key: &[&str], value: &Value| -> Result<bool> {
+        let Ok(path) = storage.path(key) else {
+            tracing::debug!(key = ?key, "skipping legacy record with an invalid name");
+            return Ok(false);
+        };
+        if !overwrite && path.exists() {
+            return Ok(false);
+        }
+        storage.write(key, value)?;
+        Ok(true)
+    };
+    let mut imported = 0;
+    for file in json_files(&tree.join("info"), 1) {
+        let Ok(mut session) = read_json(&file) else {
+            continue;
+        };
+        let Some(session_id) = session["id"].as_str().map(str::to_string) else {
+            continue;
+        };
+        if session.get("projectID").is_none() {
+            session["projectID"] = json!(project);
+        }
+        let key = ["session", project, &session_id];
+        if storage.path(&key).is_err() {
+            tracing::debug!(session = %session_id, "skipping legacy session with an invalid ID");
+            continue;
+        }
+        if copy(&key, &session)? {
+            imported += 1;
+        }
+        for file in json_files(&tree.join("message").join(&session_id), 1) {
+            let Ok(message) = read_json(&file) else {
+                continue;
+            };
+            let Some(message_id) = message["id"].as_str().map(str::to_string) else {
+                continue;
+            };
+            copy(&["message", &session_id, &message_id], &message)?;
+            let parts = tree.join("part").join(&session_id).join(&message_id);
+            for file in json_files(&parts, 1) {
+                let Ok(part) = read_json(&file) else {
+                    continue;
+                };
+                if let Some(part_id) = part["id"].as_str() {
+                    copy(&["part", &message_id, part_id], &part)?;
+                }
+            }
+        }
+    }
+    Ok(imported)
+}
+
+/// The worktree of a legacy tree, from the first message that records one
+fn legacy_worktree(tree: &Path) -> Option<PathBuf> {
+    json_files(&tree.join("message"), 2)
+        .iter()
+        .filter_map(|file| read_json(file).ok())
+        .find_map(|message| message["path"]["root"].as_str().map(PathBuf::from))
+        .filter(|worktree| worktree.is_dir())
+}
+
+/// `.json` files exactly `depth` levels below `dir`, sorted
+fn json_files(dir: &Path, depth: usize) -> Vec<PathBuf> {
+    if !dir.is_dir() {
+        return Vec::new();
+    }
+    let mut files: Vec<PathBuf> = walkdir::WalkDir::new(dir)
+        .min_depth(depth)
+        .max_depth(depth)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn read_json(path: &Path) -> Result<Value> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
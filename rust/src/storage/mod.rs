@@ -0,0 +1,461 @@
+//! Persistent JSON storage for sessions, messages and parts
+//!
+//! Mirrors the JavaScript implementation's storage/storage.ts: every record
+//! is a JSON file addressed by a key such as `["session", projectID, id]`,
+//! stored as `<data dir>/storage/session/<projectID>/<id>.json`. Sessions are
+//! grouped per project, messages per session and parts per message, so the
+//! Rust and JavaScript agents read each other's sessions.
+//!
+//! Writes go to a temporary file that is renamed over the target, so readers
+//! never see a half-written record. Each record has a sidecar `.lock` file:
+//! reads take a shared lock and writes an exclusive one, which keeps
+//! concurrent agents (threads or processes) from interleaving updates. The
+//! session record's lock also guards its messages and parts while a whole
+//! session is saved, loaded or removed. A save only writes the messages and
+//! parts that changed since the session was loaded or last saved, and only
+//! removes the ones this process saw and has since dropped, so two agents
+//! appending to one session (`--no-fork`) keep each other's messages.
+//!
+//! The layout is versioned; see `migration` for how older layouts, and
+//! session trees the JavaScript agent left in project directories, are
+//! brought up to date on startup.
+
+pub mod migration;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::{AgentError, Result};
+use crate::session::{MessageInfo, MessageWithParts, Part, Session, SessionInfo};
+use crate::util::Filesystem;
+
+/// Directory under the data directory holding all records
+pub const STORAGE_DIR: &str = "storage";
+
+/// Project ID for directories outside a git repository
+pub const GLOBAL_PROJECT: &str = "global";
+
+/// Distinguishes temporary files written by threads of one process
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Digests of a session's messages and parts as last loaded or saved by
+/// this process
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Stored {
+    messages: HashMap<String, StoredMessage>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct StoredMessage {
+    info: u64,
+    parts: HashMap<String, u64>,
+}
+
+/// JSON records stored under one root directory
+#[derive(Debug, Clone)]
+pub struct Storage {
+    root: PathBuf,
+}
+
+impl Storage {
+    /// Storage rooted at `root` itself
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Storage in the `storage` directory of `data_dir`
+    pub fn from_data_dir(data_dir: &Path) -> Self {
+        Self::new(data_dir.join(STORAGE_DIR))
+    }
+
+    /// Storage in the global data directory
+    pub fn open() -> Self {
+        Self::from_data_dir(&crate::global::data_dir())
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The file for `key`, refusing keys that would leave the storage root
+    pub fn path(&self, key: &[&str]) -> Result<PathBuf> {
+        let (name, dirs) = key.split_last().ok_or_else(|| invalid_key(key))?;
+        let mut path = self.root.clone();
+        for segment in key {
+            if !is_safe_segment(segment) {
+                return Err(invalid_key(key));
+            }
+        }
+        path.extend(dirs);
+        path.push(format!("{}.json", name));
+        if !Filesystem::contains(&self.root, &path) {
+            return Err(invalid_key(key));
+        }
+        Ok(path)
+    }
+
+    /// Read the record at `key`
+    pub fn read<T: DeserializeOwned>(&self, key: &[&str]) -> Result<T> {
+        let target = self.path(key)?;
+        // Locking would create a lock file for a record that does not exist
+        if !target.exists() {
+            return Err(not_found(&target));
+        }
+        let _lock = lock(&target, false)?;
+        let content = std::fs::read_to_string(&target).map_err(|e| io_error(e, &target))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Replace the record at `key`
+    pub fn write<T: Serialize>(&self, key: &[&str], content: &T) -> Result<()> {
+        self.write_json(key, &serde_json::to_string_pretty(content)?)
+    }
+
+    fn write_json(&self, key: &[&str], json: &str) -> Result<()> {
+        let target = self.path(key)?;
+        let _lock = lock(&target, true)?;
+        write_atomic(&target, json)
+    }
+
+    /// Change the record at `key` in place, holding the write lock throughout
+    pub fn update<T: Serialize + DeserializeOwned>(
+        &self,
+        key: &[&str],
+        edit: impl FnOnce(&mut T),
+    ) -> Result<T> {
+        let target = self.path(key)?;
+        if !target.exists() {
+            return Err(not_found(&target));
+        }
+        let _lock = lock(&target, true)?;
+        let content = std::fs::read_to_string(&target).map_err(|e| io_error(e, &target))?;
+        let mut value: T = serde_json::from_str(&content)?;
+        edit(&mut value);
+        write_atomic(&target, &serde_json::to_string_pretty(&value)?)?;
+        Ok(value)
+    }
+
+    /// Delete the record at `key`; missing records are ignored
+    pub fn remove(&self, key: &[&str]) -> Result<()> {
+        let target = self.path(key)?;
+        if !target.exists() {
+            return Ok(());
+        }
+        let _lock = lock(&target, true)?;
+        remove_record(&target)
+    }
+
+    /// Keys of all records under `prefix`, sorted
+    pub fn list(&self, prefix: &[&str]) -> Result<Vec<Vec<String>>> {
+        for segment in prefix {
+            if !is_safe_segment(segment) {
+                return Err(invalid_key(prefix));
+            }
+        }
+        let dir = prefix.iter().fold(self.root.clone(), |dir, s| dir.join(s));
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut keys: Vec<Vec<String>> = walkdir::WalkDir::new(&dir)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| {
+                let relative = entry.path().strip_prefix(&dir).ok()?.to_str()?;
+                let relative = relative.strip_suffix(".json")?;
+                let mut key: Vec<String> = prefix.iter().map(|s| s.to_string()).collect();
+                key.extend(
+                    Path::new(relative)
+                        .iter()
+                        .map(|s| s.to_string_lossy().to_string()),
+                );
+                Some(key)
+            })
+            .collect();
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Persist a session with all its messages and parts.
+    ///
+    /// Only messages and parts that changed since `session` was loaded or
+    /// last saved are written. Those this process saw and has since dropped
+    /// (reverted, or dropped by a failed compaction) are removed; records
+    /// another agent added meanwhile are left alone.
+    pub fn write_session(&self, session: &mut Session) -> Result<()> {
+        let session_id = session.info.id.clone();
+        let target = self.path(&["session", &session.info.project_id, &session_id])?;
+        let _lock = lock(&target, true)?;
+        // Another agent may have saved the session since it was loaded
+        let present: HashSet<String> = self
+            .list(&["message", &session_id])?
+            .into_iter()
+            .filter_map(|key| key.last().cloned())
+            .collect();
+        let mut previous = std::mem::take(&mut session.stored.messages);
+        for message in &session.messages {
+            let message_id = message.info.id();
+            let seen = previous
+                .remove(message_id)
+                .filter(|_| present.contains(message_id));
+            let info = serde_json::to_string_pretty(&message.info)?;
+            let mut stored = StoredMessage {
+                info: digest(&info),
+                parts: HashMap::new(),
+            };
+            if seen.as_ref().map(|seen| seen.info) != Some(stored.info) {
+                self.write_json(&["message", &session_id, message_id], &info)?;
+            }
+            for part in &message.parts {
+                let json = serde_json::to_string_pretty(part)?;
+                let hash = digest(&json);
+                if seen.as_ref().and_then(|seen| seen.parts.get(part.id())) != Some(&hash) {
+                    self.write_json(&["part", message_id, part.id()], &json)?;
+                }
+                stored.parts.insert(part.id().to_string(), hash);
+            }
+            for part_id in seen.iter().flat_map(|seen| seen.parts.keys()) {
+                if !stored.parts.contains_key(part_id) {
+                    self.remove(&["part", message_id, part_id])?;
+                }
+            }
+            session
+                .stored
+                .messages
+                .insert(message_id.to_string(), stored);
+        }
+        for message_id in previous.keys() {
+            self.remove_parts(message_id)?;
+            self.remove(&["message", &session_id, message_id])?;
+        }
+        write_atomic(&target, &serde_json::to_string_pretty(&session.info)?)
+    }
+
+    /// Load a session with its messages (in ID order) and their parts.
+    ///
+    /// Part types only the JavaScript agent knows (files, patches, ...) are
+    /// skipped, so sessions it wrote can still be resumed.
+    pub fn read_session(&self, project_id: &str, session_id: &str) -> Result<Session> {
+        let target = self.path(&["session", project_id, session_id])?;
+        if !target.exists() {
+            return Err(not_found(&target));
+        }
+        let _lock = lock(&target, false)?;
+        let content = std::fs::read_to_string(&target).map_err(|e| io_error(e, &target))?;
+        let info: SessionInfo = serde_json::from_str(&content)?;
+        let mut messages = Vec::new();
+        let mut stored = Stored::default();
+        for key in self.list(&["message", session_id])? {
+            let info: MessageInfo = self.read(&as_key(&key))?;
+            let mut seen = StoredMessage {
+                info: digest(&serde_json::to_string_pretty(&info)?),
+                parts: HashMap::new(),
+            };
+            let mut parts = Vec::new();
+            for key in self.list(&["part", info.id()])? {
+                match self.read::<Part>(&as_key(&key)) {
+                    Ok(part) => {
+                        let hash = digest(&serde_json::to_string_pretty(&part)?);
+                        seen.parts.insert(part.id().to_string(), hash);
+                        parts.push(part);
+                    }
+                    Err(AgentError::Json(e)) => {
+                        tracing::debug!(key = ?key, error = %e, "skipping unsupported part")
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            stored.messages.insert(info.id().to_string(), seen);
+            messages.push(MessageWithParts { info, parts });
+        }
+        Ok(Session {
+            info,
+            messages,
+            stored,
+        })
+    }
+
+    /// Metadata of every session stored for `project_id`, in ID order
+    pub fn sessions(&self, project_id: &str) -> Result<Vec<SessionInfo>> {
+        self.list(&["session", project_id])?
+            .iter()
+            .map(|key| self.read(&as_key(key)))
+            .collect()
+    }
+
+    /// Delete a session with all its messages and parts
+    pub fn remove_session(&self, project_id: &str, session_id: &str) -> Result<()> {
+        let target = self.path(&["session", project_id, session_id])?;
+        if !target.exists() {
+            return Ok(());
+        }
+        let _lock = lock(&target, true)?;
+        for key in self.list(&["message", session_id])? {
+            if let Some(message_id) = key.last() {
+                self.remove_parts(message_id)?;
+            }
+            self.remove(&as_key(&key))?;
+        }
+        remove_record(&target)
+    }
+
+    fn remove_parts(&self, message_id: &str) -> Result<()> {
+        for key in self.list(&["part", message_id])? {
+            self.remove(&as_key(&key))?;
+        }
+        Ok(())
+    }
+}
+
+/// Migrate the global storage to the latest layout and import legacy
+/// session trees around `directory`. Failures are logged and otherwise
+/// ignored: the run can go on, at worst without older sessions.
+pub fn init(directory: &Path) {
+    let storage = Storage::open();
+    if let Err(e) = migration::run(&storage) {
+        tracing::warn!(error = %e, "failed to migrate session storage");
+    }
+    match migration::import_legacy(&storage, directory) {
+        Ok(0) => {}
+        Ok(count) => tracing::info!(count, "imported legacy sessions"),
+        Err(e) => tracing::warn!(error = %e, "failed to import legacy sessions"),
+    }
+}
+
+/// The project a directory belongs to, like the JavaScript
+/// `Project.fromDirectory`: the oldest root commit of its git repository, or
+/// `global` outside a repository.
+pub fn project_id(directory: &Path) -> String {
+    Command::new("git")
+        .args(["rev-list", "--max-parents=0", "--all"])
+        .current_dir(directory)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let mut roots: Vec<&str> = stdout
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .collect();
+            roots.sort();
+            roots.first().map(|root| root.to_string())
+        })
+        .unwrap_or_else(|| GLOBAL_PROJECT.to_string())
+}
+
+/// Key segments are plain file names: no separators, no `.`/`..`, no NUL
+fn is_safe_segment(segment: &str) -> bool {
+    !segment.is_empty() && segment != "." && segment != ".." && !segment.contains(['/', '\\', '\0'])
+}
+
+fn as_key(key: &[String]) -> Vec<&str> {
+    key.iter().map(String::as_str).collect()
+}
+
+fn invalid_key<S: AsRef<str>>(key: &[S]) -> AgentError {
+    let key: Vec<&str> = key.iter().map(AsRef::as_ref).collect();
+    AgentError::Session {
+        session_id: None,
+        message: format!("Invalid storage key: {:?}", key),
+    }
+}
+
+fn not_found(path: &Path) -> AgentError {
+    AgentError::NotFound {
+        message: format!("Resource not found: {}", path.display()),
+    }
+}
+
+fn io_error(error: std::io::Error, path: &Path) -> AgentError {
+    if error.kind() == ErrorKind::NotFound {
+        not_found(path)
+    } else {
+        error.into()
+    }
+}
+
+fn digest(json: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    json.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Lock the sidecar lock file of `target`, released when the file is dropped
+fn lock(target: &Path, exclusive: bool) -> Result<File> {
+    let path = lock_path(target);
+    loop {
+        if let Some(dir) = target.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        if exclusive {
+            file.lock()?;
+        } else {
+            file.lock_shared()?;
+        }
+        // A lock file deleted by `remove_record` while we waited on it no
+        // longer guards the record; lock the current one instead
+        if is_current(&file, &path) {
+            return Ok(file);
+        }
+    }
+}
+
+fn lock_path(target: &Path) -> PathBuf {
+    target.with_extension("json.lock")
+}
+
+/// Whether `file` is still the one at `path`
+#[cfg(unix)]
+fn is_current(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata(), std::fs::metadata(path)) {
+        (Ok(open), Ok(current)) => open.dev() == current.dev() && open.ino() == current.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_current(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+/// Delete `target` and its lock file; the caller holds the exclusive lock
+fn remove_record(target: &Path) -> Result<()> {
+    for path in [target.to_path_buf(), lock_path(target)] {
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Write to a temporary file next to `target`, then rename it into place
+fn write_atomic(target: &Path, content: &str) -> Result<()> {
+    let tmp = target.with_extension(format!(
+        "json.{}.{}.tmp",
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    if let Err(e) = std::fs::write(&tmp, content).and_then(|_| std::fs::rename(&tmp, target)) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    Ok(())
+}
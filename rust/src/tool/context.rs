@@ -5,6 +5,8 @@
 
 use std::path::{Path, PathBuf};
 
+use crate::storage::Storage;
+
 /// Context passed to tool executions
 #[derive(Debug, Clone)]
 pub struct ToolContext {
@@ -22,6 +24,8 @@ pub struct ToolContext {
     pub provider_id: Option<String>,
     /// Model ID being used
     pub model_id: Option<String>,
+    /// Where tools keep per-session state such as todos
+    pub storage: Storage,
 }
 
 impl ToolContext {
@@ -39,6 +43,7 @@ impl ToolContext {
             call_id: None,
             provider_id: None,
             model_id: None,
+            storage: Storage::open(),
         }
     }
 
@@ -59,6 +64,12 @@ impl ToolContext {
         self
     }
 
+    /// Keep per-session state in `storage` instead of the data directory
+    pub fn with_storage(mut self, storage: Storage) -> Self {
+        self.storage = storage;
+        self
+    }
+
     /// Resolve a path relative to the working directory
     pub fn resolve_path(&self, path: &str) -> PathBuf {
         let path = PathBuf::from(path);
@@ -138,17 +138,7 @@ impl Tool for EditTool {
         // Calculate diff
         let diff = create_diff(&content_old, &content_new, &filepath.to_string_lossy());
 
-        // Count additions and deletions
-        let text_diff = TextDiff::from_lines(&content_old, &content_new);
-        let mut additions = 0;
-        let mut deletions = 0;
-        for change in text_diff.iter_all_changes() {
-            match change.tag() {
-                ChangeTag::Insert => additions += 1,
-                ChangeTag::Delete => deletions += 1,
-                ChangeTag::Equal => {}
-            }
-        }
+        let (additions, deletions) = count_changes(&content_old, &content_new);
 
         Ok(ToolResult {
             title,
@@ -175,7 +165,7 @@ fn normalize_line_endings(text: &str) -> String {
 }
 
 /// Create a unified diff string
-fn create_diff(old: &str, new: &str, path: &str) -> String {
+pub(crate) fn create_diff(old: &str, new: &str, path: &str) -> String {
     let diff = TextDiff::from_lines(old, new);
 
     let mut result = format!("--- {}\n+++ {}\n", path, path);
@@ -204,6 +194,20 @@ fn create_diff(old: &str, new: &str, path: &str) -> String {
     result
 }
 
+/// Count added and deleted lines between two versions of a file
+pub(crate) fn count_changes(old: &str, new: &str) -> (usize, usize) {
+    let mut additions = 0;
+    let mut deletions = 0;
+    for change in TextDiff::from_lines(old, new).iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => additions += 1,
+            ChangeTag::Delete => deletions += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    (additions, deletions)
+}
+
 /// Perform string replacement with fallback strategies
 /// Mirrors the JavaScript implementation's replace() function with all 9 strategies.
 fn replace(content: &str, old_string: &str, new_string: &str, replace_all: bool) -> Result<String> {
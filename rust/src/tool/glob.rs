@@ -104,7 +104,7 @@ impl Tool for GlobTool {
         }
 
         // Sort by modification time (most recent first)
-        matches.sort_by(|a, b| b.1.cmp(&a.1));
+        matches.sort_by_key(|b| std::cmp::Reverse(b.1));
 
         // Format output
         let output: Vec<String> = matches
@@ -13,6 +13,7 @@ pub mod grep;
 pub mod invalid;
 pub mod list;
 pub mod multiedit;
+pub mod patch;
 pub mod read;
 pub mod todo;
 pub mod webfetch;
@@ -44,7 +45,7 @@ pub struct ToolResult {
 }
 
 /// File attachment for tool results (e.g., images)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FileAttachment {
     pub id: String,
     #[serde(rename = "sessionID")]
@@ -99,6 +100,7 @@ impl ToolRegistry {
                 Box::new(todo::TodoWriteTool),
                 Box::new(todo::TodoReadTool),
                 Box::new(multiedit::MultiEditTool),
+                Box::new(patch::PatchTool),
             ],
         }
     }
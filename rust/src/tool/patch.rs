@@ -0,0 +1,567 @@
+//! Patch tool implementation
+//!
+//! Applies patches in the Codex `apply_patch` format that GPT-family models
+//! are trained on, matching the JavaScript implementation's patch tool and
+//! `js/src/patch` parser. Every hunk is checked against the current file
+//! contents before anything is written, and the files are then written
+//! together: if one of them cannot be written, the others are put back.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use super::{
+    context::ToolContext,
+    edit::{count_changes, create_diff},
+    Tool, ToolResult,
+};
+use crate::error::{AgentError, Result};
+
+/// Tool description
+const DESCRIPTION: &str = r#"Apply a patch that adds, updates, moves or deletes files.
+
+The patch is wrapped in "*** Begin Patch" and "*** End Patch" and holds one
+section per file:
+- "*** Add File: <path>" followed by the new contents, every line prefixed with "+"
+- "*** Delete File: <path>"
+- "*** Update File: <path>", optionally followed by "*** Move to: <new path>", then
+  one or more chunks. A chunk starts with "@@", optionally followed by a line that
+  occurs before the change (a function or class header), and lists the change with
+  " " for unchanged lines, "-" for removed lines and "+" for added lines. Show about
+  three unchanged lines around each change. "*** End of File" marks a chunk that
+  ends at the end of the file.
+
+Example:
+*** Begin Patch
+*** Update File: src/app.py
+@@ def greet():
+-    print("Hi")
++    print("Hello")
+*** Add File: NOTES.md
++Greeting changed.
+*** End Patch
+
+Paths are relative to the working directory. Every chunk must match the current
+file contents, otherwise no file is changed."#;
+
+const BEGIN_PATCH: &str = "*** Begin Patch";
+const END_PATCH: &str = "*** End Patch";
+const ADD_FILE: &str = "*** Add File: ";
+const DELETE_FILE: &str = "*** Delete File: ";
+const UPDATE_FILE: &str = "*** Update File: ";
+const MOVE_TO: &str = "*** Move to: ";
+const END_OF_FILE: &str = "*** End of File";
+
+/// Parameters for the patch tool
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchParams {
+    /// The full patch text
+    pub patch_text: String,
+}
+
+/// One file section of a patch
+#[derive(Debug, Clone, PartialEq)]
+pub enum Hunk {
+    Add {
+        path: String,
+        contents: String,
+    },
+    Delete {
+        path: String,
+    },
+    Update {
+        path: String,
+        move_path: Option<String>,
+        chunks: Vec<UpdateChunk>,
+    },
+}
+
+/// One `@@` chunk of an update
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UpdateChunk {
+    /// Line the chunk starts after, from the `@@` header
+    pub change_context: Option<String>,
+    pub old_lines: Vec<String>,
+    pub new_lines: Vec<String>,
+    /// The chunk ends at the end of the file
+    pub is_end_of_file: bool,
+}
+
+/// Patch tool implementation
+pub struct PatchTool;
+
+#[async_trait]
+impl Tool for PatchTool {
+    fn id(&self) -> &'static str {
+        "patch"
+    }
+
+    fn description(&self) -> &'static str {
+        DESCRIPTION
+    }
+
+    fn parameters_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "patchText": {
+                    "type": "string",
+                    "description": "The full patch text that describes all changes to be made"
+                }
+            },
+            "required": ["patchText"]
+        })
+    }
+
+    async fn execute(&self, params: Value, ctx: &ToolContext) -> Result<ToolResult> {
+        let params: PatchParams = serde_json::from_value(params)
+            .map_err(|e| AgentError::invalid_arguments("patch", e.to_string()))?;
+        let hunks = parse_patch(&params.patch_text)?;
+
+        let changes = plan(&hunks, ctx).await?;
+        commit(&changes).await?;
+
+        let mut results = Vec::new();
+        let mut total_diff = String::new();
+        let mut files = Vec::new();
+        for change in &changes {
+            let file = change.path.to_string_lossy();
+            let before = change.before.as_deref().unwrap_or_default();
+            let after = change.after.as_deref().unwrap_or_default();
+            let diff = create_diff(before, after, &file);
+            let (additions, deletions) = count_changes(before, after);
+            total_diff.push_str(&diff);
+            total_diff.push('\n');
+            let kind = match (&change.before, &change.after) {
+                (None, _) => "A",
+                (_, None) => "D",
+                _ => "M",
+            };
+            files.push(format!("  {} {}", kind, ctx.relative_path(&change.path)));
+            results.push(json!({
+                "diff": diff,
+                "filediff": {
+                    "file": file,
+                    "before": before,
+                    "after": after,
+                    "additions": additions,
+                    "deletions": deletions,
+                }
+            }));
+        }
+
+        let title = format!("{} files changed", changes.len());
+        Ok(ToolResult {
+            output: format!(
+                "Patch applied successfully. {}:\n{}",
+                title,
+                files.join("\n")
+            ),
+            title,
+            metadata: json!({
+                "diff": total_diff,
+                "results": results,
+            }),
+            attachments: None,
+        })
+    }
+}
+
+/// Parse a patch into its file sections
+pub fn parse_patch(text: &str) -> Result<Vec<Hunk>> {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    let begin = lines.iter().position(|line| line.trim() == BEGIN_PATCH);
+    let end = lines.iter().rposition(|line| line.trim() == END_PATCH);
+    let (begin, end) = match (begin, end) {
+        (Some(begin), Some(end)) if begin < end => (begin, end),
+        _ => {
+            return Err(invalid_patch(format!(
+                "the patch must start with '{}' and end with '{}'",
+                BEGIN_PATCH, END_PATCH
+            )))
+        }
+    };
+
+    let mut hunks = Vec::new();
+    let mut index = begin + 1;
+    while index < end {
+        let line = lines[index];
+        let number = index + 1;
+        index += 1;
+        if let Some(path) = line.strip_prefix(ADD_FILE) {
+            let mut contents = String::new();
+            while index < end && !lines[index].starts_with("***") {
+                // Blank lines before the next section are not contents
+                let rest = lines[index..end].iter();
+                if rest
+                    .take_while(|line| !line.starts_with("***"))
+                    .all(|line| line.trim().is_empty())
+                {
+                    break;
+                }
+                let Some(added) = lines[index].strip_prefix('+') else {
+                    return Err(invalid_patch(format!(
+                        "line {}: lines of an added file must start with '+'",
+                        index + 1
+                    )));
+                };
+                contents.push_str(added);
+                contents.push('\n');
+                index += 1;
+            }
+            hunks.push(Hunk::Add {
+                path: header_path(path, number)?,
+                contents,
+            });
+        } else if let Some(path) = line.strip_prefix(DELETE_FILE) {
+            hunks.push(Hunk::Delete {
+                path: header_path(path, number)?,
+            });
+        } else if let Some(path) = line.strip_prefix(UPDATE_FILE) {
+            let path = header_path(path, number)?;
+            let mut move_path = None;
+            if let Some(to) = lines.get(index).and_then(|line| line.strip_prefix(MOVE_TO)) {
+                move_path = Some(header_path(to, index + 1)?);
+                index += 1;
+            }
+            let (chunks, next) = parse_chunks(&lines[..end], index)?;
+            if chunks.is_empty() {
+                return Err(invalid_patch(format!(
+                    "line {}: update of '{}' has no changes",
+                    number, path
+                )));
+            }
+            index = next;
+            hunks.push(Hunk::Update {
+                path,
+                move_path,
+                chunks,
+            });
+        } else if !line.trim().is_empty() {
+            return Err(invalid_patch(format!(
+                "line {}: expected a file header ('*** Add File: ', '*** Delete File: ' or '*** Update File: '), found '{}'",
+                number, line
+            )));
+        }
+    }
+    if hunks.is_empty() {
+        return Err(invalid_patch("no file changes found in patch"));
+    }
+    Ok(hunks)
+}
+
+/// The chunks of an update starting at `index`, and the index after them
+fn parse_chunks(lines: &[&str], mut index: usize) -> Result<(Vec<UpdateChunk>, usize)> {
+    let mut chunks: Vec<UpdateChunk> = Vec::new();
+    while index < lines.len() {
+        let line = lines[index];
+        if line == END_OF_FILE {
+            match chunks.last_mut() {
+                Some(chunk) => chunk.is_end_of_file = true,
+                None => {
+                    return Err(invalid_patch(format!(
+                        "line {}: '{}' before any change",
+                        index + 1,
+                        END_OF_FILE
+                    )))
+                }
+            }
+        } else if line.starts_with("***") {
+            break;
+        } else if let Some(context) = line.strip_prefix("@@") {
+            let context = context.trim();
+            chunks.push(UpdateChunk {
+                change_context: (!context.is_empty()).then(|| context.to_string()),
+                ..Default::default()
+            });
+        } else {
+            // The first chunk may leave out its `@@` line
+            if chunks.is_empty() || chunks.last().is_some_and(|chunk| chunk.is_end_of_file) {
+                chunks.push(UpdateChunk::default());
+            }
+            let chunk = chunks.last_mut().expect("a chunk was pushed");
+            match line.chars().next() {
+                // Editors and models drop the space of empty context lines
+                None => {
+                    chunk.old_lines.push(String::new());
+                    chunk.new_lines.push(String::new());
+                }
+                Some(' ') => {
+                    chunk.old_lines.push(line[1..].to_string());
+                    chunk.new_lines.push(line[1..].to_string());
+                }
+                Some('-') => chunk.old_lines.push(line[1..].to_string()),
+                Some('+') => chunk.new_lines.push(line[1..].to_string()),
+                Some(_) => {
+                    return Err(invalid_patch(format!(
+                        "line {}: changed lines must start with ' ', '-' or '+', found '{}'",
+                        index + 1,
+                        line
+                    )))
+                }
+            }
+        }
+        index += 1;
+    }
+    Ok((chunks, index))
+}
+
+fn header_path(path: &str, number: usize) -> Result<String> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err(invalid_patch(format!("line {}: missing file path", number)));
+    }
+    Ok(path.to_string())
+}
+
+fn invalid_patch(message: impl Into<String>) -> AgentError {
+    AgentError::invalid_arguments("patch", format!("Invalid patch: {}", message.into()))
+}
+
+/// A file as it was before the patch and as it will be after it; `None`
+/// means the file does not exist
+#[derive(Debug)]
+struct FileChange {
+    path: PathBuf,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+/// Check every hunk against the current contents and work out the final
+/// state of each file, in the order the patch first touches them. Later
+/// hunks see the changes of earlier ones.
+async fn plan(hunks: &[Hunk], ctx: &ToolContext) -> Result<Vec<FileChange>> {
+    let mut changes: Vec<FileChange> = Vec::new();
+    for hunk in hunks {
+        match hunk {
+            Hunk::Add { path, contents } => {
+                let path = ctx.resolve_path(path);
+                planned(&mut changes, &path).await?.after = Some(contents.clone());
+            }
+            Hunk::Delete { path } => {
+                let path = ctx.resolve_path(path);
+                let change = planned(&mut changes, &path).await?;
+                if change.after.is_none() {
+                    return Err(AgentError::file_not_found(path.to_string_lossy(), vec![]));
+                }
+                change.after = None;
+            }
+            Hunk::Update {
+                path,
+                move_path,
+                chunks,
+            } => {
+                let path = ctx.resolve_path(path);
+                let source = planned(&mut changes, &path).await?;
+                let Some(current) = &source.after else {
+                    return Err(AgentError::file_not_found(path.to_string_lossy(), vec![]));
+                };
+                let updated = apply_chunks(current, chunks, &ctx.relative_path(&path))?;
+                match move_path {
+                    Some(move_path) => {
+                        source.after = None;
+                        let destination = ctx.resolve_path(move_path);
+                        planned(&mut changes, &destination).await?.after = Some(updated);
+                    }
+                    None => source.after = Some(updated),
+                }
+            }
+        }
+    }
+    changes.retain(|change| change.before != change.after);
+    Ok(changes)
+}
+
+/// The planned change of `path`, starting from its contents on disk
+async fn planned<'a>(changes: &'a mut Vec<FileChange>, path: &Path) -> Result<&'a mut FileChange> {
+    let index = match changes.iter().position(|change| change.path == path) {
+        Some(index) => index,
+        None => {
+            let before = if fs::metadata(path).await.is_ok_and(|meta| meta.is_file()) {
+                Some(fs::read_to_string(path).await?)
+            } else {
+                None
+            };
+            changes.push(FileChange {
+                path: path.to_path_buf(),
+                after: before.clone(),
+                before,
+            });
+            changes.len() - 1
+        }
+    };
+    Ok(&mut changes[index])
+}
+
+/// Apply update chunks to `content`. Every chunk must be found after the
+/// previous one; the result ends with a newline.
+pub fn apply_chunks(content: &str, chunks: &[UpdateChunk], file: &str) -> Result<String> {
+    let mut lines: Vec<&str> = content.split('\n').collect();
+    if lines.last() == Some(&"") {
+        lines.pop();
+    }
+
+    let mut replacements: Vec<(usize, usize, &[String])> = Vec::new();
+    let mut line_index = 0;
+    for chunk in chunks {
+        if let Some(context) = &chunk.change_context {
+            let found = seek_sequence(&lines, std::slice::from_ref(context), line_index, false)
+                .ok_or_else(|| {
+                    invalid_patch(format!("failed to find context '{}' in {}", context, file))
+                })?;
+            line_index = found + 1;
+        }
+
+        // Pure additions go after the context line, or at the end of the file
+        if chunk.old_lines.is_empty() {
+            let at = if chunk.change_context.is_some() && !chunk.is_end_of_file {
+                line_index
+            } else {
+                lines.len()
+            };
+            replacements.push((at, 0, &chunk.new_lines));
+            continue;
+        }
+
+        let mut old: &[String] = &chunk.old_lines;
+        let mut new: &[String] = &chunk.new_lines;
+        let mut found = seek_sequence(&lines, old, line_index, chunk.is_end_of_file);
+        // A trailing empty line in the chunk often stands for the final newline
+        if found.is_none() && old.last().is_some_and(|line| line.is_empty()) {
+            old = &old[..old.len() - 1];
+            if new.last().is_some_and(|line| line.is_empty()) {
+                new = &new[..new.len() - 1];
+            }
+            found = seek_sequence(&lines, old, line_index, chunk.is_end_of_file);
+        }
+        let Some(found) = found else {
+            return Err(invalid_patch(format!(
+                "failed to find expected lines in {}:\n{}",
+                file,
+                chunk.old_lines.join("\n")
+            )));
+        };
+        replacements.push((found, old.len(), new));
+        line_index = found + old.len();
+    }
+
+    replacements.sort_by_key(|(start, _, _)| *start);
+    let mut result: Vec<&str> = Vec::with_capacity(lines.len());
+    let mut next = 0;
+    for (start, removed, added) in replacements {
+        result.extend_from_slice(&lines[next..start]);
+        result.extend(added.iter().map(String::as_str));
+        next = start + removed;
+    }
+    result.extend_from_slice(&lines[next..]);
+
+    let mut updated = result.join("\n");
+    updated.push('\n');
+    Ok(updated)
+}
+
+/// Index of the first occurrence of `pattern` in `lines` at or after
+/// `start`. Lines are compared exactly, then ignoring trailing whitespace,
+/// then ignoring surrounding whitespace. With `end_of_file` a match at the
+/// end of the file is tried first.
+fn seek_sequence(
+    lines: &[&str],
+    pattern: &[String],
+    start: usize,
+    end_of_file: bool,
+) -> Option<usize> {
+    if pattern.is_empty() || pattern.len() > lines.len() {
+        return None;
+    }
+    let last = lines.len() - pattern.len();
+    let matches_at = |at: usize, same: fn(&str, &str) -> bool| {
+        pattern
+            .iter()
+            .zip(&lines[at..])
+            .all(|(expected, line)| same(expected, line))
+    };
+    let comparisons: [fn(&str, &str) -> bool; 3] = [
+        |a, b| a == b,
+        |a, b| a.trim_end() == b.trim_end(),
+        |a, b| a.trim() == b.trim(),
+    ];
+    for same in comparisons {
+        if end_of_file && last >= start && matches_at(last, same) {
+            return Some(last);
+        }
+        if let Some(found) = (start..=last).find(|&at| matches_at(at, same)) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+/// Write the planned changes. New contents are first written next to their
+/// files, then moved into place; if that fails part way, the files already
+/// changed are put back.
+async fn commit(changes: &[FileChange]) -> Result<()> {
+    let staged: Vec<Option<PathBuf>> = changes
+        .iter()
+        .map(|change| change.after.as_ref().map(|_| staging_path(&change.path)))
+        .collect();
+
+    let mut written = Vec::new();
+    for (change, tmp) in changes.iter().zip(&staged) {
+        let (Some(after), Some(tmp)) = (&change.after, tmp) else {
+            continue;
+        };
+        let result = async {
+            if let Some(parent) = change.path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(tmp, after).await
+        }
+        .await;
+        if let Err(e) = result {
+            remove_all(&written).await;
+            let _ = fs::remove_file(tmp).await;
+            return Err(e.into());
+        }
+        written.push(tmp.clone());
+    }
+
+    for (applied, (change, tmp)) in changes.iter().zip(&staged).enumerate() {
+        let result = match tmp {
+            Some(tmp) => fs::rename(tmp, &change.path).await,
+            None => fs::remove_file(&change.path).await,
+        };
+        if let Err(e) = result {
+            tracing::warn!(file = %change.path.display(), error = %e, "patch failed, restoring files");
+            for change in &changes[..applied] {
+                let restored = match &change.before {
+                    Some(before) => fs::write(&change.path, before).await,
+                    None => fs::remove_file(&change.path).await,
+                };
+                if let Err(e) = restored {
+                    tracing::warn!(file = %change.path.display(), error = %e, "failed to restore file");
+                }
+            }
+            remove_all(&written).await;
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+/// Temporary file next to `path` for its new contents
+fn staging_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}.patch.tmp", name, std::process::id()))
+}
+
+async fn remove_all(paths: &[PathBuf]) {
+    for path in paths {
+        let _ = fs::remove_file(path).await;
+    }
+}
@@ -1,19 +1,17 @@
 //! Todo tool implementation
 //!
 //! Provides TodoWrite and TodoRead tools for managing structured task lists,
-//! matching the JavaScript implementation's todo tool behavior.
-//!
-//! Note: In the Rust implementation, todos are stored in-memory within the session
-//! context since we don't have a full session storage system like the JS version.
+//! matching the JavaScript implementation's todo tool behavior. Todos are
+//! persisted under `["todo", sessionID]` in storage, as in
+//! `js/src/session/todo.ts`, so a resumed session keeps its list.
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::collections::HashMap;
-use std::sync::{Mutex, OnceLock};
 
 use super::{context::ToolContext, Tool, ToolResult};
 use crate::error::{AgentError, Result};
+use crate::storage::Storage;
 
 /// Todo item status
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -38,23 +36,15 @@ pub struct TodoItem {
     pub active_form: String,
 }
 
-/// Global in-memory todo storage (session_id -> todos)
-static TODO_STORE: OnceLock<Mutex<HashMap<String, Vec<TodoItem>>>> = OnceLock::new();
-
-fn get_store() -> &'static Mutex<HashMap<String, Vec<TodoItem>>> {
-    TODO_STORE.get_or_init(|| Mutex::new(HashMap::new()))
-}
-
-/// Get todos for a session
-pub fn get_todos(session_id: &str) -> Vec<TodoItem> {
-    let store = get_store().lock().unwrap();
-    store.get(session_id).cloned().unwrap_or_default()
+/// Get todos for a session; a missing or unreadable list is empty, as in
+/// the JavaScript implementation
+pub fn get_todos(storage: &Storage, session_id: &str) -> Vec<TodoItem> {
+    storage.read(&["todo", session_id]).unwrap_or_default()
 }
 
 /// Update todos for a session
-pub fn update_todos(session_id: &str, todos: Vec<TodoItem>) {
-    let mut store = get_store().lock().unwrap();
-    store.insert(session_id.to_string(), todos);
+pub fn update_todos(storage: &Storage, session_id: &str, todos: &[TodoItem]) -> Result<()> {
+    storage.write(&["todo", session_id], &todos)
 }
 
 /// Parameters for the TodoWrite tool
@@ -112,7 +102,7 @@ impl Tool for TodoWriteTool {
         let params: TodoWriteParams = serde_json::from_value(params)
             .map_err(|e| AgentError::invalid_arguments("todowrite", e.to_string()))?;
 
-        update_todos(&ctx.session_id, params.todos.clone());
+        update_todos(&ctx.storage, &ctx.session_id, &params.todos)?;
 
         let pending_count = params
             .todos
@@ -154,7 +144,7 @@ impl Tool for TodoReadTool {
     }
 
     async fn execute(&self, _params: Value, ctx: &ToolContext) -> Result<ToolResult> {
-        let todos = get_todos(&ctx.session_id);
+        let todos = get_todos(&ctx.storage, &ctx.session_id);
 
         let pending_count = todos
             .iter()
@@ -7,7 +7,7 @@ use serde::Deserialize;
 use serde_json::{json, Value};
 use tokio::fs;
 
-use super::{context::ToolContext, Tool, ToolResult};
+use super::{context::ToolContext, edit::count_changes, Tool, ToolResult};
 use crate::error::{AgentError, Result};
 
 /// Tool description
@@ -66,8 +66,14 @@ impl Tool for WriteTool {
         let filepath = ctx.resolve_path(&params.file_path);
         let title = ctx.relative_path(&filepath);
 
-        // Check if file exists before writing
+        // Check if file exists before writing. Binary (non-UTF-8) content
+        // has no line diff, so such files get no filediff.
         let exists = filepath.exists();
+        let content_old = if exists {
+            fs::read_to_string(&filepath).await.ok()
+        } else {
+            Some(String::new())
+        };
 
         // Create parent directories if needed
         if let Some(parent) = filepath.parent() {
@@ -79,14 +85,26 @@ impl Tool for WriteTool {
         // Write the file
         fs::write(&filepath, &params.content).await?;
 
+        let mut metadata = json!({
+            "diagnostics": {},
+            "filepath": filepath.to_string_lossy(),
+            "exists": exists,
+        });
+        if let Some(content_old) = content_old {
+            let (additions, deletions) = count_changes(&content_old, &params.content);
+            metadata["filediff"] = json!({
+                "file": filepath.to_string_lossy(),
+                "before": content_old,
+                "after": params.content,
+                "additions": additions,
+                "deletions": deletions,
+            });
+        }
+
         Ok(ToolResult {
             title,
             output: String::new(),
-            metadata: json!({
-                "diagnostics": {},
-                "filepath": filepath.to_string_lossy(),
-                "exists": exists,
-            }),
+            metadata,
             attachments: None,
         })
     }
@@ -0,0 +1,418 @@
+//! Links Notation utilities
+//!
+//! Reads and writes [Links Notation](https://github.com/link-foundation/links-notation)
+//! documents and maps JSON values onto links, playing the role of the
+//! `links-notation` and `lino-objects-codec` packages used by the JavaScript
+//! implementation.
+//!
+//! A document is a sequence of references (`word`, `"quoted text"`) and
+//! parenthesized links (`(id: value value ...)`) separated by whitespace.
+//! At the top level a line may also be written without parentheses as
+//! `id: value value ...`, which is how `.lenv` config files are laid out.
+//! A `#` at the start of a token comments out the rest of the line.
+
+use serde_json::{Map, Number, Value};
+
+use crate::error::{AgentError, Result};
+
+/// A link: either a reference (an `id` with no values) or a parenthesized
+/// list of values with an optional `id:` prefix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub id: Option<String>,
+    pub values: Vec<Link>,
+}
+
+impl Link {
+    /// A reference to `name`
+    pub fn reference(name: impl Into<String>) -> Self {
+        Self {
+            id: Some(name.into()),
+            values: Vec::new(),
+        }
+    }
+
+    /// A link with an optional id and values
+    pub fn new(id: Option<String>, values: Vec<Link>) -> Self {
+        Self { id, values }
+    }
+
+    /// Whether this is a plain reference rather than a parenthesized link
+    pub fn is_reference(&self) -> bool {
+        self.id.is_some() && self.values.is_empty()
+    }
+
+    /// The referenced name, if this is a plain reference
+    pub fn as_reference(&self) -> Option<&str> {
+        if self.is_reference() {
+            self.id.as_deref()
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for Link {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = self.as_reference() {
+            return f.write_str(&quote(name));
+        }
+        f.write_str("(")?;
+        if let Some(id) = &self.id {
+            write!(f, "{}:", quote(id))?;
+            if !self.values.is_empty() {
+                f.write_str(" ")?;
+            }
+        }
+        for (index, value) in self.values.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", value)?;
+        }
+        f.write_str(")")
+    }
+}
+
+/// Quote a reference when it cannot be written bare.
+///
+/// Uses double quotes, or single quotes when the text contains double quotes
+/// only; when both appear, the double quote is escaped by doubling it.
+pub fn quote(name: &str) -> String {
+    let bare = !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '(' | ')' | '"' | '\'' | ':'))
+        && !name.starts_with('#');
+    if bare {
+        name.to_string()
+    } else if !name.contains('"') {
+        format!("\"{}\"", name)
+    } else if !name.contains('\'') {
+        format!("'{}'", name)
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// Parse a document into its top-level links
+pub fn parse(input: &str) -> Result<Vec<Link>> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+    };
+    let mut links = Vec::new();
+    loop {
+        parser.skip_whitespace();
+        if parser.peek().is_none() {
+            return Ok(links);
+        }
+        let link = parser.value()?;
+        if link.is_reference() && parser.peek() == Some(':') {
+            links.push(parser.line(link.id)?);
+        } else {
+            links.push(link);
+        }
+    }
+}
+
+/// Write links as a document, one top-level link per line
+pub fn format(links: &[Link]) -> String {
+    links.iter().map(|link| format!("{}\n", link)).collect()
+}
+
+/// Parse a sequence of names such as `(a b "c d")`; the parentheses are
+/// optional
+pub fn parse_sequence(input: &str) -> Result<Vec<String>> {
+    let links = parse(input)?;
+    let values = match links.as_slice() {
+        [link] if !link.is_reference() && link.id.is_none() => &link.values,
+        _ => &links,
+    };
+    values
+        .iter()
+        .map(|link| {
+            link.as_reference()
+                .map(str::to_string)
+                .ok_or_else(|| invalid(&format!("expected a sequence of names, got {}", link)))
+        })
+        .collect()
+}
+
+/// Write names as a parenthesized sequence, e.g. `(a b "c d")`
+pub fn sequence<S: AsRef<str>>(names: &[S]) -> String {
+    Link::new(
+        None,
+        names
+            .iter()
+            .map(|name| Link::reference(name.as_ref()))
+            .collect(),
+    )
+    .to_string()
+}
+
+/// Parse a `.lenv` document: one `KEY: value` line per setting.
+///
+/// A value made of several references is joined with single spaces, so
+/// `GREETING: hello world` needs no quotes.
+pub fn parse_lenv(input: &str) -> Result<Vec<(String, String)>> {
+    parse(input)?
+        .into_iter()
+        .map(|link| {
+            let values: Option<Vec<&str>> = link.values.iter().map(Link::as_reference).collect();
+            match (&link.id, values) {
+                (Some(key), Some(values)) if !link.is_reference() => {
+                    Ok((key.clone(), values.join(" ")))
+                }
+                _ => Err(invalid(&format!("expected KEY: value, got {}", link))),
+            }
+        })
+        .collect()
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, message: &str) -> AgentError {
+        AgentError::Config {
+            message: format!(
+                "Invalid links notation at position {}: {}",
+                self.pos, message
+            ),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => self.pos += 1,
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    /// Skip whitespace up to the end of the current line
+    fn skip_inline_whitespace(&mut self) {
+        loop {
+            match self.peek() {
+                Some('\n') => return,
+                Some(c) if c.is_whitespace() => self.pos += 1,
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.pos += 1;
+        }
+    }
+
+    /// Whether a `:` at the current position ends a reference, i.e. it is
+    /// followed by whitespace, a parenthesis or the end of input
+    fn at_id_separator(&self) -> bool {
+        self.peek() == Some(':')
+            && self
+                .chars
+                .get(self.pos + 1)
+                .is_none_or(|c| c.is_whitespace() || matches!(c, '(' | ')'))
+    }
+
+    /// The values of a top-level `id: value ...` line
+    fn line(&mut self, id: Option<String>) -> Result<Link> {
+        self.pos += 1; // ':'
+        let mut values = Vec::new();
+        loop {
+            self.skip_inline_whitespace();
+            match self.peek() {
+                None | Some('\n') => return Ok(Link::new(id, values)),
+                _ => values.push(self.value()?),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Link> {
+        match self.peek() {
+            Some('(') => self.link(),
+            Some(')') => Err(self.error("unexpected ')'")),
+            _ => Ok(Link::reference(self.reference()?)),
+        }
+    }
+
+    fn link(&mut self) -> Result<Link> {
+        self.pos += 1; // '('
+        self.skip_whitespace();
+
+        let start = self.pos;
+        let mut id = None;
+        if !matches!(self.peek(), Some('(') | Some(')') | None) {
+            let name = self.reference()?;
+            if self.peek() == Some(':') {
+                self.pos += 1;
+                id = Some(name);
+            } else {
+                self.pos = start;
+            }
+        }
+
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => return Err(self.error("unclosed '('")),
+                Some(')') => {
+                    self.pos += 1;
+                    return Ok(Link::new(id, values));
+                }
+                _ => values.push(self.value()?),
+            }
+        }
+    }
+
+    fn reference(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.pos += 1;
+                let mut text = String::new();
+                loop {
+                    match self.peek() {
+                        None => return Err(self.error("unterminated quoted reference")),
+                        Some(c) if c == quote => {
+                            self.pos += 1;
+                            // A doubled quote stands for the quote character itself
+                            if self.peek() == Some(quote) {
+                                text.push(quote);
+                                self.pos += 1;
+                            } else {
+                                return Ok(text);
+                            }
+                        }
+                        Some(c) => {
+                            text.push(c);
+                            self.pos += 1;
+                        }
+                    }
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|c| !c.is_whitespace() && !matches!(c, '(' | ')'))
+                    && !self.at_id_separator()
+                {
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    return Err(self.error("expected a reference"));
+                }
+                Ok(self.chars[start..self.pos].iter().collect())
+            }
+        }
+    }
+}
+
+/// Encode a JSON value as a Links Notation document.
+///
+/// Strings become references; other values are typed links: `(number 1.5)`,
+/// `(bool true)`, `(null)`, `(array ...)` and `(object (key: value) ...)`.
+/// Objects and arrays are written one entry per line so recordings stay
+/// readable and diff well.
+pub fn encode_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_json(value, 0, &mut out);
+    out.push('\n');
+    out
+}
+
+fn write_json(value: &Value, indent: usize, out: &mut String) {
+    let children: Vec<(Option<&str>, &Value)> = match value {
+        Value::String(s) => return out.push_str(&quote(s)),
+        Value::Null => return out.push_str("(null)"),
+        Value::Bool(b) => return out.push_str(&format!("(bool {})", b)),
+        Value::Number(n) => return out.push_str(&format!("(number {})", n)),
+        Value::Array(items) => items.iter().map(|v| (None, v)).collect(),
+        Value::Object(map) => map.iter().map(|(k, v)| (Some(k.as_str()), v)).collect(),
+    };
+    out.push_str(if value.is_array() {
+        "(array"
+    } else {
+        "(object"
+    });
+    for (key, child) in children {
+        out.push('\n');
+        out.push_str(&"  ".repeat(indent + 1));
+        match key {
+            Some(key) => {
+                out.push_str(&format!("({}: ", quote(key)));
+                write_json(child, indent + 1, out);
+                out.push(')');
+            }
+            None => write_json(child, indent + 1, out),
+        }
+    }
+    out.push(')');
+}
+
+/// Decode a document written by [`encode_json`]
+pub fn decode_json(input: &str) -> Result<Value> {
+    match parse(input)?.as_slice() {
+        [link] => link_to_json(link),
+        _ => Err(invalid("expected exactly one top-level link")),
+    }
+}
+
+fn invalid(message: &str) -> AgentError {
+    AgentError::Config {
+        message: format!("Invalid links notation value: {}", message),
+    }
+}
+
+fn link_to_json(link: &Link) -> Result<Value> {
+    if let Some(text) = link.as_reference() {
+        return Ok(Value::String(text.to_string()));
+    }
+    if link.id.is_some() {
+        return Err(invalid("unexpected key outside an object"));
+    }
+    let (kind, rest) = match link.values.split_first() {
+        Some((kind, rest)) => (kind.as_reference(), rest),
+        None => return Err(invalid("empty link")),
+    };
+    match (kind, rest) {
+        (Some("null"), []) => Ok(Value::Null),
+        (Some("bool"), [value]) => match value.as_reference() {
+            Some("true") => Ok(Value::Bool(true)),
+            Some("false") => Ok(Value::Bool(false)),
+            _ => Err(invalid("bool must be true or false")),
+        },
+        (Some("number"), [value]) => value
+            .as_reference()
+            .and_then(|n| serde_json::from_str::<Number>(n).ok())
+            .map(Value::Number)
+            .ok_or_else(|| invalid("malformed number")),
+        (Some("array"), items) => items.iter().map(link_to_json).collect(),
+        (Some("object"), entries) => {
+            let mut map = Map::new();
+            for entry in entries {
+                match (&entry.id, entry.values.as_slice()) {
+                    (Some(key), [value]) if !entry.is_reference() => {
+                        map.insert(key.clone(), link_to_json(value)?);
+                    }
+                    _ => return Err(invalid("object entries must be (key: value)")),
+                }
+            }
+            Ok(Value::Object(map))
+        }
+        _ => Err(invalid("unknown value type")),
+    }
+}
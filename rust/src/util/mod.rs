@@ -5,6 +5,9 @@
 
 pub mod binary;
 pub mod filesystem;
+pub mod lino;
+pub mod sse;
+pub mod token;
 
 pub use binary::is_binary_file;
 pub use filesystem::Filesystem;
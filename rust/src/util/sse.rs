@@ -0,0 +1,157 @@
+//! Server-sent events utilities
+//!
+//! Incremental `text/event-stream` parsing shared by the streaming providers,
+//! plus usage extraction from stream payloads matching the JavaScript
+//! implementation's util/sse-usage-extractor.ts.
+
+use serde_json::Value;
+
+/// Sentinel payload that ends OpenAI-style streams
+pub const DONE: &str = "[DONE]";
+
+/// A dispatched event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Event {
+    /// The `event:` field, if any
+    pub event: Option<String>,
+    /// `data:` lines joined with newlines
+    pub data: String,
+    pub id: Option<String>,
+    /// Reconnection time requested by the server, in milliseconds
+    pub retry: Option<u64>,
+}
+
+impl Event {
+    /// Whether this is the `[DONE]` end-of-stream sentinel
+    pub fn is_done(&self) -> bool {
+        self.data.trim() == DONE
+    }
+}
+
+/// Incremental `text/event-stream` decoder.
+///
+/// Buffers raw bytes until a blank line terminates an event, so events split
+/// across network chunks (including multi-byte characters) are reassembled
+/// correctly. Comment lines (`:`) are skipped and events without data are
+/// not dispatched, as in the WHATWG event stream specification.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+    pending: Event,
+    data: Vec<String>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a chunk and return the events it completed
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Event> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                events.extend(self.dispatch());
+            } else {
+                self.field(line);
+            }
+        }
+        events
+    }
+
+    /// Flush a trailing event that was not terminated by a blank line
+    pub fn finish(&mut self) -> Option<Event> {
+        let mut events = self.push(b"\n\n");
+        events.pop()
+    }
+
+    fn field(&mut self, line: &str) {
+        if line.starts_with(':') {
+            return;
+        }
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match name {
+            "data" => self.data.push(value.to_string()),
+            "event" => self.pending.event = Some(value.to_string()),
+            "id" => self.pending.id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(retry) = value.parse() {
+                    self.pending.retry = Some(retry);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        let mut event = std::mem::take(&mut self.pending);
+        if self.data.is_empty() {
+            return None;
+        }
+        event.data = std::mem::take(&mut self.data).join("\n");
+        Some(event)
+    }
+}
+
+/// Parse a complete event stream body
+pub fn parse(body: &str) -> Vec<Event> {
+    let mut decoder = Decoder::new();
+    let mut events = decoder.push(body.as_bytes());
+    events.extend(decoder.finish());
+    events
+}
+
+/// Token usage reported inside a stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SseUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    pub cached_tokens: Option<u64>,
+    pub reasoning_tokens: Option<u64>,
+}
+
+/// Usage from one parsed stream payload.
+///
+/// Accepts OpenAI (`prompt_tokens`/`completion_tokens` with
+/// `*_tokens_details`), Anthropic (`input_tokens`/`output_tokens` with
+/// `cache_read_input_tokens`) and camelCase fields, found at `usage`,
+/// `x_groq.usage` or `choices[0].usage`. All-zero usage is ignored.
+pub fn usage_from_value(payload: &Value) -> Option<SseUsage> {
+    let usage = [
+        payload.get("usage"),
+        payload.pointer("/x_groq/usage"),
+        payload.pointer("/choices/0/usage"),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|usage| usage.is_object())?;
+
+    let first = |paths: &[&str]| paths.iter().find_map(|path| usage.pointer(path)?.as_u64());
+    let prompt = first(&["/prompt_tokens", "/input_tokens", "/promptTokens"])?;
+    let completion = first(&["/completion_tokens", "/output_tokens", "/completionTokens"])?;
+    if prompt == 0 && completion == 0 {
+        return None;
+    }
+    Some(SseUsage {
+        prompt_tokens: prompt,
+        completion_tokens: completion,
+        total_tokens: first(&["/total_tokens", "/totalTokens"]).unwrap_or(prompt + completion),
+        cached_tokens: first(&[
+            "/prompt_tokens_details/cached_tokens",
+            "/cache_read_input_tokens",
+            "/cachedTokens",
+        ]),
+        reasoning_tokens: first(&[
+            "/completion_tokens_details/reasoning_tokens",
+            "/reasoning_tokens",
+        ]),
+    })
+}
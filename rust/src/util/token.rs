@@ -0,0 +1,183 @@
+//! Token estimation
+//!
+//! Counts tokens for context accounting, matching the JavaScript
+//! implementation's util/token.ts. Two levels of accuracy are available:
+//!
+//! 1. Real BPE tokenization with the o200k_base table (GPT-4o, GPT-4.1,
+//!    GPT-5), which is bundled in the binary.
+//! 2. A character heuristic (about 4 characters per token), used when the
+//!    table cannot be loaded and where speed matters more than accuracy.
+//!
+//! Other model families use their own vocabularies, so even the BPE count is
+//! an approximation for them; the compaction safety margin absorbs the
+//! difference.
+
+use std::sync::OnceLock;
+
+use crate::provider::{ChatMessage, ChatRequest, ContentPart, ToolSpec};
+
+/// Characters per token assumed by the heuristic
+pub const CHARS_PER_TOKEN: usize = 4;
+
+/// Tokens added per message for role markers and separators
+pub const MESSAGE_OVERHEAD: u64 = 3;
+
+/// Heuristic token count: characters / 4, rounded half up
+pub fn estimate(input: &str) -> u64 {
+    let chars = input.chars().count();
+    ((chars + CHARS_PER_TOKEN / 2) / CHARS_PER_TOKEN) as u64
+}
+
+/// A token counter
+pub trait Tokenizer: Send + Sync {
+    /// Short name for diagnostics (e.g. "o200k_base")
+    fn name(&self) -> &str;
+
+    fn count(&self, text: &str) -> u64;
+
+    /// Whether counts come from a real vocabulary rather than a heuristic
+    fn is_precise(&self) -> bool;
+}
+
+/// The character heuristic as a tokenizer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Heuristic;
+
+impl Tokenizer for Heuristic {
+    fn name(&self) -> &str {
+        "heuristic"
+    }
+
+    fn count(&self, text: &str) -> u64 {
+        estimate(text)
+    }
+
+    fn is_precise(&self) -> bool {
+        false
+    }
+}
+
+/// BPE tokenizer backed by the bundled o200k_base table
+pub struct Bpe {
+    encoder: tiktoken_rs::CoreBPE,
+}
+
+impl Bpe {
+    /// Load the bundled o200k_base table
+    pub fn o200k_base() -> Option<Self> {
+        match tiktoken_rs::o200k_base() {
+            Ok(encoder) => Some(Self { encoder }),
+            Err(e) => {
+                tracing::info!(error = %e, "BPE table unavailable, using character-based estimation");
+                None
+            }
+        }
+    }
+}
+
+impl Tokenizer for Bpe {
+    fn name(&self) -> &str {
+        "o200k_base"
+    }
+
+    fn count(&self, text: &str) -> u64 {
+        self.encoder.encode_ordinary(text).len() as u64
+    }
+
+    fn is_precise(&self) -> bool {
+        true
+    }
+}
+
+/// The default tokenizer: BPE when the table loads, else the heuristic.
+///
+/// The table is parsed on first use and shared afterwards.
+pub fn default_tokenizer() -> &'static dyn Tokenizer {
+    static BPE: OnceLock<Option<Bpe>> = OnceLock::new();
+    match BPE.get_or_init(Bpe::o200k_base) {
+        Some(bpe) => bpe,
+        None => &Heuristic,
+    }
+}
+
+/// A token count and whether it came from a real vocabulary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Count {
+    pub count: u64,
+    pub precise: bool,
+}
+
+/// Count tokens with the default tokenizer.
+///
+/// Use this where accuracy matters (overflow detection, output caps); for
+/// logging prefer the cheaper [`estimate`].
+pub fn count_tokens(input: &str) -> Count {
+    if input.is_empty() {
+        return Count {
+            count: 0,
+            precise: true,
+        };
+    }
+    let tokenizer = default_tokenizer();
+    Count {
+        count: tokenizer.count(input),
+        precise: tokenizer.is_precise(),
+    }
+}
+
+/// Tokens for one conversation message, including its overhead
+pub fn message_tokens(tokenizer: &dyn Tokenizer, message: &ChatMessage) -> u64 {
+    let content = match message {
+        ChatMessage::User { text } => tokenizer.count(text),
+        ChatMessage::Assistant { content } => content
+            .iter()
+            .map(|part| match part {
+                ContentPart::Text { text } | ContentPart::Reasoning { text, .. } => {
+                    tokenizer.count(text)
+                }
+                ContentPart::ToolCall { tool, input, .. } => {
+                    tokenizer.count(tool) + tokenizer.count(&input.to_string())
+                }
+            })
+            .sum(),
+        ChatMessage::Tool { tool, output, .. } => {
+            tokenizer.count(tool) + tool_output_tokens(tokenizer, output)
+        }
+    };
+    content + MESSAGE_OVERHEAD
+}
+
+/// Tokens for a conversation
+pub fn messages_tokens(tokenizer: &dyn Tokenizer, messages: &[ChatMessage]) -> u64 {
+    messages
+        .iter()
+        .map(|message| message_tokens(tokenizer, message))
+        .sum()
+}
+
+/// Tokens for the tool schemas advertised to the model
+pub fn tools_tokens(tokenizer: &dyn Tokenizer, tools: &[ToolSpec]) -> u64 {
+    tools
+        .iter()
+        .map(|tool| {
+            tokenizer.count(&tool.name)
+                + tokenizer.count(&tool.description)
+                + tokenizer.count(&tool.parameters.to_string())
+        })
+        .sum()
+}
+
+/// Tokens for a tool's output
+pub fn tool_output_tokens(tokenizer: &dyn Tokenizer, output: &str) -> u64 {
+    tokenizer.count(output)
+}
+
+/// Input tokens of a whole request: system prompt, messages and tools
+pub fn request_tokens(tokenizer: &dyn Tokenizer, request: &ChatRequest) -> u64 {
+    let system: u64 = request
+        .system
+        .iter()
+        .map(|block| tokenizer.count(block))
+        .sum();
+    system + messages_tokens(tokenizer, &request.messages) + tools_tokens(tokenizer, &request.tools)
+}
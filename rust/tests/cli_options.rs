@@ -9,14 +9,45 @@ use link_assistant_agent::defaults::{
     DEFAULT_COMPACTION_MODELS_ENV, DEFAULT_COMPACTION_MODEL_ENV,
     DEFAULT_COMPACTION_SAFETY_MARGIN_PERCENT_ENV, DEFAULT_MODEL_ENV,
 };
+use link_assistant_agent::global::DATA_DIR_ENV;
+use link_assistant_agent::session::Session;
+use link_assistant_agent::storage::Storage;
 use predicates::prelude::*;
 use std::io::Write;
+use tempfile::TempDir;
 
 /// Helper to create a Command for the agent binary.
 fn agent_cmd() -> assert_cmd::Command {
     Command::cargo_bin("agent").unwrap()
 }
 
+/// Directories for a run that resumes `session_id`: a data directory
+/// holding that session and a working directory outside any git repository.
+struct StoredSession {
+    data: TempDir,
+    work: TempDir,
+}
+
+impl StoredSession {
+    fn new(session_id: &str) -> Self {
+        let data = TempDir::new().unwrap();
+        let work = TempDir::new().unwrap();
+        let mut session = Session::new(work.path());
+        session.info.id = session_id.to_string();
+        Storage::from_data_dir(data.path())
+            .write_session(&mut session)
+            .unwrap();
+        Self { data, work }
+    }
+
+    fn agent_cmd(&self) -> assert_cmd::Command {
+        let mut cmd = agent_cmd();
+        cmd.current_dir(self.work.path())
+            .env(DATA_DIR_ENV, self.data.path());
+        cmd
+    }
+}
+
 // ── Model option ──────────────────────────────────────────────────────
 
 #[test]
@@ -459,7 +490,8 @@ fn compact_json_single_line() {
 
 #[test]
 fn resume_option_accepted() {
-    agent_cmd()
+    StoredSession::new("ses_abc123")
+        .agent_cmd()
         .args(["--dry-run", "--resume", "ses_abc123", "-p", "hello"])
         .assert()
         .success();
@@ -467,7 +499,8 @@ fn resume_option_accepted() {
 
 #[test]
 fn resume_short_flag() {
-    agent_cmd()
+    StoredSession::new("ses_abc123")
+        .agent_cmd()
         .args(["--dry-run", "-r", "ses_abc123", "-p", "hello"])
         .assert()
         .success();
@@ -477,7 +510,8 @@ fn resume_short_flag() {
 
 #[test]
 fn continue_option_accepted() {
-    agent_cmd()
+    StoredSession::new("ses_abc123")
+        .agent_cmd()
         .args(["--dry-run", "--continue", "-p", "hello"])
         .assert()
         .success();
@@ -485,7 +519,8 @@ fn continue_option_accepted() {
 
 #[test]
 fn continue_short_flag() {
-    agent_cmd()
+    StoredSession::new("ses_abc123")
+        .agent_cmd()
         .args(["--dry-run", "-c", "-p", "hello"])
         .assert()
         .success();
@@ -495,7 +530,8 @@ fn continue_short_flag() {
 
 #[test]
 fn no_fork_option_accepted() {
-    agent_cmd()
+    StoredSession::new("ses_abc")
+        .agent_cmd()
         .args([
             "--dry-run",
             "--no-fork",
@@ -777,7 +813,8 @@ fn compaction_safety_margin_cli_overrides_env_default() {
 #[test]
 fn all_options_accepted_together() {
     // Verify that the binary accepts all options simultaneously without conflict.
-    agent_cmd()
+    StoredSession::new("ses_abc")
+        .agent_cmd()
         .args([
             "--model",
             "opencode/gpt-5",
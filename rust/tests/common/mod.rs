@@ -0,0 +1,217 @@
+//! Shared helpers for provider tests.
+//!
+//! A minimal HTTP/1.1 server on a random local port that records incoming
+//! requests and replays scripted responses, so providers can be exercised
+//! without network access. Streaming responses are written chunk by chunk
+//! with optional delays between them.
+
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A request received by the mock server
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    /// Header value by case-insensitive name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).expect("request body is not JSON")
+    }
+}
+
+/// A scripted response
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// Wait before sending the status line and headers
+    pub head_delay: Duration,
+    /// Body pieces, each written after waiting for its delay
+    pub chunks: Vec<(Duration, String)>,
+}
+
+impl MockResponse {
+    /// A `text/event-stream` response with one `data:` event per payload
+    pub fn sse<S: AsRef<str>>(events: &[S]) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".into(), "text/event-stream".into())],
+            head_delay: Duration::ZERO,
+            chunks: events
+                .iter()
+                .map(|event| (Duration::ZERO, format!("data: {}\n\n", event.as_ref())))
+                .collect(),
+        }
+    }
+
+    /// A response with raw body pieces written in order
+    pub fn chunked(status: u16, chunks: Vec<(Duration, String)>) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".into(), "text/event-stream".into())],
+            head_delay: Duration::ZERO,
+            chunks,
+        }
+    }
+
+    /// A JSON response
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            head_delay: Duration::ZERO,
+            chunks: vec![(Duration::ZERO, body.to_string())],
+        }
+    }
+
+    /// Read the request, then close the connection without answering
+    pub fn disconnect() -> Self {
+        Self {
+            status: 0,
+            headers: Vec::new(),
+            head_delay: Duration::ZERO,
+            chunks: Vec::new(),
+        }
+    }
+
+    /// Delay the response headers, as a slow upstream would
+    pub fn with_head_delay(mut self, delay: Duration) -> Self {
+        self.head_delay = delay;
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// A running mock server; responses are served in order, one per connection
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockServer {
+    pub async fn start(responses: Vec<MockResponse>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            let mut responses = responses.into_iter();
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let Some(request) = read_request(&mut stream).await else {
+                    continue;
+                };
+                recorded.lock().unwrap().push(request);
+                let Some(response) = responses.next() else {
+                    let _ = write_head(&mut stream, 500, &[]).await;
+                    continue;
+                };
+                tokio::spawn(async move {
+                    let _ = write_response(&mut stream, response).await;
+                });
+            }
+        });
+        Self { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<RecordedRequest> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+    let length = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = buffer[header_end..].to_vec();
+    while body.len() < length {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+
+    Some(RecordedRequest {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+    })
+}
+
+async fn write_head(
+    stream: &mut TcpStream,
+    status: u16,
+    headers: &[(String, String)],
+) -> std::io::Result<()> {
+    let mut head = format!("HTTP/1.1 {} Mock\r\nConnection: close\r\n", status);
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    stream.flush().await
+}
+
+async fn write_response(stream: &mut TcpStream, response: MockResponse) -> std::io::Result<()> {
+    if response.status == 0 {
+        return stream.shutdown().await;
+    }
+    if !response.head_delay.is_zero() {
+        tokio::time::sleep(response.head_delay).await;
+    }
+    write_head(stream, response.status, &response.headers).await?;
+    for (delay, chunk) in response.chunks {
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        stream.write_all(chunk.as_bytes()).await?;
+        stream.flush().await?;
+    }
+    stream.shutdown().await
+}
@@ -1,15 +1,10 @@
 //! Rust counterpart of `js/tests/compaction-model.ts`.
 //!
-//! The full session-compaction logic (`SessionCompaction.computeSafetyMarginRatio`,
-//! `SessionCompaction.isOverflow`, `SessionCompaction.contextDiagnostics`) only
-//! exists in the JavaScript implementation. The Rust port currently exposes
-//! the compaction defaults via `link_assistant_agent::defaults` and the
-//! `--compaction-model` / `--compaction-models` / `--compaction-safety-margin`
-//! CLI flags via `link_assistant_agent::cli::Args`.
-//!
-//! These tests mirror the JS behavior at the surface that *is* observable
-//! in Rust: the centralized defaults and the CLI override precedence.
+//! Covers the compaction defaults and CLI flags, the cascade parsing and
+//! overflow arithmetic of `session::compaction`, and compaction inside the
+//! agent loop, driven by scripted providers.
 
+use async_trait::async_trait;
 use clap::Parser;
 use link_assistant_agent::cli::{
     Args, DEFAULT_COMPACTION_MODEL, DEFAULT_COMPACTION_MODELS,
@@ -17,9 +12,25 @@ use link_assistant_agent::cli::{
 };
 use link_assistant_agent::defaults::{
     default_compaction_model_from_env, default_compaction_models_from_env,
-    default_compaction_safety_margin_percent_from_env, DEFAULT_COMPACTION_MODELS_ENV,
+    default_compaction_safety_margin_percent_from_env, model_parts, DEFAULT_COMPACTION_MODELS_ENV,
     DEFAULT_COMPACTION_MODEL_ENV, DEFAULT_COMPACTION_SAFETY_MARGIN_PERCENT_ENV,
 };
+use link_assistant_agent::error::{AgentError, Result};
+use link_assistant_agent::provider::models::{Catalog, Model};
+use link_assistant_agent::provider::{
+    ChatMessage, ChatRequest, ChatResponse, ContentPart, Provider, Usage,
+};
+use link_assistant_agent::session::compaction::{
+    self, active_messages, context_diagnostics, current_tokens, is_overflow, parse_models,
+    safety_margin_ratio, Compaction, CompactionModel, Target, CONTINUE_TEXT, SUMMARY_REQUEST,
+};
+use link_assistant_agent::session::prompt::{PromptInput, SessionPrompt};
+use link_assistant_agent::session::{system, MessageInfo, Part, Session, SessionEvent};
+use link_assistant_agent::tool::ToolRegistry;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tempfile::TempDir;
 
 fn empty_env() -> impl Fn(&str) -> Option<String> {
     |_| None
@@ -108,3 +119,370 @@ fn cli_flag_for_compaction_safety_margin_overrides_default() {
 fn default_safety_margin_is_25_percent() {
     assert_eq!(DEFAULT_COMPACTION_SAFETY_MARGIN_PERCENT, 25);
 }
+
+#[test]
+fn cascade_notation_parses_with_or_without_parentheses() {
+    assert_eq!(
+        parse_models("(gpt-5-nano opencode/big-pickle same)").unwrap(),
+        vec!["gpt-5-nano", "opencode/big-pickle", "same"]
+    );
+    assert_eq!(
+        parse_models("opencode/big-pickle").unwrap(),
+        vec!["opencode/big-pickle"]
+    );
+    assert!(parse_models("(gpt-5-nano (nested))").is_err());
+    assert!(matches!(
+        parse_models("(unclosed"),
+        Err(AgentError::Config { .. })
+    ));
+}
+
+#[test]
+fn default_cascade_resolves_against_the_catalog() {
+    let catalog = Catalog::bundled();
+    let cascade = compaction::cascade(DEFAULT_COMPACTION_MODELS, &catalog).unwrap();
+    assert_eq!(cascade.last(), Some(&CompactionModel::Same));
+    assert!(cascade.contains(&CompactionModel::Model(model_parts("opencode/gpt-5-nano"))));
+}
+
+#[test]
+fn cascade_entries_resolve_by_kind() {
+    let catalog = Catalog::bundled();
+    assert_eq!(
+        CompactionModel::resolve("SAME", &catalog),
+        Some(CompactionModel::Same)
+    );
+    assert_eq!(
+        CompactionModel::resolve("groq/some-model", &catalog),
+        Some(CompactionModel::Model(model_parts("groq/some-model")))
+    );
+    assert_eq!(
+        CompactionModel::resolve("gpt-5-nano", &catalog),
+        Some(CompactionModel::Model(model_parts("opencode/gpt-5-nano")))
+    );
+    assert_eq!(
+        CompactionModel::resolve("no-such-model-anywhere", &catalog),
+        None
+    );
+    let cascade = compaction::cascade("(no-such-model-anywhere same)", &catalog).unwrap();
+    assert_eq!(cascade, vec![CompactionModel::Same]);
+}
+
+#[test]
+fn safety_margin_ratio_depends_on_the_compaction_context() {
+    assert_eq!(safety_margin_ratio(200_000, 25, None), 0.75);
+    assert_eq!(safety_margin_ratio(200_000, 25, Some(100_000)), 0.75);
+    assert_eq!(safety_margin_ratio(200_000, 25, Some(200_000)), 0.75);
+    assert_eq!(safety_margin_ratio(200_000, 25, Some(400_000)), 1.0);
+    assert_eq!(safety_margin_ratio(200_000, 10, None), 0.9);
+    assert_eq!(Compaction::default().safety_margin_ratio(200_000), 0.75);
+}
+
+fn model(context: u64, output: u64) -> Model {
+    serde_json::from_value(json!({
+        "id": "test-model",
+        "limit": { "context": context, "output": output }
+    }))
+    .unwrap()
+}
+
+#[test]
+fn overflow_is_measured_against_the_safe_share_of_usable_context() {
+    let model = model(10_000, 2_000);
+    let diagnostics = context_diagnostics(&model, 5_000, 0.75).unwrap();
+    assert_eq!(diagnostics.usable_context, 8_000);
+    assert_eq!(diagnostics.safe_limit, 6_000);
+    assert_eq!(diagnostics.headroom, 1_000);
+    assert!(!diagnostics.overflow);
+
+    assert!(!is_overflow(&model, 6_000, 0.75));
+    assert!(is_overflow(&model, 6_001, 0.75));
+    assert!(!is_overflow(&model, 7_000, 1.0));
+    // Unknown context windows never overflow
+    assert!(!is_overflow(&self::model(0, 0), 1_000_000, 0.75));
+}
+
+#[test]
+fn output_reservation_is_capped() {
+    let diagnostics = context_diagnostics(&model(200_000, 0), 0, 1.0).unwrap();
+    assert_eq!(diagnostics.output_limit, 32_000);
+    let diagnostics = context_diagnostics(&model(200_000, 128_000), 0, 1.0).unwrap();
+    assert_eq!(diagnostics.output_limit, 32_000);
+}
+
+#[test]
+fn autocompact_can_be_disabled_from_the_environment() {
+    assert!(!compaction::disabled_from_env(empty_env()));
+    assert!(compaction::disabled_from_env(env_with(
+        compaction::DISABLE_AUTOCOMPACT_ENV,
+        "true"
+    )));
+}
+
+/// Replays canned responses, or fails every call when it has none
+struct ScriptedProvider {
+    id: &'static str,
+    responses: Mutex<VecDeque<ChatResponse>>,
+    requests: Mutex<Vec<ChatRequest>>,
+}
+
+impl ScriptedProvider {
+    fn new(id: &'static str, responses: Vec<ChatResponse>) -> Self {
+        Self {
+            id,
+            responses: Mutex::new(responses.into()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn requests(&self) -> Vec<ChatRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Provider for ScriptedProvider {
+    fn id(&self) -> &str {
+        self.id
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        self.requests.lock().unwrap().push(request.clone());
+        self.responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| AgentError::Api {
+                provider: self.id.to_string(),
+                status: Some(429),
+                message: "Rate limited".to_string(),
+                retryable: true,
+            })
+    }
+}
+
+fn response(content: ContentPart, finish: &str, input: u64) -> ChatResponse {
+    ChatResponse {
+        content: vec![content],
+        finish_reason: finish.to_string(),
+        usage: Usage {
+            input,
+            output: 5,
+            ..Default::default()
+        },
+        model: None,
+    }
+}
+
+fn text(text: &str, input: u64) -> ChatResponse {
+    let content = ContentPart::Text {
+        text: text.to_string(),
+    };
+    response(content, "stop", input)
+}
+
+fn tool_call(input: u64) -> ChatResponse {
+    let content = ContentPart::ToolCall {
+        call_id: "call_1".to_string(),
+        tool: "list".to_string(),
+        input: json!({}),
+    };
+    response(content, "tool-calls", input)
+}
+
+fn input(message: &str) -> PromptInput {
+    PromptInput {
+        text: message.to_string(),
+        model: model_parts("scripted/test-model"),
+        system: Some("test system".to_string()),
+        append_system: None,
+        temperature: None,
+    }
+}
+
+fn user_text(message: &ChatMessage) -> &str {
+    match message {
+        ChatMessage::User { text } => text,
+        other => panic!("expected a user message, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn overflowing_step_is_compacted_through_the_cascade() {
+    let dir = TempDir::new().unwrap();
+    let registry = ToolRegistry::new();
+    let session_provider = ScriptedProvider::new(
+        "scripted",
+        vec![
+            tool_call(7_000),
+            text("summary of the work", 7_000),
+            text("done", 100),
+        ],
+    );
+    let small = ScriptedProvider::new("small", Vec::new());
+    let failing = ScriptedProvider::new("failing", Vec::new());
+    let cascade = vec![
+        Target::Model {
+            model: model_parts("small/tiny"),
+            provider: &small,
+            context_limit: Some(1_000),
+        },
+        Target::Model {
+            model: model_parts("failing/model"),
+            provider: &failing,
+            context_limit: Some(100_000),
+        },
+        Target::Same,
+    ];
+    let prompt = SessionPrompt::new(&session_provider, &registry, dir.path())
+        .with_model_info(model(10_000, 2_000))
+        .with_compaction(Compaction::new(cascade, 25));
+
+    let mut session = Session::new(dir.path());
+    let mut compactions = Vec::new();
+    prompt
+        .prompt(&mut session, input("list the files"), &mut |event| {
+            if let SessionEvent::Compaction { model, tokens } = event {
+                compactions.push((model, tokens));
+            }
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        compactions,
+        vec![(model_parts("scripted/test-model"), 7_005)]
+    );
+    // Too small for the conversation: never asked
+    assert!(small.requests().is_empty());
+    // Failed: the next entry was tried
+    assert_eq!(failing.requests().len(), 1);
+
+    let requests = session_provider.requests();
+    assert_eq!(requests.len(), 3);
+    let summary_request = &requests[1];
+    assert_eq!(summary_request.system, system::summarize());
+    assert!(summary_request.tools.is_empty());
+    assert_eq!(
+        user_text(summary_request.messages.last().unwrap()),
+        SUMMARY_REQUEST
+    );
+    assert_eq!(&failing.requests()[0].messages, &summary_request.messages);
+
+    // After compacting only the summary and what follows are sent
+    let resumed = &requests[2];
+    assert_eq!(resumed.messages.len(), 3);
+    assert_eq!(user_text(&resumed.messages[0]), "What did we do so far?");
+    assert_eq!(
+        resumed.messages[1],
+        ChatMessage::Assistant {
+            content: vec![ContentPart::Text {
+                text: "summary of the work".to_string()
+            }]
+        }
+    );
+    assert_eq!(user_text(&resumed.messages[2]), CONTINUE_TEXT);
+
+    let summary = session
+        .messages
+        .iter()
+        .find_map(|m| match &m.info {
+            MessageInfo::Assistant(a) if a.summary == Some(true) => Some(a),
+            _ => None,
+        })
+        .expect("summary message");
+    assert_eq!(summary.model_id, "test-model");
+    assert_eq!(active_messages(&session.messages).len(), 4);
+    assert!(matches!(
+        active_messages(&session.messages)[0].parts[0],
+        Part::Compaction(_)
+    ));
+}
+
+#[tokio::test]
+async fn overflowing_history_is_compacted_before_the_next_prompt() {
+    let dir = TempDir::new().unwrap();
+    let registry = ToolRegistry::new();
+    let provider = ScriptedProvider::new(
+        "scripted",
+        vec![
+            text("first answer", 7_000),
+            text("summary", 7_000),
+            text("second answer", 100),
+        ],
+    );
+    let prompt =
+        SessionPrompt::new(&provider, &registry, dir.path()).with_model_info(model(10_000, 2_000));
+
+    let mut session = Session::new(dir.path());
+    let mut compacted = 0;
+    for message in ["first", "second"] {
+        prompt
+            .prompt(&mut session, input(message), &mut |event| {
+                if matches!(event, SessionEvent::Compaction { .. }) {
+                    compacted += 1;
+                }
+            })
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(compacted, 1);
+    let requests = provider.requests();
+    let last = &requests[2];
+    assert_eq!(last.messages.len(), 3);
+    assert_eq!(user_text(&last.messages[0]), "What did we do so far?");
+    assert_eq!(user_text(&last.messages[2]), "second");
+    assert_eq!(
+        current_tokens(active_messages(&session.messages)),
+        Some(105)
+    );
+}
+
+#[tokio::test]
+async fn compaction_fails_when_no_cascade_model_can_hold_the_conversation() {
+    let dir = TempDir::new().unwrap();
+    let registry = ToolRegistry::new();
+    let provider = ScriptedProvider::new("scripted", vec![tool_call(7_000)]);
+    let small = ScriptedProvider::new("small", Vec::new());
+    let cascade = vec![Target::Model {
+        model: model_parts("small/tiny"),
+        provider: &small,
+        context_limit: Some(1_000),
+    }];
+    let prompt = SessionPrompt::new(&provider, &registry, dir.path())
+        .with_model_info(model(10_000, 2_000))
+        .with_compaction(Compaction::new(cascade, 25));
+
+    let mut session = Session::new(dir.path());
+    let result = prompt
+        .prompt(&mut session, input("list the files"), &mut |_| {})
+        .await;
+
+    assert!(matches!(result, Err(AgentError::Session { .. })));
+    assert!(small.requests().is_empty());
+    // The unanswered compaction request is not left in the history
+    assert!(!session
+        .messages
+        .iter()
+        .flat_map(|m| &m.parts)
+        .any(|part| matches!(part, Part::Compaction(_))));
+}
+
+#[tokio::test]
+async fn disabled_compaction_never_summarizes() {
+    let dir = TempDir::new().unwrap();
+    let registry = ToolRegistry::new();
+    let provider = ScriptedProvider::new("scripted", vec![tool_call(7_000), text("done", 7_000)]);
+    let prompt = SessionPrompt::new(&provider, &registry, dir.path())
+        .with_model_info(model(10_000, 2_000))
+        .with_compaction(Compaction::disabled());
+
+    let mut session = Session::new(dir.path());
+    prompt
+        .prompt(&mut session, input("list the files"), &mut |_| {})
+        .await
+        .unwrap();
+
+    assert_eq!(provider.requests().len(), 2);
+}
@@ -0,0 +1,292 @@
+//! Tests for tool-output pruning in `session::compaction`.
+//!
+//! Mirrors the JavaScript `SessionCompaction.prune`: old tool output beyond
+//! the protected window is marked as compacted and replaced by a short
+//! marker when the conversation is sent to the model.
+
+use async_trait::async_trait;
+use link_assistant_agent::defaults::model_parts;
+use link_assistant_agent::error::Result;
+use link_assistant_agent::provider::models::Model;
+use link_assistant_agent::provider::{
+    ChatMessage, ChatRequest, ChatResponse, ContentPart, Provider, Usage,
+};
+use link_assistant_agent::session::compaction::{
+    prune, prune_disabled_from_env, Compaction, DISABLE_PRUNE_ENV, PRUNE_PROTECT,
+};
+use link_assistant_agent::session::message::{to_chat_messages, ToolState, PRUNED_OUTPUT};
+use link_assistant_agent::session::prompt::{PromptInput, SessionPrompt};
+use link_assistant_agent::session::{MessageWithParts, Part, Session, SessionEvent};
+use link_assistant_agent::tool::ToolRegistry;
+use serde_json::json;
+use std::sync::Mutex;
+use tempfile::TempDir;
+
+/// Tool output of about `tokens` tokens under the character heuristic
+fn output(tokens: u64) -> String {
+    "abcd".repeat(tokens as usize)
+}
+
+fn user(id: &str) -> MessageWithParts {
+    serde_json::from_value(json!({
+        "info": {
+            "role": "user",
+            "id": id,
+            "sessionID": "ses_test",
+            "time": { "created": 1 },
+            "agent": "build",
+            "model": { "providerID": "scripted", "modelID": "test-model" }
+        },
+        "parts": [{
+            "type": "text",
+            "id": format!("prt_{}", id),
+            "sessionID": "ses_test",
+            "messageID": id,
+            "text": "do the work"
+        }]
+    }))
+    .unwrap()
+}
+
+fn assistant(id: &str, input_tokens: u64, outputs: &[u64]) -> MessageWithParts {
+    let parts: Vec<_> = outputs
+        .iter()
+        .enumerate()
+        .map(|(index, tokens)| {
+            json!({
+                "type": "tool",
+                "id": format!("prt_{}_{}", id, index),
+                "sessionID": "ses_test",
+                "messageID": id,
+                "callID": format!("call_{}_{}", id, index),
+                "tool": "read",
+                "state": {
+                    "status": "completed",
+                    "input": {},
+                    "output": output(*tokens),
+                    "title": "read",
+                    "metadata": {},
+                    "time": { "start": 1, "end": 2 }
+                }
+            })
+        })
+        .collect();
+    serde_json::from_value(json!({
+        "info": {
+            "role": "assistant",
+            "id": id,
+            "sessionID": "ses_test",
+            "time": { "created": 1, "completed": 2 },
+            "parentID": "msg_parent",
+            "modelID": "test-model",
+            "providerID": "scripted",
+            "mode": "build",
+            "path": { "cwd": "/", "root": "/" },
+            "cost": 0.0,
+            "tokens": { "input": input_tokens, "output": 0, "reasoning": 0, "cache": { "read": 0, "write": 0 } },
+            "finish": if outputs.is_empty() { "stop" } else { "tool-calls" }
+        },
+        "parts": parts
+    }))
+    .unwrap()
+}
+
+/// Three turns; the first carries four 20k-token tool outputs
+fn history(last_input_tokens: u64) -> Vec<MessageWithParts> {
+    vec![
+        user("msg_01"),
+        assistant("msg_02", 100, &[20_000, 20_000, 20_000, 20_000]),
+        user("msg_03"),
+        assistant("msg_04", 100, &[]),
+        user("msg_05"),
+        assistant("msg_06", last_input_tokens, &[]),
+    ]
+}
+
+fn compacted(messages: &[MessageWithParts]) -> Vec<&str> {
+    messages
+        .iter()
+        .flat_map(|m| &m.parts)
+        .filter_map(|part| match part {
+            Part::Tool(tool) => match &tool.state {
+                ToolState::Completed { time, .. } if time.compacted.is_some() => {
+                    Some(tool.id.as_str())
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn outputs_beyond_the_protected_window_are_pruned() {
+    let mut messages = history(100);
+    let pruned = prune(&mut messages);
+
+    // The newest 40k tokens of tool output are protected
+    assert_eq!(pruned.tokens, 40_000);
+    assert_eq!(
+        pruned
+            .parts
+            .iter()
+            .map(|p| p.part_id.as_str())
+            .collect::<Vec<_>>(),
+        ["prt_msg_02_1", "prt_msg_02_0"]
+    );
+    let mut ids = compacted(&messages);
+    ids.sort();
+    assert_eq!(ids, ["prt_msg_02_0", "prt_msg_02_1"]);
+
+    let chat = to_chat_messages(&messages);
+    let outputs: Vec<&str> = chat
+        .iter()
+        .filter_map(|m| match m {
+            ChatMessage::Tool { output, .. } => Some(output.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(outputs.len(), 4);
+    assert_eq!(outputs[0], PRUNED_OUTPUT);
+    assert_eq!(outputs[1], PRUNED_OUTPUT);
+    assert_eq!(outputs[2].len(), 80_000);
+}
+
+#[test]
+fn pruning_stops_at_previously_pruned_output() {
+    let mut messages = history(100);
+    prune(&mut messages);
+    let again = prune(&mut messages);
+    assert!(again.parts.is_empty());
+    assert_eq!(again.tokens, 0);
+}
+
+#[test]
+fn small_gains_are_not_pruned() {
+    // 50k tokens: only 10k beyond the protected window
+    let mut messages = vec![
+        user("msg_01"),
+        assistant("msg_02", 100, &[25_000, 25_000]),
+        user("msg_03"),
+        assistant("msg_04", 100, &[]),
+    ];
+    assert!(prune(&mut messages).parts.is_empty());
+    assert!(compacted(&messages).is_empty());
+}
+
+#[test]
+fn recent_turns_are_never_pruned() {
+    let mut messages = vec![
+        user("msg_01"),
+        assistant(
+            "msg_02",
+            100,
+            &[PRUNE_PROTECT, PRUNE_PROTECT, PRUNE_PROTECT],
+        ),
+    ];
+    assert!(prune(&mut messages).parts.is_empty());
+}
+
+#[test]
+fn pruning_can_be_disabled_from_the_environment() {
+    assert!(!prune_disabled_from_env(|_| None));
+    assert!(prune_disabled_from_env(|key| {
+        (key == DISABLE_PRUNE_ENV).then(|| "1".to_string())
+    }));
+}
+
+struct ScriptedProvider {
+    requests: Mutex<Vec<ChatRequest>>,
+}
+
+#[async_trait]
+impl Provider for ScriptedProvider {
+    fn id(&self) -> &str {
+        "scripted"
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        self.requests.lock().unwrap().push(request.clone());
+        Ok(ChatResponse {
+            content: vec![ContentPart::Text {
+                text: "done".to_string(),
+            }],
+            finish_reason: "stop".to_string(),
+            usage: Usage {
+                input: 100,
+                output: 5,
+                ..Default::default()
+            },
+            model: None,
+        })
+    }
+}
+
+fn model() -> Model {
+    serde_json::from_value(json!({
+        "id": "test-model",
+        "limit": { "context": 100_000, "output": 2_000 }
+    }))
+    .unwrap()
+}
+
+async fn run(compaction: Compaction<'_>) -> (Vec<ChatRequest>, Session, usize) {
+    let dir = TempDir::new().unwrap();
+    let registry = ToolRegistry::new();
+    let provider = ScriptedProvider {
+        requests: Mutex::new(Vec::new()),
+    };
+    let prompt = SessionPrompt::new(&provider, &registry, dir.path())
+        .with_model_info(model())
+        .with_compaction(compaction);
+
+    // 80k tokens against a safe limit of 73.5k
+    let mut session = Session::new(dir.path());
+    session.messages = history(80_000);
+    let mut compactions = 0;
+    prompt
+        .prompt(
+            &mut session,
+            PromptInput {
+                text: "next".to_string(),
+                model: model_parts("scripted/test-model"),
+                system: Some("test system".to_string()),
+                append_system: None,
+                temperature: None,
+            },
+            &mut |event| {
+                if matches!(event, SessionEvent::Compaction { .. }) {
+                    compactions += 1;
+                }
+            },
+        )
+        .await
+        .unwrap();
+    let requests = provider.requests.into_inner().unwrap();
+    (requests, session, compactions)
+}
+
+#[tokio::test]
+async fn pruning_first_avoids_a_summarization_call() {
+    let (requests, session, compactions) = run(Compaction::default()).await;
+
+    assert_eq!(compactions, 0);
+    assert_eq!(requests.len(), 1);
+    let pruned = requests[0]
+        .messages
+        .iter()
+        .filter(|m| matches!(m, ChatMessage::Tool { output, .. } if output == PRUNED_OUTPUT))
+        .count();
+    assert_eq!(pruned, 2);
+    assert_eq!(compacted(&session.messages).len(), 2);
+}
+
+#[tokio::test]
+async fn disabled_pruning_summarizes_instead() {
+    let (requests, session, compactions) = run(Compaction::default().with_prune(false)).await;
+
+    assert_eq!(compactions, 1);
+    // The summary, then the turn itself
+    assert_eq!(requests.len(), 2);
+    assert!(compacted(&session.messages).is_empty());
+}
@@ -57,3 +57,58 @@ fn test_file_not_found_with_suggestions() {
     let suggestions = json["data"]["suggestions"].as_array().unwrap();
     assert_eq!(suggestions.len(), 3);
 }
+
+#[test]
+fn test_api_error_retryability_follows_status() {
+    let err = AgentError::api("groq", Some(429), "rate limited");
+    let json = err.to_json();
+    assert_eq!(json["name"], "APIError");
+    assert_eq!(json["data"]["statusCode"], 429);
+    assert_eq!(json["data"]["isRetryable"], true);
+
+    let err = AgentError::api("groq", Some(400), "bad request");
+    assert_eq!(err.to_json()["data"]["isRetryable"], false);
+}
+
+#[test]
+fn test_timeout_errors_are_distinct_and_retryable() {
+    let errors = [
+        (
+            AgentError::ConnectTimeout {
+                provider: "groq".to_string(),
+                timeout_ms: 30000,
+            },
+            "ConnectTimeoutError",
+        ),
+        (
+            AgentError::FirstByteTimeout {
+                provider: "groq".to_string(),
+                timeout_ms: 300000,
+            },
+            "FirstByteTimeoutError",
+        ),
+        (
+            AgentError::StreamIdleTimeout {
+                provider: "groq".to_string(),
+                timeout_ms: 120000,
+            },
+            "StreamIdleTimeoutError",
+        ),
+        (
+            AgentError::StepTimeout {
+                provider: "groq".to_string(),
+                timeout_ms: 600000,
+            },
+            "StepTimeoutError",
+        ),
+    ];
+    for (err, name) in errors {
+        assert!(err.is_timeout());
+        let json = err.to_json();
+        assert_eq!(json["name"], name);
+        assert_eq!(json["data"]["providerID"], "groq");
+        assert_eq!(json["data"]["isRetryable"], true);
+        assert!(json["data"]["timeoutMs"].as_u64().unwrap() > 0);
+    }
+    assert!(!AgentError::api("groq", Some(504), "gateway timeout").is_timeout());
+}
@@ -29,3 +29,15 @@ fn agent_help_runs_cleanly() {
         .assert()
         .success();
 }
+
+#[test]
+fn dry_run_does_not_fetch_the_models_catalog() {
+    let data_dir = tempfile::TempDir::new().unwrap();
+    Command::cargo_bin("agent")
+        .unwrap()
+        .args(["--dry-run", "-p", "hello"])
+        .env("LINK_ASSISTANT_AGENT_DATA_DIR", data_dir.path())
+        .assert()
+        .success();
+    assert!(!data_dir.path().join("models.json").exists());
+}
@@ -1,12 +1,15 @@
 //! Rust counterpart of `js/tests/integration/resume.js`.
 //!
-//! The JS suite covers the resume integration path against the
-//! live agent runtime. The Rust port shares the same CLI surface but the
-//! live runtime integrations land incrementally; this file pins the base
-//! name so the JS and Rust test trees stay aligned.
+//! Runs the agent in dry-run mode against a temporary data directory, then
+//! resumes the stored session with `--resume`/`--continue`, with and without
+//! `--no-fork`, and checks the history that ends up in storage.
 
 use assert_cmd::Command;
+use link_assistant_agent::session::{Part, Session};
+use link_assistant_agent::storage::{Storage, GLOBAL_PROJECT};
 use predicates::prelude::*;
+use serde_json::Value;
+use tempfile::TempDir;
 
 #[test]
 fn dry_run_completes_without_credentials() {
@@ -29,3 +32,161 @@ fn agent_help_runs_cleanly() {
         .assert()
         .success();
 }
+
+/// A data directory and a working directory outside any git repository
+struct Sandbox {
+    data: TempDir,
+    work: TempDir,
+}
+
+impl Sandbox {
+    fn new() -> Self {
+        Self {
+            data: TempDir::new().unwrap(),
+            work: TempDir::new().unwrap(),
+        }
+    }
+
+    fn agent(&self, args: &[&str]) -> Command {
+        let mut command = Command::cargo_bin("agent").unwrap();
+        command
+            .args(["--dry-run", "--compact-json"])
+            .args(args)
+            .current_dir(self.work.path())
+            .env("LINK_ASSISTANT_AGENT_DATA_DIR", self.data.path());
+        command
+    }
+
+    /// Run a prompt and return the stdout events
+    fn run(&self, args: &[&str]) -> Vec<Value> {
+        let output = self.agent(args).assert().success().get_output().clone();
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    fn storage(&self) -> Storage {
+        Storage::from_data_dir(self.data.path())
+    }
+
+    fn session(&self, id: &str) -> Session {
+        self.storage().read_session(GLOBAL_PROJECT, id).unwrap()
+    }
+
+    fn session_ids(&self) -> Vec<String> {
+        self.storage()
+            .sessions(GLOBAL_PROJECT)
+            .unwrap()
+            .into_iter()
+            .map(|info| info.id)
+            .collect()
+    }
+}
+
+fn user_texts(session: &Session) -> Vec<String> {
+    session
+        .messages
+        .iter()
+        .flat_map(|m| &m.parts)
+        .filter_map(|part| match part {
+            Part::Text(text) if text.text.starts_with("prompt ") => Some(text.text.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn resume_status(events: &[Value]) -> &Value {
+    events
+        .iter()
+        .find(|e| e["type"] == "status" && e["mode"] == "resume")
+        .expect("a resume status event")
+}
+
+#[test]
+fn resume_forks_the_stored_history_by_default() {
+    let sandbox = Sandbox::new();
+    sandbox.run(&["-p", "prompt one"]);
+    let original = sandbox.session_ids().pop().unwrap();
+
+    let events = sandbox.run(&["--resume", &original, "-p", "prompt two"]);
+    let status = resume_status(&events);
+    assert_eq!(status["forked"], true);
+    assert_eq!(status["originalSessionID"], original.as_str());
+    let forked = status["sessionID"].as_str().unwrap().to_string();
+    assert_ne!(forked, original);
+
+    // The fork carries the earlier turn; the original is untouched
+    assert_eq!(
+        user_texts(&sandbox.session(&forked)),
+        ["prompt one", "prompt two"]
+    );
+    assert_eq!(user_texts(&sandbox.session(&original)), ["prompt one"]);
+    assert_eq!(sandbox.session_ids().len(), 2);
+
+    // Events of the resumed run belong to the fork
+    assert!(events
+        .iter()
+        .filter(|e| e["type"] == "step_start")
+        .all(|e| e["sessionID"] == forked.as_str()));
+}
+
+#[test]
+fn no_fork_appends_to_the_original_session() {
+    let sandbox = Sandbox::new();
+    sandbox.run(&["-p", "prompt one"]);
+    let original = sandbox.session_ids().pop().unwrap();
+
+    let events = sandbox.run(&["--resume", &original, "--no-fork", "-p", "prompt two"]);
+    let status = resume_status(&events);
+    assert_eq!(status["forked"], false);
+    assert_eq!(status["sessionID"], original.as_str());
+
+    assert_eq!(sandbox.session_ids(), std::slice::from_ref(&original));
+    let session = sandbox.session(&original);
+    assert_eq!(user_texts(&session), ["prompt one", "prompt two"]);
+    // Two turns of user message and reply
+    assert_eq!(session.messages.len(), 4);
+}
+
+#[test]
+fn continue_picks_the_most_recent_session() {
+    let sandbox = Sandbox::new();
+    sandbox.run(&["-p", "prompt one"]);
+    sandbox.run(&["-p", "prompt two"]);
+    let sessions = sandbox.storage().sessions(GLOBAL_PROJECT).unwrap();
+    let latest = sessions
+        .iter()
+        .max_by_key(|info| info.time.updated)
+        .unwrap()
+        .id
+        .clone();
+
+    let events = sandbox.run(&["--continue", "--no-fork", "-p", "prompt three"]);
+    assert_eq!(resume_status(&events)["sessionID"], latest.as_str());
+    assert_eq!(
+        user_texts(&sandbox.session(&latest)),
+        ["prompt two", "prompt three"]
+    );
+}
+
+#[test]
+fn continue_without_sessions_fails() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .agent(&["--continue", "-p", "hello"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No existing sessions found"));
+}
+
+#[test]
+fn resuming_an_unknown_session_fails() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .agent(&["--resume", "ses_missing", "-p", "hello"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Session not found: ses_missing"));
+}
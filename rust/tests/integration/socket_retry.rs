@@ -1,12 +1,19 @@
 //! Rust counterpart of `js/tests/integration/socket-retry.js`.
 //!
-//! The JS suite covers the socket retry integration path against the
-//! live agent runtime. The Rust port shares the same CLI surface but the
-//! live runtime integrations land incrementally; this file pins the base
-//! name so the JS and Rust test trees stay aligned.
+//! Connections dropped before a response are retried; refused connections
+//! fail immediately instead of spending the retry budget.
+
+#[path = "../common/mod.rs"]
+mod common;
 
 use assert_cmd::Command;
+use common::{MockResponse, MockServer};
+use link_assistant_agent::provider::openai::OpenAiCompatibleProvider;
+use link_assistant_agent::provider::retry_fetch::RetryPolicy;
+use link_assistant_agent::provider::{ChatMessage, ChatRequest, ContentPart, Provider};
 use predicates::prelude::*;
+use serde_json::json;
+use std::time::{Duration, Instant};
 
 #[test]
 fn dry_run_completes_without_credentials() {
@@ -29,3 +36,61 @@ fn agent_help_runs_cleanly() {
         .assert()
         .success();
 }
+
+fn request() -> ChatRequest {
+    ChatRequest {
+        model: "model".to_string(),
+        system: Vec::new(),
+        messages: vec![ChatMessage::User {
+            text: "hi".to_string(),
+        }],
+        tools: Vec::new(),
+        temperature: None,
+        max_output_tokens: None,
+        thinking_budget: None,
+    }
+}
+
+fn policy() -> RetryPolicy {
+    RetryPolicy::default().with_initial_delay(Duration::from_millis(10))
+}
+
+#[tokio::test]
+async fn dropped_connections_are_retried() {
+    let server = MockServer::start(vec![
+        MockResponse::disconnect(),
+        MockResponse::sse(&[
+            json!({ "choices": [{ "delta": { "content": "reconnected" }, "finish_reason": "stop" }] })
+                .to_string(),
+            "[DONE]".to_string(),
+        ]),
+    ])
+    .await;
+
+    let provider = OpenAiCompatibleProvider::new("mock", &server.url).with_retry(policy());
+    let response = provider.complete(&request()).await.unwrap();
+
+    assert_eq!(
+        response.content,
+        vec![ContentPart::Text {
+            text: "reconnected".to_string()
+        }]
+    );
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn refused_connections_fail_fast() {
+    // Bind and drop a listener to get a port nothing listens on
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let provider = OpenAiCompatibleProvider::new("mock", format!("http://127.0.0.1:{}", port))
+        .with_retry(policy().with_min_retry_interval(Duration::from_secs(30)));
+
+    let started = Instant::now();
+    assert!(provider.complete(&request()).await.is_err());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
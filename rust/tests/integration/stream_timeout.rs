@@ -1,12 +1,28 @@
 //! Rust counterpart of `js/tests/integration/stream-timeout.js`.
 //!
-//! The JS suite covers the stream timeout integration path against the
-//! live agent runtime. The Rust port shares the same CLI surface but the
-//! live runtime integrations land incrementally; this file pins the base
-//! name so the JS and Rust test trees stay aligned.
+//! Each layer of the timeout hierarchy (first byte, inter-chunk idle,
+//! step) fails with its own error, and the agent loop retries a stalled
+//! stream instead of hanging.
+
+#[path = "../common/mod.rs"]
+mod common;
 
 use assert_cmd::Command;
+use clap::Parser;
+use common::{MockResponse, MockServer};
+use link_assistant_agent::cli::Args;
+use link_assistant_agent::defaults::model_parts;
+use link_assistant_agent::error::AgentError;
+use link_assistant_agent::provider::openai::OpenAiCompatibleProvider;
+use link_assistant_agent::provider::timeout::{Timeouts, STREAM_CHUNK_TIMEOUT_ENV};
+use link_assistant_agent::provider::{ChatMessage, ChatRequest, Provider};
+use link_assistant_agent::session::prompt::{PromptInput, SessionPrompt};
+use link_assistant_agent::session::{Part, Session, SessionEvent};
+use link_assistant_agent::tool::ToolRegistry;
 use predicates::prelude::*;
+use serde_json::json;
+use std::time::Duration;
+use tempfile::TempDir;
 
 #[test]
 fn dry_run_completes_without_credentials() {
@@ -29,3 +45,193 @@ fn agent_help_runs_cleanly() {
         .assert()
         .success();
 }
+
+fn delta(text: &str) -> String {
+    format!(
+        "data: {}\n\n",
+        json!({ "choices": [{ "delta": { "content": text } }] })
+    )
+}
+
+fn finish() -> String {
+    format!(
+        "data: {}\n\ndata: [DONE]\n\n",
+        json!({ "choices": [{ "delta": {}, "finish_reason": "stop" }] })
+    )
+}
+
+/// A stream that sends one chunk, then goes silent for `stall`
+fn stalled(stall: Duration) -> MockResponse {
+    MockResponse::chunked(
+        200,
+        vec![(Duration::ZERO, delta("partial")), (stall, finish())],
+    )
+}
+
+fn request() -> ChatRequest {
+    ChatRequest {
+        model: "model".to_string(),
+        system: Vec::new(),
+        messages: vec![ChatMessage::User {
+            text: "hi".to_string(),
+        }],
+        tools: Vec::new(),
+        temperature: None,
+        max_output_tokens: None,
+        thinking_budget: None,
+    }
+}
+
+fn timeouts() -> Timeouts {
+    Timeouts::default()
+        .with_first_byte(Duration::from_millis(300))
+        .with_chunk(Duration::from_millis(300))
+}
+
+#[test]
+fn timeouts_come_from_env_then_flags() {
+    let defaults = Timeouts::from_env(|_| None);
+    assert_eq!(defaults.chunk, Duration::from_millis(120_000));
+    assert_eq!(defaults.step, Duration::from_millis(600_000));
+    let from_env =
+        Timeouts::from_env(|key| (key == STREAM_CHUNK_TIMEOUT_ENV).then(|| "5000".into()));
+    assert_eq!(from_env.chunk, Duration::from_millis(5000));
+
+    let args = Args::parse_from([
+        "agent",
+        "--connect-timeout-ms",
+        "1000",
+        "--first-byte-timeout-ms",
+        "2000",
+        "--stream-chunk-timeout-ms",
+        "3000",
+        "--stream-step-timeout-ms",
+        "4000",
+    ]);
+    assert_eq!(
+        args.timeouts(),
+        Timeouts {
+            connect: Duration::from_millis(1000),
+            first_byte: Duration::from_millis(2000),
+            chunk: Duration::from_millis(3000),
+            step: Duration::from_millis(4000),
+        }
+    );
+}
+
+#[tokio::test]
+async fn slow_response_headers_hit_the_first_byte_timeout() {
+    let server = MockServer::start(vec![
+        MockResponse::sse(&["[DONE]"]).with_head_delay(Duration::from_secs(3))
+    ])
+    .await;
+    let provider = OpenAiCompatibleProvider::new("mock", &server.url).with_timeouts(timeouts());
+
+    let error = provider.complete(&request()).await.unwrap_err();
+    assert!(
+        matches!(
+            error,
+            AgentError::FirstByteTimeout {
+                timeout_ms: 300,
+                ..
+            }
+        ),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn stalled_streams_hit_the_idle_timeout() {
+    let server = MockServer::start(vec![stalled(Duration::from_secs(3))]).await;
+    let provider = OpenAiCompatibleProvider::new("mock", &server.url).with_timeouts(timeouts());
+
+    let error = provider.complete(&request()).await.unwrap_err();
+    assert!(
+        matches!(
+            error,
+            AgentError::StreamIdleTimeout {
+                timeout_ms: 300,
+                ..
+            }
+        ),
+        "{error:?}"
+    );
+}
+
+fn input() -> PromptInput {
+    PromptInput {
+        text: "hi".to_string(),
+        model: model_parts("mock/model"),
+        system: Some("test system".to_string()),
+        append_system: None,
+        temperature: None,
+    }
+}
+
+#[tokio::test]
+async fn stalled_steps_are_retried() {
+    let dir = TempDir::new().unwrap();
+    let server = MockServer::start(vec![
+        stalled(Duration::from_secs(3)),
+        MockResponse::chunked(
+            200,
+            vec![(Duration::ZERO, delta("done")), (Duration::ZERO, finish())],
+        ),
+    ])
+    .await;
+    let provider = OpenAiCompatibleProvider::new("mock", &server.url).with_timeouts(timeouts());
+    let registry = ToolRegistry::new();
+    let prompt = SessionPrompt::new(&provider, &registry, dir.path())
+        .with_timeouts(timeouts())
+        .with_timeout_retry_delays(vec![Duration::from_millis(10)]);
+    let mut session = Session::new(dir.path());
+
+    let mut retries = Vec::new();
+    let mut texts = Vec::new();
+    prompt
+        .prompt(&mut session, input(), &mut |event| match event {
+            SessionEvent::Retry { attempt, error, .. } => retries.push((attempt, error)),
+            SessionEvent::Part(Part::Text(part)) => texts.push(part.text),
+            _ => {}
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(retries.len(), 1);
+    assert_eq!(retries[0].0, 1);
+    assert!(retries[0].1.contains("stalled"));
+    assert_eq!(texts, vec!["done".to_string()]);
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn steps_fail_once_retries_are_exhausted() {
+    let dir = TempDir::new().unwrap();
+    // Chunks keep arriving, but the step as a whole takes too long
+    let trickle = (0..10)
+        .map(|_| (Duration::from_millis(100), delta(".")))
+        .chain([(Duration::ZERO, finish())])
+        .collect();
+    let server = MockServer::start(vec![MockResponse::chunked(200, trickle)]).await;
+    let provider = OpenAiCompatibleProvider::new("mock", &server.url).with_timeouts(timeouts());
+    let registry = ToolRegistry::new();
+    let prompt = SessionPrompt::new(&provider, &registry, dir.path())
+        .with_timeouts(timeouts().with_step(Duration::from_millis(400)))
+        .with_timeout_retry_delays(Vec::new());
+    let mut session = Session::new(dir.path());
+
+    let error = prompt
+        .prompt(&mut session, input(), &mut |_| {})
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            error,
+            AgentError::StepTimeout {
+                timeout_ms: 400,
+                ..
+            }
+        ),
+        "{error:?}"
+    );
+}
@@ -1,12 +1,16 @@
 //! Rust counterpart of `js/tests/integration/timeout-retry.js`.
 //!
-//! The JS suite covers the timeout retry integration path against the
-//! live agent runtime. The Rust port shares the same CLI surface but the
-//! live runtime integrations land incrementally; this file pins the base
-//! name so the JS and Rust test trees stay aligned.
+//! Rate-limited and failing provider requests are retried by the agent
+//! itself, with a `retry` event on the output stream before every wait.
+
+#[path = "../common/mod.rs"]
+mod common;
 
 use assert_cmd::Command;
+use common::{MockResponse, MockServer};
 use predicates::prelude::*;
+use serde_json::{json, Value};
+use tempfile::TempDir;
 
 #[test]
 fn dry_run_completes_without_credentials() {
@@ -29,3 +33,104 @@ fn agent_help_runs_cleanly() {
         .assert()
         .success();
 }
+
+fn answer(text: &str) -> MockResponse {
+    MockResponse::sse(&[
+        json!({ "choices": [{ "delta": { "content": text }, "finish_reason": "stop" }] })
+            .to_string(),
+        "[DONE]".to_string(),
+    ])
+}
+
+/// Run the agent against `url` as the `mock/model` provider
+async fn run_agent(url: String, extra_args: &[&str]) -> (Vec<Value>, bool) {
+    let data_dir = TempDir::new().unwrap();
+    let extra_args: Vec<String> = extra_args.iter().map(|a| a.to_string()).collect();
+    let output = tokio::task::spawn_blocking(move || {
+        Command::cargo_bin("agent")
+            .unwrap()
+            .args(["--model", "mock/model", "--compact-json", "-p", "hi"])
+            .args(extra_args)
+            .env("LINK_ASSISTANT_AGENT_DATA_DIR", data_dir.path())
+            .env("LINK_ASSISTANT_AGENT_DISABLE_MODELS_FETCH", "1")
+            .env("MOCK_BASE_URL", url)
+            .output()
+            .unwrap()
+    })
+    .await
+    .unwrap();
+    let events = serde_json::Deserializer::from_slice(&output.stdout)
+        .into_iter::<Value>()
+        .filter_map(|event| event.ok())
+        .collect();
+    (events, output.status.success())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rate_limited_requests_are_retried_with_a_retry_event() {
+    let server = MockServer::start(vec![
+        MockResponse::json(429, json!({ "error": { "message": "rate limited" } }))
+            .with_header("retry-after-ms", "100"),
+        answer("after the wait"),
+    ])
+    .await;
+
+    let (events, success) = run_agent(server.url.clone(), &["--min-retry-interval", "0"]).await;
+
+    assert!(success);
+    let retry = events
+        .iter()
+        .find(|event| event["type"] == "retry")
+        .expect("no retry event");
+    assert_eq!(retry["attempt"], 1);
+    assert_eq!(retry["statusCode"], 429);
+    assert_eq!(retry["provider"], "mock");
+    assert!(retry["delay"].as_u64().unwrap() >= 100);
+    assert!(retry["sessionID"].as_str().unwrap().starts_with("ses_"));
+    assert!(events
+        .iter()
+        .any(|event| event["type"] == "text" && event["text"] == "after the wait"));
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn retries_do_not_break_claude_stream_json() {
+    let server = MockServer::start(vec![
+        MockResponse::json(429, json!({ "error": { "message": "rate limited" } }))
+            .with_header("retry-after-ms", "100"),
+        answer("after the wait"),
+    ])
+    .await;
+
+    let (events, success) = run_agent(
+        server.url.clone(),
+        &["--min-retry-interval", "0", "--json-standard", "claude"],
+    )
+    .await;
+
+    assert!(success);
+    assert_eq!(server.requests().len(), 2);
+    // Only Claude frames are written: no OpenCode retry event in between
+    let frames: Vec<&Value> = events
+        .iter()
+        .filter(|event| event.get("type").is_some())
+        .collect();
+    assert!(frames.iter().all(|frame| frame.get("sessionID").is_none()));
+    assert!(!frames.iter().any(|frame| frame["type"] == "retry"));
+    assert!(frames.iter().any(|frame| frame["type"] == "result"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn no_retry_on_rate_limits_fails_fast() {
+    let server = MockServer::start(vec![MockResponse::json(
+        429,
+        json!({ "error": { "message": "rate limited" } }),
+    )])
+    .await;
+
+    let (events, success) = run_agent(server.url.clone(), &["--no-retry-on-rate-limits"]).await;
+
+    assert!(!success);
+    assert!(!events.iter().any(|event| event["type"] == "retry"));
+    assert_eq!(server.requests().len(), 1);
+}
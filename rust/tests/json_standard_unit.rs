@@ -2,16 +2,17 @@
 //!
 //! The JavaScript implementation includes a JSON-standard module
 //! (`js/src/json-standard/`) that converts events between the OpenCode JSON
-//! format and the Claude streaming format. The Rust port currently emits
-//! OpenCode-shaped JSON natively without a separate conversion layer, so
-//! there is no equivalent module to test.
-//!
-//! These tests verify the surface that *is* observable in Rust today:
-//! the `--json-standard` CLI flag accepts the same values as JS and rejects
-//! invalid ones identically.
+//! format and the Claude streaming format. In Rust the conversion of
+//! session events is private to the CLI, so it is checked end to end: the
+//! `--json-standard` flag accepts the same values as JS, and a dry run in
+//! the Claude standard writes `init`, `message` and `result` frames.
 
+use assert_cmd::Command;
 use clap::Parser;
 use link_assistant_agent::cli::Args;
+use link_assistant_agent::global::DATA_DIR_ENV;
+use serde_json::Value;
+use tempfile::TempDir;
 
 #[test]
 fn default_json_standard_is_opencode() {
@@ -36,3 +37,53 @@ fn rejects_unknown_json_standard() {
     let result = Args::try_parse_from(["agent", "--json-standard", "nonsense"]);
     assert!(result.is_err());
 }
+
+fn claude_frames(args: &[&str]) -> Vec<Value> {
+    let data = TempDir::new().unwrap();
+    let output = Command::cargo_bin("agent")
+        .unwrap()
+        .env(DATA_DIR_ENV, data.path())
+        .args(["--dry-run", "-p", "hi"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|frame| frame.get("session_id").is_some())
+        .collect()
+}
+
+#[test]
+fn claude_standard_writes_stream_json_frames() {
+    let frames = claude_frames(&["--json-standard", "claude"]);
+    let types: Vec<&str> = frames.iter().filter_map(|f| f["type"].as_str()).collect();
+    assert_eq!(types, ["init", "message", "result"]);
+
+    assert_eq!(frames[0]["model"], "link-assistant/echo");
+    assert_eq!(
+        frames[1]["content"][0]["text"],
+        "[DRY RUN] Received message: hi"
+    );
+    let result = &frames[2];
+    assert_eq!(result["status"], "success");
+    assert!(result["duration_ms"].is_u64());
+    assert_eq!(result["total_cost_usd"], 0.0);
+    assert_eq!(result["usage"]["input_tokens"], 1);
+    assert_eq!(result["usage"]["cache_read_input_tokens"], 0);
+    assert_eq!(result["model"], "echo");
+}
+
+#[test]
+fn stream_json_output_format_selects_the_claude_standard() {
+    let frames = claude_frames(&[
+        "--output-format",
+        "stream-json",
+        "--no-output-response-model",
+    ]);
+    let result = frames.last().unwrap();
+    assert_eq!(result["type"], "result");
+    assert!(result.get("model").is_none());
+}
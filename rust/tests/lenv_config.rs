@@ -0,0 +1,145 @@
+//! Tests for `.lenv` settings files read through `global::getenv`.
+
+mod common;
+
+use assert_cmd::Command;
+use common::{MockResponse, MockServer};
+use link_assistant_agent::defaults::DEFAULT_COMPACTION_MODEL_ENV;
+use link_assistant_agent::error::AgentError;
+use link_assistant_agent::global::{read_lenv, LENV_FILE};
+use predicates::prelude::*;
+use serde_json::json;
+use tempfile::TempDir;
+
+#[test]
+fn missing_lenv_holds_no_settings() {
+    let dir = TempDir::new().unwrap();
+    assert!(read_lenv(dir.path()).unwrap().is_empty());
+}
+
+#[test]
+fn lenv_settings_are_read() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(
+        dir.path().join(LENV_FILE),
+        "# local overrides\nLINK_ASSISTANT_AGENT_DEFAULT_MODEL: opencode/big-pickle\n",
+    )
+    .unwrap();
+    let settings = read_lenv(dir.path()).unwrap();
+    assert_eq!(
+        settings
+            .get("LINK_ASSISTANT_AGENT_DEFAULT_MODEL")
+            .map(String::as_str),
+        Some("opencode/big-pickle")
+    );
+}
+
+#[test]
+fn malformed_lenv_names_the_file() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join(LENV_FILE), "KEY: (unclosed\n").unwrap();
+    match read_lenv(dir.path()) {
+        Err(AgentError::Config { message }) => assert!(message.contains(LENV_FILE), "{message}"),
+        other => panic!("expected a config error, got {:?}", other),
+    }
+}
+
+fn agent(dir: &TempDir) -> Command {
+    let mut cmd = Command::cargo_bin("agent").unwrap();
+    cmd.current_dir(dir.path())
+        .env("LINK_ASSISTANT_AGENT_DATA_DIR", dir.path().join("data"))
+        .env("LINK_ASSISTANT_AGENT_DISABLE_MODELS_FETCH", "1")
+        .env_remove(DEFAULT_COMPACTION_MODEL_ENV);
+    cmd
+}
+
+#[test]
+fn lenv_supplies_defaults_to_the_cli() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(
+        dir.path().join(LENV_FILE),
+        format!("{}: opencode/big-pickle\n", DEFAULT_COMPACTION_MODEL_ENV),
+    )
+    .unwrap();
+    agent(&dir)
+        .args(["--dry-run", "--verbose", "--compact-json", "-p", "hello"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Compaction model: opencode/big-pickle",
+        ));
+}
+
+#[test]
+fn environment_takes_precedence_over_lenv() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(
+        dir.path().join(LENV_FILE),
+        format!("{}: opencode/big-pickle\n", DEFAULT_COMPACTION_MODEL_ENV),
+    )
+    .unwrap();
+    agent(&dir)
+        .env(DEFAULT_COMPACTION_MODEL_ENV, "opencode/gpt-5-nano")
+        .args(["--dry-run", "--verbose", "--compact-json", "-p", "hello"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Compaction model: opencode/gpt-5-nano",
+        ));
+}
+
+#[test]
+fn malformed_lenv_fails_the_run() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(dir.path().join(LENV_FILE), "KEY: (unclosed\n").unwrap();
+    agent(&dir)
+        .args(["--dry-run", "-p", "hello"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(LENV_FILE));
+}
+
+#[test]
+fn lenv_is_read_from_the_working_directory() {
+    let dir = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    std::fs::write(
+        work.path().join(LENV_FILE),
+        format!("{}: opencode/big-pickle\n", DEFAULT_COMPACTION_MODEL_ENV),
+    )
+    .unwrap();
+    agent(&dir)
+        .arg("--working-directory")
+        .arg(work.path())
+        .args(["--dry-run", "--verbose", "--compact-json", "-p", "hello"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Compaction model: opencode/big-pickle",
+        ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lenv_configures_providers() {
+    let server = MockServer::start(vec![MockResponse::sse(&[
+        json!({ "choices": [{ "delta": { "content": "from lenv" }, "finish_reason": "stop" }] })
+            .to_string(),
+        "[DONE]".to_string(),
+    ])])
+    .await;
+    let dir = TempDir::new().unwrap();
+    std::fs::write(
+        dir.path().join(LENV_FILE),
+        format!("MOCK_BASE_URL: \"{}\"\n", server.url),
+    )
+    .unwrap();
+    let mut cmd = agent(&dir);
+    cmd.env_remove("MOCK_BASE_URL")
+        .args(["--model", "mock/model", "--compact-json", "-p", "hi"]);
+    let output = tokio::task::spawn_blocking(move || cmd.output().unwrap())
+        .await
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("from lenv"));
+    assert_eq!(server.requests().len(), 1);
+}
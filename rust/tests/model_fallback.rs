@@ -1,16 +1,24 @@
 //! Rust counterpart of `js/tests/model-fallback.ts`.
 //!
-//! The provider/model fallback chain is implemented in
-//! `js/src/provider/` and is JS-specific (it depends on the `ai` SDK and
-//! the OpenCode Zen provider stack). The Rust port does not expose a
-//! provider chain yet; the binary connects to a single configured
-//! provider via the `--model` flag and the centralized defaults.
-//!
-//! These tests verify the surface that *is* observable in Rust: the model
-//! string parses into provider/model parts the same way as JS, and the
-//! defaults helpers honor environment overrides.
+//! Fallback is opt-in: with `--fallback-model`, a step whose model is
+//! reported as not supported is retried once with the fallback model and a
+//! warning is emitted. Without it the `ModelNotSupportedError` is surfaced
+//! as is. Model strings parse into provider/model parts the same way as JS.
+
+mod common;
 
+use async_trait::async_trait;
+use common::{MockResponse, MockServer};
 use link_assistant_agent::defaults::{model_parts, ModelParts};
+use link_assistant_agent::error::{AgentError, Result};
+use link_assistant_agent::provider::echo::EchoProvider;
+use link_assistant_agent::provider::{ChatRequest, ChatResponse, Provider};
+use link_assistant_agent::session::prompt::{PromptInput, SessionPrompt};
+use link_assistant_agent::session::{MessageInfo, Part, Session, SessionEvent};
+use link_assistant_agent::tool::ToolRegistry;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use tempfile::TempDir;
 
 #[test]
 fn provider_and_model_parts_split_on_first_slash() {
@@ -44,3 +52,141 @@ fn provider_and_model_parts_handle_empty_string() {
     assert_eq!(parts.provider_id, "");
     assert_eq!(parts.model_id, "");
 }
+
+/// Provider whose model has been withdrawn
+struct UnsupportedProvider {
+    requests: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl Provider for UnsupportedProvider {
+    fn id(&self) -> &str {
+        "opencode"
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        self.requests.lock().unwrap().push(request.model.clone());
+        Err(AgentError::ModelNotSupported {
+            provider: "opencode".to_string(),
+            message: format!("Model {} not supported", request.model),
+        })
+    }
+}
+
+fn input() -> PromptInput {
+    PromptInput {
+        text: "hello".to_string(),
+        model: model_parts("opencode/kimi-k2.5-free"),
+        system: Some("test system".to_string()),
+        append_system: None,
+        temperature: None,
+    }
+}
+
+#[tokio::test]
+async fn unsupported_model_switches_to_the_fallback() {
+    let dir = TempDir::new().unwrap();
+    let primary = UnsupportedProvider {
+        requests: Mutex::new(Vec::new()),
+    };
+    let fallback = EchoProvider::new();
+    let registry = ToolRegistry::new();
+    let prompt = SessionPrompt::new(&primary, &registry, dir.path())
+        .with_fallback(&fallback, model_parts("link-assistant/echo"));
+    let mut session = Session::new(dir.path());
+    let mut events = Vec::new();
+
+    prompt
+        .prompt(&mut session, input(), &mut |event| events.push(event))
+        .await
+        .unwrap();
+
+    assert_eq!(*primary.requests.lock().unwrap(), vec!["kimi-k2.5-free"]);
+    let (from, to, message) = events
+        .iter()
+        .find_map(|event| match event {
+            SessionEvent::ModelFallback { from, to, message } => Some((from, to, message)),
+            _ => None,
+        })
+        .expect("a fallback event");
+    assert_eq!(from.model_id, "kimi-k2.5-free");
+    assert_eq!(to.provider_id, "link-assistant");
+    assert_eq!(message, "Model kimi-k2.5-free not supported");
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, SessionEvent::Part(Part::Text(t)) if t.text == "hello")));
+    match &session.messages.last().unwrap().info {
+        MessageInfo::Assistant(a) => {
+            assert_eq!(a.provider_id, "link-assistant");
+            assert_eq!(a.model_id, "echo");
+        }
+        other => panic!("expected assistant message, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn without_a_fallback_the_error_is_surfaced() {
+    let dir = TempDir::new().unwrap();
+    let primary = UnsupportedProvider {
+        requests: Mutex::new(Vec::new()),
+    };
+    let registry = ToolRegistry::new();
+    let prompt = SessionPrompt::new(&primary, &registry, dir.path());
+    let mut session = Session::new(dir.path());
+
+    let error = prompt
+        .prompt(&mut session, input(), &mut |_| {})
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, AgentError::ModelNotSupported { .. }));
+    assert_eq!(primary.requests.lock().unwrap().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cli_fallback_model_takes_over() {
+    let data_dir = TempDir::new().unwrap();
+    let server = MockServer::start(vec![MockResponse::json(
+        401,
+        json!({
+            "type": "error",
+            "error": { "type": "ModelError", "message": "Model minimax-m2.5-free not supported" }
+        }),
+    )])
+    .await;
+
+    let output = tokio::task::spawn_blocking(move || {
+        assert_cmd::Command::cargo_bin("agent")
+            .unwrap()
+            .args([
+                "--model",
+                "opencode/minimax-m2.5-free",
+                "--fallback-model",
+                "link-assistant/echo",
+                "--compact-json",
+                "-p",
+                "hi",
+            ])
+            .env("LINK_ASSISTANT_AGENT_DATA_DIR", data_dir.path())
+            .env("LINK_ASSISTANT_AGENT_DISABLE_MODELS_FETCH", "1")
+            .env("OPENCODE_BASE_URL", &server.url)
+            .output()
+            .unwrap()
+    })
+    .await
+    .unwrap();
+
+    let events: Vec<Value> = serde_json::Deserializer::from_slice(&output.stdout)
+        .into_iter::<Value>()
+        .filter_map(|event| event.ok())
+        .collect();
+    assert!(events.iter().any(|event| event["type"] == "warning"
+        && event["message"]
+            .as_str()
+            .unwrap()
+            .contains("Falling back to link-assistant/echo")));
+    assert!(events
+        .iter()
+        .any(|event| event["type"] == "text" && event["text"] == "hi"));
+    assert!(!events.iter().any(|event| event["type"] == "error"));
+}
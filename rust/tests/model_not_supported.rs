@@ -1,23 +1,25 @@
 //! Rust counterpart of `js/tests/model-not-supported.ts`.
 //!
-//! The JS test asserts that the agent surfaces a clear error when the
-//! configured model is not supported by the active provider. The Rust port
-//! does not yet ship a model registry that can validate provider/model
-//! combinations, so the JS-only paths cannot be exercised directly.
-//!
-//! These tests verify the equivalent surface that *is* observable in Rust:
-//! the CLI accepts any non-empty `--model` value (validation happens later
-//! at the provider boundary) and the centralized default model string is
-//! consistent with the documented free-tier model set.
+//! Some providers answer requests for models they no longer serve with
+//! HTTP 401 and a `ModelError` body. The agent reports these as
+//! `ModelNotSupportedError` rather than an authentication failure, so the
+//! opt-in `--fallback-model` can take over (see `model_fallback.rs`).
+
+mod common;
 
 use clap::Parser;
+use common::{MockResponse, MockServer};
 use link_assistant_agent::cli::{Args, DEFAULT_MODEL};
+use link_assistant_agent::error::AgentError;
+use link_assistant_agent::provider::openai::OpenAiCompatibleProvider;
+use link_assistant_agent::provider::{is_model_not_supported, ChatMessage, ChatRequest, Provider};
+use serde_json::json;
 
 #[test]
 fn cli_rejects_empty_model() {
     let result = Args::try_parse_from(["agent", "--model", ""]);
-    // clap allows empty strings by default; the agent layer is responsible
-    // for surfacing a "model not supported" error. Just make sure it parses.
+    // clap allows empty strings by default; catalog validation is responsible
+    // for rejecting it. Just make sure it parses.
     assert!(result.is_ok());
 }
 
@@ -39,3 +41,76 @@ fn cli_accepts_arbitrary_provider_model_combinations() {
 fn default_model_is_the_documented_free_tier_model() {
     assert_eq!(DEFAULT_MODEL, "opencode/minimax-m2.5-free");
 }
+
+#[test]
+fn detects_nested_and_flat_model_errors() {
+    let nested = json!({
+        "type": "error",
+        "error": { "type": "ModelError", "message": "Model kimi-k2.5-free not supported" }
+    });
+    let flat = json!({ "type": "ModelError", "message": "Model not available" });
+    assert!(is_model_not_supported(&nested.to_string()));
+    assert!(is_model_not_supported(&flat.to_string()));
+}
+
+#[test]
+fn does_not_flag_other_errors() {
+    let auth =
+        json!({ "type": "error", "error": { "type": "AuthError", "message": "Invalid API key" } });
+    let rate_limit = json!({ "type": "error", "error": { "type": "RateLimitError", "message": "Rate limit exceeded" } });
+    assert!(!is_model_not_supported(&auth.to_string()));
+    assert!(!is_model_not_supported(&rate_limit.to_string()));
+    assert!(!is_model_not_supported("{}"));
+    assert!(!is_model_not_supported("Unauthorized"));
+    assert!(!is_model_not_supported(""));
+    assert!(!is_model_not_supported("{"));
+}
+
+#[test]
+fn plain_text_bodies_fall_back_to_patterns() {
+    assert!(is_model_not_supported(
+        "Error: ModelError: Model not available"
+    ));
+    assert!(is_model_not_supported(
+        "Model not supported: kimi-k2.5-free"
+    ));
+    assert!(is_model_not_supported("Model NOT FOUND: kimi-k2.5-free"));
+    assert!(is_model_not_supported("{ broken json with ModelError text"));
+}
+
+#[tokio::test]
+async fn unauthorized_model_errors_are_not_reported_as_auth_failures() {
+    let server = MockServer::start(vec![MockResponse::json(
+        401,
+        json!({
+            "type": "error",
+            "error": { "type": "ModelError", "message": "Model kimi-k2.5-free not supported" }
+        }),
+    )])
+    .await;
+    let provider = OpenAiCompatibleProvider::new("opencode", &server.url).with_api_key("public");
+
+    let error = provider
+        .complete(&ChatRequest {
+            model: "kimi-k2.5-free".to_string(),
+            system: Vec::new(),
+            messages: vec![ChatMessage::User {
+                text: "hi".to_string(),
+            }],
+            tools: Vec::new(),
+            temperature: None,
+            max_output_tokens: None,
+            thinking_budget: None,
+        })
+        .await
+        .unwrap_err();
+
+    match &error {
+        AgentError::ModelNotSupported { provider, message } => {
+            assert_eq!(provider, "opencode");
+            assert_eq!(message, "Model kimi-k2.5-free not supported");
+        }
+        other => panic!("expected ModelNotSupported, got {other:?}"),
+    }
+    assert_eq!(error.to_json()["name"], "ModelNotSupportedError");
+}
@@ -1,17 +1,18 @@
 //! Rust counterpart of `js/tests/model-strict-validation.ts`.
 //!
-//! The JS suite exercises strict validation against the OpenCode Zen and
-//! models.dev metadata endpoints. The Rust port does not yet hit those
-//! endpoints, and storage migration / provider lookup logic is JS-only.
-//!
-//! These tests verify the surface that *is* observable in Rust: the
-//! centralized default constants are well-formed, every provider id and
-//! model id pair extracted from the defaults is non-empty, and CLI parsing
-//! preserves the model string exactly.
+//! `--model` is checked against the models catalog before any request is
+//! made: unknown provider/model pairs fail with a structured
+//! `ProviderModelNotFoundError` listing close matches instead of silently
+//! using another model. The catalog is the bundled snapshot here, so the
+//! tests never reach models.dev.
 
 use clap::Parser;
 use link_assistant_agent::cli::{Args, DEFAULT_COMPACTION_MODEL, DEFAULT_MODEL};
 use link_assistant_agent::defaults::{model_parts, ModelParts};
+use link_assistant_agent::error::AgentError;
+use link_assistant_agent::provider::models::Catalog;
+use link_assistant_agent::provider::validate_from_env;
+use serde_json::Value;
 
 fn assert_well_formed_model(model: &str) {
     let ModelParts {
@@ -60,3 +61,94 @@ fn parsing_unusual_models_does_not_panic() {
         assert!(!parts.provider_id.is_empty());
     }
 }
+
+fn validate(model: &str) -> Result<(), AgentError> {
+    validate_from_env(&Catalog::bundled(), &model_parts(model), |_| None)
+}
+
+fn suggestions(model: &str) -> Vec<String> {
+    match validate(model) {
+        Err(AgentError::ModelNotFound { suggestions, .. }) => suggestions,
+        other => panic!("expected ModelNotFound for {model}, got {other:?}"),
+    }
+}
+
+#[test]
+fn catalog_models_pass_validation() {
+    for model in [
+        DEFAULT_MODEL,
+        DEFAULT_COMPACTION_MODEL,
+        "anthropic/claude-sonnet-4-5",
+        "claude-oauth/claude-sonnet-4-5",
+        "link-assistant/echo",
+    ] {
+        validate(model).unwrap_or_else(|e| panic!("{model} rejected: {e}"));
+    }
+}
+
+#[test]
+fn misspelled_model_lists_close_matches() {
+    let suggestions = suggestions("opencode/minimax-m2.5-fre");
+    assert_eq!(suggestions[0], "opencode/minimax-m2.5-free");
+    assert!(suggestions.len() <= 3);
+}
+
+#[test]
+fn unknown_provider_is_rejected() {
+    let error = validate("opencod/minimax-m2.5-free").unwrap_err();
+    let json = error.to_json();
+    assert_eq!(json["name"], "ProviderModelNotFoundError");
+    assert_eq!(json["data"]["providerID"], "opencod");
+    assert_eq!(json["data"]["modelID"], "minimax-m2.5-free");
+    assert_eq!(json["data"]["suggestions"][0], "opencode/minimax-m2.5-free");
+    assert!(json["data"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("Did you mean one of these?"));
+}
+
+#[test]
+fn model_without_provider_suggests_the_qualified_name() {
+    assert!(suggestions("big-pickle").contains(&"opencode/big-pickle".to_string()));
+}
+
+#[test]
+fn providers_outside_the_catalog_are_not_checked() {
+    // Local servers serve whatever models are installed
+    validate("ollama/qwen3:8b").unwrap();
+    // Cache recordings may name any upstream model
+    validate("link-assistant/cache/groq/llama").unwrap();
+    // Custom OpenAI-compatible endpoints
+    validate_from_env(
+        &Catalog::bundled(),
+        &model_parts("my-proxy/any-model"),
+        |key| (key == "MY_PROXY_BASE_URL").then(|| "http://localhost:9999/v1".to_string()),
+    )
+    .unwrap();
+    assert!(validate("my-proxy/any-model").is_err());
+}
+
+#[test]
+fn cli_reports_unknown_models_before_calling_the_provider() {
+    let data_dir = tempfile::TempDir::new().unwrap();
+    let output = assert_cmd::Command::cargo_bin("agent")
+        .unwrap()
+        .args(["--model", "opencode/minimax-m2.5-fre", "--compact-json"])
+        .write_stdin("hi\n")
+        .env("LINK_ASSISTANT_AGENT_DATA_DIR", data_dir.path())
+        .env("LINK_ASSISTANT_AGENT_DISABLE_MODELS_FETCH", "1")
+        .env("OPENCODE_BASE_URL", "http://127.0.0.1:9/v1")
+        .output()
+        .unwrap();
+
+    let error = serde_json::Deserializer::from_slice(&output.stdout)
+        .into_iter::<Value>()
+        .filter_map(|event| event.ok())
+        .find(|event| event["type"] == "error")
+        .expect("an error event");
+    assert_eq!(error["error"]["name"], "ProviderModelNotFoundError");
+    assert_eq!(
+        error["error"]["data"]["suggestions"][0],
+        "opencode/minimax-m2.5-free"
+    );
+}
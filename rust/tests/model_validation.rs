@@ -1,18 +1,17 @@
 //! Rust counterpart of `js/tests/model-validation.ts`.
 //!
 //! The JS test exercises model parsing, finish-reason inference, loop-exit
-//! conditions and provider state. Most of that surface is JS-specific
-//! (the AI SDK provider wrappers, the loop runtime, etc.). The Rust port
-//! exposes the model parsing helpers via `link_assistant_agent::defaults`
-//! and it is the only piece that can be mirrored 1:1 right now.
-//!
-//! These tests cover the equivalent Rust-side surface.
+//! conditions and provider state. The Rust side covers model parsing via
+//! `link_assistant_agent::defaults` and checks the parsed parts against the
+//! models catalog, which is what `--model` validation relies on.
 
 use link_assistant_agent::cli::DEFAULT_MODEL;
 use link_assistant_agent::defaults::{
     default_model_from_env, default_model_parts_from_env, model_parts, ModelParts,
     DEFAULT_MODEL_ENV,
 };
+use link_assistant_agent::error::AgentError;
+use link_assistant_agent::provider::models::Catalog;
 
 fn empty_env() -> impl Fn(&str) -> Option<String> {
     |_| None
@@ -72,3 +71,32 @@ fn model_parts_handle_trailing_slash() {
     assert_eq!(parts.provider_id, "opencode");
     assert_eq!(parts.model_id, "");
 }
+
+#[test]
+fn default_model_is_in_the_catalog() {
+    let catalog = Catalog::bundled();
+    catalog
+        .check(&default_model_parts_from_env(empty_env()))
+        .unwrap();
+    catalog
+        .check(&default_model_parts_from_env(env_with(
+            DEFAULT_MODEL_ENV,
+            "groq/llama-3.3-70b-versatile",
+        )))
+        .unwrap();
+}
+
+#[test]
+fn blank_model_is_not_in_the_catalog() {
+    match Catalog::bundled().check(&model_parts("")) {
+        Err(AgentError::ModelNotFound {
+            provider_id,
+            model_id,
+            ..
+        }) => {
+            assert_eq!(provider_id, "");
+            assert_eq!(model_id, "");
+        }
+        other => panic!("expected ModelNotFound, got {:?}", other),
+    }
+}
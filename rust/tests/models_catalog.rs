@@ -0,0 +1,144 @@
+//! Tests for the models catalog and its data-directory cache.
+
+mod common;
+
+use common::{MockResponse, MockServer};
+use link_assistant_agent::defaults::model_parts;
+use link_assistant_agent::global::{data_dir_from_env, DATA_DIR_ENV};
+use link_assistant_agent::provider::models::{
+    get_from_env, Catalog, ModelsCache, CACHE_FILE, DISABLE_FETCH_ENV,
+};
+use serde_json::json;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+fn catalog_json(context: u64) -> serde_json::Value {
+    json!({
+        "groq": {
+            "id": "groq",
+            "name": "Groq",
+            "env": ["GROQ_API_KEY"],
+            "models": {
+                "llama": {
+                    "id": "llama",
+                    "name": "Llama",
+                    "tool_call": true,
+                    "cost": { "input": 0.5, "output": 1.0 },
+                    "limit": { "context": context, "output": 8192 }
+                }
+            }
+        }
+    })
+}
+
+#[test]
+fn bundled_snapshot_knows_the_default_model() {
+    let catalog = Catalog::bundled();
+    let model = catalog
+        .lookup(&model_parts("opencode/minimax-m2.5-free"))
+        .expect("default model is in the snapshot");
+    assert_eq!(model.limit.context, 204_800);
+    assert_eq!(model.cost.base.input, 0.0);
+    assert!(catalog
+        .lookup(&model_parts("link-assistant/echo"))
+        .is_some());
+}
+
+#[test]
+fn claude_oauth_shares_anthropic_entries() {
+    let catalog = Catalog::bundled();
+    let anthropic = catalog.lookup(&model_parts("anthropic/claude-sonnet-4-5"));
+    assert!(anthropic.is_some());
+    assert_eq!(
+        catalog.lookup(&model_parts("claude-oauth/claude-sonnet-4-5")),
+        anthropic
+    );
+}
+
+#[test]
+fn context_limit_is_none_for_unknown_models() {
+    let catalog = Catalog::from_json(&catalog_json(0).to_string()).unwrap();
+    assert_eq!(catalog.context_limit(&model_parts("groq/llama")), None);
+    assert_eq!(catalog.context_limit(&model_parts("groq/missing")), None);
+    assert!(Catalog::from_json("[1, 2]").is_err());
+}
+
+#[tokio::test]
+async fn refresh_writes_the_cache() {
+    let dir = TempDir::new().unwrap();
+    let server = MockServer::start(vec![MockResponse::json(200, catalog_json(131_072))]).await;
+    let cache = ModelsCache::new(dir.path()).with_url(format!("{}/api.json", server.url));
+    assert!(cache.is_stale());
+
+    let catalog = cache.get().await;
+
+    assert_eq!(
+        catalog.context_limit(&model_parts("groq/llama")),
+        Some(131_072)
+    );
+    assert!(dir.path().join(CACHE_FILE).exists());
+    assert!(!cache.is_stale());
+    assert_eq!(server.requests()[0].path, "/api.json");
+
+    // Fresh caches are used without another request
+    cache.get().await;
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn invalid_responses_keep_the_existing_cache() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join(CACHE_FILE);
+    std::fs::write(&path, catalog_json(1000).to_string()).unwrap();
+    let server = MockServer::start(vec![
+        MockResponse::json(200, json!(["not", "a", "catalog"])),
+        MockResponse::json(503, json!({ "error": "down" })),
+    ])
+    .await;
+    let cache = ModelsCache::new(dir.path()).with_url(server.url.clone());
+
+    assert!(cache.refresh().await.is_err());
+    assert!(cache.refresh().await.is_err());
+    assert_eq!(
+        cache.load().context_limit(&model_parts("groq/llama")),
+        Some(1000)
+    );
+}
+
+#[tokio::test]
+async fn offline_lookups_fall_back_to_the_bundled_snapshot() {
+    let dir = TempDir::new().unwrap();
+    let catalog = get_from_env(dir.path(), |key| {
+        (key == DISABLE_FETCH_ENV).then(|| "1".to_string())
+    })
+    .await;
+    assert_eq!(catalog, Catalog::bundled());
+    assert!(!dir.path().join(CACHE_FILE).exists());
+
+    // Unreachable servers are not fatal either
+    let cache = ModelsCache::new(dir.path()).with_url("http://127.0.0.1:9/api.json");
+    assert_eq!(cache.get().await, Catalog::bundled());
+}
+
+#[test]
+fn data_dir_resolution_order() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |key: &str| {
+            vars.iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value.to_string())
+        }
+    };
+    assert_eq!(
+        data_dir_from_env(env(&[(DATA_DIR_ENV, "/data"), ("HOME", "/home/u")])),
+        PathBuf::from("/data")
+    );
+    assert_eq!(
+        data_dir_from_env(env(&[("XDG_DATA_HOME", "/xdg"), ("HOME", "/home/u")])),
+        PathBuf::from("/xdg/link-assistant-agent")
+    );
+    assert_eq!(
+        data_dir_from_env(env(&[("HOME", "/home/u")])),
+        PathBuf::from("/home/u/.local/share/link-assistant-agent")
+    );
+}
@@ -0,0 +1,487 @@
+//! Tests for the Anthropic Messages API provider and Claude OAuth credentials.
+//!
+//! Requests go to a local mock server that replays streamed message events.
+
+mod common;
+
+use assert_cmd::Command;
+use common::{MockResponse, MockServer};
+use link_assistant_agent::auth::{
+    claude_credentials_path, claude_oauth_token_from_env, read_claude_credentials,
+};
+use link_assistant_agent::defaults::model_parts;
+use link_assistant_agent::error::AgentError;
+use link_assistant_agent::provider::anthropic::{AnthropicAuth, AnthropicProvider, SYSTEM_HEADER};
+use link_assistant_agent::provider::{
+    create_from_env, ChatMessage, ChatRequest, ContentPart, Provider, ToolSpec,
+};
+use predicates::prelude::*;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+fn request(messages: Vec<ChatMessage>) -> ChatRequest {
+    ChatRequest {
+        model: "claude-sonnet-4-5".to_string(),
+        system: vec!["base prompt".to_string(), "environment".to_string()],
+        messages,
+        tools: vec![
+            ToolSpec {
+                name: "read".to_string(),
+                description: "Read a file".to_string(),
+                parameters: json!({ "type": "object" }),
+            },
+            ToolSpec {
+                name: "write".to_string(),
+                description: "Write a file".to_string(),
+                parameters: json!({ "type": "object" }),
+            },
+        ],
+        temperature: None,
+        max_output_tokens: None,
+        thinking_budget: None,
+    }
+}
+
+fn user(text: &str) -> ChatMessage {
+    ChatMessage::User {
+        text: text.to_string(),
+    }
+}
+
+fn events(events: Vec<Value>) -> MockResponse {
+    let payloads: Vec<String> = events.iter().map(|e| e.to_string()).collect();
+    MockResponse::sse(&payloads)
+}
+
+fn message_start() -> Value {
+    json!({
+        "type": "message_start",
+        "message": {
+            "model": "claude-sonnet-4-5-20250929",
+            "usage": {
+                "input_tokens": 20,
+                "cache_read_input_tokens": 100,
+                "cache_creation_input_tokens": 7,
+                "output_tokens": 1
+            }
+        }
+    })
+}
+
+#[tokio::test]
+async fn streams_thinking_text_and_tool_use_blocks() {
+    let server = MockServer::start(vec![events(vec![
+        message_start(),
+        json!({ "type": "content_block_start", "index": 0,
+            "content_block": { "type": "thinking", "thinking": "" } }),
+        json!({ "type": "content_block_delta", "index": 0,
+            "delta": { "type": "thinking_delta", "thinking": "Let me look" } }),
+        json!({ "type": "content_block_delta", "index": 0,
+            "delta": { "type": "signature_delta", "signature": "sig-1" } }),
+        json!({ "type": "content_block_stop", "index": 0 }),
+        json!({ "type": "content_block_start", "index": 1,
+            "content_block": { "type": "text", "text": "" } }),
+        json!({ "type": "ping" }),
+        json!({ "type": "content_block_delta", "index": 1,
+            "delta": { "type": "text_delta", "text": "Reading it." } }),
+        json!({ "type": "content_block_start", "index": 2,
+            "content_block": { "type": "tool_use", "id": "toolu_1", "name": "read", "input": {} } }),
+        json!({ "type": "content_block_delta", "index": 2,
+            "delta": { "type": "input_json_delta", "partial_json": "{\"filePath\":" } }),
+        json!({ "type": "content_block_delta", "index": 2,
+            "delta": { "type": "input_json_delta", "partial_json": "\"a.txt\"}" } }),
+        json!({ "type": "message_delta", "delta": { "stop_reason": "tool_use" },
+            "usage": { "output_tokens": 42 } }),
+        json!({ "type": "message_stop" }),
+    ])])
+    .await;
+    let provider = AnthropicProvider::new("anthropic", AnthropicAuth::ApiKey("key".into()))
+        .with_base_url(&server.url);
+
+    let response = provider.complete(&request(vec![user("hi")])).await.unwrap();
+
+    assert_eq!(
+        response.content,
+        vec![
+            ContentPart::Reasoning {
+                text: "Let me look".to_string(),
+                metadata: Some(json!({ "signature": "sig-1" })),
+            },
+            ContentPart::Text {
+                text: "Reading it.".to_string()
+            },
+            ContentPart::ToolCall {
+                call_id: "toolu_1".to_string(),
+                tool: "read".to_string(),
+                input: json!({ "filePath": "a.txt" }),
+            },
+        ]
+    );
+    assert_eq!(response.finish_reason, "tool-calls");
+    assert_eq!(
+        response.model.as_deref(),
+        Some("claude-sonnet-4-5-20250929")
+    );
+    assert_eq!(response.usage.input, 20);
+    assert_eq!(response.usage.output, 42);
+    assert_eq!(response.usage.cache_read, 100);
+    assert_eq!(response.usage.cache_write, 7);
+}
+
+#[test]
+fn request_body_places_cache_breakpoints_and_round_trips_tools() {
+    let provider = AnthropicProvider::new("anthropic", AnthropicAuth::ApiKey("key".into()));
+    let body = provider.request_body(&request(vec![
+        user("read a.txt"),
+        ChatMessage::Assistant {
+            content: vec![
+                ContentPart::Reasoning {
+                    text: "thinking".to_string(),
+                    metadata: Some(json!({ "signature": "sig" })),
+                },
+                ContentPart::ToolCall {
+                    call_id: "toolu_1".to_string(),
+                    tool: "read".to_string(),
+                    input: json!({ "filePath": "a.txt" }),
+                },
+            ],
+        },
+        ChatMessage::Tool {
+            call_id: "toolu_1".to_string(),
+            tool: "read".to_string(),
+            output: "contents".to_string(),
+            is_error: false,
+        },
+    ]));
+
+    assert_eq!(body["stream"], true);
+    assert!(body["max_tokens"].as_u64().unwrap() > 0);
+    assert_eq!(body["system"][0]["cache_control"]["type"], "ephemeral");
+    assert_eq!(body["system"][1]["cache_control"]["type"], "ephemeral");
+    assert!(body["tools"][0].get("cache_control").is_none());
+    assert_eq!(body["tools"][1]["cache_control"]["type"], "ephemeral");
+    assert_eq!(
+        body["tools"][1]["input_schema"],
+        json!({ "type": "object" })
+    );
+
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(
+        messages[1]["content"][0],
+        json!({ "type": "thinking", "thinking": "thinking", "signature": "sig" })
+    );
+    assert_eq!(messages[1]["content"][1]["type"], "tool_use");
+    assert_eq!(messages[1]["content"][1]["input"]["filePath"], "a.txt");
+    assert_eq!(messages[2]["role"], "user");
+    assert_eq!(messages[2]["content"][0]["type"], "tool_result");
+    assert_eq!(messages[2]["content"][0]["tool_use_id"], "toolu_1");
+    assert_eq!(
+        messages[2]["content"][0]["cache_control"]["type"],
+        "ephemeral"
+    );
+}
+
+#[tokio::test]
+async fn authentication_headers_follow_credential_type() {
+    let done = || {
+        events(vec![
+            message_start(),
+            json!({ "type": "message_delta", "delta": { "stop_reason": "end_turn" },
+                "usage": { "output_tokens": 1 } }),
+        ])
+    };
+    let server = MockServer::start(vec![done(), done()]).await;
+
+    AnthropicProvider::new("anthropic", AnthropicAuth::ApiKey("sk-ant".into()))
+        .with_base_url(&server.url)
+        .complete(&request(vec![user("hi")]))
+        .await
+        .unwrap();
+    AnthropicProvider::new("claude-oauth", AnthropicAuth::OAuth("oauth-token".into()))
+        .with_base_url(&server.url)
+        .complete(&request(vec![user("hi")]))
+        .await
+        .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path, "/messages");
+    assert_eq!(requests[0].header("x-api-key"), Some("sk-ant"));
+    assert_eq!(requests[0].header("anthropic-version"), Some("2023-06-01"));
+    assert!(requests[0].header("authorization").is_none());
+    assert_eq!(
+        requests[1].header("authorization"),
+        Some("Bearer oauth-token")
+    );
+    assert!(requests[1].header("x-api-key").is_none());
+    assert!(requests[1]
+        .header("anthropic-beta")
+        .unwrap()
+        .starts_with("oauth-2025-04-20,"));
+}
+
+#[tokio::test]
+async fn overloaded_errors_are_retryable() {
+    let server = MockServer::start(vec![
+        MockResponse::json(
+            529,
+            json!({ "type": "error", "error": { "type": "overloaded_error", "message": "Overloaded" } }),
+        ),
+        events(vec![
+            message_start(),
+            json!({ "type": "error", "error": { "type": "overloaded_error", "message": "Overloaded" } }),
+        ]),
+    ])
+    .await;
+    let provider = AnthropicProvider::new("anthropic", AnthropicAuth::ApiKey("key".into()))
+        .with_base_url(&server.url);
+
+    for _ in 0..2 {
+        match provider.complete(&request(vec![user("hi")])).await {
+            Err(AgentError::Api {
+                message, retryable, ..
+            }) => {
+                assert_eq!(message, "Overloaded");
+                assert!(retryable);
+            }
+            other => panic!("expected API error, got {:?}", other.map(|_| ())),
+        }
+    }
+}
+
+fn write_credentials(home: &TempDir, token: &str) {
+    let path = claude_credentials_path(home.path());
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(
+        &path,
+        json!({
+            "claudeAiOauth": {
+                "accessToken": token,
+                "refreshToken": "refresh",
+                "expiresAt": 4102444800000u64,
+                "scopes": ["user:inference"],
+                "subscriptionType": "max"
+            }
+        })
+        .to_string(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn request_body_starts_the_system_prompt_with_the_header() {
+    let provider = AnthropicProvider::new("claude-oauth", AnthropicAuth::OAuth("token".into()));
+    let body = provider.request_body(&request(vec![user("hi")]));
+    let system = body["system"].as_array().unwrap();
+    assert_eq!(system.len(), 3);
+    assert_eq!(
+        system[0]["text"],
+        "You are Claude Code, Anthropic's official CLI for Claude."
+    );
+    assert_eq!(system[0]["cache_control"]["type"], "ephemeral");
+    assert_eq!(system[1]["text"], "base prompt");
+    assert!(system[2].get("cache_control").is_none());
+
+    // Requests without a system prompt still carry the header
+    let mut bare = request(vec![user("hi")]);
+    bare.system.clear();
+    let body = provider.request_body(&bare);
+    assert_eq!(
+        body["system"],
+        json!([{
+            "type": "text",
+            "text": SYSTEM_HEADER,
+            "cache_control": { "type": "ephemeral" },
+        }])
+    );
+}
+
+#[test]
+fn claude_credentials_are_read_from_home() {
+    let home = TempDir::new().unwrap();
+    assert_eq!(
+        read_claude_credentials(&claude_credentials_path(home.path())).unwrap(),
+        None
+    );
+
+    write_credentials(&home, "file-token");
+    let creds = read_claude_credentials(&claude_credentials_path(home.path()))
+        .unwrap()
+        .unwrap();
+    assert_eq!(creds.access_token, "file-token");
+    assert_eq!(creds.subscription_type.as_deref(), Some("max"));
+    assert!(!creds.is_expired(0));
+
+    let home_path = home.path().to_string_lossy().to_string();
+    let token = claude_oauth_token_from_env(|key| match key {
+        "HOME" => Some(home_path.clone()),
+        _ => None,
+    })
+    .unwrap();
+    assert_eq!(token.as_deref(), Some("file-token"));
+
+    let token = claude_oauth_token_from_env(|key| match key {
+        "CLAUDE_CODE_OAUTH_TOKEN" => Some("env-token".to_string()),
+        "HOME" => Some(home_path.clone()),
+        _ => None,
+    })
+    .unwrap();
+    assert_eq!(token.as_deref(), Some("env-token"));
+}
+
+#[test]
+fn expired_claude_credentials_are_rejected() {
+    let home = TempDir::new().unwrap();
+    let path = claude_credentials_path(home.path());
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(
+        &path,
+        json!({
+            "claudeAiOauth": { "accessToken": "stale", "expiresAt": 1_000u64 }
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let err = read_claude_credentials(&path).unwrap_err();
+    assert!(matches!(err, AgentError::Authentication { .. }));
+    assert!(err.to_string().contains("expired"));
+
+    // The environment token is used without touching the file
+    let home_path = home.path().to_string_lossy().to_string();
+    let token = claude_oauth_token_from_env(|key| match key {
+        "CLAUDE_CODE_OAUTH_TOKEN" => Some("env-token".to_string()),
+        "HOME" => Some(home_path.clone()),
+        _ => None,
+    })
+    .unwrap();
+    assert_eq!(token.as_deref(), Some("env-token"));
+    let err =
+        claude_oauth_token_from_env(|key| (key == "HOME").then(|| home_path.clone())).unwrap_err();
+    assert!(matches!(err, AgentError::Authentication { .. }));
+}
+
+#[test]
+fn create_from_env_resolves_anthropic_providers() {
+    let home = TempDir::new().unwrap();
+    let home_path = home.path().to_string_lossy().to_string();
+
+    let err = create_from_env(&model_parts("claude-oauth/claude-sonnet-4-5"), |key| {
+        (key == "HOME").then(|| home_path.clone())
+    })
+    .err()
+    .unwrap();
+    assert!(matches!(err, AgentError::Authentication { .. }));
+
+    write_credentials(&home, "file-token");
+    let provider = create_from_env(&model_parts("claude-oauth/claude-sonnet-4-5"), |key| {
+        (key == "HOME").then(|| home_path.clone())
+    })
+    .unwrap();
+    assert_eq!(provider.id(), "claude-oauth");
+
+    let provider = create_from_env(&model_parts("anthropic/claude-sonnet-4-5"), |key| {
+        (key == "ANTHROPIC_API_KEY").then(|| "sk-ant".to_string())
+    })
+    .unwrap();
+    assert_eq!(provider.id(), "anthropic");
+}
+
+#[test]
+fn request_body_enables_thinking_with_a_budget() {
+    let provider = AnthropicProvider::new("anthropic", AnthropicAuth::ApiKey("sk-ant".into()));
+    let mut request = request(vec![user("hi")]);
+    request.max_output_tokens = Some(32_000);
+    request.temperature = Some(0.5);
+
+    let body = provider.request_body(&request);
+    assert!(body.get("thinking").is_none());
+    assert_eq!(body["max_tokens"], 32_000);
+    assert_eq!(body["temperature"], 0.5);
+
+    request.thinking_budget = Some(16_000);
+    let body = provider.request_body(&request);
+    assert_eq!(
+        body["thinking"],
+        json!({ "type": "enabled", "budget_tokens": 16_000 })
+    );
+    // The budget comes on top of the answer, at the default temperature
+    assert_eq!(body["max_tokens"], 48_000);
+    assert!(body.get("temperature").is_none());
+}
+
+#[test]
+fn use_existing_claude_oauth_requires_credentials() {
+    let home = TempDir::new().unwrap();
+    Command::cargo_bin("agent")
+        .unwrap()
+        .args(["--use-existing-claude-oauth", "-p", "hello"])
+        .env("HOME", home.path())
+        .env_remove("USERPROFILE")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "No Claude OAuth credentials found",
+        ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn use_existing_claude_oauth_authenticates_an_anthropic_fallback() {
+    let home = TempDir::new().unwrap();
+    write_credentials(&home, "oauth-token");
+    let data_dir = TempDir::new().unwrap();
+    let primary = MockServer::start(vec![MockResponse::json(
+        401,
+        json!({
+            "type": "error",
+            "error": { "type": "ModelError", "message": "Model big-pickle not supported" }
+        }),
+    )])
+    .await;
+    let fallback = MockServer::start(vec![events(vec![
+        message_start(),
+        json!({ "type": "content_block_start", "index": 0,
+            "content_block": { "type": "text", "text": "" } }),
+        json!({ "type": "content_block_delta", "index": 0,
+            "delta": { "type": "text_delta", "text": "from fallback" } }),
+        json!({ "type": "message_delta", "delta": { "stop_reason": "end_turn" },
+            "usage": { "output_tokens": 1 } }),
+    ])])
+    .await;
+
+    let (home_path, data_path) = (home.path().to_owned(), data_dir.path().to_owned());
+    let (primary_url, fallback_url) = (primary.url.clone(), fallback.url.clone());
+    let output = tokio::task::spawn_blocking(move || {
+        Command::cargo_bin("agent")
+            .unwrap()
+            .args([
+                "--use-existing-claude-oauth",
+                "--model",
+                "opencode/big-pickle",
+                "--fallback-model",
+                "anthropic/claude-sonnet-4-5",
+                "-p",
+                "hello",
+            ])
+            .env("HOME", home_path)
+            .env_remove("USERPROFILE")
+            // The flag takes precedence over an API key, as for the main model
+            .env("ANTHROPIC_API_KEY", "sk-ant")
+            .env("LINK_ASSISTANT_AGENT_DATA_DIR", data_path)
+            .env("LINK_ASSISTANT_AGENT_DISABLE_MODELS_FETCH", "1")
+            .env("OPENCODE_BASE_URL", primary_url)
+            .env("ANTHROPIC_BASE_URL", fallback_url)
+            .output()
+            .unwrap()
+    })
+    .await
+    .unwrap();
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains("from fallback"));
+    let requests = fallback.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].header("authorization"),
+        Some("Bearer oauth-token")
+    );
+}
@@ -0,0 +1,88 @@
+//! Tests for the record/replay cache provider (`link-assistant/cache/...`).
+
+mod common;
+
+use common::{MockResponse, MockServer};
+use link_assistant_agent::defaults::model_parts;
+use link_assistant_agent::provider::cache::{cache_key, CACHE_DIR_ENV};
+use link_assistant_agent::provider::{create_from_env, ChatMessage, ChatRequest, ContentPart};
+use serde_json::json;
+use tempfile::TempDir;
+
+fn request(text: &str) -> ChatRequest {
+    ChatRequest {
+        model: "cache/groq/llama".to_string(),
+        system: vec!["system".to_string()],
+        messages: vec![ChatMessage::User {
+            text: text.to_string(),
+        }],
+        tools: Vec::new(),
+        temperature: None,
+        max_output_tokens: None,
+        thinking_budget: None,
+    }
+}
+
+#[tokio::test]
+async fn misses_are_recorded_and_hits_replayed_offline() {
+    let cache = TempDir::new().unwrap();
+    let server = MockServer::start(vec![MockResponse::sse(&[
+        json!({ "model": "llama", "choices": [{ "delta": { "content": "recorded answer" }, "finish_reason": "stop" }] }).to_string(),
+        "[DONE]".to_string(),
+    ])])
+    .await;
+    let root = cache.path().to_string_lossy().to_string();
+    let url = server.url.clone();
+
+    let recording = create_from_env(
+        &model_parts("link-assistant/cache/groq/llama"),
+        |key| match key {
+            "GROQ_BASE_URL" => Some(url.clone()),
+            "GROQ_API_KEY" => Some("key".to_string()),
+            k if k == CACHE_DIR_ENV => Some(root.clone()),
+            _ => None,
+        },
+    )
+    .unwrap();
+    let first = recording.complete(&request("hello")).await.unwrap();
+    assert_eq!(
+        first.content,
+        vec![ContentPart::Text {
+            text: "recorded answer".to_string()
+        }]
+    );
+    assert_eq!(server.requests()[0].json()["model"], "llama");
+
+    let file = cache
+        .path()
+        .join("groq")
+        .join("llama")
+        .join(format!("{}.lino", cache_key(&request("hello"))));
+    let content = std::fs::read_to_string(&file).unwrap();
+    assert!(content.contains("\"recorded answer\""));
+
+    // No key and no server: replay must not touch the network
+    let replaying = create_from_env(&model_parts("link-assistant/cache/groq/llama"), |key| {
+        (key == CACHE_DIR_ENV).then(|| root.clone())
+    })
+    .unwrap();
+    let mut changed_system = request("hello");
+    changed_system.system = vec!["different environment".to_string()];
+    let replayed = replaying.complete(&changed_system).await.unwrap();
+    assert_eq!(replayed, first);
+    assert_eq!(server.requests().len(), 1);
+
+    let miss = replaying.complete(&request("something new")).await;
+    assert!(miss.is_err());
+}
+
+#[test]
+fn cache_model_ids_need_provider_and_model() {
+    assert!(create_from_env(&model_parts("link-assistant/cache/opencode"), |_| None).is_err());
+}
+
+#[test]
+fn cache_keys_depend_on_the_conversation() {
+    assert_eq!(cache_key(&request("a")), cache_key(&request("a")));
+    assert_ne!(cache_key(&request("a")), cache_key(&request("b")));
+}
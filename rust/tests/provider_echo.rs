@@ -0,0 +1,121 @@
+//! Tests for the echo provider (`link-assistant/echo`) and `--dry-run`.
+
+use assert_cmd::Command;
+use link_assistant_agent::defaults::model_parts;
+use link_assistant_agent::provider::echo::EchoProvider;
+use link_assistant_agent::provider::{
+    create_from_env, ChatMessage, ChatRequest, ContentPart, Provider,
+};
+use predicates::prelude::*;
+use serde_json::Value;
+
+fn request(messages: Vec<ChatMessage>, temperature: Option<f64>) -> ChatRequest {
+    ChatRequest {
+        model: "echo".to_string(),
+        system: Vec::new(),
+        messages,
+        tools: Vec::new(),
+        temperature,
+        max_output_tokens: None,
+        thinking_budget: None,
+    }
+}
+
+fn user(text: &str) -> ChatMessage {
+    ChatMessage::User {
+        text: text.to_string(),
+    }
+}
+
+fn texts(stdout: &[u8]) -> Vec<String> {
+    serde_json::Deserializer::from_slice(stdout)
+        .into_iter::<Value>()
+        .filter_map(|event| event.ok())
+        .filter(|event| event["type"] == "text")
+        .map(|event| event["text"].as_str().unwrap_or_default().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn echoes_the_latest_user_message() {
+    let response = EchoProvider::new()
+        .complete(&request(
+            vec![
+                user("first"),
+                ChatMessage::Assistant {
+                    content: vec![ContentPart::Text {
+                        text: "first".to_string(),
+                    }],
+                },
+                user("How are you?"),
+            ],
+            None,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        response.content,
+        vec![ContentPart::Text {
+            text: "How are you?".to_string()
+        }]
+    );
+    assert_eq!(response.finish_reason, "stop");
+    assert_eq!(response.usage.input, 3);
+}
+
+#[tokio::test]
+async fn dry_run_replies_are_labelled() {
+    let response = EchoProvider::dry_run()
+        .complete(&request(vec![user("hi")], Some(0.5)))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        response.content,
+        vec![ContentPart::Text {
+            text: "[DRY RUN] Received message: hi, temperature: 0.5".to_string()
+        }]
+    );
+}
+
+#[test]
+fn echo_model_is_registered() {
+    let provider = create_from_env(&model_parts("link-assistant/echo"), |_| None).unwrap();
+    assert_eq!(provider.id(), "link-assistant");
+}
+
+#[test]
+fn explicit_echo_model_runs_the_loop() {
+    let output = Command::cargo_bin("agent")
+        .unwrap()
+        .args([
+            "--model",
+            "link-assistant/echo",
+            "--compact-json",
+            "-p",
+            "hi",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(texts(&output.stdout), vec!["hi".to_string()]);
+}
+
+#[test]
+fn dry_run_handles_multi_turn_stdin() {
+    Command::cargo_bin("agent")
+        .unwrap()
+        .args(["--dry-run", "--compact-json"])
+        .write_stdin("first message\nsecond message\n")
+        .assert()
+        .success()
+        .stdout(predicate::function(|stdout: &str| {
+            texts(stdout.as_bytes())
+                == vec![
+                    "[DRY RUN] Received message: first message".to_string(),
+                    "[DRY RUN] Received message: second message".to_string(),
+                ]
+        }));
+}
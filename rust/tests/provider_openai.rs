@@ -0,0 +1,283 @@
+//! Tests for the OpenAI-compatible chat-completions provider.
+//!
+//! Requests go to a local mock server that replays streamed
+//! chat-completion chunks.
+
+mod common;
+
+use common::{MockResponse, MockServer};
+use link_assistant_agent::defaults::model_parts;
+use link_assistant_agent::error::AgentError;
+use link_assistant_agent::provider::openai::OpenAiCompatibleProvider;
+use link_assistant_agent::provider::{
+    create_from_env, ChatMessage, ChatRequest, ContentPart, Provider, ToolSpec,
+};
+use serde_json::json;
+use std::time::Duration;
+
+fn request() -> ChatRequest {
+    ChatRequest {
+        model: "test-model".to_string(),
+        system: vec!["be brief".to_string()],
+        messages: vec![ChatMessage::User {
+            text: "hi".to_string(),
+        }],
+        tools: vec![ToolSpec {
+            name: "read".to_string(),
+            description: "Read a file".to_string(),
+            parameters: json!({ "type": "object" }),
+        }],
+        temperature: Some(0.5),
+        max_output_tokens: None,
+        thinking_budget: None,
+    }
+}
+
+fn chunk(delta: serde_json::Value, finish: Option<&str>) -> String {
+    json!({
+        "model": "test-model-2024",
+        "choices": [{ "index": 0, "delta": delta, "finish_reason": finish }]
+    })
+    .to_string()
+}
+
+#[tokio::test]
+async fn streamed_text_and_usage_are_assembled() {
+    let server = MockServer::start(vec![MockResponse::sse(&[
+        chunk(json!({ "role": "assistant", "content": "Hel" }), None),
+        chunk(json!({ "content": "lo" }), Some("stop")),
+        json!({
+            "choices": [],
+            "usage": {
+                "prompt_tokens": 12,
+                "completion_tokens": 3,
+                "prompt_tokens_details": { "cached_tokens": 2 },
+                "completion_tokens_details": { "reasoning_tokens": 1 }
+            }
+        })
+        .to_string(),
+        "[DONE]".to_string(),
+    ])])
+    .await;
+    let provider = OpenAiCompatibleProvider::new("test", &server.url).with_api_key("sk-test");
+
+    let response = provider.complete(&request()).await.unwrap();
+
+    assert_eq!(
+        response.content,
+        vec![ContentPart::Text {
+            text: "Hello".to_string()
+        }]
+    );
+    assert_eq!(response.finish_reason, "stop");
+    assert_eq!(response.model.as_deref(), Some("test-model-2024"));
+    assert_eq!(response.usage.input, 10);
+    assert_eq!(response.usage.cache_read, 2);
+    assert_eq!(response.usage.output, 3);
+    assert_eq!(response.usage.reasoning, 1);
+}
+
+#[tokio::test]
+async fn request_carries_messages_tools_and_auth() {
+    let server = MockServer::start(vec![MockResponse::sse(&[
+        chunk(json!({ "content": "ok" }), Some("stop")),
+        "[DONE]".to_string(),
+    ])])
+    .await;
+    let provider = OpenAiCompatibleProvider::new("test", &server.url)
+        .with_api_key("sk-test")
+        .with_header("X-Title", "opencode");
+
+    provider.complete(&request()).await.unwrap();
+
+    let recorded = &server.requests()[0];
+    assert_eq!(recorded.method, "POST");
+    assert_eq!(recorded.path, "/chat/completions");
+    assert_eq!(recorded.header("authorization"), Some("Bearer sk-test"));
+    assert_eq!(recorded.header("x-title"), Some("opencode"));
+    let body = recorded.json();
+    assert_eq!(body["model"], "test-model");
+    assert_eq!(body["stream"], true);
+    assert_eq!(body["temperature"], 0.5);
+    assert_eq!(
+        body["messages"][0],
+        json!({ "role": "system", "content": "be brief" })
+    );
+    assert_eq!(
+        body["messages"][1],
+        json!({ "role": "user", "content": "hi" })
+    );
+    assert_eq!(body["tools"][0]["function"]["name"], "read");
+}
+
+#[tokio::test]
+async fn tool_calls_are_assembled_across_chunks() {
+    let server = MockServer::start(vec![MockResponse::sse(&[
+        chunk(
+            json!({ "tool_calls": [{ "index": 0, "id": "call_a", "type": "function",
+                "function": { "name": "read", "arguments": "{\"file" } }] }),
+            None,
+        ),
+        chunk(
+            json!({ "tool_calls": [{ "index": 0, "function": { "arguments": "Path\":\"a.txt\"}" } }] }),
+            None,
+        ),
+        chunk(
+            json!({ "tool_calls": [{ "index": 1, "id": "call_b",
+                "function": { "name": "list", "arguments": "" } }] }),
+            Some("tool_calls"),
+        ),
+        "[DONE]".to_string(),
+    ])])
+    .await;
+    let provider = OpenAiCompatibleProvider::new("test", &server.url);
+
+    let response = provider.complete(&request()).await.unwrap();
+
+    assert_eq!(response.finish_reason, "tool-calls");
+    let calls: Vec<_> = response.tool_calls().collect();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(
+        calls[0],
+        ("call_a", "read", &json!({ "filePath": "a.txt" }))
+    );
+    assert_eq!(calls[1], ("call_b", "list", &json!({})));
+}
+
+#[tokio::test]
+async fn events_split_across_network_chunks_are_decoded() {
+    let event = format!(
+        "data: {}\n\n",
+        chunk(json!({ "content": "héllo" }), Some("stop"))
+    );
+    let bytes = event.as_bytes();
+    let split = event.find('é').unwrap() + 1;
+    let server = MockServer::start(vec![MockResponse::chunked(
+        200,
+        vec![
+            (
+                Duration::ZERO,
+                String::from_utf8_lossy(&bytes[..10]).to_string(),
+            ),
+            (
+                Duration::from_millis(20),
+                String::from_utf8_lossy(&bytes[10..split - 1]).to_string(),
+            ),
+            (
+                Duration::from_millis(20),
+                String::from_utf8(bytes[split - 1..].to_vec()).unwrap(),
+            ),
+            (Duration::ZERO, "data: [DONE]\n\n".to_string()),
+        ],
+    )])
+    .await;
+    let provider = OpenAiCompatibleProvider::new("test", &server.url);
+
+    let response = provider.complete(&request()).await.unwrap();
+
+    assert_eq!(
+        response.content,
+        vec![ContentPart::Text {
+            text: "héllo".to_string()
+        }]
+    );
+}
+
+#[tokio::test]
+async fn http_errors_are_mapped() {
+    let server = MockServer::start(vec![
+        MockResponse::json(429, json!({ "error": { "message": "slow down" } })),
+        MockResponse::json(401, json!({ "error": { "message": "bad key" } })),
+    ])
+    .await;
+    let provider = OpenAiCompatibleProvider::new("test", &server.url);
+
+    match provider.complete(&request()).await {
+        Err(AgentError::Api {
+            status,
+            message,
+            retryable,
+            ..
+        }) => {
+            assert_eq!(status, Some(429));
+            assert_eq!(message, "slow down");
+            assert!(retryable);
+        }
+        other => panic!("expected API error, got {:?}", other.map(|_| ())),
+    }
+    assert!(matches!(
+        provider.complete(&request()).await,
+        Err(AgentError::Authentication { .. })
+    ));
+}
+
+#[tokio::test]
+async fn stream_error_payload_is_surfaced() {
+    let server = MockServer::start(vec![MockResponse::sse(&[json!({
+        "error": { "message": "upstream overloaded", "code": 503 }
+    })
+    .to_string()])])
+    .await;
+    let provider = OpenAiCompatibleProvider::new("test", &server.url);
+
+    let err = provider.complete(&request()).await.unwrap_err();
+    assert!(matches!(
+        err,
+        AgentError::Api {
+            status: Some(503),
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn create_from_env_uses_base_url_override_and_known_headers() {
+    let server = MockServer::start(vec![MockResponse::sse(&[
+        chunk(json!({ "content": "ok" }), Some("stop")),
+        "[DONE]".to_string(),
+    ])])
+    .await;
+    let url = server.url.clone();
+    let provider = create_from_env(&model_parts("openrouter/some/model"), |key| match key {
+        "OPENROUTER_BASE_URL" => Some(url.clone()),
+        "OPENROUTER_API_KEY" => Some("or-key".to_string()),
+        _ => None,
+    })
+    .unwrap();
+
+    assert_eq!(provider.id(), "openrouter");
+    let mut req = request();
+    req.model = "some/model".to_string();
+    provider.complete(&req).await.unwrap();
+
+    let recorded = &server.requests()[0];
+    assert_eq!(recorded.header("authorization"), Some("Bearer or-key"));
+    assert_eq!(recorded.header("x-title"), Some("opencode"));
+    assert_eq!(recorded.json()["model"], "some/model");
+}
+
+#[test]
+fn create_from_env_requires_keys_for_hosted_providers() {
+    let err = create_from_env(&model_parts("groq/llama"), |_| None)
+        .err()
+        .unwrap();
+    assert!(
+        matches!(err, AgentError::Authentication { ref message } if message.contains("GROQ_API_KEY"))
+    );
+
+    assert!(create_from_env(&model_parts("opencode/minimax-m2.5-free"), |_| None).is_ok());
+    assert!(create_from_env(&model_parts("ollama/llama3"), |_| None).is_ok());
+}
+
+#[test]
+fn create_from_env_enables_custom_providers_via_base_url() {
+    assert!(matches!(
+        create_from_env(&model_parts("my-proxy/model"), |_| None),
+        Err(AgentError::ProviderInit { .. })
+    ));
+    let provider = create_from_env(&model_parts("my-proxy/model"), |key| {
+        (key == "MY_PROXY_BASE_URL").then(|| "http://localhost:9999/v1".to_string())
+    })
+    .unwrap();
+    assert_eq!(provider.id(), "my-proxy");
+}
@@ -1,17 +1,27 @@
 //! Rust counterpart of `js/tests/retry-fetch.ts`.
 //!
-//! The JS test exercises the retry/back-off wrapper around `fetch`. The
-//! Rust port currently uses `reqwest` for HTTP without a centralized
-//! retry layer; once one is added, this file should mirror the JS test
-//! cases (rate limiting, retry-after header parsing, signal isolation,
-//! etc.).
-//!
-//! For now we verify the related CLI surface that the JS suite implicitly
-//! relies on: `--retry-timeout`, `--retry-on-rate-limits` and
-//! `--no-retry-on-rate-limits` are wired through `Args`.
+//! Exercises the retrying HTTP layer (`provider::retry_fetch`) against a
+//! local mock server: rate limits, `retry-after` parsing, server errors and
+//! the retry budget, plus the CLI flags that configure it.
+
+mod common;
 
+use chrono::{TimeZone, Utc};
 use clap::Parser;
+use common::{MockResponse, MockServer};
 use link_assistant_agent::cli::Args;
+use link_assistant_agent::error::AgentError;
+use link_assistant_agent::provider::openai::OpenAiCompatibleProvider;
+use link_assistant_agent::provider::retry_fetch::{
+    parse_retry_after, RetryEvent, RetryPolicy, MAX_RETRY_DELAY_ENV, MIN_RETRY_INTERVAL_ENV,
+    RETRY_TIMEOUT_ENV, SERVER_ERROR_MAX_RETRIES,
+};
+use link_assistant_agent::provider::timeout::Timeouts;
+use link_assistant_agent::provider::{ChatMessage, ChatRequest, ContentPart, Provider};
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[test]
 fn retry_timeout_defaults_to_unset() {
@@ -36,3 +46,277 @@ fn no_retry_on_rate_limits_disables_it() {
     let args = Args::parse_from(["agent", "--no-retry-on-rate-limits"]);
     assert!(!args.retry_on_rate_limits());
 }
+
+#[test]
+fn retry_flags_configure_the_policy() {
+    let args = Args::parse_from([
+        "agent",
+        "--retry-timeout",
+        "60",
+        "--max-retry-delay",
+        "5",
+        "--min-retry-interval",
+        "1",
+        "--no-retry-on-rate-limits",
+    ]);
+    let policy = args.retry_policy();
+    assert_eq!(policy.retry_timeout, Duration::from_secs(60));
+    assert_eq!(policy.max_retry_delay, Duration::from_secs(5));
+    assert_eq!(policy.min_retry_interval, Duration::from_secs(1));
+    assert!(!policy.retry_on_rate_limits);
+}
+
+#[test]
+fn policy_defaults_and_env_overrides() {
+    let defaults = RetryPolicy::from_env(|_| None);
+    assert_eq!(defaults.retry_timeout, Duration::from_secs(604_800));
+    assert_eq!(defaults.max_retry_delay, Duration::from_secs(1_200));
+    assert_eq!(defaults.min_retry_interval, Duration::from_secs(30));
+    assert!(defaults.retry_on_rate_limits);
+
+    let policy = RetryPolicy::from_env(|key| match key {
+        k if k == RETRY_TIMEOUT_ENV => Some("10".to_string()),
+        k if k == MAX_RETRY_DELAY_ENV => Some("3".to_string()),
+        k if k == MIN_RETRY_INTERVAL_ENV => Some("not a number".to_string()),
+        _ => None,
+    });
+    assert_eq!(policy.retry_timeout, Duration::from_secs(10));
+    assert_eq!(policy.max_retry_delay, Duration::from_secs(3));
+    assert_eq!(policy.min_retry_interval, Duration::from_secs(30));
+}
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in pairs {
+        map.insert(*name, HeaderValue::from_str(value).unwrap());
+    }
+    map
+}
+
+#[test]
+fn parses_retry_after_ms_before_retry_after() {
+    let now = Utc::now();
+    let both = headers(&[("retry-after-ms", "1500"), ("retry-after", "60")]);
+    assert_eq!(
+        parse_retry_after(&both, now),
+        Some(Duration::from_millis(1500))
+    );
+    let invalid_ms = headers(&[("retry-after-ms", "soon"), ("retry-after", "2")]);
+    assert_eq!(
+        parse_retry_after(&invalid_ms, now),
+        Some(Duration::from_secs(2))
+    );
+}
+
+#[test]
+fn parses_retry_after_seconds_and_dates() {
+    let now = Utc.with_ymd_and_hms(2015, 10, 21, 7, 27, 0).unwrap();
+    assert_eq!(
+        parse_retry_after(&headers(&[("retry-after", "1.5")]), now),
+        Some(Duration::from_millis(1500))
+    );
+    assert_eq!(
+        parse_retry_after(
+            &headers(&[("retry-after", "Wed, 21 Oct 2015 07:28:00 GMT")]),
+            now
+        ),
+        Some(Duration::from_secs(60))
+    );
+    // Past dates, zero and garbage are ignored
+    for value in ["Wed, 21 Oct 2015 07:00:00 GMT", "0", "-5", "later"] {
+        assert_eq!(
+            parse_retry_after(&headers(&[("retry-after", value)]), now),
+            None
+        );
+    }
+    assert_eq!(parse_retry_after(&HeaderMap::new(), now), None);
+}
+
+#[test]
+fn rate_limit_delays_respect_floor_cap_and_budget() {
+    let policy = RetryPolicy::default()
+        .with_initial_delay(Duration::from_secs(2))
+        .with_max_retry_delay(Duration::from_secs(5))
+        .with_min_retry_interval(Duration::from_secs(1));
+    let remaining = Duration::from_secs(3600);
+    let within = |delay: Duration, expected: Duration| {
+        assert!(
+            delay >= expected && delay <= expected.mul_f64(1.1),
+            "{:?} not within 10% above {:?}",
+            delay,
+            expected
+        )
+    };
+
+    // Exponential backoff without headers, capped at the maximum delay
+    within(
+        policy
+            .rate_limit_delay(&HeaderMap::new(), 1, remaining)
+            .unwrap(),
+        Duration::from_secs(2),
+    );
+    within(
+        policy
+            .rate_limit_delay(&HeaderMap::new(), 5, remaining)
+            .unwrap(),
+        Duration::from_secs(5),
+    );
+    // retry-after is used as is, but never below the minimum interval
+    within(
+        policy
+            .rate_limit_delay(&headers(&[("retry-after", "120")]), 1, remaining)
+            .unwrap(),
+        Duration::from_secs(120),
+    );
+    within(
+        policy
+            .rate_limit_delay(&headers(&[("retry-after-ms", "10")]), 1, remaining)
+            .unwrap(),
+        Duration::from_secs(1),
+    );
+    // Waiting longer than the remaining budget is left to the caller
+    assert_eq!(
+        policy.rate_limit_delay(&headers(&[("retry-after", "7200")]), 1, remaining),
+        None
+    );
+}
+
+/// A policy with delays short enough for tests, recording its events
+fn fast_policy() -> (RetryPolicy, Arc<Mutex<Vec<RetryEvent>>>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    let policy = RetryPolicy::default()
+        .with_initial_delay(Duration::from_millis(10))
+        .with_min_retry_interval(Duration::ZERO)
+        .with_listener(move |event| recorded.lock().unwrap().push(event.clone()));
+    (policy, events)
+}
+
+async fn get(policy: &RetryPolicy, url: &str) -> reqwest::Response {
+    let client = reqwest::Client::new();
+    policy
+        .send("mock", &Timeouts::default(), || client.get(url))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn passes_through_successful_and_client_error_responses() {
+    let server = MockServer::start(vec![
+        MockResponse::json(200, json!({ "ok": true })),
+        MockResponse::json(404, json!({ "error": "missing" })),
+    ])
+    .await;
+    let (policy, events) = fast_policy();
+
+    assert_eq!(get(&policy, &server.url).await.status(), 200);
+    assert_eq!(get(&policy, &server.url).await.status(), 404);
+    assert_eq!(server.requests().len(), 2);
+    assert!(events.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn retries_rate_limits_using_retry_after() {
+    let server = MockServer::start(vec![
+        MockResponse::json(429, json!({ "error": "slow down" }))
+            .with_header("retry-after-ms", "50"),
+        MockResponse::json(200, json!({ "ok": true })),
+    ])
+    .await;
+    let (policy, events) = fast_policy();
+
+    let response = get(&policy, &server.url).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(server.requests().len(), 2);
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].provider, "mock");
+    assert_eq!(events[0].attempt, 1);
+    assert_eq!(events[0].status, Some(429));
+    assert!(events[0].delay >= Duration::from_millis(50));
+}
+
+#[tokio::test]
+async fn rate_limits_are_returned_when_retries_are_disabled_or_exhausted() {
+    let server = MockServer::start(vec![
+        MockResponse::json(429, json!({})),
+        MockResponse::json(429, json!({})),
+        MockResponse::json(429, json!({})).with_header("retry-after", "3600"),
+    ])
+    .await;
+    let (policy, events) = fast_policy();
+
+    let disabled = policy.clone().with_retry_on_rate_limits(false);
+    assert_eq!(get(&disabled, &server.url).await.status(), 429);
+    let no_budget = policy.clone().with_retry_timeout(Duration::ZERO);
+    assert_eq!(get(&no_budget, &server.url).await.status(), 429);
+    // retry-after beyond the budget: give up instead of waiting
+    let short_budget = policy.with_retry_timeout(Duration::from_secs(60));
+    assert_eq!(get(&short_budget, &server.url).await.status(), 429);
+
+    assert_eq!(server.requests().len(), 3);
+    assert!(events.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn server_errors_are_retried_a_fixed_number_of_times() {
+    let responses = (0..=SERVER_ERROR_MAX_RETRIES)
+        .map(|_| MockResponse::json(503, json!({ "error": "unavailable" })))
+        .collect();
+    let server = MockServer::start(responses).await;
+    let (policy, events) = fast_policy();
+
+    let response = get(&policy, &server.url).await;
+    assert_eq!(response.status(), 503);
+    assert_eq!(server.requests().len() as u32, SERVER_ERROR_MAX_RETRIES + 1);
+    let attempts: Vec<u32> = events.lock().unwrap().iter().map(|e| e.attempt).collect();
+    assert_eq!(attempts, vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn providers_retry_before_mapping_errors() {
+    let server = MockServer::start(vec![
+        MockResponse::json(502, json!({ "error": "bad gateway" })),
+        MockResponse::sse(&[
+            json!({ "choices": [{ "delta": { "content": "recovered" }, "finish_reason": "stop" }] })
+                .to_string(),
+            "[DONE]".to_string(),
+        ]),
+        MockResponse::json(429, json!({ "error": { "message": "rate limited" } })),
+    ])
+    .await;
+    let (policy, _) = fast_policy();
+    let request = ChatRequest {
+        model: "model".to_string(),
+        system: Vec::new(),
+        messages: vec![ChatMessage::User {
+            text: "hi".to_string(),
+        }],
+        tools: Vec::new(),
+        temperature: None,
+        max_output_tokens: None,
+        thinking_budget: None,
+    };
+
+    let provider = OpenAiCompatibleProvider::new("mock", &server.url).with_retry(policy.clone());
+    let response = provider.complete(&request).await.unwrap();
+    assert_eq!(
+        response.content,
+        vec![ContentPart::Text {
+            text: "recovered".to_string()
+        }]
+    );
+
+    let failing = OpenAiCompatibleProvider::new("mock", &server.url)
+        .with_retry(policy.with_retry_on_rate_limits(false));
+    let error = failing.complete(&request).await.unwrap_err();
+    assert!(matches!(
+        error,
+        AgentError::Api {
+            status: Some(429),
+            retryable: true,
+            ..
+        }
+    ));
+}
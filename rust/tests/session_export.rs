@@ -0,0 +1,279 @@
+//! Tests for `agent export` and the `session::export` formats.
+
+use assert_cmd::Command;
+use link_assistant_agent::global::DATA_DIR_ENV;
+use link_assistant_agent::session::export::{export, Format};
+use link_assistant_agent::session::{MessageWithParts, Session};
+use link_assistant_agent::storage::{Storage, GLOBAL_PROJECT};
+use predicates::prelude::*;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+/// A user request answered by a tool call and a closing text
+fn session() -> Session {
+    let mut session = Session::new(std::path::Path::new("/work"));
+    session.info.id = "ses_export".to_string();
+    session.info.title = "Fix the build".to_string();
+    let messages: Vec<MessageWithParts> = serde_json::from_value(json!([
+        {
+            "info": {
+                "role": "user",
+                "id": "msg_01",
+                "sessionID": "ses_export",
+                "time": { "created": 1_700_000_000_000u64 },
+                "agent": "build",
+                "model": { "providerID": "opencode", "modelID": "grok-code" }
+            },
+            "parts": [{
+                "type": "text",
+                "id": "prt_01",
+                "sessionID": "ses_export",
+                "messageID": "msg_01",
+                "text": "Why does the build fail?"
+            }]
+        },
+        {
+            "info": {
+                "role": "assistant",
+                "id": "msg_02",
+                "sessionID": "ses_export",
+                "time": { "created": 1_700_000_001_000u64, "completed": 1_700_000_002_000u64 },
+                "parentID": "msg_01",
+                "modelID": "grok-code",
+                "providerID": "opencode",
+                "mode": "build",
+                "path": { "cwd": "/work", "root": "/work" },
+                "cost": 0.0125,
+                "tokens": { "input": 1200, "output": 80, "reasoning": 0, "cache": { "read": 300, "write": 0 } },
+                "finish": "tool-calls"
+            },
+            "parts": [
+                {
+                    "type": "tool",
+                    "id": "prt_02",
+                    "sessionID": "ses_export",
+                    "messageID": "msg_02",
+                    "callID": "call_1",
+                    "tool": "bash",
+                    "state": {
+                        "status": "completed",
+                        "input": { "command": "cargo build" },
+                        "output": "error: ```unterminated``` string",
+                        "title": "cargo build",
+                        "metadata": {},
+                        "time": { "start": 1, "end": 2 }
+                    }
+                },
+                {
+                    "type": "tool",
+                    "id": "prt_03",
+                    "sessionID": "ses_export",
+                    "messageID": "msg_02",
+                    "callID": "call_2",
+                    "tool": "read",
+                    "state": {
+                        "status": "error",
+                        "input": { "filePath": "/work/missing.rs" },
+                        "error": "File not found",
+                        "time": { "start": 2, "end": 3 }
+                    }
+                },
+                {
+                    "type": "text",
+                    "id": "prt_04",
+                    "sessionID": "ses_export",
+                    "messageID": "msg_02",
+                    "text": "A string literal is not closed."
+                }
+            ]
+        }
+    ]))
+    .unwrap();
+    session.messages = messages;
+    session
+}
+
+fn diffs() -> Vec<Value> {
+    vec![json!({ "file": "src/lib.rs", "before": "", "after": "", "additions": 3, "deletions": 1 })]
+}
+
+#[test]
+fn format_names_parse() {
+    assert_eq!("opencode".parse::<Format>().unwrap(), Format::OpenCode);
+    assert_eq!("claude".parse::<Format>().unwrap(), Format::Claude);
+    assert_eq!("markdown".parse::<Format>().unwrap(), Format::Markdown);
+    assert!("html".parse::<Format>().is_err());
+}
+
+#[test]
+fn opencode_export_round_trips() {
+    let session = session();
+    let output = export(&session, &diffs(), Format::OpenCode).unwrap();
+    let document: Value = serde_json::from_str(&output).unwrap();
+
+    assert_eq!(document["info"]["id"], "ses_export");
+    assert_eq!(document["diffs"][0]["file"], "src/lib.rs");
+    let messages: Vec<MessageWithParts> =
+        serde_json::from_value(document["messages"].clone()).unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[1].parts.len(), 3);
+
+    // Without recorded changes there is no `diffs` member
+    let output = export(&session, &[], Format::OpenCode).unwrap();
+    let document: Value = serde_json::from_str(&output).unwrap();
+    assert!(document.get("diffs").is_none());
+}
+
+#[test]
+fn claude_export_is_a_jsonl_transcript() {
+    let output = export(&session(), &[], Format::Claude).unwrap();
+    let lines: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["type"], "user");
+    assert_eq!(lines[0]["parentUuid"], Value::Null);
+    assert_eq!(lines[0]["message"]["content"], "Why does the build fail?");
+
+    let assistant = &lines[1];
+    assert_eq!(assistant["type"], "assistant");
+    assert_eq!(assistant["parentUuid"], "msg_01");
+    assert_eq!(assistant["sessionId"], "ses_export");
+    assert_eq!(assistant["message"]["model"], "grok-code");
+    assert_eq!(assistant["message"]["stop_reason"], "tool_use");
+    assert_eq!(assistant["message"]["usage"]["input_tokens"], 1200);
+    assert_eq!(
+        assistant["message"]["usage"]["cache_read_input_tokens"],
+        300
+    );
+    let content = assistant["message"]["content"].as_array().unwrap();
+    assert_eq!(content[0]["type"], "tool_use");
+    assert_eq!(content[0]["id"], "call_1");
+    assert_eq!(content[0]["input"]["command"], "cargo build");
+    assert_eq!(content[2]["text"], "A string literal is not closed.");
+
+    let results = lines[2]["message"]["content"].as_array().unwrap();
+    assert_eq!(lines[2]["type"], "user");
+    assert_eq!(lines[2]["parentUuid"], "msg_02");
+    assert_eq!(results[0]["tool_use_id"], "call_1");
+    assert_eq!(results[0]["is_error"], false);
+    assert_eq!(results[1]["content"], "File not found");
+    assert_eq!(results[1]["is_error"], true);
+}
+
+#[test]
+fn markdown_export_is_readable() {
+    let output = export(&session(), &diffs(), Format::Markdown).unwrap();
+
+    assert!(output.starts_with("# Fix the build\n"));
+    assert!(output.contains("- Session: `ses_export`"));
+    assert!(output.contains("## User\n\nWhy does the build fail?"));
+    assert!(output.contains("## Assistant (opencode/grok-code)"));
+    assert!(output.contains("**Tool: bash** (completed)"));
+    assert!(output.contains("\"command\": \"cargo build\""));
+    // Output containing a fence gets a longer one
+    assert!(output.contains("````\nerror: ```unterminated``` string\n````"));
+    assert!(output.contains("**Tool: read** (error)"));
+    assert!(output.contains("| `src/lib.rs` | 3 | 1 |"));
+    assert!(output.contains("- Input tokens: 1200"));
+    assert!(output.contains("- Cost: $0.0125"));
+}
+
+struct Sandbox {
+    data: TempDir,
+    work: TempDir,
+}
+
+impl Sandbox {
+    fn new() -> Self {
+        Self {
+            data: TempDir::new().unwrap(),
+            work: TempDir::new().unwrap(),
+        }
+    }
+
+    fn agent(&self) -> Command {
+        let mut command = Command::cargo_bin("agent").unwrap();
+        command
+            .current_dir(self.work.path())
+            .env(DATA_DIR_ENV, self.data.path());
+        command
+    }
+
+    fn storage(&self) -> Storage {
+        Storage::from_data_dir(self.data.path())
+    }
+}
+
+#[test]
+fn export_writes_the_latest_session_by_default() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .agent()
+        .args(["--dry-run", "--compact-json", "--prompt", "hello export"])
+        .assert()
+        .success();
+
+    let output = sandbox
+        .agent()
+        .arg("export")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let document: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(document["info"]["projectID"], GLOBAL_PROJECT);
+    assert_eq!(document["messages"].as_array().unwrap().len(), 2);
+
+    sandbox
+        .agent()
+        .args(["export", "--format", "markdown"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("## User\n\nhello export"));
+}
+
+#[test]
+fn export_reads_a_session_by_id_with_its_diffs() {
+    let sandbox = Sandbox::new();
+    let mut session = session();
+    session.info.project_id = GLOBAL_PROJECT.to_string();
+    let storage = sandbox.storage();
+    storage.write_session(&mut session).unwrap();
+    storage
+        .write(&["session_diff", "ses_export"], &diffs())
+        .unwrap();
+
+    sandbox
+        .agent()
+        .args(["export", "ses_export", "--format", "claude"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"tool_use_id\":\"call_1\""));
+    sandbox
+        .agent()
+        .args(["export", "ses_export", "--format", "markdown"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("| `src/lib.rs` | 3 | 1 |"));
+}
+
+#[test]
+fn export_fails_for_unknown_sessions() {
+    let sandbox = Sandbox::new();
+    sandbox
+        .agent()
+        .args(["export", "ses_missing"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Session not found: ses_missing"));
+    sandbox
+        .agent()
+        .arg("export")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No stored sessions found"));
+}
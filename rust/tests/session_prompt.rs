@@ -0,0 +1,309 @@
+//! Tests for the agent loop in `session::prompt`.
+//!
+//! A scripted provider replays canned responses so the loop can be driven
+//! through multi-step tool use without a network connection.
+
+use async_trait::async_trait;
+use link_assistant_agent::defaults::model_parts;
+use link_assistant_agent::error::Result;
+use link_assistant_agent::permission::{policy, Mode};
+use link_assistant_agent::provider::models::Catalog;
+use link_assistant_agent::provider::{
+    ChatMessage, ChatRequest, ChatResponse, ContentPart, Provider, Usage,
+};
+use link_assistant_agent::session::message::{to_chat_messages, ToolState};
+use link_assistant_agent::session::prompt::{
+    PromptInput, SessionPrompt, OUTPUT_TOKEN_MAX, THINKING_BUDGET_TOKENS,
+};
+use link_assistant_agent::session::{MessageInfo, Part, Session, SessionEvent};
+use link_assistant_agent::tool::ToolRegistry;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tempfile::TempDir;
+
+struct ScriptedProvider {
+    responses: Mutex<VecDeque<ChatResponse>>,
+    requests: Mutex<Vec<ChatRequest>>,
+}
+
+impl ScriptedProvider {
+    fn new(responses: Vec<ChatResponse>) -> Self {
+        Self {
+            responses: Mutex::new(responses.into()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for ScriptedProvider {
+    fn id(&self) -> &str {
+        "scripted"
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        self.requests.lock().unwrap().push(request.clone());
+        Ok(self
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .expect("no scripted response left"))
+    }
+}
+
+fn text(text: &str) -> ChatResponse {
+    ChatResponse {
+        content: vec![ContentPart::Text {
+            text: text.to_string(),
+        }],
+        finish_reason: "stop".to_string(),
+        usage: Usage {
+            input: 10,
+            output: 5,
+            ..Default::default()
+        },
+        model: None,
+    }
+}
+
+fn tool_call(tool: &str, input: serde_json::Value) -> ChatResponse {
+    ChatResponse {
+        content: vec![ContentPart::ToolCall {
+            call_id: "call_1".to_string(),
+            tool: tool.to_string(),
+            input,
+        }],
+        finish_reason: "tool-calls".to_string(),
+        usage: Usage {
+            input: 10,
+            output: 5,
+            ..Default::default()
+        },
+        model: None,
+    }
+}
+
+fn input(message: &str) -> PromptInput {
+    PromptInput {
+        text: message.to_string(),
+        model: model_parts("scripted/test-model"),
+        system: Some("test system".to_string()),
+        append_system: None,
+        temperature: None,
+    }
+}
+
+async fn run(
+    provider: &ScriptedProvider,
+    dir: &TempDir,
+    session: &mut Session,
+    message: &str,
+    mode: Mode,
+) -> Vec<Part> {
+    let registry = ToolRegistry::new();
+    let prompt = SessionPrompt::new(provider, &registry, dir.path())
+        .with_policy(policy(mode, None).unwrap());
+    let mut parts = Vec::new();
+    prompt
+        .prompt(session, input(message), &mut |event| {
+            if let SessionEvent::Part(part) = event {
+                parts.push(part);
+            }
+        })
+        .await
+        .unwrap();
+    parts
+}
+
+#[tokio::test]
+async fn text_response_emits_one_step() {
+    let dir = TempDir::new().unwrap();
+    let provider = ScriptedProvider::new(vec![text("hello back")]);
+    let mut session = Session::new(dir.path());
+
+    let parts = run(&provider, &dir, &mut session, "hello", Mode::Auto).await;
+
+    assert!(matches!(parts[0], Part::StepStart(_)));
+    assert!(matches!(&parts[1], Part::Text(t) if t.text == "hello back"));
+    assert!(matches!(&parts[2], Part::StepFinish(f) if f.reason == "stop"));
+    assert_eq!(session.messages.len(), 2);
+    match &session.messages[1].info {
+        MessageInfo::Assistant(a) => {
+            assert_eq!(a.finish.as_deref(), Some("stop"));
+            assert_eq!(a.tokens.input, 10);
+            assert_eq!(a.provider_id, "scripted");
+            assert_eq!(a.model_id, "test-model");
+        }
+        other => panic!("expected assistant message, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn tool_calls_are_executed_and_fed_back() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("out.txt");
+    let provider = ScriptedProvider::new(vec![
+        tool_call(
+            "write",
+            json!({ "filePath": file.to_string_lossy(), "content": "written" }),
+        ),
+        text("done"),
+    ]);
+    let mut session = Session::new(dir.path());
+
+    let parts = run(&provider, &dir, &mut session, "write a file", Mode::Auto).await;
+
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "written");
+    let tool = parts
+        .iter()
+        .find_map(|p| match p {
+            Part::Tool(t) => Some(t),
+            _ => None,
+        })
+        .unwrap();
+    assert!(matches!(tool.state, ToolState::Completed { .. }));
+
+    let requests = provider.requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(requests[0].tools.iter().any(|t| t.name == "write"));
+    assert!(!requests[0].tools.iter().any(|t| t.name == "invalid"));
+    assert!(requests[1]
+        .messages
+        .iter()
+        .any(|m| matches!(m, ChatMessage::Tool { call_id, .. } if call_id == "call_1")));
+    assert_eq!(session.messages.len(), 3);
+}
+
+#[tokio::test]
+async fn unknown_tools_are_routed_through_invalid_tool() {
+    let dir = TempDir::new().unwrap();
+    let provider = ScriptedProvider::new(vec![tool_call("nope", json!({})), text("ok")]);
+    let mut session = Session::new(dir.path());
+
+    let parts = run(&provider, &dir, &mut session, "hi", Mode::Auto).await;
+
+    let tool = parts
+        .iter()
+        .find_map(|p| match p {
+            Part::Tool(t) => Some(t),
+            _ => None,
+        })
+        .unwrap();
+    match &tool.state {
+        ToolState::Completed { output, .. } => assert!(output.contains("nope")),
+        other => panic!("expected completed invalid tool, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn readonly_policy_rejects_edits() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("blocked.txt");
+    let provider = ScriptedProvider::new(vec![
+        tool_call(
+            "write",
+            json!({ "filePath": file.to_string_lossy(), "content": "nope" }),
+        ),
+        text("understood"),
+    ]);
+    let mut session = Session::new(dir.path());
+
+    let parts = run(&provider, &dir, &mut session, "write", Mode::Readonly).await;
+
+    assert!(!file.exists());
+    assert!(parts.iter().any(|p| matches!(
+        p,
+        Part::Tool(t) if matches!(&t.state, ToolState::Error { error, .. } if error.contains("Permission denied"))
+    )));
+}
+
+#[tokio::test]
+async fn follow_up_prompts_reuse_session_history() {
+    let dir = TempDir::new().unwrap();
+    let provider = ScriptedProvider::new(vec![text("first"), text("second")]);
+    let mut session = Session::new(dir.path());
+
+    run(&provider, &dir, &mut session, "one", Mode::Auto).await;
+    run(&provider, &dir, &mut session, "two", Mode::Auto).await;
+
+    let requests = provider.requests.lock().unwrap();
+    assert_eq!(
+        requests[1].messages,
+        vec![
+            ChatMessage::User {
+                text: "one".to_string()
+            },
+            ChatMessage::Assistant {
+                content: vec![ContentPart::Text {
+                    text: "first".to_string()
+                }]
+            },
+            ChatMessage::User {
+                text: "two".to_string()
+            },
+        ]
+    );
+    assert_eq!(to_chat_messages(&session.messages).len(), 4);
+}
+
+/// Thinking budget and answer limit requested for `scripted/test-model`
+/// when the catalog lists it as `model` under a provider using `npm`
+async fn thinking_request(npm: &str, model: serde_json::Value) -> (Option<u64>, Option<u64>) {
+    let dir = TempDir::new().unwrap();
+    let provider = ScriptedProvider::new(vec![text("done")]);
+    let registry = ToolRegistry::new();
+    let catalog = Catalog::from_json(
+        &json!({ "scripted": { "id": "scripted", "npm": npm, "models": { "test-model": model } } })
+            .to_string(),
+    )
+    .unwrap();
+    let prompt = SessionPrompt::new(&provider, &registry, dir.path()).with_catalog(&catalog);
+    let mut session = Session::new(dir.path());
+    prompt
+        .prompt(&mut session, input("hi"), &mut |_| {})
+        .await
+        .unwrap();
+    let request = provider.requests.lock().unwrap()[0].clone();
+    (request.thinking_budget, request.max_output_tokens)
+}
+
+#[tokio::test]
+async fn anthropic_reasoning_models_think_within_their_output_limit() {
+    let anthropic = "@ai-sdk/anthropic";
+    let reasoning = |output: u64| json!({ "id": "test-model", "reasoning": true, "limit": { "context": 200_000, "output": output } });
+
+    // Room for both: the usual answer limit plus the budget
+    assert_eq!(
+        thinking_request(anthropic, reasoning(64_000)).await,
+        (Some(THINKING_BUDGET_TOKENS), Some(OUTPUT_TOKEN_MAX))
+    );
+    // A tighter limit shrinks the answer, not the budget
+    assert_eq!(
+        thinking_request(anthropic, reasoning(40_000)).await,
+        (
+            Some(THINKING_BUDGET_TOKENS),
+            Some(40_000 - THINKING_BUDGET_TOKENS)
+        )
+    );
+    // A `thinking` option sets the budget, or turns thinking off
+    let mut model = reasoning(64_000);
+    model["options"] = json!({ "thinking": { "type": "enabled", "budgetTokens": 4_000 } });
+    assert_eq!(
+        thinking_request(anthropic, model.clone()).await,
+        (Some(4_000), Some(OUTPUT_TOKEN_MAX))
+    );
+    model["options"] = json!({ "thinking": { "type": "disabled" } });
+    assert_eq!(thinking_request(anthropic, model).await.0, None);
+
+    // Models that do not reason, or other APIs, get no budget
+    let plain = json!({ "id": "test-model", "limit": { "context": 200_000, "output": 64_000 } });
+    assert_eq!(thinking_request(anthropic, plain).await.0, None);
+    assert_eq!(
+        thinking_request("@ai-sdk/openai-compatible", reasoning(64_000))
+            .await
+            .0,
+        None
+    );
+}
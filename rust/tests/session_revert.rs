@@ -0,0 +1,309 @@
+//! Tests for session revert and unrevert (`session::revert`) and the
+//! `revert`/`unrevert` commands.
+
+use assert_cmd::Command;
+use async_trait::async_trait;
+use link_assistant_agent::defaults::model_parts;
+use link_assistant_agent::error::{AgentError, Result};
+use link_assistant_agent::global::DATA_DIR_ENV;
+use link_assistant_agent::provider::{
+    ChatMessage, ChatRequest, ChatResponse, ContentPart, Provider, Usage,
+};
+use link_assistant_agent::session::message::Part;
+use link_assistant_agent::session::prompt::{PromptInput, SessionPrompt};
+use link_assistant_agent::session::{revert, Session, SessionEvent};
+use link_assistant_agent::snapshot::Snapshot;
+use link_assistant_agent::storage::{self, Storage};
+use link_assistant_agent::tool::ToolRegistry;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = std::process::Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+/// Runs `command` through bash, then finishes
+struct BashProvider {
+    command: &'static str,
+}
+
+#[async_trait]
+impl Provider for BashProvider {
+    fn id(&self) -> &str {
+        "scripted"
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        let ran = matches!(request.messages.last(), Some(ChatMessage::Tool { .. }));
+        let (content, finish_reason) = if ran {
+            (
+                ContentPart::Text {
+                    text: "done".to_string(),
+                },
+                "stop",
+            )
+        } else {
+            (
+                ContentPart::ToolCall {
+                    call_id: "call_1".to_string(),
+                    tool: "bash".to_string(),
+                    input: json!({ "command": self.command, "description": "Change files" }),
+                },
+                "tool-calls",
+            )
+        };
+        Ok(ChatResponse {
+            content: vec![content],
+            finish_reason: finish_reason.to_string(),
+            usage: Usage::default(),
+            model: None,
+        })
+    }
+}
+
+async fn turn(session: &mut Session, snapshot: &Snapshot, command: &'static str) {
+    let provider = BashProvider { command };
+    let registry = ToolRegistry::new();
+    let prompt = SessionPrompt::new(&provider, &registry, snapshot.worktree())
+        .with_snapshot(snapshot.clone());
+    let input = PromptInput {
+        text: command.to_string(),
+        model: model_parts("scripted/main"),
+        system: Some("test system".to_string()),
+        append_system: None,
+        temperature: None,
+    };
+    prompt
+        .prompt(session, input, &mut |_: SessionEvent| {})
+        .await
+        .unwrap();
+}
+
+/// Two turns in a git repository: the first writes `a.txt`, the second
+/// rewrites it and adds `b.txt`
+async fn two_turns() -> (TempDir, TempDir, Snapshot, Session) {
+    let work = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    git(work.path(), &["init", "-q"]);
+    fs::write(work.path().join("a.txt"), "zero\n").unwrap();
+    git(work.path(), &["add", "."]);
+    git(work.path(), &["commit", "-q", "-m", "init"]);
+    let snapshot = Snapshot::for_directory(data.path(), work.path()).unwrap();
+    let mut session = Session::new(work.path());
+    turn(&mut session, &snapshot, "echo one > a.txt").await;
+    turn(
+        &mut session,
+        &snapshot,
+        "echo two > a.txt && echo new > b.txt",
+    )
+    .await;
+    assert_eq!(session.messages.len(), 6);
+    (work, data, snapshot, session)
+}
+
+fn read(dir: &Path, file: &str) -> Option<String> {
+    fs::read_to_string(dir.join(file)).ok()
+}
+
+#[tokio::test]
+async fn reverting_a_turn_rolls_back_its_files() {
+    let (work, _data, snapshot, mut session) = two_turns().await;
+    let second_turn = session.messages[3].info.id().to_string();
+
+    // Reverting an assistant message reverts the turn that produced it
+    let assistant = session.messages[4].info.id().to_string();
+    let reverted = revert::revert(&mut session, Some(&snapshot), &assistant, None).unwrap();
+    assert_eq!(reverted.message_id, second_turn);
+    assert_eq!(reverted.part_id, None);
+    assert!(reverted.diff.as_deref().unwrap().contains("-two"));
+    assert_eq!(read(work.path(), "a.txt").as_deref(), Some("one\n"));
+    assert_eq!(read(work.path(), "b.txt"), None);
+    // The conversation is only marked until the next prompt
+    assert_eq!(session.messages.len(), 6);
+    assert_eq!(session.info.revert.as_ref(), Some(&reverted));
+
+    // Going further back first restores, then reverts from the new target
+    let first_turn = session.messages[0].info.id().to_string();
+    let further = revert::revert(&mut session, Some(&snapshot), &first_turn, None).unwrap();
+    assert_eq!(further.snapshot, reverted.snapshot);
+    assert_eq!(read(work.path(), "a.txt").as_deref(), Some("zero\n"));
+
+    assert!(revert::unrevert(&mut session, Some(&snapshot)));
+    assert_eq!(read(work.path(), "a.txt").as_deref(), Some("two\n"));
+    assert_eq!(read(work.path(), "b.txt").as_deref(), Some("new\n"));
+    assert!(session.info.revert.is_none());
+    assert!(!revert::unrevert(&mut session, Some(&snapshot)));
+}
+
+#[tokio::test]
+async fn the_next_prompt_drops_reverted_messages() {
+    let (work, _data, snapshot, mut session) = two_turns().await;
+    let second_turn = session.messages[3].info.id().to_string();
+    revert::revert(&mut session, Some(&snapshot), &second_turn, None).unwrap();
+
+    // A fork leaves the reverted messages behind as well
+    assert_eq!(session.fork(work.path()).messages.len(), 3);
+
+    turn(&mut session, &snapshot, "echo three > c.txt").await;
+    assert!(session.info.revert.is_none());
+    assert_eq!(session.messages.len(), 6);
+    assert!(session
+        .messages
+        .iter()
+        .all(|message| message.info.id() != second_turn));
+    assert_eq!(read(work.path(), "a.txt").as_deref(), Some("one\n"));
+    assert_eq!(read(work.path(), "c.txt").as_deref(), Some("three\n"));
+}
+
+#[tokio::test]
+async fn parts_after_a_tool_call_can_be_reverted_alone() {
+    let (work, _data, snapshot, mut session) = two_turns().await;
+    let step = &session.messages[4];
+    let Some(Part::Patch(patch)) = step.parts.last() else {
+        panic!("expected a patch part");
+    };
+    let (message_id, part_id) = (step.info.id().to_string(), patch.id.clone());
+
+    let reverted =
+        revert::revert(&mut session, Some(&snapshot), &message_id, Some(&part_id)).unwrap();
+    assert_eq!(reverted.message_id, message_id);
+    assert_eq!(reverted.part_id.as_deref(), Some(part_id.as_str()));
+    assert_eq!(read(work.path(), "a.txt").as_deref(), Some("one\n"));
+
+    // The tool call stays in the conversation, the patch and the closing
+    // step go
+    revert::cleanup(&mut session);
+    assert_eq!(session.messages.len(), 5);
+    let parts = &session.messages[4].parts;
+    assert!(parts.iter().any(|part| matches!(part, Part::Tool(_))));
+    assert!(!parts.iter().any(|part| matches!(part, Part::Patch(_))));
+}
+
+#[tokio::test]
+async fn unknown_targets_are_session_errors() {
+    let (_work, _data, snapshot, mut session) = two_turns().await;
+    let error = revert::revert(&mut session, Some(&snapshot), "msg_missing", None).unwrap_err();
+    assert!(matches!(error, AgentError::Session { .. }));
+    assert_eq!(
+        error.to_string(),
+        "Session error: Message not found: msg_missing"
+    );
+    assert!(session.info.revert.is_none());
+}
+
+fn agent(data: &TempDir, work: &TempDir) -> Command {
+    let mut command = Command::cargo_bin("agent").unwrap();
+    command
+        .current_dir(work.path())
+        .env(DATA_DIR_ENV, data.path())
+        .args(["--compact-json"]);
+    command
+}
+
+fn events(output: &[u8]) -> Vec<Value> {
+    String::from_utf8_lossy(output)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        // Log records share stdout with the events
+        .filter(|event| event.get("type").is_some())
+        .collect()
+}
+
+/// A stored dry-run session of `work`
+fn stored_session(data: &TempDir, work: &TempDir) -> Session {
+    let output = agent(data, work)
+        .args(["--dry-run", "-p", "hello revert"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let storage = Storage::from_data_dir(data.path());
+    let project_id = storage::project_id(work.path());
+    let info = &storage.sessions(&project_id).unwrap()[0];
+    storage.read_session(&project_id, &info.id).unwrap()
+}
+
+fn reload(data: &TempDir, work: &TempDir, session: &Session) -> Session {
+    Storage::from_data_dir(data.path())
+        .read_session(&storage::project_id(work.path()), session.id())
+        .unwrap()
+}
+
+#[test]
+fn revert_and_unrevert_subcommands_update_the_stored_session() {
+    let (data, work) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let session = stored_session(&data, &work);
+    let user = session.messages[0].info.id().to_string();
+
+    let output = agent(&data, &work)
+        .args(["revert", session.id(), &user])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let event = &events(&output.stdout)[0];
+    assert_eq!(event["type"], "revert");
+    assert_eq!(event["sessionID"], session.id());
+    assert_eq!(event["messageID"], user.as_str());
+    let stored = reload(&data, &work, &session);
+    assert_eq!(stored.info.revert.unwrap().message_id, user);
+    assert_eq!(stored.messages.len(), session.messages.len());
+
+    let output = agent(&data, &work)
+        .args(["unrevert", session.id()])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(events(&output.stdout)[0]["type"], "unrevert");
+    assert!(reload(&data, &work, &session).info.revert.is_none());
+
+    agent(&data, &work)
+        .args(["revert", session.id(), "msg_missing"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn revert_is_accepted_as_json_input() {
+    let (data, work) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let session = stored_session(&data, &work);
+    let user = session.messages[0].info.id().to_string();
+
+    let input = [
+        json!({ "command": "revert", "messageID": user }),
+        json!({ "command": "unrevert" }),
+        json!({ "command": "unrevert" }),
+        json!({ "command": "revert", "messageID": user }),
+        json!({ "message": "again" }),
+    ]
+    .map(|line| line.to_string() + "\n")
+    .concat();
+    let output = agent(&data, &work)
+        .args(["--dry-run", "--resume", session.id(), "--no-fork"])
+        .write_stdin(input)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let types: Vec<Value> = events(&output.stdout)
+        .iter()
+        .map(|event| event["type"].clone())
+        .filter(|kind| kind != "status")
+        .collect();
+    assert_eq!(
+        types[..5],
+        ["revert", "unrevert", "warning", "revert", "step_start"]
+    );
+    // The message after the revert replaced the reverted turn
+    let stored = reload(&data, &work, &session);
+    assert!(stored.info.revert.is_none());
+    assert_eq!(stored.messages.len(), 2);
+    assert_ne!(stored.messages[0].info.id(), user);
+}
@@ -0,0 +1,309 @@
+//! Tests for `agent stats` and the `session::stats` ledger.
+
+use assert_cmd::Command;
+use link_assistant_agent::global::DATA_DIR_ENV;
+use link_assistant_agent::provider::models::{Catalog, Cost};
+use link_assistant_agent::session::message::Tokens;
+use link_assistant_agent::session::stats::{Filter, Stats, FREE_TIER, PAID_TIER, UNKNOWN_TIER};
+use link_assistant_agent::session::{MessageWithParts, Session};
+use link_assistant_agent::storage::Storage;
+use predicates::prelude::*;
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+const DAY: u64 = 24 * 60 * 60 * 1000;
+const NOW: u64 = 1_800_000_000_000;
+
+fn catalog() -> Catalog {
+    Catalog::from_json(
+        &json!({
+            "opencode": {
+                "id": "opencode",
+                "models": {
+                    "minimax-m2.5-free": { "id": "minimax-m2.5-free", "cost": { "input": 0, "output": 0 } },
+                    "claude-sonnet": {
+                        "id": "claude-sonnet",
+                        "cost": { "input": 3, "output": 15, "cache_read": 0.3, "cache_write": 3.75 }
+                    }
+                }
+            }
+        })
+        .to_string(),
+    )
+    .unwrap()
+}
+
+fn tokens(input: u64, output: u64, cache_read: u64) -> Tokens {
+    serde_json::from_value(json!({
+        "input": input, "output": output, "reasoning": 0,
+        "cache": { "read": cache_read, "write": 0 }
+    }))
+    .unwrap()
+}
+
+/// A turn on `model`: the user message, then an assistant message that
+/// called `tools` and recorded `cost`
+fn turn(
+    session: &str,
+    index: u32,
+    model: &str,
+    created: u64,
+    tools: &[&str],
+    cost: f64,
+) -> Vec<MessageWithParts> {
+    let user = format!("msg_{}_{:02}u", session, index);
+    let assistant = format!("msg_{}_{:02}a", session, index);
+    let parts: Vec<Value> = tools
+        .iter()
+        .enumerate()
+        .map(|(i, tool)| {
+            json!({
+                "type": "tool",
+                "id": format!("prt_{}_{}", assistant, i),
+                "sessionID": session,
+                "messageID": assistant,
+                "callID": format!("call_{}", i),
+                "tool": tool,
+                "state": {
+                    "status": "completed",
+                    "input": {},
+                    "output": "ok",
+                    "title": tool,
+                    "metadata": {},
+                    "time": { "start": 1, "end": 2 }
+                }
+            })
+        })
+        .collect();
+    serde_json::from_value(json!([
+        {
+            "info": {
+                "role": "user",
+                "id": user,
+                "sessionID": session,
+                "time": { "created": created },
+                "agent": "build",
+                "model": { "providerID": "opencode", "modelID": model }
+            },
+            "parts": []
+        },
+        {
+            "info": {
+                "role": "assistant",
+                "id": assistant,
+                "sessionID": session,
+                "time": { "created": created, "completed": created + 1 },
+                "parentID": user,
+                "modelID": model,
+                "providerID": "opencode",
+                "mode": "build",
+                "path": { "cwd": "/", "root": "/" },
+                "cost": cost,
+                "tokens": tokens(1_000_000, 100_000, 0),
+                "finish": "stop"
+            },
+            "parts": parts
+        }
+    ]))
+    .unwrap()
+}
+
+fn store(storage: &Storage, id: &str, project: &str, messages: Vec<MessageWithParts>) {
+    let mut session = Session::new(std::path::Path::new("/"));
+    session.info.id = id.to_string();
+    session.info.project_id = project.to_string();
+    session.info.time.created = messages.first().map(created).unwrap_or(NOW);
+    session.info.time.updated = messages.last().map(created).unwrap_or(NOW);
+    session.messages = messages;
+    storage.write_session(&mut session).unwrap();
+}
+
+fn created(message: &MessageWithParts) -> u64 {
+    serde_json::to_value(&message.info).unwrap()["time"]["created"]
+        .as_u64()
+        .unwrap()
+}
+
+/// Two projects: a free session from ten days ago, and a recent session
+/// that fell back from the free model to a paid one
+fn seeded() -> (TempDir, Storage) {
+    let dir = TempDir::new().unwrap();
+    let storage = Storage::from_data_dir(dir.path());
+    store(
+        &storage,
+        "ses_old",
+        "prj_a",
+        turn(
+            "ses_old",
+            1,
+            "minimax-m2.5-free",
+            NOW - 10 * DAY,
+            &["read", "bash"],
+            0.0,
+        ),
+    );
+    let mut recent = turn("ses_new", 1, "minimax-m2.5-free", NOW - DAY, &["read"], 0.0);
+    recent.extend(turn(
+        "ses_new",
+        2,
+        "claude-sonnet",
+        NOW - DAY + 1000,
+        &["edit"],
+        0.0,
+    ));
+    recent.extend(turn(
+        "ses_new",
+        3,
+        "mystery-model",
+        NOW - DAY + 2000,
+        &[],
+        0.25,
+    ));
+    store(&storage, "ses_new", "prj_b", recent);
+    (dir, storage)
+}
+
+fn filter() -> Filter {
+    Filter {
+        now: NOW,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn tokens_are_priced_per_million() {
+    let cost: Cost = serde_json::from_value(json!({
+        "input": 3, "output": 15, "cache_read": 0.3,
+        "context_over_200k": { "input": 6, "output": 22.5 }
+    }))
+    .unwrap();
+
+    let small = tokens(100_000, 10_000, 50_000);
+    assert!((small.cost(&cost) - (0.3 + 0.15 + 0.015)).abs() < 1e-9);
+
+    // Prompts over 200K tokens, cache reads included, use the higher tier
+    let large = tokens(190_000, 10_000, 20_000);
+    assert!((large.cost(&cost) - (1.14 + 0.225)).abs() < 1e-9);
+
+    // Reasoning is charged as output
+    let reasoning = Tokens {
+        reasoning: 1_000_000,
+        ..Default::default()
+    };
+    assert_eq!(reasoning.cost(&cost), 15.0);
+}
+
+#[test]
+fn sessions_are_grouped_by_model_provider_day_project_and_tier() {
+    let (_dir, storage) = seeded();
+    let stats = Stats::collect(&storage, &catalog(), &filter()).unwrap();
+
+    assert_eq!(stats.total.sessions, 2);
+    assert_eq!(stats.total.messages, 8);
+    assert_eq!(stats.total.tokens.input, 4_000_000);
+    assert_eq!(stats.total.tool_calls["read"], 2);
+    assert_eq!(stats.total.total_tool_calls(), 4);
+    assert_eq!(stats.days, 10);
+
+    let free = &stats.by_model["opencode/minimax-m2.5-free"];
+    assert_eq!((free.sessions, free.messages, free.cost), (2, 4, 0.0));
+    // No recorded cost: estimated from the catalog
+    let paid = &stats.by_model["opencode/claude-sonnet"];
+    assert!((paid.cost - 4.5).abs() < 1e-9);
+    assert_eq!(paid.tool_calls["edit"], 1);
+    // Recorded costs are kept
+    assert_eq!(stats.by_model["opencode/mystery-model"].cost, 0.25);
+    assert!((stats.total.cost - 4.75).abs() < 1e-9);
+
+    assert_eq!(stats.by_provider["opencode"].messages, 8);
+    assert_eq!(stats.by_project["prj_a"].sessions, 1);
+    assert_eq!(stats.by_project["prj_b"].messages, 6);
+    assert_eq!(stats.by_day.len(), 2);
+    assert_eq!(stats.by_tier[FREE_TIER].tokens.input, 2_000_000);
+    assert_eq!(stats.by_tier[PAID_TIER].sessions, 1);
+    assert_eq!(stats.by_tier[UNKNOWN_TIER].messages, 2);
+}
+
+#[test]
+fn filters_limit_the_sessions_counted() {
+    let (_dir, storage) = seeded();
+
+    let recent = Filter {
+        days: Some(7),
+        ..filter()
+    };
+    let stats = Stats::collect(&storage, &catalog(), &recent).unwrap();
+    assert_eq!(stats.total.sessions, 1);
+    assert!(!stats.by_project.contains_key("prj_a"));
+
+    let project = Filter {
+        project: Some("prj_a".to_string()),
+        ..filter()
+    };
+    let stats = Stats::collect(&storage, &catalog(), &project).unwrap();
+    assert_eq!(stats.total.messages, 2);
+    assert_eq!(stats.by_project.keys().collect::<Vec<_>>(), ["prj_a"]);
+}
+
+#[test]
+fn empty_storage_has_empty_stats() {
+    let dir = TempDir::new().unwrap();
+    let storage = Storage::from_data_dir(dir.path());
+    let stats = Stats::collect(&storage, &catalog(), &filter()).unwrap();
+    assert_eq!(stats, Stats::default());
+    assert!(stats.table(None).contains("OVERVIEW"));
+}
+
+#[test]
+fn table_lists_tools_and_groups() {
+    let (_dir, storage) = seeded();
+    let stats = Stats::collect(&storage, &catalog(), &filter()).unwrap();
+
+    let table = stats.table(None);
+    assert!(table.contains("│Sessions"));
+    assert!(table.contains("TOOL USAGE"));
+    assert!(table.contains(" read "));
+    assert!(table.contains("BY MODEL"));
+    assert!(table.contains("opencode/claude-sonnet"));
+    assert!(table.contains("$4.50"));
+
+    // Only the most used tool
+    let table = stats.table(Some(1));
+    assert!(table.contains(" read "));
+    assert!(!table.contains(" bash "));
+}
+
+#[test]
+fn stats_command_prints_json() {
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    let agent = || {
+        let mut command = Command::cargo_bin("agent").unwrap();
+        command
+            .current_dir(work.path())
+            .env(DATA_DIR_ENV, data.path());
+        command
+    };
+    agent()
+        .args(["--dry-run", "--compact-json", "--prompt", "count me"])
+        .assert()
+        .success();
+
+    let output = agent()
+        .args(["stats", "--format", "json"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let stats: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(stats["total"]["sessions"], 1);
+    assert_eq!(stats["total"]["messages"], 2);
+    assert_eq!(stats["byProject"]["global"]["sessions"], 1);
+
+    agent()
+        .args(["stats", "--project", "--days", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("OVERVIEW"));
+}
@@ -0,0 +1,336 @@
+//! Tests for session summaries (`session::summary`) and the `summary`
+//! event written after each turn.
+
+use assert_cmd::Command;
+use async_trait::async_trait;
+use link_assistant_agent::defaults::model_parts;
+use link_assistant_agent::error::{AgentError, Result};
+use link_assistant_agent::global::DATA_DIR_ENV;
+use link_assistant_agent::provider::timeout::Timeouts;
+use link_assistant_agent::provider::{ChatRequest, ChatResponse, ContentPart, Provider, Usage};
+use link_assistant_agent::session::prompt::{PromptInput, SessionPrompt};
+use link_assistant_agent::session::summary::{
+    self, FileDiff, SessionSummary, SUMMARIZE_SESSION_ENV,
+};
+use link_assistant_agent::session::{system, MessageWithParts, Session, SessionEvent};
+use link_assistant_agent::storage::{Storage, GLOBAL_PROJECT};
+use link_assistant_agent::tool::ToolRegistry;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// An assistant message with one completed call of `tool`
+fn tool_call(index: u32, tool: &str, metadata: Value) -> Value {
+    json!({
+        "info": {
+            "role": "assistant",
+            "id": format!("msg_{:02}", index),
+            "sessionID": "ses_summary",
+            "time": { "created": index },
+            "parentID": "msg_00",
+            "modelID": "test",
+            "providerID": "scripted",
+            "mode": "build",
+            "path": { "cwd": "/work", "root": "/work" },
+            "cost": 0,
+            "tokens": { "input": 0, "output": 0, "reasoning": 0, "cache": { "read": 0, "write": 0 } }
+        },
+        "parts": [{
+            "type": "tool",
+            "id": format!("prt_{:02}", index),
+            "sessionID": "ses_summary",
+            "messageID": format!("msg_{:02}", index),
+            "callID": format!("call_{}", index),
+            "tool": tool,
+            "state": {
+                "status": "completed",
+                "input": {},
+                "output": "",
+                "title": tool,
+                "metadata": metadata,
+                "time": { "start": 1, "end": 2 }
+            }
+        }]
+    })
+}
+
+fn filediff(file: &str, before: &str, after: &str, additions: u64, deletions: u64) -> Value {
+    json!({
+        "filediff": {
+            "file": file, "before": before, "after": after,
+            "additions": additions, "deletions": deletions
+        }
+    })
+}
+
+#[test]
+fn changes_are_rolled_up_per_file() {
+    let messages: Vec<MessageWithParts> = serde_json::from_value(json!([
+        tool_call(1, "write", filediff("/work/src/new.rs", "", "a\nb\n", 2, 0)),
+        tool_call(2, "edit", filediff("/work/src/lib.rs", "x\n", "y\n", 1, 1)),
+        tool_call(
+            3,
+            "multiedit",
+            json!({ "results": [
+                filediff("/work/src/lib.rs", "y\n", "z\nw\n", 2, 1),
+                filediff("/elsewhere/notes.md", "", "n\n", 1, 0)
+            ]})
+        ),
+        tool_call(4, "read", json!({ "preview": "" }))
+    ]))
+    .unwrap();
+
+    let diffs = summary::diffs(&messages, Path::new("/work"));
+    let files: Vec<&str> = diffs.iter().map(|diff| diff.file.as_str()).collect();
+    assert_eq!(files, ["src/new.rs", "src/lib.rs", "/elsewhere/notes.md"]);
+    assert_eq!(
+        diffs[1],
+        FileDiff {
+            file: "src/lib.rs".to_string(),
+            before: "x\n".to_string(),
+            after: "z\nw\n".to_string(),
+            additions: 3,
+            deletions: 2,
+        }
+    );
+
+    let totals = SessionSummary::new(&diffs, None);
+    assert_eq!(
+        (totals.files, totals.additions, totals.deletions),
+        (3, 6, 2)
+    );
+}
+
+#[test]
+fn summaries_are_disabled_with_false_or_zero() {
+    assert!(!summary::disabled_from_env(|_| None));
+    assert!(summary::disabled_from_env(|key| {
+        (key == SUMMARIZE_SESSION_ENV).then(|| "FALSE".to_string())
+    }));
+    assert!(summary::disabled_from_env(|_| Some("0".to_string())));
+    assert!(!summary::disabled_from_env(|_| Some("true".to_string())));
+}
+
+/// Writes a file, then finishes; recap requests get `recap`, or fail when
+/// it is `None`
+struct WritingProvider {
+    steps: Mutex<VecDeque<ContentPart>>,
+    recap: Option<&'static str>,
+}
+
+impl WritingProvider {
+    fn new(file: &Path, recap: Option<&'static str>) -> Self {
+        Self {
+            steps: Mutex::new(VecDeque::from([
+                ContentPart::ToolCall {
+                    call_id: "call_1".to_string(),
+                    tool: "write".to_string(),
+                    input: json!({ "filePath": file, "content": "one\ntwo\n" }),
+                },
+                ContentPart::Text {
+                    text: "Wrote the file.".to_string(),
+                },
+            ])),
+            recap,
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for WritingProvider {
+    fn id(&self) -> &str {
+        "scripted"
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        let content = if request.system == system::summarize_turn() {
+            let recap = self.recap.ok_or_else(|| AgentError::Api {
+                provider: "scripted".to_string(),
+                status: Some(429),
+                message: "rate limited".to_string(),
+                retryable: true,
+            })?;
+            ContentPart::Text {
+                text: format!("  {}\n", recap),
+            }
+        } else {
+            self.steps.lock().unwrap().pop_front().unwrap()
+        };
+        let finish_reason = match content {
+            ContentPart::ToolCall { .. } => "tool-calls",
+            _ => "stop",
+        };
+        Ok(ChatResponse {
+            content: vec![content],
+            finish_reason: finish_reason.to_string(),
+            usage: Usage::default(),
+            model: None,
+        })
+    }
+}
+
+/// Run one turn in `dir`, returning the summary events
+async fn turn(dir: &Path, session: &mut Session, recap: Option<&'static str>) -> Vec<SessionEvent> {
+    let provider = WritingProvider::new(&dir.join("notes.txt"), recap);
+    let registry = ToolRegistry::new();
+    let prompt = SessionPrompt::new(&provider, &registry, dir)
+        .with_summary(&provider, model_parts("scripted/small"));
+    let mut events = Vec::new();
+    prompt
+        .prompt(
+            session,
+            PromptInput {
+                text: "write notes".to_string(),
+                model: model_parts("scripted/main"),
+                system: Some("test system".to_string()),
+                append_system: None,
+                temperature: None,
+            },
+            &mut |event| {
+                if let SessionEvent::Summary { .. } = event {
+                    events.push(event);
+                }
+            },
+        )
+        .await
+        .unwrap();
+    events
+}
+
+#[tokio::test]
+async fn completed_turns_update_the_summary() {
+    let dir = TempDir::new().unwrap();
+    let mut session = Session::new(dir.path());
+    let events = turn(dir.path(), &mut session, Some("Wrote notes.txt.")).await;
+
+    assert_eq!(events.len(), 1);
+    let SessionEvent::Summary { summary, diffs } = &events[0] else {
+        unreachable!()
+    };
+    assert_eq!(summary.body.as_deref(), Some("Wrote notes.txt."));
+    assert_eq!(
+        (summary.files, summary.additions, summary.deletions),
+        (1, 2, 0)
+    );
+    assert_eq!(diffs[0].file, "notes.txt");
+    assert_eq!(diffs[0].after, "one\ntwo\n");
+    assert_eq!(session.info.summary.as_ref(), Some(summary));
+}
+
+#[tokio::test]
+async fn failed_recaps_still_report_changes() {
+    let dir = TempDir::new().unwrap();
+    let mut session = Session::new(dir.path());
+    let events = turn(dir.path(), &mut session, None).await;
+
+    let SessionEvent::Summary { summary, diffs } = &events[0] else {
+        unreachable!()
+    };
+    assert_eq!(summary.body, None);
+    assert_eq!(summary.files, 1);
+    assert_eq!(diffs.len(), 1);
+}
+
+/// A `WritingProvider` whose recap requests never answer
+struct StalledRecapProvider(WritingProvider);
+
+#[async_trait]
+impl Provider for StalledRecapProvider {
+    fn id(&self) -> &str {
+        "scripted"
+    }
+
+    async fn complete(&self, request: &ChatRequest) -> Result<ChatResponse> {
+        if request.system == system::summarize_turn() {
+            std::future::pending::<()>().await;
+        }
+        self.0.complete(request).await
+    }
+}
+
+#[tokio::test]
+async fn stalled_recaps_do_not_hold_up_the_turn() {
+    let dir = TempDir::new().unwrap();
+    let mut session = Session::new(dir.path());
+    let provider = StalledRecapProvider(WritingProvider::new(&dir.path().join("notes.txt"), None));
+    let registry = ToolRegistry::new();
+    let prompt = SessionPrompt::new(&provider, &registry, dir.path())
+        .with_timeouts(Timeouts::default().with_step(Duration::from_millis(200)))
+        .with_summary(&provider, model_parts("scripted/small"));
+    let mut summaries = Vec::new();
+    let mut emit = |event| {
+        if let SessionEvent::Summary { summary, .. } = event {
+            summaries.push(summary);
+        }
+    };
+    let turn = prompt.prompt(
+        &mut session,
+        PromptInput {
+            text: "write notes".to_string(),
+            model: model_parts("scripted/main"),
+            system: Some("test system".to_string()),
+            append_system: None,
+            temperature: None,
+        },
+        &mut emit,
+    );
+    tokio::time::timeout(Duration::from_secs(30), turn)
+        .await
+        .expect("the turn waited for the recap")
+        .unwrap();
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].body, None);
+    assert_eq!(summaries[0].files, 1);
+}
+
+fn run(data: &TempDir, args: &[&str]) -> Vec<Value> {
+    let work = TempDir::new().unwrap();
+    let output = Command::cargo_bin("agent")
+        .unwrap()
+        .current_dir(work.path())
+        .env(DATA_DIR_ENV, data.path())
+        .env_remove(SUMMARIZE_SESSION_ENV)
+        .args(["--dry-run", "--compact-json", "-p", "hello summary"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+#[test]
+fn summary_event_is_emitted_and_stored() {
+    let data = TempDir::new().unwrap();
+    let events = run(&data, &[]);
+    let event = events
+        .iter()
+        .find(|event| event["type"] == "summary")
+        .expect("a summary event");
+    assert_eq!(event["files"], 0);
+    assert_eq!(event["changes"], json!([]));
+    assert!(event["body"].as_str().unwrap().starts_with("[DRY RUN]"));
+
+    let storage = Storage::from_data_dir(data.path());
+    let info = &storage.sessions(GLOBAL_PROJECT).unwrap()[0];
+    assert_eq!(info.summary.as_ref().unwrap().files, 0);
+    let diffs: Vec<FileDiff> = storage.read(&["session_diff", &info.id]).unwrap();
+    assert!(diffs.is_empty());
+}
+
+#[test]
+fn no_summarize_session_skips_the_summary() {
+    let data = TempDir::new().unwrap();
+    let events = run(&data, &["--no-summarize-session"]);
+    assert!(events.iter().all(|event| event["type"] != "summary"));
+    let storage = Storage::from_data_dir(data.path());
+    assert!(storage.sessions(GLOBAL_PROJECT).unwrap()[0]
+        .summary
+        .is_none());
+}
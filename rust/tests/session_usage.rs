@@ -1,18 +1,28 @@
 //! Rust counterpart of `js/tests/session-usage.ts`.
 //!
-//! The JS test exercises the session-usage tracking module
-//! (`js/src/session/usage.ts`), which converts streaming usage events from
-//! the AI SDK into a per-session token-cost ledger. The Rust port does not
-//! yet ship a session/usage module, so the JS-only paths cannot be mirrored.
-//!
-//! When the Rust port grows a session/usage module, this file should
-//! mirror the JS test cases (token totals, decimal precision, finish
-//! reasons, cache read/write tracking, metadata fallback). For now we
-//! verify that the constants the JS tests anchor to (default model, free
-//! tier identifiers) round-trip through the Rust defaults helpers.
+//! The JS test exercises `Session.getUsage`, which converts the usage
+//! reported for a step into session token counters and prices it from the
+//! models catalog. Rust providers report usage already normalized (the JS
+//! NaN/Infinity/nested-object sanitizing has no counterpart), so the port
+//! mirrors the pricing cases: token totals, cache read/write tracking,
+//! models without a price and the over-200K tier. It also checks that
+//! every step_finish carries its usage, context usage and the responding
+//! model, both on the stored part and in the JSON output.
 
+use assert_cmd::Command;
+use async_trait::async_trait;
 use link_assistant_agent::cli::DEFAULT_MODEL;
-use link_assistant_agent::defaults::default_model_parts;
+use link_assistant_agent::defaults::{default_model_parts, model_parts};
+use link_assistant_agent::error::Result;
+use link_assistant_agent::global::DATA_DIR_ENV;
+use link_assistant_agent::provider::models::{Catalog, Model};
+use link_assistant_agent::provider::{ChatRequest, ChatResponse, ContentPart, Provider, Usage};
+use link_assistant_agent::session::prompt::{PromptInput, SessionPrompt};
+use link_assistant_agent::session::usage::StepUsage;
+use link_assistant_agent::session::{MessageInfo, Part, Session, SessionEvent};
+use link_assistant_agent::tool::ToolRegistry;
+use serde_json::{json, Value};
+use tempfile::TempDir;
 
 #[test]
 fn default_model_identifies_a_free_tier_model() {
@@ -31,3 +41,255 @@ fn default_model_full_string_round_trips() {
     let combined = format!("{}/{}", parts.provider_id, parts.model_id);
     assert_eq!(combined, DEFAULT_MODEL);
 }
+
+/// $3/M input, $15/M output, $0.30/M cache reads, $3.75/M cache writes
+fn priced_model() -> Model {
+    serde_json::from_value(json!({
+        "id": "test-model",
+        "cost": { "input": 3, "output": 15, "cache_read": 0.3, "cache_write": 3.75 },
+        "limit": { "context": 100_000, "output": 2_000 }
+    }))
+    .unwrap()
+}
+
+fn usage(input: u64, output: u64) -> Usage {
+    Usage {
+        input,
+        output,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn calculates_cost_from_valid_token_data() {
+    let step = StepUsage::new(&usage(1000, 500), Some(&priced_model()));
+    assert_eq!(step.tokens.input, 1000);
+    assert_eq!(step.tokens.output, 500);
+    assert_eq!(step.tokens.reasoning, 0);
+    // 1000 * 3 / 1M + 500 * 15 / 1M
+    assert!((step.cost - 0.0105).abs() < 1e-12);
+}
+
+#[test]
+fn tracks_and_prices_cache_reads_and_writes() {
+    let reported = Usage {
+        cache_read: 200,
+        cache_write: 100,
+        ..usage(1000, 500)
+    };
+    let step = StepUsage::new(&reported, Some(&priced_model()));
+    assert_eq!(step.tokens.cache.read, 200);
+    assert_eq!(step.tokens.cache.write, 100);
+    // 0.0105 + 200 * 0.3 / 1M + 100 * 3.75 / 1M
+    assert!((step.cost - 0.010935).abs() < 1e-12);
+}
+
+#[test]
+fn models_without_a_price_cost_nothing() {
+    let free: Model = serde_json::from_value(json!({ "id": "test-model-free" })).unwrap();
+    assert_eq!(StepUsage::new(&usage(1000, 500), Some(&free)).cost, 0.0);
+    // Unknown to the catalog
+    let step = StepUsage::new(&usage(1000, 500), None);
+    assert_eq!(step.cost, 0.0);
+    assert_eq!(step.tokens.output, 500);
+}
+
+#[test]
+fn large_prompts_use_the_over_200k_tier() {
+    let model: Model = serde_json::from_value(json!({
+        "id": "long-context",
+        "cost": {
+            "input": 3, "output": 15,
+            "context_over_200k": { "input": 6, "output": 22.5 }
+        }
+    }))
+    .unwrap();
+    let step = StepUsage::new(&usage(500_000, 100_000), Some(&model));
+    assert!((step.cost - (3.0 + 2.25)).abs() < 1e-9);
+    assert!(step.cost.is_finite());
+}
+
+/// Answers every request with fixed usage, reporting a dated model ID
+struct MeteredProvider;
+
+#[async_trait]
+impl Provider for MeteredProvider {
+    fn id(&self) -> &str {
+        "scripted"
+    }
+
+    async fn complete(&self, _request: &ChatRequest) -> Result<ChatResponse> {
+        Ok(ChatResponse {
+            content: vec![ContentPart::Text {
+                text: "done".to_string(),
+            }],
+            finish_reason: "stop".to_string(),
+            usage: Usage {
+                input: 2_000,
+                output: 100,
+                reasoning: 50,
+                cache_read: 1_000,
+                cache_write: 0,
+            },
+            model: Some("test-model-2026-01-01".to_string()),
+        })
+    }
+}
+
+#[tokio::test]
+async fn step_finish_records_usage_cost_and_responding_model() {
+    let dir = TempDir::new().unwrap();
+    let registry = ToolRegistry::new();
+    let catalog = Catalog::from_json(
+        &json!({ "scripted": { "id": "scripted", "models": { "test-model": priced_model() } } })
+            .to_string(),
+    )
+    .unwrap();
+    let prompt = SessionPrompt::new(&MeteredProvider, &registry, dir.path()).with_catalog(&catalog);
+
+    let mut session = Session::new(dir.path());
+    let mut finishes = Vec::new();
+    prompt
+        .prompt(
+            &mut session,
+            PromptInput {
+                text: "hi".to_string(),
+                model: model_parts("scripted/test-model"),
+                system: Some("test system".to_string()),
+                append_system: None,
+                temperature: None,
+            },
+            &mut |event| {
+                if let SessionEvent::Part(Part::StepFinish(finish)) = event {
+                    finishes.push(finish);
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(finishes.len(), 1);
+    let finish = &finishes[0];
+    assert_eq!(finish.tokens.input, 2_000);
+    assert_eq!(finish.tokens.reasoning, 50);
+    assert_eq!(finish.tokens.cache.read, 1_000);
+    // 2000 * 3 + (100 + 50) * 15 + 1000 * 0.3, per million
+    assert!((finish.cost - 0.0085500).abs() < 1e-12);
+    let model = finish.model.as_ref().unwrap();
+    assert_eq!(model.provider_id, "scripted");
+    assert_eq!(model.requested_model_id, "test-model");
+    assert_eq!(
+        model.responded_model_id.as_deref(),
+        Some("test-model-2026-01-01")
+    );
+
+    // The assistant message carries the same usage
+    let MessageInfo::Assistant(assistant) = &session.messages[1].info else {
+        panic!("expected an assistant message");
+    };
+    assert_eq!(assistant.tokens, finish.tokens);
+    assert_eq!(assistant.cost, finish.cost);
+
+    // Context usage: input + cache read + output against the catalog limits
+    let context = finish.context.unwrap();
+    assert_eq!(context.context_limit, 100_000);
+    assert_eq!(context.output_limit, 2_000);
+    assert_eq!(context.current_tokens, 3_100);
+    assert!(!context.overflow);
+}
+
+/// Answers without reporting any usage
+struct UnmeteredProvider;
+
+#[async_trait]
+impl Provider for UnmeteredProvider {
+    fn id(&self) -> &str {
+        "scripted"
+    }
+
+    async fn complete(&self, _request: &ChatRequest) -> Result<ChatResponse> {
+        Ok(ChatResponse {
+            content: vec![ContentPart::Text {
+                text: "done".to_string(),
+            }],
+            finish_reason: "stop".to_string(),
+            usage: Usage::default(),
+            model: None,
+        })
+    }
+}
+
+#[tokio::test]
+async fn step_finish_estimates_context_usage_without_reported_usage() {
+    let dir = TempDir::new().unwrap();
+    let registry = ToolRegistry::new();
+    let catalog = Catalog::from_json(
+        &json!({ "scripted": { "id": "scripted", "models": { "test-model": priced_model() } } })
+            .to_string(),
+    )
+    .unwrap();
+    let prompt =
+        SessionPrompt::new(&UnmeteredProvider, &registry, dir.path()).with_catalog(&catalog);
+
+    let mut session = Session::new(dir.path());
+    let mut finishes = Vec::new();
+    prompt
+        .prompt(
+            &mut session,
+            PromptInput {
+                text: "hi ".repeat(1_000),
+                model: model_parts("scripted/test-model"),
+                system: Some("test system".to_string()),
+                append_system: None,
+                temperature: None,
+            },
+            &mut |event| {
+                if let SessionEvent::Part(Part::StepFinish(finish)) = event {
+                    finishes.push(finish);
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(finishes[0].tokens.input, 0);
+    let context = finishes[0].context.unwrap();
+    assert!(context.current_tokens >= 1_000, "{context:?}");
+}
+
+fn step_finish(args: &[&str]) -> Value {
+    let data = TempDir::new().unwrap();
+    let output = Command::cargo_bin("agent")
+        .unwrap()
+        .env(DATA_DIR_ENV, data.path())
+        .args(["--dry-run", "--compact-json", "-p", "hi"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .find(|event| event["type"] == "step_finish")
+        .expect("a step_finish event")
+}
+
+#[test]
+fn step_finish_event_reports_usage_and_model() {
+    let event = step_finish(&[]);
+    assert_eq!(event["reason"], "stop");
+    assert_eq!(event["tokens"]["input"], 1);
+    assert_eq!(event["tokens"]["cache"]["write"], 0);
+    assert_eq!(event["cost"], 0.0);
+    assert_eq!(event["model"]["providerID"], "link-assistant");
+    assert_eq!(event["model"]["requestedModelID"], "echo");
+    assert_eq!(event["model"]["respondedModelID"], "echo");
+}
+
+#[test]
+fn no_output_response_model_omits_the_model() {
+    let event = step_finish(&["--no-output-response-model"]);
+    assert!(event.get("model").is_none());
+    assert_eq!(event["tokens"]["output"], 1);
+}
@@ -0,0 +1,233 @@
+//! Tests for the shadow-git `snapshot` module and the snapshots taken
+//! around steps that run mutating tools.
+
+use async_trait::async_trait;
+use link_assistant_agent::defaults::model_parts;
+use link_assistant_agent::error::Result;
+use link_assistant_agent::provider::{ChatRequest, ChatResponse, ContentPart, Provider, Usage};
+use link_assistant_agent::session::message::Part;
+use link_assistant_agent::session::prompt::{PromptInput, SessionPrompt};
+use link_assistant_agent::session::{Session, SessionEvent};
+use link_assistant_agent::snapshot::{self, Snapshot, DISABLE_SNAPSHOT_ENV, SNAPSHOT_DIR};
+use link_assistant_agent::tool::ToolRegistry;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use tempfile::TempDir;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = std::process::Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {:?} failed", args);
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// A git repository with one committed file, and a data directory
+fn repository() -> (TempDir, TempDir, Snapshot) {
+    let work = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    git(work.path(), &["init", "-q"]);
+    fs::write(work.path().join("a.txt"), "one\ntwo\n").unwrap();
+    git(work.path(), &["add", "."]);
+    git(work.path(), &["commit", "-q", "-m", "init"]);
+    let snapshot = Snapshot::for_directory(data.path(), work.path()).unwrap();
+    (work, data, snapshot)
+}
+
+#[test]
+fn snapshots_need_a_git_repository() {
+    let plain = TempDir::new().unwrap();
+    let data = TempDir::new().unwrap();
+    assert!(Snapshot::for_directory(data.path(), plain.path()).is_none());
+}
+
+#[test]
+fn snapshots_are_disabled_from_the_environment() {
+    assert!(!snapshot::disabled_from_env(|_| None));
+    assert!(snapshot::disabled_from_env(|key| {
+        (key == DISABLE_SNAPSHOT_ENV).then(|| "1".to_string())
+    }));
+}
+
+#[test]
+fn tracked_trees_are_diffed_per_file() {
+    let (work, data, snapshot) = repository();
+    let before = snapshot.track().unwrap();
+    // The shadow repository lives under the data directory
+    assert!(data.path().join(SNAPSHOT_DIR).exists());
+    assert_eq!(snapshot.track().unwrap(), before);
+
+    fs::write(work.path().join("a.txt"), "one\n2\nthree\n").unwrap();
+    fs::write(work.path().join("b.txt"), "new\n").unwrap();
+    let patch = snapshot.patch(&before);
+    assert_eq!(patch.hash, before);
+    let mut files = patch.files.clone();
+    files.sort();
+    let root = snapshot.worktree();
+    assert_eq!(
+        files,
+        [
+            root.join("a.txt").to_string_lossy(),
+            root.join("b.txt").to_string_lossy()
+        ]
+    );
+    assert!(snapshot.diff(&before).contains("+three"));
+
+    let after = snapshot.track().unwrap();
+    assert_ne!(after, before);
+    let diffs = snapshot.diff_full(&before, &after);
+    assert_eq!(diffs.len(), 2);
+    assert_eq!(diffs[0].file, "a.txt");
+    assert_eq!(diffs[0].before, "one\ntwo\n");
+    assert_eq!(diffs[0].after, "one\n2\nthree\n");
+    assert_eq!((diffs[0].additions, diffs[0].deletions), (2, 1));
+    assert_eq!(diffs[1].file, "b.txt");
+    assert_eq!(diffs[1].before, "");
+
+    // The project's own repository is left alone
+    assert_eq!(git(work.path(), &["rev-list", "--count", "HEAD"]), "1");
+    assert!(git(work.path(), &["status", "--porcelain"]).contains("?? b.txt"));
+}
+
+#[test]
+fn unknown_hashes_give_empty_results() {
+    let (_work, _data, snapshot) = repository();
+    snapshot.track().unwrap();
+    assert!(snapshot.patch("0000000").files.is_empty());
+    assert!(snapshot.diff_full("0000000", "1111111").is_empty());
+}
+
+/// Answers with one tool call, then with text (recaps included)
+struct ScriptedProvider {
+    steps: Mutex<VecDeque<ContentPart>>,
+}
+
+impl ScriptedProvider {
+    fn new(tool: &str, input: Value) -> Self {
+        Self {
+            steps: Mutex::new(VecDeque::from([
+                ContentPart::ToolCall {
+                    call_id: "call_1".to_string(),
+                    tool: tool.to_string(),
+                    input,
+                },
+                ContentPart::Text {
+                    text: "done".to_string(),
+                },
+            ])),
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for ScriptedProvider {
+    fn id(&self) -> &str {
+        "scripted"
+    }
+
+    async fn complete(&self, _request: &ChatRequest) -> Result<ChatResponse> {
+        let content = self
+            .steps
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or(ContentPart::Text {
+                text: "recap".to_string(),
+            });
+        let finish_reason = match content {
+            ContentPart::ToolCall { .. } => "tool-calls",
+            _ => "stop",
+        };
+        Ok(ChatResponse {
+            content: vec![content],
+            finish_reason: finish_reason.to_string(),
+            usage: Usage::default(),
+            model: None,
+        })
+    }
+}
+
+async fn run(work: &Path, snapshot: Snapshot, provider: &ScriptedProvider) -> Session {
+    let registry = ToolRegistry::new();
+    let prompt = SessionPrompt::new(provider, &registry, work)
+        .with_snapshot(snapshot)
+        .with_summary(provider, model_parts("scripted/small"));
+    let mut session = Session::new(work);
+    prompt
+        .prompt(
+            &mut session,
+            PromptInput {
+                text: "change things".to_string(),
+                model: model_parts("scripted/main"),
+                system: Some("test system".to_string()),
+                append_system: None,
+                temperature: None,
+            },
+            &mut |_: SessionEvent| {},
+        )
+        .await
+        .unwrap();
+    session
+}
+
+#[tokio::test]
+async fn steps_running_bash_are_snapshotted() {
+    let (work, _data, snapshot) = repository();
+    let provider = ScriptedProvider::new(
+        "bash",
+        json!({ "command": "echo made > by-bash.txt", "description": "Create a file" }),
+    );
+    let session = run(work.path(), snapshot.clone(), &provider).await;
+
+    let parts = &session.messages[1].parts;
+    let Part::StepStart(start) = &parts[0] else {
+        panic!("expected a step-start part");
+    };
+    let Some(Part::StepFinish(finish)) = parts.iter().find(|p| matches!(p, Part::StepFinish(_)))
+    else {
+        panic!("expected a step-finish part");
+    };
+    let before = start.snapshot.clone().unwrap();
+    let after = finish.snapshot.clone().unwrap();
+    let Some(Part::Patch(patch)) = parts.last() else {
+        panic!("expected a patch part");
+    };
+    assert_eq!(patch.hash, before);
+    assert_eq!(
+        patch.files,
+        [snapshot.worktree().join("by-bash.txt").to_string_lossy()]
+    );
+    assert_eq!(snapshot.diff_full(&before, &after)[0].after, "made\n");
+
+    // The closing step ran no tools and was not snapshotted
+    let Part::StepStart(closing) = &session.messages[2].parts[0] else {
+        panic!("expected a step-start part");
+    };
+    assert!(closing.snapshot.is_none());
+
+    // The session summary sees the change made through bash
+    let summary = session.info.summary.unwrap();
+    assert_eq!((summary.files, summary.additions), (1, 1));
+}
+
+#[tokio::test]
+async fn read_only_steps_are_not_snapshotted() {
+    let (work, data, snapshot) = repository();
+    let provider = ScriptedProvider::new("read", json!({ "filePath": work.path().join("a.txt") }));
+    let session = run(work.path(), snapshot, &provider).await;
+
+    let parts = &session.messages[1].parts;
+    assert!(parts.iter().all(|part| match part {
+        Part::StepStart(start) => start.snapshot.is_none(),
+        Part::StepFinish(finish) => finish.snapshot.is_none(),
+        Part::Patch(_) => false,
+        _ => true,
+    }));
+    assert!(!data.path().join(SNAPSHOT_DIR).exists());
+}
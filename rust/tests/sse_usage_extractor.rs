@@ -1,16 +1,15 @@
 //! Rust counterpart of `js/tests/sse-usage-extractor.ts`.
 //!
-//! The JS test exercises an SSE chunk parser that pulls usage statistics
-//! out of streaming responses (OpenRouter, OpenCode Zen, etc.). The Rust
-//! port does not yet ship a streaming SSE parser. When it does, this file
-//! should mirror the JS test cases byte-for-byte.
-//!
-//! For now we verify the related Rust surface: the `--json-standard` flag
-//! that selects the consumer format and the centralized defaults that the
-//! parser anchors against.
+//! Mirrors the JS cases for pulling usage statistics out of streaming
+//! responses (OpenRouter, OpenCode Zen, Groq, Anthropic), and covers the
+//! incremental `text/event-stream` decoder in `util::sse` the providers use.
+//! The `--json-standard` flag that selects the consumer format is checked
+//! here as well.
 
 use clap::Parser;
 use link_assistant_agent::cli::Args;
+use link_assistant_agent::util::sse::{parse, usage_from_value, Decoder, Event, SseUsage};
+use serde_json::Value;
 
 #[test]
 fn json_standard_defaults_to_opencode() {
@@ -23,3 +22,126 @@ fn json_standard_supports_claude_format() {
     let args = Args::parse_from(["agent", "--json-standard", "claude"]);
     assert_eq!(args.json_standard, "claude");
 }
+
+// usage_from_value
+
+/// The last usage in a stream body, read event by event as the providers do
+fn stream_usage(body: &str) -> Option<SseUsage> {
+    parse(body)
+        .iter()
+        .rev()
+        .filter(|event| !event.is_done())
+        .filter_map(|event| serde_json::from_str::<Value>(&event.data).ok())
+        .find_map(|payload| usage_from_value(&payload))
+}
+
+#[test]
+fn extracts_openai_format_usage() {
+    let chunk = "data: {\"id\":\"chatcmpl-123\",\"choices\":[],\"usage\":{\"prompt_tokens\":1500,\"completion_tokens\":80,\"total_tokens\":1580}}\n\ndata: [DONE]\n\n";
+    let usage = stream_usage(chunk).unwrap();
+    assert_eq!(usage.prompt_tokens, 1500);
+    assert_eq!(usage.completion_tokens, 80);
+    assert_eq!(usage.total_tokens, 1580);
+}
+
+#[test]
+fn extracts_anthropic_format_usage() {
+    let chunk = "data: {\"type\":\"message_delta\",\"usage\":{\"input_tokens\":2000,\"output_tokens\":150}}\n\n";
+    let usage = stream_usage(chunk).unwrap();
+    assert_eq!(usage.prompt_tokens, 2000);
+    assert_eq!(usage.completion_tokens, 150);
+    assert_eq!(usage.total_tokens, 2150);
+}
+
+#[test]
+fn extracts_cached_tokens_from_prompt_tokens_details() {
+    let chunk = "data: {\"usage\":{\"prompt_tokens\":5000,\"completion_tokens\":200,\"total_tokens\":5200,\"prompt_tokens_details\":{\"cached_tokens\":3000}}}\n\n";
+    assert_eq!(stream_usage(chunk).unwrap().cached_tokens, Some(3000));
+}
+
+#[test]
+fn extracts_reasoning_tokens_from_completion_tokens_details() {
+    let chunk = "data: {\"usage\":{\"prompt_tokens\":1000,\"completion_tokens\":500,\"total_tokens\":1500,\"completion_tokens_details\":{\"reasoning_tokens\":300}}}\n\n";
+    assert_eq!(stream_usage(chunk).unwrap().reasoning_tokens, Some(300));
+}
+
+#[test]
+fn extracts_anthropic_cache_read_input_tokens() {
+    let chunk = "data: {\"usage\":{\"input_tokens\":4000,\"output_tokens\":100,\"cache_read_input_tokens\":2500}}\n\n";
+    assert_eq!(stream_usage(chunk).unwrap().cached_tokens, Some(2500));
+}
+
+#[test]
+fn returns_none_without_usage() {
+    let chunk = "data: {\"id\":\"chatcmpl-123\",\"choices\":[{\"delta\":{\"content\":\"Hello\"}}]}\n\ndata: [DONE]\n\n";
+    assert_eq!(stream_usage(chunk), None);
+    assert_eq!(stream_usage(""), None);
+}
+
+#[test]
+fn returns_none_for_all_zero_usage() {
+    let chunk =
+        "data: {\"usage\":{\"prompt_tokens\":0,\"completion_tokens\":0,\"total_tokens\":0}}\n\n";
+    assert_eq!(stream_usage(chunk), None);
+}
+
+#[test]
+fn extracts_groq_usage_from_x_groq() {
+    let chunk = "data: {\"x_groq\":{\"usage\":{\"prompt_tokens\":800,\"completion_tokens\":100,\"total_tokens\":900}}}\n\n";
+    let usage = stream_usage(chunk).unwrap();
+    assert_eq!(usage.prompt_tokens, 800);
+    assert_eq!(usage.completion_tokens, 100);
+}
+
+#[test]
+fn extracts_usage_from_openrouter_style_stream() {
+    let body = [
+        r#"data: {"id":"gen-123","choices":[{"index":0,"delta":{"role":"assistant","content":""},"finish_reason":null}]}"#,
+        "",
+        r#"data: {"id":"gen-123","choices":[{"index":0,"delta":{"content":"Hello"},"finish_reason":null}]}"#,
+        "",
+        r#"data: {"id":"gen-123","choices":[{"index":0,"delta":{},"finish_reason":"stop"}],"usage":{"prompt_tokens":15506,"completion_tokens":80,"total_tokens":15586,"prompt_tokens_details":{"cached_tokens":0},"completion_tokens_details":{"reasoning_tokens":0}}}"#,
+        "",
+        "data: [DONE]",
+        "",
+    ]
+    .join("\n");
+    let usage = stream_usage(&body).unwrap();
+    assert_eq!(usage.prompt_tokens, 15506);
+    assert_eq!(usage.completion_tokens, 80);
+    assert_eq!(usage.total_tokens, 15586);
+    assert_eq!(usage.cached_tokens, Some(0));
+    assert_eq!(usage.reasoning_tokens, Some(0));
+}
+
+// Decoder
+
+#[test]
+fn decoder_reassembles_events_split_across_chunks() {
+    let mut decoder = Decoder::new();
+    assert!(decoder.push(b"data: {\"a\":").is_empty());
+    assert!(decoder.push(b"1}\r").is_empty());
+    let events = decoder.push(b"\n\r\ndata: caf\xc3");
+    assert_eq!(events[0].data, "{\"a\":1}");
+    assert!(decoder.push(b"\xa9").is_empty());
+    assert_eq!(decoder.finish().unwrap().data, "café");
+    assert_eq!(decoder.finish(), None);
+}
+
+#[test]
+fn decoder_joins_multi_line_data_and_reads_fields() {
+    let events = parse(": keep-alive\n\nevent: message_start\nid: 7\nretry: 3000\ndata: first\ndata:second\n\nevent: ping\n\ndata: [DONE]\n\n");
+    assert_eq!(
+        events[0],
+        Event {
+            event: Some("message_start".to_string()),
+            data: "first\nsecond".to_string(),
+            id: Some("7".to_string()),
+            retry: Some(3000),
+        }
+    );
+    // Comments and events without data are not dispatched
+    assert_eq!(events.len(), 2);
+    assert!(events[1].is_done());
+    assert_eq!(events[1].event, None);
+}
@@ -1,16 +1,20 @@
 //! Rust counterpart of `js/tests/storage-migration.ts`.
 //!
-//! The JS test exercises a storage migration helper that moves session
-//! files between layout versions. The Rust port does not yet persist
-//! sessions to disk in the same layout, so the JS-only paths cannot be
-//! mirrored directly.
-//!
-//! When the Rust port grows a session storage module, this file should
-//! mirror the JS test cases (path safety, atomic writes, version
-//! detection). For now we verify the related Rust filesystem helpers
-//! that the storage layer would build on.
+//! The JS test exercises path safety, version detection and the storage
+//! layout that sessions are persisted in. These tests cover the Rust
+//! `storage` module: key sanitisation built on `Filesystem::contains`,
+//! atomic writes, locking under concurrent updates, the session/message/part
+//! layout, layout migrations and reading sessions the JS agent wrote.
 
+use assert_cmd::Command;
+use link_assistant_agent::error::AgentError;
+use link_assistant_agent::session::{MessageWithParts, Part, Session, SessionInfo};
+use link_assistant_agent::storage::migration::{self, LEGACY_MARKER, MIGRATION_FILE};
+use link_assistant_agent::storage::{project_id, Storage, GLOBAL_PROJECT, STORAGE_DIR};
 use link_assistant_agent::util::Filesystem;
+use serde_json::{json, Value};
+use std::path::Path;
+use tempfile::TempDir;
 
 #[test]
 fn parent_contains_child_path() {
@@ -27,3 +31,584 @@ fn overlapping_paths_are_detected_in_either_order() {
     assert!(Filesystem::overlaps("/tmp", "/tmp/sessions"));
     assert!(Filesystem::overlaps("/tmp/sessions", "/tmp"));
 }
+
+#[test]
+fn records_are_json_files_under_their_key() {
+    let dir = TempDir::new().unwrap();
+    let storage = Storage::new(dir.path());
+    storage
+        .write(&["session", "global", "ses_1"], &json!({ "id": "ses_1" }))
+        .unwrap();
+
+    let path = dir.path().join("session/global/ses_1.json");
+    let stored: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(stored, json!({ "id": "ses_1" }));
+    let read: Value = storage.read(&["session", "global", "ses_1"]).unwrap();
+    assert_eq!(read, stored);
+
+    // No temporary files are left behind
+    let leftovers: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty());
+}
+
+#[test]
+fn missing_records_are_not_found() {
+    let dir = TempDir::new().unwrap();
+    let storage = Storage::new(dir.path());
+    assert!(matches!(
+        storage.read::<Value>(&["session", "global", "ses_missing"]),
+        Err(AgentError::NotFound { .. })
+    ));
+    assert!(matches!(
+        storage.update::<Value>(&["todo", "ses_missing"], |_| {}),
+        Err(AgentError::NotFound { .. })
+    ));
+    storage
+        .remove(&["session", "global", "ses_missing"])
+        .unwrap();
+    // Reading a missing record creates nothing
+    assert!(!dir.path().join("session").exists());
+
+    // Not even a lock file next to records that do exist
+    storage
+        .write(&["session", "global", "ses_1"], &json!({}))
+        .unwrap();
+    assert!(storage
+        .read::<Value>(&["session", "global", "ses_missing"])
+        .is_err());
+    assert!(storage
+        .update::<Value>(&["session", "global", "ses_missing"], |_| {})
+        .is_err());
+    assert!(!dir
+        .path()
+        .join("session/global/ses_missing.json.lock")
+        .exists());
+}
+
+#[test]
+fn keys_cannot_leave_the_storage_root() {
+    let dir = TempDir::new().unwrap();
+    let storage = Storage::new(dir.path().join(STORAGE_DIR));
+    for key in [
+        vec!["session", "..", "escape"],
+        vec!["session", "../escape"],
+        vec!["session", "/etc/passwd"],
+        vec!["session", "a\\b"],
+        vec!["session", "nul\0byte"],
+        vec!["session", ""],
+        vec![],
+    ] {
+        assert!(
+            matches!(
+                storage.write(&key, &json!({})),
+                Err(AgentError::Session { .. })
+            ),
+            "{:?}",
+            key
+        );
+    }
+    assert!(storage.list(&[".."]).is_err());
+    assert!(!dir.path().join("escape.json").exists());
+}
+
+#[test]
+fn list_returns_sorted_keys_under_a_prefix() {
+    let dir = TempDir::new().unwrap();
+    let storage = Storage::new(dir.path());
+    for id in ["msg_b", "msg_a", "msg_c"] {
+        storage
+            .write(&["message", "ses_1", id], &json!({}))
+            .unwrap();
+    }
+    storage
+        .write(&["message", "ses_2", "msg_z"], &json!({}))
+        .unwrap();
+
+    assert_eq!(
+        storage.list(&["message", "ses_1"]).unwrap(),
+        [
+            ["message", "ses_1", "msg_a"],
+            ["message", "ses_1", "msg_b"],
+            ["message", "ses_1", "msg_c"],
+        ]
+    );
+    assert_eq!(storage.list(&["message"]).unwrap().len(), 4);
+    assert!(storage.list(&["message", "ses_3"]).unwrap().is_empty());
+}
+
+#[test]
+fn concurrent_updates_are_not_lost() {
+    let dir = TempDir::new().unwrap();
+    let storage = Storage::new(dir.path());
+    storage.write(&["counter"], &0u64).unwrap();
+
+    std::thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| {
+                for _ in 0..25 {
+                    storage.update::<u64>(&["counter"], |n| *n += 1).unwrap();
+                }
+            });
+        }
+    });
+    assert_eq!(storage.read::<u64>(&["counter"]).unwrap(), 200);
+}
+
+fn message(session_id: &str, id: &str, parts: &[&str]) -> MessageWithParts {
+    let parts: Vec<Value> = parts
+        .iter()
+        .map(|part| {
+            json!({
+                "type": "text",
+                "id": part,
+                "sessionID": session_id,
+                "messageID": id,
+                "text": format!("text of {}", part)
+            })
+        })
+        .collect();
+    serde_json::from_value(json!({
+        "info": {
+            "role": "user",
+            "id": id,
+            "sessionID": session_id,
+            "time": { "created": 1 },
+            "agent": "build",
+            "model": { "providerID": "opencode", "modelID": "big-pickle" }
+        },
+        "parts": parts
+    }))
+    .unwrap()
+}
+
+#[test]
+fn sessions_round_trip_with_messages_and_parts() {
+    let dir = TempDir::new().unwrap();
+    let storage = Storage::new(dir.path());
+    let mut session = Session::new(dir.path());
+    let id = session.id().to_string();
+    session.messages = vec![
+        message(&id, "msg_01", &["prt_01", "prt_02"]),
+        message(&id, "msg_02", &["prt_03"]),
+    ];
+    storage.write_session(&mut session).unwrap();
+
+    assert!(dir
+        .path()
+        .join(format!("session/{}/{}.json", GLOBAL_PROJECT, id))
+        .is_file());
+    assert!(dir
+        .path()
+        .join(format!("message/{}/msg_01.json", id))
+        .is_file());
+    assert!(dir.path().join("part/msg_01/prt_02.json").is_file());
+
+    let loaded = storage.read_session(GLOBAL_PROJECT, &id).unwrap();
+    assert_eq!(loaded.info, session.info);
+    assert_eq!(loaded.messages, session.messages);
+
+    let listed: Vec<SessionInfo> = storage.sessions(GLOBAL_PROJECT).unwrap();
+    assert_eq!(listed, [session.info.clone()]);
+}
+
+#[test]
+fn dropped_messages_and_parts_are_removed_on_save() {
+    let dir = TempDir::new().unwrap();
+    let storage = Storage::new(dir.path());
+    let mut session = Session::new(dir.path());
+    let id = session.id().to_string();
+    session.messages = vec![
+        message(&id, "msg_01", &["prt_01", "prt_02"]),
+        message(&id, "msg_02", &["prt_03"]),
+    ];
+    storage.write_session(&mut session).unwrap();
+
+    session.messages.pop();
+    session.messages[0].parts.pop();
+    storage.write_session(&mut session).unwrap();
+
+    let loaded = storage.read_session(GLOBAL_PROJECT, &id).unwrap();
+    assert_eq!(loaded.messages, session.messages);
+    assert!(storage.list(&["part", "msg_02"]).unwrap().is_empty());
+
+    storage.remove_session(GLOBAL_PROJECT, &id).unwrap();
+    assert!(storage.sessions(GLOBAL_PROJECT).unwrap().is_empty());
+    assert!(storage.list(&["message", &id]).unwrap().is_empty());
+    assert!(storage.list(&["part"]).unwrap().is_empty());
+    // Removed records leave no lock files behind
+    let leftovers: Vec<_> = walkdir::WalkDir::new(dir.path())
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .collect();
+    assert!(leftovers.is_empty(), "{:?}", leftovers);
+}
+
+#[test]
+fn two_writers_on_one_session_keep_each_others_messages() {
+    let dir = TempDir::new().unwrap();
+    let storage = Storage::new(dir.path());
+    let mut base = Session::new(dir.path());
+    let id = base.id().to_string();
+    base.messages = vec![message(&id, "msg_00", &["prt_00"])];
+    storage.write_session(&mut base).unwrap();
+
+    // Two agents resume the session without forking and append turns
+    std::thread::scope(|scope| {
+        for writer in ["a", "b"] {
+            let (storage, id) = (&storage, &id);
+            scope.spawn(move || {
+                let mut session = storage.read_session(GLOBAL_PROJECT, id).unwrap();
+                for turn in 0..10 {
+                    let message_id = format!("msg_{}{:02}", writer, turn);
+                    let part_id = format!("prt_{}{:02}", writer, turn);
+                    session
+                        .messages
+                        .push(message(id, &message_id, &[part_id.as_str()]));
+                    storage.write_session(&mut session).unwrap();
+                }
+                // Dropping a message only removes that message
+                session.messages.pop();
+                storage.write_session(&mut session).unwrap();
+            });
+        }
+    });
+
+    let loaded = storage.read_session(GLOBAL_PROJECT, &id).unwrap();
+    let ids: Vec<&str> = loaded
+        .messages
+        .iter()
+        .map(|message| message.info.id())
+        .collect();
+    assert_eq!(ids.len(), 19);
+    assert_eq!(ids[0], "msg_00");
+    assert!(ids.contains(&"msg_a08") && ids.contains(&"msg_b08"));
+    assert!(!ids.contains(&"msg_a09") && !ids.contains(&"msg_b09"));
+    assert!(storage.list(&["part", "msg_a09"]).unwrap().is_empty());
+    assert_eq!(loaded.messages[0].parts.len(), 1);
+}
+
+#[test]
+fn unchanged_messages_are_not_rewritten() {
+    let dir = TempDir::new().unwrap();
+    let storage = Storage::new(dir.path());
+    let mut session = Session::new(dir.path());
+    let id = session.id().to_string();
+    session.messages = vec![message(&id, "msg_01", &["prt_01"])];
+    storage.write_session(&mut session).unwrap();
+
+    // Marks the stored copies; a rewrite would drop the marks
+    let mark = |path: &Path| {
+        let mut value = read_file(path);
+        value["mark"] = json!(true);
+        write_file(path, &value);
+    };
+    let message_path = dir.path().join(format!("message/{}/msg_01.json", id));
+    let part_path = dir.path().join("part/msg_01/prt_01.json");
+    mark(&message_path);
+    mark(&part_path);
+
+    storage.write_session(&mut session).unwrap();
+    assert_eq!(read_file(&message_path)["mark"], true);
+    assert_eq!(read_file(&part_path)["mark"], true);
+
+    let Part::Text(text) = &mut session.messages[0].parts[0] else {
+        unreachable!()
+    };
+    text.text = "changed".to_string();
+    storage.write_session(&mut session).unwrap();
+    assert_eq!(read_file(&message_path)["mark"], true);
+    assert!(read_file(&part_path).get("mark").is_none());
+}
+
+#[test]
+fn directories_outside_git_belong_to_the_global_project() {
+    let dir = TempDir::new().unwrap();
+    assert_eq!(project_id(dir.path()), GLOBAL_PROJECT);
+}
+
+#[test]
+fn dry_run_sessions_are_persisted() {
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    Command::cargo_bin("agent")
+        .unwrap()
+        .args(["--dry-run", "-p", "hello"])
+        .current_dir(work.path())
+        .env("LINK_ASSISTANT_AGENT_DATA_DIR", data.path())
+        .assert()
+        .success();
+
+    let storage = Storage::from_data_dir(data.path());
+    let sessions = storage.sessions(GLOBAL_PROJECT).unwrap();
+    assert_eq!(sessions.len(), 1);
+    let session = storage
+        .read_session(GLOBAL_PROJECT, &sessions[0].id)
+        .unwrap();
+    // The user message and the echoed reply
+    assert_eq!(session.messages.len(), 2);
+}
+
+fn write_file(path: &Path, value: &Value) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, serde_json::to_string(value).unwrap()).unwrap();
+}
+
+fn read_file(path: &Path) -> Value {
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+/// A session record as the JavaScript agent writes it
+fn js_session(id: &str, project: &str) -> Value {
+    json!({
+        "id": id,
+        "projectID": project,
+        "directory": "/work",
+        "title": "JS session",
+        "version": "0.9.0",
+        "time": { "created": 1, "updated": 2 },
+        "share": { "url": "https://example.com/s/1" }
+    })
+}
+
+fn js_message(session_id: &str, id: &str, root: &Path) -> Value {
+    json!({
+        "id": id,
+        "sessionID": session_id,
+        "role": "assistant",
+        "time": { "created": 1, "completed": 2 },
+        "parentID": "msg_parent",
+        "modelID": "big-pickle",
+        "providerID": "opencode",
+        "mode": "build",
+        "path": { "cwd": root, "root": root },
+        "system": ["js-only field"],
+        "cost": 0,
+        "tokens": { "input": 1, "output": 1, "reasoning": 0, "cache": { "read": 0, "write": 0 } }
+    })
+}
+
+fn js_part(session_id: &str, message_id: &str, id: &str, kind: &str) -> Value {
+    match kind {
+        "text" => json!({
+            "id": id, "sessionID": session_id, "messageID": message_id,
+            "type": "text", "text": "hello from js"
+        }),
+        other => json!({
+            "id": id, "sessionID": session_id, "messageID": message_id,
+            "type": other, "hash": "abc", "files": []
+        }),
+    }
+}
+
+#[test]
+fn fresh_storage_is_created_at_the_latest_version() {
+    let dir = TempDir::new().unwrap();
+    let storage = Storage::new(dir.path().join(STORAGE_DIR));
+    assert_eq!(migration::version(storage.root()), 0);
+
+    assert_eq!(migration::run(&storage).unwrap(), migration::latest());
+    assert_eq!(migration::version(storage.root()), migration::latest());
+    // The marker is plain text, as the JS agent reads it
+    assert_eq!(
+        std::fs::read_to_string(storage.root().join(MIGRATION_FILE)).unwrap(),
+        migration::latest().to_string()
+    );
+    // Nothing to back up
+    assert!(!migration::backup_dir(storage.root(), 0).exists());
+
+    assert_eq!(migration::run(&storage).unwrap(), 0);
+}
+
+#[test]
+fn session_diffs_move_out_of_sessions_with_a_backup() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().join(STORAGE_DIR);
+    let mut session = js_session("ses_1", GLOBAL_PROJECT);
+    session["summary"] = json!({ "diffs": [
+        { "file": "a.rs", "additions": 3, "deletions": 1 },
+        { "file": "b.rs", "additions": 2, "deletions": 4 }
+    ]});
+    write_file(&root.join("session/global/ses_1.json"), &session);
+    std::fs::write(root.join(MIGRATION_FILE), "1").unwrap();
+
+    let storage = Storage::new(&root);
+    assert_eq!(migration::run(&storage).unwrap(), 1);
+
+    let migrated = read_file(&root.join("session/global/ses_1.json"));
+    assert_eq!(
+        migrated["summary"],
+        json!({ "additions": 5, "deletions": 5 })
+    );
+    assert_eq!(
+        read_file(&root.join("session_diff/ses_1.json"))
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+    // The backup holds the layout as it was before
+    let backup = migration::backup_dir(&root, 1);
+    assert_eq!(
+        read_file(&backup.join("session/global/ses_1.json"))["summary"]["diffs"]
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+    assert_eq!(
+        storage.sessions(GLOBAL_PROJECT).unwrap()[0].title,
+        "JS session"
+    );
+}
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = std::process::Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {:?} failed", args);
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+#[test]
+fn old_per_project_trees_are_migrated() {
+    let data = TempDir::new().unwrap();
+    let worktree = TempDir::new().unwrap();
+    git(worktree.path(), &["init", "-q"]);
+    git(
+        worktree.path(),
+        &["commit", "-q", "--allow-empty", "-m", "init"],
+    );
+    let root_commit = git(worktree.path(), &["rev-list", "--max-parents=0", "HEAD"]);
+
+    let tree = data.path().join("project/home-user-repo/storage/session");
+    write_file(&tree.join("info/ses_1.json"), &js_session("ses_1", "old"));
+    write_file(
+        &tree.join("message/ses_1/msg_1.json"),
+        &js_message("ses_1", "msg_1", worktree.path()),
+    );
+    write_file(
+        &tree.join("part/ses_1/msg_1/prt_1.json"),
+        &js_part("ses_1", "msg_1", "prt_1", "text"),
+    );
+
+    let storage = Storage::from_data_dir(data.path());
+    migration::run(&storage).unwrap();
+
+    assert_eq!(project_id(worktree.path()), root_commit);
+    let session = storage.read_session(&root_commit, "ses_1").unwrap();
+    assert_eq!(session.messages.len(), 1);
+    assert_eq!(session.messages[0].parts.len(), 1);
+    let project: Value = storage.read(&["project", &root_commit]).unwrap();
+    assert_eq!(project["vcs"], "git");
+}
+
+#[test]
+fn legacy_project_directories_are_imported_once() {
+    let data = TempDir::new().unwrap();
+    let work = TempDir::new().unwrap();
+    for (legacy, session_id) in [
+        (".opencode", "ses_old"),
+        (".link-assistant-agent", "ses_new"),
+    ] {
+        let tree = work.path().join(legacy).join("storage/session");
+        let message_id = format!("msg_{}", session_id);
+        write_file(
+            &tree.join(format!("info/{}.json", session_id)),
+            &js_session(session_id, GLOBAL_PROJECT),
+        );
+        write_file(
+            &tree.join(format!("message/{}/{}.json", session_id, message_id)),
+            &js_message(session_id, &message_id, work.path()),
+        );
+    }
+    // Names that would leave the storage directory are skipped
+    let evil = work.path().join(".opencode/storage/session/info/evil.json");
+    write_file(&evil, &js_session("../../evil", GLOBAL_PROJECT));
+
+    let storage = Storage::from_data_dir(data.path());
+    assert_eq!(migration::import_legacy(&storage, work.path()).unwrap(), 2);
+    let ids: Vec<String> = storage
+        .sessions(GLOBAL_PROJECT)
+        .unwrap()
+        .into_iter()
+        .map(|info| info.id)
+        .collect();
+    assert_eq!(ids, ["ses_new", "ses_old"]);
+    assert!(!data.path().join("evil.json").exists());
+
+    // Importing again keeps what is already stored
+    storage
+        .update::<Value>(&["session", GLOBAL_PROJECT, "ses_old"], |s| {
+            s["title"] = json!("renamed")
+        })
+        .unwrap();
+    assert_eq!(migration::import_legacy(&storage, work.path()).unwrap(), 0);
+    let info: SessionInfo = storage
+        .read(&["session", GLOBAL_PROJECT, "ses_old"])
+        .unwrap();
+    assert_eq!(info.title, "renamed");
+    // The legacy tree is left in place, backed up and marked as imported
+    let legacy = work.path().join(".opencode").join(STORAGE_DIR);
+    assert!(legacy.join("session/info").is_dir());
+    assert!(migration::backup_dir(&legacy, 0)
+        .join("session/info/ses_old.json")
+        .is_file());
+    let marker = std::fs::read_to_string(legacy.join(LEGACY_MARKER)).unwrap();
+    assert_eq!(marker, format!("{}\n", storage.root().display()));
+
+    // Marked trees are not scanned again by the same storage, but another
+    // data directory still imports them
+    write_file(
+        &legacy.join("session/info/ses_late.json"),
+        &js_session("ses_late", GLOBAL_PROJECT),
+    );
+    assert_eq!(migration::import_legacy(&storage, work.path()).unwrap(), 0);
+    let other_data = TempDir::new().unwrap();
+    let other = Storage::from_data_dir(other_data.path());
+    assert_eq!(migration::import_legacy(&other, work.path()).unwrap(), 3);
+    let marker = std::fs::read_to_string(legacy.join(LEGACY_MARKER)).unwrap();
+    assert_eq!(marker.lines().count(), 2);
+}
+
+#[test]
+fn sessions_written_by_the_js_agent_can_be_resumed() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path();
+    write_file(
+        &root.join("session/global/ses_js.json"),
+        &js_session("ses_js", GLOBAL_PROJECT),
+    );
+    write_file(
+        &root.join("message/ses_js/msg_1.json"),
+        &js_message("ses_js", "msg_1", root),
+    );
+    write_file(
+        &root.join("part/msg_1/prt_1.json"),
+        &js_part("ses_js", "msg_1", "prt_1", "text"),
+    );
+    write_file(
+        &root.join("part/msg_1/prt_2.json"),
+        &js_part("ses_js", "msg_1", "prt_2", "snapshot"),
+    );
+
+    let storage = Storage::new(root);
+    let mut session = storage.read_session(GLOBAL_PROJECT, "ses_js").unwrap();
+    // The part type only the JS agent knows is skipped
+    assert_eq!(session.messages[0].parts.len(), 1);
+
+    // Saving it again keeps the JS-only fields and parts
+    storage.write_session(&mut session).unwrap();
+    let info = read_file(&root.join("session/global/ses_js.json"));
+    assert_eq!(info["share"]["url"], "https://example.com/s/1");
+    let message = read_file(&root.join("message/ses_js/msg_1.json"));
+    assert_eq!(message["system"][0], "js-only field");
+    assert!(root.join("part/msg_1/prt_2.json").is_file());
+}
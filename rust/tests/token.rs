@@ -1,16 +1,17 @@
 //! Rust counterpart of `js/tests/token.ts`.
 //!
-//! The JS test exercises a token estimation helper that wraps the
-//! `gpt-tokenizer` package. The Rust port does not yet ship a tokenizer
-//! and does not expose a token-counting function. When it does, this
-//! file should mirror the JS test cases (chunk vs. message totals,
-//! tokenizer cache, etc.).
-//!
-//! For now we verify a related surface: the centralized defaults expose
-//! a non-zero context safety margin so the runtime always reserves
-//! headroom for token slop.
+//! Mirrors the JS cases for the character heuristic and BPE counting in
+//! `util::token`, and covers the message, tool schema and tool output
+//! totals used for context accounting. The centralized defaults must also
+//! keep a non-zero context safety margin for estimation slop.
 
 use link_assistant_agent::cli::DEFAULT_COMPACTION_SAFETY_MARGIN_PERCENT;
+use link_assistant_agent::provider::{ChatMessage, ChatRequest, ContentPart, ToolSpec};
+use link_assistant_agent::util::token::{
+    count_tokens, default_tokenizer, estimate, message_tokens, request_tokens, tools_tokens,
+    Heuristic, MESSAGE_OVERHEAD,
+};
+use serde_json::json;
 
 #[test]
 fn safety_margin_is_a_positive_percentage() {
@@ -24,3 +25,112 @@ fn safety_margin_matches_documented_default() {
     // probability of context overflow when providers under-report tokens.
     assert_eq!(DEFAULT_COMPACTION_SAFETY_MARGIN_PERCENT, 25);
 }
+
+#[test]
+fn estimate_is_four_characters_per_token() {
+    assert_eq!(estimate(""), 0);
+    // 11 chars -> 2.75 -> 3
+    assert_eq!(estimate("hello world"), 3);
+    assert_eq!(estimate(&"a".repeat(400)), 100);
+    // 0.25 rounds down, 0.5 rounds up
+    assert_eq!(estimate("a"), 0);
+    assert_eq!(estimate("ab"), 1);
+    // Characters, not bytes
+    assert_eq!(estimate("éééé"), 1);
+}
+
+#[test]
+fn count_tokens_of_empty_string_is_zero() {
+    let result = count_tokens("");
+    assert_eq!(result.count, 0);
+    assert!(result.precise);
+}
+
+#[test]
+fn bundled_bpe_table_is_used() {
+    assert_eq!(default_tokenizer().name(), "o200k_base");
+    let result = count_tokens("Hello world");
+    assert!(result.precise);
+    assert_eq!(result.count, 2);
+}
+
+#[test]
+fn count_is_reasonable_for_larger_text() {
+    let text = "The quick brown fox jumps over the lazy dog. ".repeat(22);
+    let result = count_tokens(&text);
+    assert!(result.count > 100 && result.count < 500, "{}", result.count);
+}
+
+#[test]
+fn bpe_and_heuristic_agree_on_the_order_of_magnitude_for_code() {
+    let code = "function calculateTotal(items: Item[]): number {\n  return items.reduce((sum, item) => sum + item.price * item.quantity, 0);\n}";
+    let bpe = count_tokens(code).count;
+    let heuristic = estimate(code);
+    assert!(bpe > 0 && heuristic > 0);
+    assert!(bpe.abs_diff(heuristic) < heuristic * 2);
+}
+
+#[test]
+fn messages_count_every_part_plus_overhead() {
+    let tokenizer = Heuristic;
+    let user = ChatMessage::User {
+        text: "a".repeat(40),
+    };
+    assert_eq!(message_tokens(&tokenizer, &user), 10 + MESSAGE_OVERHEAD);
+
+    let assistant = ChatMessage::Assistant {
+        content: vec![
+            ContentPart::Reasoning {
+                text: "b".repeat(8),
+                metadata: None,
+            },
+            ContentPart::Text {
+                text: "c".repeat(8),
+            },
+            ContentPart::ToolCall {
+                call_id: "call_1".to_string(),
+                tool: "read".to_string(),
+                input: json!({ "path": "x" }),
+            },
+        ],
+    };
+    // 2 + 2 + "read" (1) + {"path":"x"} (12 chars -> 3)
+    assert_eq!(message_tokens(&tokenizer, &assistant), 8 + MESSAGE_OVERHEAD);
+
+    let tool = ChatMessage::Tool {
+        call_id: "call_1".to_string(),
+        tool: "read".to_string(),
+        output: "d".repeat(400),
+        is_error: false,
+    };
+    assert_eq!(message_tokens(&tokenizer, &tool), 101 + MESSAGE_OVERHEAD);
+}
+
+#[test]
+fn requests_include_system_prompt_and_tool_schemas() {
+    let tools = vec![ToolSpec {
+        name: "read".to_string(),
+        description: "Read a file".to_string(),
+        parameters: json!({ "type": "object" }),
+    }];
+    let request = ChatRequest {
+        model: "gpt-5".to_string(),
+        system: vec!["You are helpful".to_string()],
+        messages: vec![ChatMessage::User {
+            text: "hi".to_string(),
+        }],
+        tools: tools.clone(),
+        temperature: None,
+        max_output_tokens: None,
+        thinking_budget: None,
+    };
+    let tokenizer = default_tokenizer();
+    let total = request_tokens(tokenizer, &request);
+    assert_eq!(
+        total,
+        tokenizer.count("You are helpful")
+            + message_tokens(tokenizer, &request.messages[0])
+            + tools_tokens(tokenizer, &tools)
+    );
+    assert!(tools_tokens(tokenizer, &tools) > 0);
+}
@@ -0,0 +1,309 @@
+//! Tests for the patch tool and its `apply_patch` format parser.
+
+use link_assistant_agent::error::AgentError;
+use link_assistant_agent::tool::patch::{apply_chunks, parse_patch, Hunk, PatchTool, UpdateChunk};
+use link_assistant_agent::tool::{Tool, ToolContext};
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn create_context(dir: &Path) -> ToolContext {
+    ToolContext::new("ses_test", "msg_test", dir)
+}
+
+fn lines(text: &[&str]) -> Vec<String> {
+    text.iter().map(|line| line.to_string()).collect()
+}
+
+// --- Parser tests ---
+
+#[test]
+fn test_parse_all_section_kinds() {
+    let hunks = parse_patch(
+        "*** Begin Patch\n\
+         *** Add File: new.txt\n\
+         +first\n\
+         +second\n\
+         *** Delete File: old.txt\n\
+         *** Update File: src/lib.rs\n\
+         *** Move to: src/main.rs\n\
+         @@ fn main() {\n\
+         -    old();\n\
+         +    new();\n\
+         \x20}\n\
+         *** End of File\n\
+         *** End Patch\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        hunks,
+        [
+            Hunk::Add {
+                path: "new.txt".to_string(),
+                contents: "first\nsecond\n".to_string(),
+            },
+            Hunk::Delete {
+                path: "old.txt".to_string(),
+            },
+            Hunk::Update {
+                path: "src/lib.rs".to_string(),
+                move_path: Some("src/main.rs".to_string()),
+                chunks: vec![UpdateChunk {
+                    change_context: Some("fn main() {".to_string()),
+                    old_lines: lines(&["    old();", "}"]),
+                    new_lines: lines(&["    new();", "}"]),
+                    is_end_of_file: true,
+                }],
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_parse_first_chunk_without_header() {
+    let hunks = parse_patch(
+        "*** Begin Patch\n*** Update File: a.txt\n one\n-two\n+2\n@@\n-four\n*** End Patch",
+    )
+    .unwrap();
+    let Hunk::Update { chunks, .. } = &hunks[0] else {
+        panic!("expected an update");
+    };
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].old_lines, lines(&["one", "two"]));
+    assert_eq!(chunks[0].change_context, None);
+    assert_eq!(chunks[1].old_lines, lines(&["four"]));
+}
+
+#[test]
+fn test_parse_errors() {
+    for patch in [
+        "*** Update File: a.txt\n-x\n+y",
+        "*** Begin Patch\n*** End Patch",
+        "*** Begin Patch\n*** Update File: a.txt\n*** End Patch",
+        "*** Begin Patch\n*** Add File: a.txt\nno plus\n*** End Patch",
+        "*** Begin Patch\n*** Update File: a.txt\n@@\n?x\n*** End Patch",
+        "*** Begin Patch\n*** Rename File: a.txt\n*** End Patch",
+    ] {
+        let error = parse_patch(patch).unwrap_err();
+        assert!(
+            matches!(error, AgentError::InvalidArguments { .. }),
+            "{:?}",
+            patch
+        );
+    }
+}
+
+// --- Chunk application tests ---
+
+#[test]
+fn test_apply_chunks_uses_context_to_pick_the_occurrence() {
+    let content = "fn a() {\n    run();\n}\nfn b() {\n    run();\n}\n";
+    let chunks = [UpdateChunk {
+        change_context: Some("fn b() {".to_string()),
+        old_lines: lines(&["    run();"]),
+        new_lines: lines(&["    walk();"]),
+        is_end_of_file: false,
+    }];
+    assert_eq!(
+        apply_chunks(content, &chunks, "a.rs").unwrap(),
+        "fn a() {\n    run();\n}\nfn b() {\n    walk();\n}\n"
+    );
+}
+
+#[test]
+fn test_apply_chunks_tolerates_whitespace_differences() {
+    let chunks = [UpdateChunk {
+        old_lines: lines(&["value = 1"]),
+        new_lines: lines(&["value = 2"]),
+        ..Default::default()
+    }];
+    assert_eq!(
+        apply_chunks("  value = 1  \n", &chunks, "a.txt").unwrap(),
+        "value = 2\n"
+    );
+}
+
+#[test]
+fn test_apply_chunks_end_of_file_and_pure_additions() {
+    let content = "x\nend\nx\nend\n";
+    let chunks = [
+        UpdateChunk {
+            old_lines: lines(&["end"]),
+            new_lines: lines(&["END"]),
+            is_end_of_file: true,
+            ..Default::default()
+        },
+        UpdateChunk {
+            new_lines: lines(&["appended"]),
+            ..Default::default()
+        },
+    ];
+    assert_eq!(
+        apply_chunks(content, &chunks, "a.txt").unwrap(),
+        "x\nend\nx\nEND\nappended\n"
+    );
+}
+
+// --- Tool execute tests ---
+
+#[tokio::test]
+async fn test_patch_adds_updates_moves_and_deletes() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("update.txt"), "one\ntwo\nthree\n").unwrap();
+    fs::write(temp.path().join("move.txt"), "keep\nchange\n").unwrap();
+    fs::write(temp.path().join("delete.txt"), "gone\n").unwrap();
+
+    let patch = "*** Begin Patch
+*** Add File: nested/added.txt
++hello
+*** Update File: update.txt
+@@
+ one
+-two
++2
+ three
+*** Update File: move.txt
+*** Move to: moved/here.txt
+@@
+ keep
+-change
++changed
+*** Delete File: delete.txt
+*** End Patch";
+    let result = PatchTool
+        .execute(json!({ "patchText": patch }), &create_context(temp.path()))
+        .await
+        .unwrap();
+
+    let read = |file: &str| fs::read_to_string(temp.path().join(file)).ok();
+    assert_eq!(read("nested/added.txt").as_deref(), Some("hello\n"));
+    assert_eq!(read("update.txt").as_deref(), Some("one\n2\nthree\n"));
+    assert_eq!(read("moved/here.txt").as_deref(), Some("keep\nchanged\n"));
+    assert_eq!(read("move.txt"), None);
+    assert_eq!(read("delete.txt"), None);
+
+    assert_eq!(result.title, "5 files changed");
+    assert!(result.output.contains("  A nested/added.txt"));
+    assert!(result.output.contains("  D delete.txt"));
+    let results = result.metadata["results"].as_array().unwrap();
+    assert_eq!(results.len(), 5);
+    let update = &results[1]["filediff"];
+    assert_eq!(
+        update["file"],
+        temp.path().join("update.txt").to_string_lossy().as_ref()
+    );
+    assert_eq!(update["before"], "one\ntwo\nthree\n");
+    assert_eq!(update["after"], "one\n2\nthree\n");
+    assert_eq!(
+        (update["additions"].as_u64(), update["deletions"].as_u64()),
+        (Some(1), Some(1))
+    );
+    let deleted = &results[4]["filediff"];
+    assert_eq!(
+        (deleted["after"].as_str(), deleted["deletions"].as_u64()),
+        (Some(""), Some(1))
+    );
+    assert!(result.metadata["diff"].as_str().unwrap().contains("+2"));
+}
+
+#[tokio::test]
+async fn test_patch_validates_every_hunk_before_writing() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("a.txt"), "a\n").unwrap();
+    fs::write(temp.path().join("b.txt"), "b\n").unwrap();
+
+    let patch = "*** Begin Patch
+*** Update File: a.txt
+-a
++A
+*** Add File: c.txt
++c
+*** Update File: b.txt
+-not in the file
++B
+*** End Patch";
+    let error = PatchTool
+        .execute(json!({ "patchText": patch }), &create_context(temp.path()))
+        .await
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("failed to find expected lines in b.txt"));
+
+    assert_eq!(
+        fs::read_to_string(temp.path().join("a.txt")).unwrap(),
+        "a\n"
+    );
+    assert!(!temp.path().join("c.txt").exists());
+    // No staging files are left behind
+    assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 2);
+}
+
+#[tokio::test]
+async fn test_patch_missing_files() {
+    let temp = TempDir::new().unwrap();
+    let ctx = create_context(temp.path());
+    for patch in [
+        "*** Begin Patch\n*** Update File: missing.txt\n-a\n+b\n*** End Patch",
+        "*** Begin Patch\n*** Delete File: missing.txt\n*** End Patch",
+    ] {
+        let error = PatchTool
+            .execute(json!({ "patchText": patch }), &ctx)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AgentError::FileNotFound { .. }));
+    }
+}
+
+#[tokio::test]
+async fn test_patch_later_hunks_see_earlier_ones() {
+    let temp = TempDir::new().unwrap();
+    let patch = "*** Begin Patch
+*** Add File: a.txt
++draft
+*** Update File: a.txt
+-draft
++final
+*** End Patch";
+    let result = PatchTool
+        .execute(json!({ "patchText": patch }), &create_context(temp.path()))
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(temp.path().join("a.txt")).unwrap(),
+        "final\n"
+    );
+    assert_eq!(result.title, "1 files changed");
+    assert_eq!(result.metadata["results"][0]["filediff"]["before"], "");
+}
+
+#[tokio::test]
+async fn test_patch_restores_written_files_when_a_write_fails() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("a.txt"), "a\n").unwrap();
+    // A directory cannot be replaced by a file, which only shows up while
+    // the files are moved into place
+    fs::create_dir(temp.path().join("taken")).unwrap();
+    fs::write(temp.path().join("taken/inner.txt"), "inner\n").unwrap();
+
+    let patch = "*** Begin Patch
+*** Update File: a.txt
+-a
++A
+*** Add File: taken
++file
+*** End Patch";
+    PatchTool
+        .execute(json!({ "patchText": patch }), &create_context(temp.path()))
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        fs::read_to_string(temp.path().join("a.txt")).unwrap(),
+        "a\n"
+    );
+    assert!(temp.path().join("taken/inner.txt").exists());
+    assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 2);
+}
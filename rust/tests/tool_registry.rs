@@ -27,6 +27,7 @@ fn test_tool_lookup() {
     assert!(registry.get("todowrite").is_some());
     assert!(registry.get("todoread").is_some());
     assert!(registry.get("multiedit").is_some());
+    assert!(registry.get("patch").is_some());
     assert!(registry.get("invalid").is_some());
     assert!(registry.get("nonexistent").is_none());
 }
@@ -56,8 +57,8 @@ fn test_registry_has_all_js_tools() {
 #[test]
 fn test_registry_tool_count_matches_js() {
     let registry = ToolRegistry::new();
-    // JavaScript registry has 15 tools
-    assert_eq!(registry.all().len(), 15);
+    // The JavaScript registry's 15 tools, plus patch
+    assert_eq!(registry.all().len(), 16);
 }
 
 #[test]
@@ -69,5 +70,5 @@ fn test_registry_descriptions() {
         assert!(!id.is_empty());
         assert!(!description.is_empty());
     }
-    assert_eq!(descriptions.len(), 15);
+    assert_eq!(descriptions.len(), 16);
 }
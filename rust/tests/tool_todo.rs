@@ -3,13 +3,14 @@
 //! Mirrors test coverage from js/tests/integration/todo.tools.test.js
 //! and the original inline tests from rust/src/tool/todo.rs.
 
+use link_assistant_agent::storage::Storage;
 use link_assistant_agent::tool::todo::{TodoReadTool, TodoWriteTool};
 use link_assistant_agent::tool::{Tool, ToolContext};
 use serde_json::json;
 use tempfile::TempDir;
 
 fn create_context(dir: &std::path::Path, session_id: &str) -> ToolContext {
-    ToolContext::new(session_id, "msg_test", dir)
+    ToolContext::new(session_id, "msg_test", dir).with_storage(Storage::new(dir.join("storage")))
 }
 
 #[tokio::test]
@@ -168,3 +169,30 @@ fn test_todoread_tool_id() {
     let tool = TodoReadTool;
     assert_eq!(tool.id(), "todoread");
 }
+
+#[tokio::test]
+async fn test_todos_are_stored_per_session() {
+    let temp = TempDir::new().unwrap();
+    let ctx = create_context(temp.path(), "ses_test_todo_stored");
+    let params = json!({
+        "todos": [
+            { "content": "Persist", "status": "pending", "activeForm": "Persisting" }
+        ]
+    });
+    TodoWriteTool.execute(params, &ctx).await.unwrap();
+
+    let stored: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(temp.path().join("storage/todo/ses_test_todo_stored.json"))
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(stored[0]["content"], "Persist");
+
+    // A new context for the same session, as after a resume, reads them back
+    let resumed = create_context(temp.path(), "ses_test_todo_stored");
+    let result = TodoReadTool.execute(json!({}), &resumed).await.unwrap();
+    assert!(result.output.contains("Persist"));
+    let other = create_context(temp.path(), "ses_test_todo_other");
+    let result = TodoReadTool.execute(json!({}), &other).await.unwrap();
+    assert_eq!(result.title, "0 todos");
+}
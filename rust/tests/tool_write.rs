@@ -49,6 +49,31 @@ async fn test_write_overwrite_file() {
 
     assert_eq!(fs::read_to_string(&file_path).unwrap(), "new content");
     assert_eq!(result.metadata["exists"], true);
+    let filediff = &result.metadata["filediff"];
+    assert_eq!(filediff["before"], "old content");
+    assert_eq!(filediff["after"], "new content");
+    assert_eq!(filediff["additions"], 1);
+    assert_eq!(filediff["deletions"], 1);
+}
+
+#[tokio::test]
+async fn test_write_over_binary_file_has_no_filediff() {
+    let temp = TempDir::new().unwrap();
+    let file_path = temp.path().join("image.bin");
+    fs::write(&file_path, [0x89, b'P', b'N', b'G', 0xff, 0xfe, 0x00]).unwrap();
+
+    let tool = WriteTool;
+    let ctx = create_context(temp.path());
+    let params = json!({
+        "content": "now text",
+        "filePath": file_path.to_string_lossy()
+    });
+
+    let result = tool.execute(params, &ctx).await.unwrap();
+
+    assert_eq!(fs::read_to_string(&file_path).unwrap(), "now text");
+    assert_eq!(result.metadata["exists"], true);
+    assert!(result.metadata.get("filediff").is_none());
 }
 
 #[tokio::test]
@@ -0,0 +1,154 @@
+//! Tests for the Links Notation reader/writer in `util::lino`.
+
+use link_assistant_agent::error::AgentError;
+use link_assistant_agent::util::lino::{
+    decode_json, encode_json, format, parse, parse_lenv, parse_sequence, quote, sequence, Link,
+};
+use serde_json::json;
+
+#[test]
+fn parses_references_and_links() {
+    let links = parse("(a b c)\nword \"two words\" (id: x (y z))").unwrap();
+    assert_eq!(links.len(), 4);
+    assert_eq!(
+        links[0],
+        Link::new(
+            None,
+            vec![
+                Link::reference("a"),
+                Link::reference("b"),
+                Link::reference("c")
+            ]
+        )
+    );
+    assert_eq!(links[1].as_reference(), Some("word"));
+    assert_eq!(links[2].as_reference(), Some("two words"));
+    assert_eq!(links[3].id.as_deref(), Some("id"));
+    assert_eq!(links[3].values.len(), 2);
+    assert_eq!(links[3].to_string(), "(id: x (y z))");
+}
+
+#[test]
+fn quoting_round_trips() {
+    for text in [
+        "plain",
+        "with space",
+        "say \"hi\"",
+        "it's \"both\"",
+        "a:b",
+        "#not-a-comment",
+        "",
+    ] {
+        let parsed = parse(&quote(text)).unwrap();
+        assert_eq!(parsed, vec![Link::reference(text)], "text: {text}");
+    }
+}
+
+#[test]
+fn malformed_input_is_a_config_error() {
+    for input in ["(a b", "a)", "\"open"] {
+        assert!(matches!(parse(input), Err(AgentError::Config { .. })));
+    }
+}
+
+#[test]
+fn json_values_round_trip() {
+    let value = json!({
+        "content": [{ "type": "text", "text": "Hello (world)\nsecond line" }],
+        "finish_reason": "stop",
+        "usage": { "input": 10, "ratio": 0.5, "negative": -3 },
+        "flags": [true, false, null],
+        "empty": {},
+        "list": [],
+        "numeric_string": "42"
+    });
+
+    let encoded = encode_json(&value);
+    assert!(encoded.contains("(finish_reason: stop)"));
+    assert_eq!(decode_json(&encoded).unwrap(), value);
+}
+
+#[test]
+fn decoding_rejects_unknown_shapes() {
+    assert!(decode_json("(tuple a b)").is_err());
+    assert!(decode_json("(object a)").is_err());
+    assert!(decode_json("a b").is_err());
+}
+
+#[test]
+fn comments_run_to_the_end_of_the_line() {
+    let links = parse("# cascade\n(a # first\n b) c#d # trailing").unwrap();
+    assert_eq!(
+        links,
+        vec![
+            Link::new(None, vec![Link::reference("a"), Link::reference("b")]),
+            Link::reference("c#d"),
+        ]
+    );
+    assert_eq!(
+        parse("\"# quoted\"").unwrap(),
+        vec![Link::reference("# quoted")]
+    );
+}
+
+#[test]
+fn top_level_lines_may_omit_parentheses() {
+    let links = parse("papa: loves mama\nson: (loves (mama papa))\n(x y)").unwrap();
+    assert_eq!(links.len(), 3);
+    assert_eq!(links[0].to_string(), "(papa: loves mama)");
+    assert_eq!(links[1].to_string(), "(son: (loves (mama papa)))");
+    assert_eq!(parse(&format(&links)).unwrap(), links);
+}
+
+#[test]
+fn colons_inside_references_are_kept() {
+    assert_eq!(
+        parse("(url: https://example.com:8080/v1)").unwrap()[0].values,
+        vec![Link::reference("https://example.com:8080/v1")]
+    );
+}
+
+#[test]
+fn sequences_round_trip() {
+    let names = ["gpt-5-nano", "opencode/big-pickle", "two words", "same"];
+    let written = sequence(&names);
+    assert_eq!(
+        written,
+        "(gpt-5-nano opencode/big-pickle \"two words\" same)"
+    );
+    assert_eq!(parse_sequence(&written).unwrap(), names);
+    assert_eq!(parse_sequence("a b").unwrap(), ["a", "b"]);
+    assert!(matches!(
+        parse_sequence("(a (b c))"),
+        Err(AgentError::Config { .. })
+    ));
+}
+
+#[test]
+fn lenv_documents_map_keys_to_values() {
+    let entries = parse_lenv(
+        "# agent settings\nLINK_ASSISTANT_AGENT_VERBOSE: true\nBASE_URL: http://localhost:8080/v1\nGREETING: hello world\nQUOTED: \"a (b)\"\n",
+    )
+    .unwrap();
+    assert_eq!(
+        entries,
+        vec![
+            (
+                "LINK_ASSISTANT_AGENT_VERBOSE".to_string(),
+                "true".to_string()
+            ),
+            (
+                "BASE_URL".to_string(),
+                "http://localhost:8080/v1".to_string()
+            ),
+            ("GREETING".to_string(), "hello world".to_string()),
+            ("QUOTED".to_string(), "a (b)".to_string()),
+        ]
+    );
+    for input in ["JUST_A_WORD", "KEY: (nested value)", "KEY: (unclosed"] {
+        assert!(
+            matches!(parse_lenv(input), Err(AgentError::Config { .. })),
+            "input: {input}"
+        );
+    }
+}